@@ -14,7 +14,7 @@
 # ==============================================================================
 """RusTree: Optimized PyTree Utilities written in Rust."""
 
-from rustree import accessors, typing
+from rustree import accessors, diff, lenses, optree_compat, schema, testing, tracing, typing
 from rustree.accessors import (
     AutoEntry,
     DataclassEntry,
@@ -28,9 +28,93 @@ from rustree.accessors import (
     SequenceEntry,
     StructSequenceEntry,
 )
-from rustree.ops import tree_is_leaf
+from rustree.diff import PyTreePatch, PyTreePatchOp, tree_diff, tree_patch
+from rustree.lenses import PyTreeLens, lens, tree_replace
+from rustree.ops import (
+    all_leaves,
+    broadcast_prefix,
+    prefix_errors,
+    tree_align,
+    tree_all,
+    tree_any,
+    tree_broadcast_common,
+    tree_broadcast_map,
+    tree_broadcast_map_with_path,
+    tree_broadcast_prefix,
+    tree_copy,
+    tree_count,
+    tree_deepcopy,
+    tree_filter,
+    tree_flatten,
+    tree_flatten_into,
+    tree_flatten_multiple,
+    tree_flatten_with_accessor,
+    tree_flatten_with_path,
+    tree_format,
+    tree_hash,
+    tree_is_leaf,
+    tree_leaf_depths,
+    tree_leaves,
+    tree_map,
+    tree_map_with_path,
+    tree_map_with_path_,
+    tree_max,
+    tree_mean,
+    tree_min,
+    tree_partition,
+    tree_ravel,
+    tree_reduce_with_path,
+    tree_replace_nones,
+    tree_shared_leaves,
+    tree_structure,
+    tree_sum,
+    tree_transpose,
+    tree_transpose_map,
+    tree_type_counts,
+    tree_unflatten,
+    treespec_leaf,
+    treespec_defaultdict,
+    treespec_deque,
+    treespec_dict,
+    treespec_from_collection,
+    treespec_list,
+    treespec_none,
+    treespec_ordereddict,
+    treespec_tuple,
+)
+from rustree.registry import (
+    RegisteredType,
+    RegistrySnapshot,
+    deserialization_limits,
+    dict_insertion_ordered,
+    get_default_leaf_predicate,
+    is_leaf_type,
+    is_registered,
+    load_registrations,
+    metrics,
+    register_attrs,
+    register_dataclass,
+    register_node_class,
+    register_pydantic,
+    register_pytree_node,
+    register_pytree_node_class,
+    registered_types,
+    registry_restore,
+    registry_snapshot,
+    reset_metrics,
+    scoped_registrations,
+    set_default_leaf_predicate,
+    treespec_cache_clear,
+    treespec_cache_size,
+    unregister_pytree_node,
+)
+from rustree.schema import SchemaViolation, TreeSchema
 from rustree.typing import (
     PyTreeKind,
+    PyTreeNode,
+    PyTreeSpec,
+    TreeSpecBuilder,
+    Unraveler,
     is_namedtuple,
     is_namedtuple_class,
     is_namedtuple_instance,
@@ -44,9 +128,99 @@ from rustree.typing import (
 
 __all__ = [
     # Tree operations
+    'tree_flatten',
+    'tree_flatten_into',
+    'tree_flatten_multiple',
+    'tree_flatten_with_accessor',
+    'tree_flatten_with_path',
+    'tree_unflatten',
+    'tree_leaves',
+    'tree_structure',
     'tree_is_leaf',
+    'all_leaves',
+    'tree_map',
+    'tree_map_with_path',
+    'tree_map_with_path_',
+    'tree_partition',
+    'tree_filter',
+    'tree_broadcast_map',
+    'tree_broadcast_map_with_path',
+    'tree_broadcast_prefix',
+    'broadcast_prefix',
+    'prefix_errors',
+    'tree_broadcast_common',
+    'tree_reduce_with_path',
+    'tree_count',
+    'tree_sum',
+    'tree_mean',
+    'tree_max',
+    'tree_min',
+    'tree_all',
+    'tree_any',
+    'tree_leaf_depths',
+    'tree_shared_leaves',
+    'tree_type_counts',
+    'tree_copy',
+    'tree_deepcopy',
+    'tree_replace_nones',
+    'tree_hash',
+    'tree_format',
+    'tree_align',
+    'tree_transpose',
+    'tree_transpose_map',
+    'tree_ravel',
+    'treespec_leaf',
+    'treespec_none',
+    'treespec_tuple',
+    'treespec_list',
+    'treespec_dict',
+    'treespec_ordereddict',
+    'treespec_defaultdict',
+    'treespec_deque',
+    'treespec_from_collection',
+    # Diff and patch
+    'tree_diff',
+    'tree_patch',
+    'PyTreePatch',
+    'PyTreePatchOp',
+    # Lens
+    'lens',
+    'PyTreeLens',
+    'tree_replace',
+    # Schema
+    'TreeSchema',
+    'SchemaViolation',
+    # Registry
+    'register_pytree_node',
+    'register_pytree_node_class',
+    'register_node_class',
+    'register_dataclass',
+    'register_attrs',
+    'register_pydantic',
+    'unregister_pytree_node',
+    'RegistrySnapshot',
+    'registry_snapshot',
+    'registry_restore',
+    'scoped_registrations',
+    'dict_insertion_ordered',
+    'deserialization_limits',
+    'set_default_leaf_predicate',
+    'get_default_leaf_predicate',
+    'is_leaf_type',
+    'RegisteredType',
+    'registered_types',
+    'is_registered',
+    'load_registrations',
+    'metrics',
+    'reset_metrics',
+    'treespec_cache_clear',
+    'treespec_cache_size',
     # Typing
+    'PyTreeSpec',
     'PyTreeKind',
+    'PyTreeNode',
+    'TreeSpecBuilder',
+    'Unraveler',
     'is_namedtuple',
     'is_namedtuple_class',
     'is_namedtuple_instance',
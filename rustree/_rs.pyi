@@ -16,18 +16,392 @@
 # pylint: disable=all
 
 import enum
-from collections.abc import Callable, Collection
+import builtins
+from collections.abc import Callable, Collection, Hashable, Iterable, Iterator, Mapping, Sequence
+from typing import Any, Generic, Literal, NamedTuple, TypeVar, overload
 
 from rustree.typing import (
+    CustomTreeNode,
     FlattenFunc,
-    PyTreeEntry,
+    PyTreeAccessor,
+    StructSequence,
     T,
     UnflattenFunc,
 )
 
+_T_co = TypeVar('_T_co', covariant=True)
+_KT_co = TypeVar('_KT_co', covariant=True)
+_VT_co = TypeVar('_VT_co', covariant=True)
+
 # Set if the type allows subclassing (see CPython's Include/object.h)
 Py_TPFLAGS_BASETYPE: int  # (1UL << 10)
+# The C API capsule, see `rustree/include/rustree.h`
+_C_API: object
 
+def flatten(
+    tree: T,
+    /,
+    leaf_predicate: Callable[[T], bool] | None = None,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+    *,
+    prune_none: bool = False,
+    sort_dict_keys: bool | Callable[[Any], Any] | None = None,
+    shared_subtrees: bool = False,
+    weak_leaves: bool = False,
+    cache: bool = False,
+    max_nodes: int | None = None,
+    max_leaves: int | None = None,
+) -> tuple[list[T], PyTreeSpec]: ...
+def structure(
+    tree: T,
+    /,
+    leaf_predicate: Callable[[T], bool] | None = None,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> PyTreeSpec: ...
+def flatten_into(
+    tree: T,
+    leaves: list[T],
+    /,
+    leaf_predicate: Callable[[T], bool] | None = None,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+    *,
+    prune_none: bool = False,
+    sort_dict_keys: bool | Callable[[Any], Any] | None = None,
+    max_nodes: int | None = None,
+    max_leaves: int | None = None,
+) -> PyTreeSpec: ...
+def flatten_multiple(
+    trees: Iterable[T],
+    /,
+    leaf_predicate: Callable[[T], bool] | None = None,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> tuple[list[list[T]], PyTreeSpec]: ...
+def flatten_with_accessor(
+    tree: T,
+    /,
+    leaf_predicate: Callable[[T], bool] | None = None,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+    *,
+    prune_none: bool = False,
+    sort_dict_keys: bool | Callable[[Any], Any] | None = None,
+    max_nodes: int | None = None,
+    max_leaves: int | None = None,
+) -> tuple[list[PyTreeAccessor], list[T], PyTreeSpec]: ...
+def flatten_with_path(
+    tree: T,
+    /,
+    leaf_predicate: Callable[[T], bool] | None = None,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+    *,
+    prune_none: bool = False,
+    sort_dict_keys: bool | Callable[[Any], Any] | None = None,
+    max_nodes: int | None = None,
+    max_leaves: int | None = None,
+) -> tuple[list[tuple[tuple[Any, ...], T]], PyTreeSpec]: ...
+def map(
+    func: Callable[..., Any],
+    tree: T,
+    /,
+    *rests: Any,
+    leaf_predicate: Callable[[T], bool] | None = None,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+    sort_dict_keys: bool | Callable[[Any], Any] | None = None,
+) -> Any: ...
+def map_with_path(
+    func: Callable[..., Any],
+    tree: T,
+    /,
+    *rests: Any,
+    leaf_predicate: Callable[[T], bool] | None = None,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+    sort_dict_keys: bool | Callable[[Any], Any] | None = None,
+) -> Any: ...
+def map_with_path_(
+    func: Callable[..., Any],
+    tree: T,
+    /,
+    *rests: Any,
+    leaf_predicate: Callable[[T], bool] | None = None,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+    sort_dict_keys: bool | Callable[[Any], Any] | None = None,
+) -> T: ...
+def transpose_map(
+    func: Callable[..., Any],
+    tree: T,
+    /,
+    *rests: Any,
+    inner_treespec: PyTreeSpec | None = None,
+    leaf_predicate: Callable[[T], bool] | None = None,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> Any: ...
+def partition(
+    predicate: Callable[[Any], Any],
+    tree: T,
+    /,
+    fill_value: Any = None,
+    leaf_predicate: Callable[[T], bool] | None = None,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> tuple[T, T]: ...
+def filter(
+    predicate: Callable[[Any], Any],
+    tree: T,
+    /,
+    fill_value: Any = None,
+    leaf_predicate: Callable[[T], bool] | None = None,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> T: ...
+def reduce_with_path(
+    func: Callable[[Any, tuple[Any, ...], T], Any],
+    tree: T,
+    /,
+    *initial: Any,
+    leaf_predicate: Callable[[T], bool] | None = None,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> Any: ...
+def count(
+    predicate: Callable[[T], Any],
+    tree: T,
+    /,
+    leaf_predicate: Callable[[T], bool] | None = None,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> int: ...
+def sum(
+    tree: T,
+    /,
+    leaf_predicate: Callable[[T], bool] | None = None,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+    *,
+    start: Any = 0,
+) -> Any: ...
+def mean(
+    tree: T,
+    /,
+    leaf_predicate: Callable[[T], bool] | None = None,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> Any: ...
+def max(
+    tree: T,
+    /,
+    leaf_predicate: Callable[[T], bool] | None = None,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+    *default: T,
+    key: Callable[[T], Any] | None = None,
+) -> T: ...
+def min(
+    tree: T,
+    /,
+    leaf_predicate: Callable[[T], bool] | None = None,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+    *default: T,
+    key: Callable[[T], Any] | None = None,
+) -> T: ...
+def leaves(
+    tree: T,
+    /,
+    leaf_predicate: Callable[[T], bool] | None = None,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+    *,
+    of_type: type | tuple[type, ...] | None = None,
+    exact_type: bool = False,
+    weak_leaves: bool = False,
+) -> list[Any]: ...
+def shared_leaves(
+    tree: T,
+    /,
+    leaf_predicate: Callable[[T], bool] | None = None,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+    *,
+    ignore_types: type | tuple[type, ...] | None = None,
+) -> list[list[tuple[Any, ...]]]: ...
+@overload
+def type_counts(
+    tree: T,
+    /,
+    leaf_predicate: Callable[[T], bool] | None = None,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+    *,
+    with_sizes: Literal[False] = False,
+) -> dict[type, int]: ...
+@overload
+def type_counts(
+    tree: T,
+    /,
+    leaf_predicate: Callable[[T], bool] | None = None,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+    *,
+    with_sizes: Literal[True],
+) -> dict[type, tuple[int, int]]: ...
+def all(
+    tree: T,
+    /,
+    leaf_predicate: Callable[[T], bool] | None = None,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> bool: ...
+def any(
+    tree: T,
+    /,
+    leaf_predicate: Callable[[T], bool] | None = None,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> bool: ...
+def leaf_depths(
+    tree: T,
+    /,
+    leaf_predicate: Callable[[T], bool] | None = None,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> list[int]: ...
+def copy(
+    tree: T,
+    /,
+    leaf_predicate: Callable[[T], bool] | None = None,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> T: ...
+def deepcopy(
+    tree: T,
+    /,
+    leaf_predicate: Callable[[T], bool] | None = None,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+    *,
+    copy_leaf: Callable[[Any, dict[int, Any]], Any] | None = None,
+    memo: dict[int, Any] | None = None,
+) -> T: ...
+def replace_nones(
+    sentinel: Any,
+    tree: T,
+    /,
+    leaf_predicate: Callable[[T], bool] | None = None,
+    namespace: str = '',
+) -> Any: ...
+def hash(
+    tree: T,
+    /,
+    leaf_predicate: Callable[[T], bool] | None = None,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+    *,
+    leaf_hash: Callable[[T], Hashable] | None = None,
+) -> int: ...
+def format(
+    tree: T,
+    /,
+    leaf_predicate: Callable[[T], bool] | None = None,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+    *,
+    max_depth: int | None = None,
+    max_children: int | None = None,
+    leaf_repr: Callable[[T], str] | None = None,
+    max_leaf_width: int | None = None,
+    indent: int = 4,
+) -> str: ...
+def align(tree: Any, treespec: PyTreeSpec, /) -> Any: ...
+def transpose(
+    outer_treespec: PyTreeSpec,
+    inner_treespec: PyTreeSpec,
+    tree: Any,
+    /,
+    leaf_predicate: Callable[[Any], bool] | None = None,
+) -> Any: ...
+def ravel(
+    tree: Any,
+    backend: Any,
+    /,
+    leaf_predicate: Callable[[Any], bool] | None = None,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> tuple[Any, Unraveler]: ...
+def make_leaf(none_is_leaf: bool = False) -> PyTreeSpec: ...
+def make_none(none_is_leaf: bool = False) -> PyTreeSpec: ...
+def make_container(
+    kind: PyTreeKind,
+    children: Iterable[PyTreeSpec],
+    metadata: Any = None,
+    /,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> PyTreeSpec: ...
+def make_from_collection(
+    collection: Any,
+    /,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> PyTreeSpec: ...
+def broadcast_map(
+    func: Callable[..., Any],
+    tree: T,
+    /,
+    *rests: Any,
+    leaf_predicate: Callable[[T], bool] | None = None,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> Any: ...
+def broadcast_map_with_path(
+    func: Callable[..., Any],
+    tree: T,
+    /,
+    *rests: Any,
+    leaf_predicate: Callable[[T], bool] | None = None,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> Any: ...
+def broadcast_prefix(
+    prefix_tree: Any,
+    full_tree: T,
+    /,
+    leaf_predicate: Callable[[Any], bool] | None = None,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> T: ...
+def broadcast_prefix_leaves(
+    prefix_tree: Any,
+    full_tree: Any,
+    /,
+    leaf_predicate: Callable[[Any], bool] | None = None,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> list[Any]: ...
+def prefix_errors(
+    prefix_tree: Any,
+    full_tree: Any,
+    /,
+    leaf_predicate: Callable[[Any], bool] | None = None,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> list[PrefixError]: ...
+def broadcast_common(
+    tree: Any,
+    other_tree: Any,
+    /,
+    leaf_predicate: Callable[[Any], bool] | None = None,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> tuple[Any, Any]: ...
 def is_leaf(
     obj: T,
     /,
@@ -35,6 +409,31 @@ def is_leaf(
     none_is_leaf: bool = False,
     namespace: str = '',
 ) -> bool: ...
+def all_leaves(
+    iterable: Iterable[T],
+    /,
+    leaf_predicate: Callable[[T], bool] | None = None,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> bool: ...
+def is_leaf_type(
+    cls: type,
+    /,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> bool: ...
+def random_tree(
+    rng: Any,
+    /,
+    max_depth: int = 3,
+    container_weights: dict[PyTreeKind | type, float] | None = None,
+    leaf_factory: Callable[[Any], Any] | None = None,
+    *,
+    prototypes: Iterable[Any] | None = None,
+    max_children: int = 4,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> Any: ...
 def is_namedtuple(obj: object | type, /) -> bool: ...
 def is_namedtuple_instance(obj: object, /) -> bool: ...
 def is_namedtuple_class(cls: type, /) -> bool: ...
@@ -57,6 +456,259 @@ class PyTreeKind(enum.IntEnum):
     DEQUE = enum.auto()  # a collections.deque
     STRUCTSEQUENCE = enum.auto()  # a PyStructSequence
 
+    @classmethod
+    def of(
+        cls,
+        obj: Any,
+        /,
+        leaf_predicate: Callable[[Any], bool] | None = None,
+        none_is_leaf: bool = False,
+        namespace: str = '',
+    ) -> PyTreeKind: ...
+    @classmethod
+    def of_type(
+        cls,
+        node_type: type,
+        /,
+        none_is_leaf: bool = False,
+        namespace: str = '',
+    ) -> PyTreeKind: ...
+    @property
+    def node_type(self) -> type | None: ...
+
+class PyTreeEntry:
+    def __init__(self, entry: Any, type: builtins.type, kind: PyTreeKind) -> None: ...
+    @property
+    def entry(self) -> Any: ...
+    @property
+    def type(self) -> builtins.type: ...
+    @property
+    def kind(self) -> PyTreeKind: ...
+    def __call__(self, obj: Any, /) -> Any: ...
+    def __add__(self, other: object, /) -> PyTreeAccessor: ...
+    def __eq__(self, other: object, /) -> bool: ...
+    def __hash__(self, /) -> int: ...
+    def codify(self, /, node: str = '') -> str: ...
+
+class GetItemEntry(PyTreeEntry): ...
+
+class GetAttrEntry(PyTreeEntry):
+    @property
+    def name(self) -> str: ...
+
+class FlattenedEntry(PyTreeEntry): ...
+
+class SequenceEntry(GetItemEntry, Generic[_T_co]):
+    @property
+    def entry(self) -> int: ...
+    @property
+    def type(self) -> builtins.type[Sequence[_T_co]]: ...
+    @property
+    def index(self) -> int: ...
+    def __call__(self, obj: Sequence[_T_co], /) -> _T_co: ...
+
+class MappingEntry(GetItemEntry, Generic[_KT_co, _VT_co]):
+    @property
+    def entry(self) -> _KT_co: ...
+    @property
+    def type(self) -> builtins.type[Mapping[_KT_co, _VT_co]]: ...
+    @property
+    def key(self) -> _KT_co: ...
+    def __call__(self, obj: Mapping[_KT_co, _VT_co], /) -> _VT_co: ...
+
+class NamedTupleEntry(SequenceEntry[T]):
+    @property
+    def type(self) -> builtins.type[NamedTuple[T]]: ...  # type: ignore[override,type-arg]
+    @property
+    def kind(self) -> Literal[PyTreeKind.NAMEDTUPLE]: ...
+    @property
+    def fields(self) -> tuple[str, ...]: ...
+    @property
+    def field(self) -> str: ...
+
+class StructSequenceEntry(SequenceEntry[T]):
+    @property
+    def type(self) -> builtins.type[StructSequence[T]]: ...  # type: ignore[override]
+    @property
+    def kind(self) -> Literal[PyTreeKind.STRUCTSEQUENCE]: ...
+    @property
+    def fields(self) -> tuple[str, ...]: ...
+    @property
+    def field(self) -> str: ...
+
+class DataclassEntry(GetAttrEntry):
+    @property
+    def entry(self) -> str | int: ...
+    @property
+    def fields(self) -> tuple[str, ...]: ...
+    @property
+    def init_fields(self) -> tuple[str, ...]: ...
+    @property
+    def field(self) -> str: ...
+
+class PyTreeSpec:
+    num_nodes: int
+    num_leaves: int
+    num_children: int
+    none_is_leaf: bool
+    namespace: str
+    type: type | None
+    kind: PyTreeKind
+    def unflatten(
+        self,
+        leaves: Iterable[T],
+        /,
+        validate: Callable[[int, tuple[Any, ...], T], Any] | None = None,
+    ) -> Any: ...
+    def walk(
+        self,
+        leaves: Iterable[T],
+        /,
+        f_node: Callable[[type | None, Any, tuple[Any, ...]], Any] | None = None,
+        f_leaf: Callable[[T], Any] | None = None,
+    ) -> Any: ...
+    def unflatten_partial(
+        self,
+        leaves: Mapping[int | tuple[Any, ...], Any],
+        /,
+        default: Any = None,
+        *,
+        default_factory: Callable[[int, tuple[Any, ...]], Any] | None = None,
+    ) -> Any: ...
+    def flatten(
+        self,
+        tree: T,
+        /,
+        leaf_predicate: Callable[[T], bool] | None = None,
+    ) -> list[T]: ...
+    def flatten_up_to(self, tree: Any, /) -> list[Any]: ...
+    def replace_subtree(self, path: Iterable[Any], new_subspec: PyTreeSpec, /) -> PyTreeSpec: ...
+    def index(self, path: Iterable[Any], /) -> int: ...
+    def leaf_range(self, path: Iterable[Any], /) -> tuple[int, int]: ...
+    def entry_type_at(self, path: Iterable[Any], /) -> tuple[type[PyTreeEntry], ...]: ...
+    def is_leaf(self, strict: bool = True) -> bool: ...
+    def is_prefix(self, other: PyTreeSpec, /, strict: bool = False) -> bool: ...
+    def is_suffix(self, other: PyTreeSpec, /, strict: bool = False) -> bool: ...
+    def compose(self, inner: PyTreeSpec, /) -> PyTreeSpec: ...
+    def __add__(self, other: PyTreeSpec, /) -> PyTreeSpec: ...
+    def transform(
+        self,
+        f_node: Callable[[PyTreeSpec], PyTreeSpec] | None = None,
+        f_leaf: Callable[[PyTreeSpec], PyTreeSpec] | None = None,
+    ) -> PyTreeSpec: ...
+    def broadcast_to_common_suffix(self, other: PyTreeSpec, /) -> PyTreeSpec: ...
+    def traverse(self) -> Iterator[PyTreeNode]: ...
+    def children(self) -> list[PyTreeSpec]: ...
+    def child(self, index: int, /) -> PyTreeSpec: ...
+    def entries(self) -> list[Any]: ...
+    def entry(self, index: int, /) -> Any: ...
+    def paths(self) -> list[tuple[Any, ...]]: ...
+    def accessors(self) -> list[PyTreeAccessor]: ...
+    @overload
+    def is_namespace_compatible(
+        self,
+        other: PyTreeSpec,
+        /,
+        *,
+        return_reason: Literal[False] = False,
+    ) -> bool: ...
+    @overload
+    def is_namespace_compatible(
+        self,
+        other: PyTreeSpec,
+        /,
+        *,
+        return_reason: Literal[True],
+    ) -> tuple[bool, str | None]: ...
+    def repr(self, depth: int | None = None, max_children: int | None = None) -> str: ...
+    def __len__(self) -> int: ...
+    def __eq__(self, other: object, /) -> bool: ...
+    def __ne__(self, other: object, /) -> bool: ...
+    def __hash__(self) -> int: ...
+    def __reduce__(self) -> tuple[Callable[..., PyTreeSpec], tuple[Any, ...]]: ...
+    def __copy__(self) -> PyTreeSpec: ...
+    def __deepcopy__(self, memo: dict[int, Any], /) -> PyTreeSpec: ...
+    def to_bytes(self) -> bytes: ...
+    @classmethod
+    def from_bytes(cls, data: bytes, /, *, trusted: bool = False) -> PyTreeSpec: ...
+
+class PyTreeNode:
+    @property
+    def kind(self) -> PyTreeKind: ...
+    @property
+    def arity(self) -> int: ...
+    @property
+    def node_data(self) -> Any: ...
+    @property
+    def node_type(self) -> type | None: ...
+    @property
+    def num_leaves(self) -> int: ...
+    @property
+    def entries(self) -> tuple[Any, ...]: ...
+    @property
+    def reference(self) -> int | None: ...
+
+class TreeSpecBuilder:
+    def __init__(self, none_is_leaf: bool = False, namespace: str = '') -> None: ...
+    def push_leaf(self) -> None: ...
+    def push_node(
+        self,
+        kind: PyTreeKind,
+        arity: int = 0,
+        metadata: Any = None,
+        /,
+        *,
+        node_type: type | None = None,
+        entries: Iterable[Any] | None = None,
+    ) -> None: ...
+    def build(self) -> PyTreeSpec: ...
+
+class PrefixError:
+    def __call__(self, name: str, /) -> ValueError: ...
+    @property
+    def path(self) -> tuple[Any, ...]: ...
+    @property
+    def reason(self) -> str: ...
+
+class Unraveler:
+    def __call__(self, flat: Any, /) -> Any: ...
+    @property
+    def treespec(self) -> PyTreeSpec: ...
+    @property
+    def shapes(self) -> tuple[Any, ...]: ...
+    @property
+    def dtypes(self) -> tuple[Any, ...]: ...
+    @property
+    def offsets(self) -> tuple[int, ...]: ...
+    @property
+    def size(self) -> int: ...
+
+class RegistrySnapshot:
+    def __len__(self) -> int: ...
+
+def set_tracer(tracer: Any | None, /) -> Any | None: ...
+def get_tracer() -> Any | None: ...
+def set_debug(enabled: bool, /, callback: Callable[[str, str], Any] | None = None) -> None: ...
+def is_debug_enabled() -> bool: ...
+def metrics() -> dict[str, Any]: ...
+def reset_metrics() -> None: ...
+def treespec_cache_clear() -> None: ...
+def treespec_cache_size() -> int: ...
+def profile(
+    fn_name: Literal[
+        'flatten',
+        'flatten_with_accessor',
+        'map',
+        'map_with_path',
+        'map_with_path_',
+        'is_leaf',
+    ],
+    tree: Any,
+    /,
+    *args: Any,
+    **kwargs: Any,
+) -> dict[str, Any]: ...
+def load_registrations(group: str = 'rustree.register', /, *, reload: bool = False) -> list[str]: ...
 def register_node(
     cls: type[Collection[T]],
     /,
@@ -64,12 +716,30 @@ def register_node(
     unflatten_func: UnflattenFunc[T],
     path_entry_type: type[PyTreeEntry],
     namespace: str = '',
+    *,
+    kind_hint: Literal['mapping', 'sequence', 'object'] | None = None,
+    data_fields: Iterable[str] | None = None,
+    init_args: Iterable[str] | None = None,
+    meta_fields: Iterable[str] | None = None,
+    init_func: Callable[..., Any] | None = None,
 ) -> None: ...
+def register_node_class(
+    cls: type[CustomTreeNode[T]],
+    /,
+    path_entry_type: type[PyTreeEntry],
+    namespace: str = '',
+    *,
+    kind_hint: Literal['mapping', 'sequence', 'object'] | None = None,
+) -> tuple[FlattenFunc[T], UnflattenFunc[T]]: ...
 def unregister_node(
     cls: type[Collection[T]],
     /,
     namespace: str = '',
 ) -> None: ...
+def registry_snapshot() -> RegistrySnapshot: ...
+def registered_types(namespace: str = '') -> list[tuple[type, PyTreeKind, bool, str]]: ...
+def is_registered(cls: type, /, namespace: str = '') -> bool: ...
+def registry_restore(snapshot: RegistrySnapshot, /) -> None: ...
 def is_dict_insertion_ordered(
     namespace: str = '',
     inherit_global_namespace: bool = True,
@@ -79,3 +749,34 @@ def set_dict_insertion_ordered(
     /,
     namespace: str = '',
 ) -> None: ...
+def get_leaf_predicate(
+    namespace: str = '',
+) -> Callable[[Any], bool] | None: ...
+def set_leaf_predicate(
+    leaf_predicate: Callable[[Any], bool] | None,
+    /,
+    namespace: str,
+) -> Callable[[Any], bool] | None: ...
+def get_at_path(
+    tree: Any,
+    path: Iterable[Any],
+    /,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> Any: ...
+def replace_at_path(
+    tree: Any,
+    path: Iterable[Any],
+    value: Any,
+    /,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> Any: ...
+def get_load_limits() -> tuple[int | None, int | None, int | None, bool]: ...
+def set_load_limits(
+    max_nodes: int | None,
+    max_arity: int | None,
+    max_depth: int | None,
+    trusted: bool,
+    /,
+) -> None: ...
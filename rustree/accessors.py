@@ -17,20 +17,28 @@
 from __future__ import annotations
 
 import dataclasses
-import sys
 from collections.abc import Iterable, Mapping, Sequence
-from typing import TYPE_CHECKING, Any, ClassVar, Generic, Literal, TypeVar, overload
+from typing import TYPE_CHECKING, Any, ClassVar, overload
 from typing_extensions import Self  # Python 3.11+
 
 import rustree._rs as _rs
-from rustree._rs import PyTreeKind
+from rustree._rs import (
+    DataclassEntry,
+    FlattenedEntry,
+    GetAttrEntry,
+    GetItemEntry,
+    MappingEntry,
+    NamedTupleEntry,
+    PyTreeEntry,
+    PyTreeKind,
+    SequenceEntry,
+    StructSequenceEntry,
+)
 
 
 if TYPE_CHECKING:
     import builtins
 
-    from rustree.typing import NamedTuple, StructSequence
-
 
 __all__ = [
     'PyTreeEntry',
@@ -47,88 +55,9 @@ __all__ = [
 ]
 
 
-SLOTS = {'slots': True} if sys.version_info >= (3, 10) else {}  # Python 3.10+
-
-
-@dataclasses.dataclass(init=True, repr=False, eq=False, frozen=True, **SLOTS)
-class PyTreeEntry:
-    """Base class for path entries."""
-
-    entry: Any
-    type: builtins.type
-    kind: PyTreeKind
-
-    def __post_init__(self, /) -> None:
-        """Post-initialize the path entry."""
-        if self.kind == PyTreeKind.LEAF:
-            raise ValueError('Cannot create a leaf path entry.')
-        if self.kind == PyTreeKind.NONE:
-            raise ValueError('Cannot create a path entry for None.')
-
-    def __call__(self, obj: Any, /) -> Any:
-        """Get the child object."""
-        try:
-            return obj[self.entry]  # should be overridden
-        except TypeError as ex:
-            raise TypeError(
-                f'{self.__class__!r} cannot access through {obj!r} via entry {self.entry!r}',
-            ) from ex
-
-    def __add__(self, other: object, /) -> PyTreeAccessor:
-        """Join the path entry with another path entry or accessor."""
-        if isinstance(other, PyTreeEntry):
-            return PyTreeAccessor((self, other))
-        if isinstance(other, PyTreeAccessor):
-            return PyTreeAccessor((self, *other))
-        return NotImplemented
-
-    def __eq__(self, other: object, /) -> bool:
-        """Check if the path entries are equal."""
-        return isinstance(other, PyTreeEntry) and (
-            (
-                self.entry,
-                self.type,
-                self.kind,
-                self.__class__.__call__.__code__.co_code,
-                self.__class__.codify.__code__.co_code,
-            )
-            == (
-                other.entry,
-                other.type,
-                other.kind,
-                other.__class__.__call__.__code__.co_code,
-                other.__class__.codify.__code__.co_code,
-            )
-        )
-
-    def __hash__(self, /) -> int:
-        """Get the hash of the path entry."""
-        return hash(
-            (
-                self.entry,
-                self.type,
-                self.kind,
-                self.__class__.__call__.__code__.co_code,
-                self.__class__.codify.__code__.co_code,
-            ),
-        )
-
-    def __repr__(self, /) -> str:
-        """Get the representation of the path entry."""
-        return f'{self.__class__.__name__}(entry={self.entry!r}, type={self.type!r})'
-
-    def codify(self, /, node: str = '') -> str:
-        """Generate code for accessing the path entry."""
-        return f'{node}[<flat index {self.entry!r}>]'  # should be overridden
-
-
-del SLOTS
-
-
-_T = TypeVar('_T')
-_T_co = TypeVar('_T_co', covariant=True)
-_KT_co = TypeVar('_KT_co', covariant=True)
-_VT_co = TypeVar('_VT_co', covariant=True)
+# The path entry classes are implemented in Rust except `AutoEntry` and `PyTreeAccessor`. The
+# constructor of `AutoEntry` returns instances of other classes and `PyTreeAccessor` is a subclass
+# of `tuple`, neither of which a Rust class can do.
 
 
 class AutoEntry(PyTreeEntry):
@@ -149,7 +78,7 @@ class AutoEntry(PyTreeEntry):
 
         if cls is not AutoEntry:
             # Use the subclass type if the type is explicitly specified
-            return super().__new__(cls)
+            return super().__new__(cls, entry, type, kind)
 
         if kind != PyTreeKind.CUSTOM:
             raise ValueError(f'Cannot create an automatic path entry for PyTreeKind {kind!r}.')
@@ -181,187 +110,15 @@ class AutoEntry(PyTreeEntry):
         raise NotImplementedError('Unreachable code.')
 
 
-class GetItemEntry(PyTreeEntry):
-    """A generic path entry class for nodes that access their children by :meth:`__getitem__`."""
-
-    __slots__: ClassVar[tuple[()]] = ()
-
-    def __call__(self, obj: Any, /) -> Any:
-        """Get the child object."""
-        return obj[self.entry]
-
-    def codify(self, /, node: str = '') -> str:
-        """Generate code for accessing the path entry."""
-        return f'{node}[{self.entry!r}]'
-
-
-class GetAttrEntry(PyTreeEntry):
-    """A generic path entry class for nodes that access their children by :meth:`__getattr__`."""
-
-    __slots__: ClassVar[tuple[()]] = ()
-
-    entry: str
-
-    @property
-    def name(self, /) -> str:
-        """Get the attribute name."""
-        return self.entry
-
-    def __call__(self, obj: Any, /) -> Any:
-        """Get the child object."""
-        return getattr(obj, self.name)
-
-    def codify(self, /, node: str = '') -> str:
-        """Generate code for accessing the path entry."""
-        return f'{node}.{self.name}'
-
-
-class FlattenedEntry(PyTreeEntry):  # pylint: disable=too-few-public-methods
-    """A fallback path entry class for flattened objects."""
-
-    __slots__: ClassVar[tuple[()]] = ()
-
-
-class SequenceEntry(GetItemEntry, Generic[_T_co]):
-    """A path entry class for sequences."""
-
-    __slots__: ClassVar[tuple[()]] = ()
-
-    entry: int
-    type: builtins.type[Sequence[_T_co]]
-
-    @property
-    def index(self, /) -> int:
-        """Get the index."""
-        return self.entry
-
-    def __call__(self, obj: Sequence[_T_co], /) -> _T_co:
-        """Get the child object."""
-        return obj[self.index]
-
-    def __repr__(self, /) -> str:
-        """Get the representation of the path entry."""
-        return f'{self.__class__.__name__}(index={self.index!r}, type={self.type!r})'
-
-
-class MappingEntry(GetItemEntry, Generic[_KT_co, _VT_co]):
-    """A path entry class for mappings."""
-
-    __slots__: ClassVar[tuple[()]] = ()
-
-    entry: _KT_co
-    type: builtins.type[Mapping[_KT_co, _VT_co]]
-
-    @property
-    def key(self, /) -> _KT_co:
-        """Get the key."""
-        return self.entry
-
-    def __call__(self, obj: Mapping[_KT_co, _VT_co], /) -> _VT_co:
-        """Get the child object."""
-        return obj[self.key]
-
-    def __repr__(self, /) -> str:
-        """Get the representation of the path entry."""
-        return f'{self.__class__.__name__}(key={self.key!r}, type={self.type!r})'
-
-
-class NamedTupleEntry(SequenceEntry[_T]):
-    """A path entry class for namedtuple objects."""
-
-    __slots__: ClassVar[tuple[()]] = ()
-
-    entry: int
-    type: builtins.type[NamedTuple[_T]]  # type: ignore[type-arg]
-    kind: Literal[PyTreeKind.NAMEDTUPLE]
-
-    @property
-    def fields(self, /) -> tuple[str, ...]:
-        """Get the field names."""
-        from rustree.typing import namedtuple_fields  # pylint: disable=import-outside-toplevel
-
-        return namedtuple_fields(self.type)
-
-    @property
-    def field(self, /) -> str:
-        """Get the field name."""
-        return self.fields[self.entry]
-
-    def __repr__(self, /) -> str:
-        """Get the representation of the path entry."""
-        return f'{self.__class__.__name__}(field={self.field!r}, type={self.type!r})'
-
-    def codify(self, /, node: str = '') -> str:
-        """Generate code for accessing the path entry."""
-        return f'{node}.{self.field}'
-
-
-class StructSequenceEntry(SequenceEntry[_T]):
-    """A path entry class for PyStructSequence objects."""
-
-    __slots__: ClassVar[tuple[()]] = ()
-
-    entry: int
-    type: builtins.type[StructSequence[_T]]
-    kind: Literal[PyTreeKind.STRUCTSEQUENCE]
-
-    @property
-    def fields(self, /) -> tuple[str, ...]:
-        """Get the field names."""
-        from rustree.typing import structseq_fields  # pylint: disable=import-outside-toplevel
-
-        return structseq_fields(self.type)
-
-    @property
-    def field(self, /) -> str:
-        """Get the field name."""
-        return self.fields[self.entry]
-
-    def __repr__(self, /) -> str:
-        """Get the representation of the path entry."""
-        return f'{self.__class__.__name__}(field={self.field!r}, type={self.type!r})'
-
-    def codify(self, /, node: str = '') -> str:
-        """Generate code for accessing the path entry."""
-        return f'{node}.{self.field}'
-
-
-class DataclassEntry(GetAttrEntry):
-    """A path entry class for dataclasses."""
-
-    __slots__: ClassVar[tuple[()]] = ()
-
-    entry: str | int  # type: ignore[assignment]
-
-    @property
-    def fields(self, /) -> tuple[str, ...]:  # pragma: no cover
-        """Get all field names."""
-        return tuple(f.name for f in dataclasses.fields(self.type))
-
-    @property
-    def init_fields(self, /) -> tuple[str, ...]:
-        """Get the init field names."""
-        return tuple(f.name for f in dataclasses.fields(self.type) if f.init)
-
-    @property
-    def field(self, /) -> str:
-        """Get the field name."""
-        if isinstance(self.entry, int):
-            return self.init_fields[self.entry]
-        return self.entry
-
-    @property
-    def name(self, /) -> str:
-        """Get the attribute name."""
-        return self.field
-
-    def __repr__(self, /) -> str:
-        """Get the representation of the path entry."""
-        return f'{self.__class__.__name__}(field={self.field!r}, type={self.type!r})'
-
-
 class PyTreeAccessor(tuple[PyTreeEntry, ...]):
-    """A path class for PyTrees."""
+    """A path class for PyTrees.
+
+    Unlike the path entry classes, the accessor class is implemented in Python rather than in Rust.
+    The accessors must stay :class:`tuple` instances, since they are unpacked, sliced, and compared
+    as the tuples of their path entries, and the Rust classes cannot subclass :class:`tuple`.
+    :meth:`PyTreeSpec.accessors` builds the path entries in Rust and creates each accessor with
+    ``tuple.__new__`` without running the validation in the Python constructor.
+    """
 
     __slots__: ClassVar[tuple[()]] = ()
 
@@ -434,10 +191,10 @@ class PyTreeAccessor(tuple[PyTreeEntry, ...]):
 
 # These classes are used internally in the Rust side for accessor APIs
 _name, _cls = '', object
-for _name in __all__:
+for _name in ('AutoEntry', 'PyTreeAccessor'):
     _cls = globals()[_name]
     if not isinstance(_cls, type):  # pragma: no cover
         raise TypeError(f'Expected a class, got {_cls!r}.')
-    _cls.__module__ = 'optree'
+    _cls.__module__ = 'rustree'
     setattr(_rs, _name, _cls)
 del _name, _cls
@@ -0,0 +1,302 @@
+# Copyright 2024-2025 Xuehai Pan. All Rights Reserved.
+#
+# Licensed under the Apache License, Version 2.0 (the "License");
+# you may not use this file except in compliance with the License.
+# You may obtain a copy of the License at
+#
+#     http://www.apache.org/licenses/LICENSE-2.0
+#
+# Unless required by applicable law or agreed to in writing, software
+# distributed under the License is distributed on an "AS IS" BASIS,
+# WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+# See the License for the specific language governing permissions and
+# limitations under the License.
+# ==============================================================================
+"""Structural diffs and patches between pytrees."""
+
+from __future__ import annotations
+
+from collections import deque
+from typing import TYPE_CHECKING, Any, Callable, Literal, NamedTuple
+
+import rustree._rs as _rs
+from rustree._rs import PyTreeKind
+from rustree.lenses import lens
+
+
+__all__ = ['PyTreePatchOp', 'PyTreePatch', 'tree_diff', 'tree_patch']
+
+
+if TYPE_CHECKING:
+    from rustree.accessors import PyTreeEntry
+    from rustree.typing import PyTreeSpec
+
+
+_MAPPING_KINDS: frozenset[PyTreeKind] = frozenset(
+    {PyTreeKind.DICT, PyTreeKind.ORDEREDDICT, PyTreeKind.DEFAULTDICT},
+)
+_SEQUENCE_KINDS: frozenset[PyTreeKind] = frozenset({PyTreeKind.LIST, PyTreeKind.DEQUE})
+
+
+def _flatten_one_level(
+    node: Any,
+    /,
+    is_leaf: Callable[[Any], bool] | None = None,
+    *,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> tuple[list[PyTreeEntry], list[Any], PyTreeSpec]:
+    """Flatten the node by one level into its path entries, children, and one-level treespec."""
+    if is_leaf is None:
+        leaf_predicate = lambda x: x is not node  # noqa: E731
+    else:
+        leaf_predicate = lambda x: x is not node or is_leaf(x)  # noqa: E731
+    accessors, children, treespec = _rs.flatten_with_accessor(
+        node,
+        leaf_predicate,
+        none_is_leaf,
+        namespace,
+    )
+    if treespec.kind == PyTreeKind.LEAF:
+        return [], children, treespec
+    return [accessor[0] for accessor in accessors], children, treespec
+
+
+class PyTreePatchOp(NamedTuple):
+    """A single patch operation at a path of a pytree.
+
+    The path is a tuple of path entry keys from the root (see :func:`rustree.lens`). For ``'insert'``
+    and ``'delete'`` operations, the last key of the path is the key in the parent mapping or the
+    index in the parent sequence. Both the old and new values are recorded so the operation can be
+    inverted.
+    """
+
+    op: Literal['insert', 'delete', 'replace']
+    path: tuple[Any, ...]
+    old: Any = None
+    new: Any = None
+
+    def inverse(self, /) -> PyTreePatchOp:
+        """Get the operation that undoes this operation."""
+        op = {'insert': 'delete', 'delete': 'insert', 'replace': 'replace'}[self.op]
+        return PyTreePatchOp(op, self.path, self.new, self.old)  # type: ignore[arg-type]
+
+
+class PyTreePatch(tuple[PyTreePatchOp, ...]):
+    """An ordered sequence of patch operations.
+
+    >>> patch = tree_diff({'a': 1, 'b': [2, 3]}, {'a': 1, 'b': [2], 'c': 4})
+    >>> patch
+    PyTreePatch([PyTreePatchOp(op='delete', path=('b', 1), old=3, new=None), PyTreePatchOp(op='insert', path=('c',), old=None, new=4)])
+    >>> patch.inverse()
+    PyTreePatch([PyTreePatchOp(op='delete', path=('c',), old=4, new=None), PyTreePatchOp(op='insert', path=('b', 1), old=None, new=3)])
+    """  # pylint: disable=line-too-long
+
+    __slots__: tuple[()] = ()
+
+    def inverse(self, /) -> PyTreePatch:
+        """Get the patch that undoes this patch."""
+        return PyTreePatch(op.inverse() for op in reversed(self))
+
+    def __repr__(self, /) -> str:
+        """Get the representation of the patch."""
+        return f'{self.__class__.__name__}({list(self)!r})'
+
+
+def _leaf_equal(old: Any, new: Any, /) -> bool:
+    if old is new:
+        return True
+    if type(old) is not type(new):
+        return False
+    try:
+        return bool(old == new)
+    except Exception:  # noqa: BLE001 # pylint: disable=broad-exception-caught
+        return False
+
+
+def tree_diff(
+    old: Any,
+    new: Any,
+    /,
+    is_leaf: Callable[[Any], bool] | None = None,
+    *,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> PyTreePatch:
+    """Compute the patch that transforms one pytree into another.
+
+    Mappings and lists / deques are diffed key by key and index by index, producing ``'insert'`` and
+    ``'delete'`` operations for added and removed children. Other nodes are diffed child by child if
+    they have the same node type and metadata, otherwise the whole node is replaced. Leaves are
+    compared with ``==`` and are replaced if they differ in type or value.
+
+    The result satisfies ``tree_patch(old, tree_diff(old, new)) == new`` and
+    ``tree_patch(new, tree_diff(old, new).inverse()) == old``.
+
+    >>> old = {'x': 1, 'y': (2, [3, 4]), 'z': None}
+    >>> new = {'x': 1, 'y': (5, [3]), 'w': 6}
+    >>> patch = tree_diff(old, new)
+    >>> [(op.op, op.path) for op in patch]
+    [('delete', ('z',)), ('replace', ('y', 0)), ('delete', ('y', 1, 1)), ('insert', ('w',))]
+    >>> tree_patch(old, patch) == new
+    True
+    >>> tree_patch(new, patch.inverse()) == old
+    True
+
+    Args:
+        old (pytree): The source pytree.
+        new (pytree): The target pytree.
+        is_leaf (callable, optional): An optionally specified function that will be called at each
+            flattening step. It should return a boolean, with :data:`True` stopping the traversal
+            and the whole subtree being treated as a leaf, and :data:`False` indicating the
+            flattening should traverse the current object.
+        none_is_leaf (bool, optional): Whether to treat :data:`None` as a leaf. If :data:`False`,
+            :data:`None` is a non-leaf node with arity 0. (default: :data:`False`)
+        namespace (str, optional): The registry namespace used for custom pytree node types.
+            (default: :const:`''`, i.e., the global namespace)
+
+    Returns:
+        A :class:`PyTreePatch` that transforms ``old`` into ``new``.
+    """
+    ops: list[PyTreePatchOp] = []
+
+    def diff(old: Any, new: Any, path: tuple[Any, ...]) -> None:
+        old_entries, old_children, old_spec = _flatten_one_level(
+            old,
+            is_leaf,
+            none_is_leaf=none_is_leaf,
+            namespace=namespace,
+        )
+        new_entries, new_children, new_spec = _flatten_one_level(
+            new,
+            is_leaf,
+            none_is_leaf=none_is_leaf,
+            namespace=namespace,
+        )
+        kind = old_spec.kind
+
+        if kind == PyTreeKind.LEAF or new_spec.kind == PyTreeKind.LEAF:
+            if kind != new_spec.kind or not _leaf_equal(old, new):
+                ops.append(PyTreePatchOp('replace', path, old, new))
+            return
+        if old_spec.type is not new_spec.type:
+            ops.append(PyTreePatchOp('replace', path, old, new))
+            return
+
+        if kind in _MAPPING_KINDS and (
+            kind != PyTreeKind.DEFAULTDICT or old.default_factory is new.default_factory
+        ):
+            old_keys = [entry.entry for entry in old_entries]
+            new_keys = [entry.entry for entry in new_entries]
+            old_key_set, new_key_set = set(old_keys), set(new_keys)
+            common = [key for key in old_keys if key in new_key_set]
+            deleted = [key for key in old_keys if key not in new_key_set]
+            inserted = [key for key in new_keys if key not in old_key_set]
+            if kind == PyTreeKind.ORDEREDDICT and (
+                old_keys != common + deleted or new_keys != common + inserted
+            ):
+                # Insertions and deletions only happen at the end of an OrderedDict
+                ops.append(PyTreePatchOp('replace', path, old, new))
+                return
+            old_map = dict(zip(old_keys, old_children))
+            new_map = dict(zip(new_keys, new_children))
+            for key in reversed(deleted):
+                ops.append(PyTreePatchOp('delete', (*path, key), old_map[key], None))
+            for key in common:
+                diff(old_map[key], new_map[key], (*path, key))
+            for key in inserted:
+                ops.append(PyTreePatchOp('insert', (*path, key), None, new_map[key]))
+            return
+
+        if kind in _SEQUENCE_KINDS and (kind != PyTreeKind.DEQUE or old.maxlen == new.maxlen):
+            num_common = min(len(old_children), len(new_children))
+            for index in reversed(range(num_common, len(old_children))):
+                ops.append(PyTreePatchOp('delete', (*path, index), old_children[index], None))
+            for index in range(num_common):
+                diff(old_children[index], new_children[index], (*path, index))
+            for index in range(num_common, len(new_children)):
+                ops.append(PyTreePatchOp('insert', (*path, index), None, new_children[index]))
+            return
+
+        # The equality of the one-level treespecs covers the node type and the node metadata
+        if old_spec != new_spec:
+            ops.append(PyTreePatchOp('replace', path, old, new))
+            return
+        for entry, old_child, new_child in zip(old_entries, old_children, new_children):
+            diff(old_child, new_child, (*path, entry.entry))
+
+    diff(old, new, ())
+    return PyTreePatch(ops)
+
+
+def tree_patch(
+    tree: Any,
+    patch: PyTreePatch | list[PyTreePatchOp],
+    /,
+    *,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> Any:
+    """Apply a patch to a pytree and return the new pytree.
+
+    The input tree is not modified. Only the nodes along the patched paths are rebuilt, all other
+    subtrees are shared with the input.
+
+    >>> tree_patch({'a': [1, 2]}, [PyTreePatchOp('insert', ('a', 2), new=3)])
+    {'a': [1, 2, 3]}
+    >>> tree_patch({'a': [1, 2]}, [PyTreePatchOp('replace', ('a',), new=None)])
+    {'a': None}
+
+    Args:
+        tree (pytree): The pytree to patch.
+        patch (PyTreePatch or list of PyTreePatchOp): The patch operations to apply in order.
+        none_is_leaf (bool, optional): Whether to treat :data:`None` as a leaf. If :data:`False`,
+            :data:`None` is a non-leaf node with arity 0. (default: :data:`False`)
+        namespace (str, optional): The registry namespace used for custom pytree node types.
+            (default: :const:`''`, i.e., the global namespace)
+
+    Returns:
+        The patched pytree.
+    """
+    for op in patch:
+        if op.op == 'replace':
+            tree = lens(op.path, none_is_leaf=none_is_leaf, namespace=namespace).set(tree, op.new)
+            continue
+        if op.op not in {'insert', 'delete'}:
+            raise ValueError(f'Unknown patch operation {op.op!r}.')
+        if not op.path:
+            raise ValueError(f'Cannot {op.op} at the root of a pytree.')
+
+        *parent_path, key = op.path
+
+        def update(parent: Any, op: PyTreePatchOp = op, key: Any = key) -> Any:
+            _, _, treespec = _flatten_one_level(
+                parent,
+                none_is_leaf=none_is_leaf,
+                namespace=namespace,
+            )
+            kind = treespec.kind
+            if kind in _MAPPING_KINDS:
+                node = parent.copy()
+                if op.op == 'insert':
+                    node[key] = op.new
+                else:
+                    del node[key]
+                return node
+            if kind in _SEQUENCE_KINDS:
+                children = list(parent)
+                if op.op == 'insert':
+                    children.insert(key, op.new)
+                else:
+                    del children[key]
+                if kind == PyTreeKind.DEQUE:
+                    return deque(children, maxlen=parent.maxlen)
+                return children
+            raise TypeError(f'Cannot {op.op} a child of node {parent!r}.')
+
+        tree = lens(
+            tuple(parent_path),
+            none_is_leaf=none_is_leaf,
+            namespace=namespace,
+        ).modify(tree, update)
+    return tree
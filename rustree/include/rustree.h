@@ -0,0 +1,101 @@
+/*
+Copyright 2024-2025 Xuehai Pan. All Rights Reserved.
+
+Licensed under the Apache License, Version 2.0 (the "License");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an "AS IS" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+================================================================================
+*/
+
+/*
+The C API of RusTree for other compiled extensions.
+
+Usage:
+
+    #include <rustree.h>
+
+    // In the module init function:
+    if (RusTree_ImportCAPI() < 0) {
+        return NULL;
+    }
+
+    PyObject *treespec = NULL;
+    PyObject *leaves = RusTree_CAPI->flatten(tree, NULL, 0, "", &treespec);
+
+All functions must be called with the GIL held. On failure, they set a Python exception and return
+NULL or -1. Use `rustree.utils.get_include()` to get the include directory.
+*/
+
+#ifndef RUSTREE_H_
+#define RUSTREE_H_
+
+#include <Python.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define RUSTREE_CAPI_VERSION 1
+#define RUSTREE_CAPSULE_NAME "rustree._rs._C_API"
+
+/* Keep in sync with `PyTreeKind` in `src/rustree/registry.rs` */
+typedef enum {
+    RUSTREE_KIND_CUSTOM = 0,
+    RUSTREE_KIND_LEAF,
+    RUSTREE_KIND_NONE,
+    RUSTREE_KIND_TUPLE,
+    RUSTREE_KIND_LIST,
+    RUSTREE_KIND_DICT,
+    RUSTREE_KIND_NAMEDTUPLE,
+    RUSTREE_KIND_ORDEREDDICT,
+    RUSTREE_KIND_DEFAULTDICT,
+    RUSTREE_KIND_DEQUE,
+    RUSTREE_KIND_STRUCTSEQUENCE,
+} RusTreeKind;
+
+typedef struct {
+    unsigned int version;
+    /* Return a new reference to the list of leaves, and store a new reference to the treespec in
+     * `*treespec` if it is not NULL. `leaf_predicate` and `namespace` can be NULL. */
+    PyObject *(*flatten)(PyObject *tree,
+                         PyObject *leaf_predicate,
+                         int none_is_leaf,
+                         const char *namespace_,
+                         PyObject **treespec);
+    /* Return a new reference to the tree reconstructed from the treespec and the leaves. */
+    PyObject *(*unflatten)(PyObject *treespec, PyObject *leaves);
+    /* Return the `RusTreeKind` of the type. `namespace` can be NULL. */
+    int (*lookup)(PyObject *cls, int none_is_leaf, const char *namespace_);
+} RusTreeCAPI;
+
+static RusTreeCAPI *RusTree_CAPI = NULL;
+
+static inline int RusTree_ImportCAPI(void) {
+    RusTree_CAPI = (RusTreeCAPI *)PyCapsule_Import(RUSTREE_CAPSULE_NAME, 0);
+    if (RusTree_CAPI == NULL) {
+        return -1;
+    }
+    if (RusTree_CAPI->version != RUSTREE_CAPI_VERSION) {
+        PyErr_Format(PyExc_ImportError,
+                     "RusTree C API version mismatch; expected %d, got %u.",
+                     RUSTREE_CAPI_VERSION,
+                     RusTree_CAPI->version);
+        RusTree_CAPI = NULL;
+        return -1;
+    }
+    return 0;
+}
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* RUSTREE_H_ */
@@ -0,0 +1,207 @@
+# Copyright 2024-2025 Xuehai Pan. All Rights Reserved.
+#
+# Licensed under the Apache License, Version 2.0 (the "License");
+# you may not use this file except in compliance with the License.
+# You may obtain a copy of the License at
+#
+#     http://www.apache.org/licenses/LICENSE-2.0
+#
+# Unless required by applicable law or agreed to in writing, software
+# distributed under the License is distributed on an "AS IS" BASIS,
+# WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+# See the License for the specific language governing permissions and
+# limitations under the License.
+# ==============================================================================
+"""Functional lenses for immutable updates into pytrees."""
+
+from __future__ import annotations
+
+from typing import TYPE_CHECKING, Any, Callable, ClassVar
+from typing_extensions import Self  # Python 3.11+
+
+import rustree._rs as _rs
+from rustree.accessors import PyTreeAccessor
+
+
+if TYPE_CHECKING:
+    from collections.abc import Mapping
+
+
+__all__ = ['PyTreeLens', 'lens', 'tree_replace']
+
+
+class PyTreeLens:
+    """A composable focus on a subtree of a pytree.
+
+    A lens is a path of keys from the root of a pytree. Each key is matched against the path entries
+    of the node it descends into, so it can be a mapping key, a sequence index, or a field name of a
+    namedtuple, PyStructSequence, dataclass, or custom node.
+
+    >>> tree = {'encoder': {'layers': [{'w': 1}, {'w': 2}]}, 'decoder': None}
+    >>> focus = lens()['encoder']['layers'][0]
+    >>> focus
+    PyTreeLens(*['encoder']['layers'][0])
+    >>> focus.get(tree)
+    {'w': 1}
+    >>> focus['w'].set(tree, 10)
+    {'encoder': {'layers': [{'w': 10}, {'w': 2}]}, 'decoder': None}
+    >>> focus['w'].modify(tree, lambda x: x + 100)
+    {'encoder': {'layers': [{'w': 101}, {'w': 2}]}, 'decoder': None}
+    >>> tree  # the original tree is not modified
+    {'encoder': {'layers': [{'w': 1}, {'w': 2}]}, 'decoder': None}
+    """
+
+    __slots__: ClassVar[tuple[str, ...]] = ('_keys', '_namespace', '_none_is_leaf')
+
+    _keys: tuple[Any, ...]
+    _none_is_leaf: bool
+    _namespace: str
+
+    def __init__(
+        self,
+        /,
+        keys: tuple[Any, ...] = (),
+        *,
+        none_is_leaf: bool = False,
+        namespace: str = '',
+    ) -> None:
+        """Initialize a lens with a path of keys."""
+        self._keys = tuple(keys)
+        self._none_is_leaf = bool(none_is_leaf)
+        self._namespace = namespace
+
+    @property
+    def path(self, /) -> tuple[Any, ...]:
+        """Get the path of keys of the lens."""
+        return self._keys
+
+    def __getitem__(self, key: Any, /) -> Self:
+        """Focus on a child of the current focus."""
+        return self.__class__(
+            (*self._keys, key),
+            none_is_leaf=self._none_is_leaf,
+            namespace=self._namespace,
+        )
+
+    def __add__(self, other: object, /) -> Self:
+        """Compose the lens with another lens."""
+        if not isinstance(other, PyTreeLens):
+            return NotImplemented
+        return self.__class__(
+            (*self._keys, *other._keys),
+            none_is_leaf=self._none_is_leaf,
+            namespace=self._namespace,
+        )
+
+    def __eq__(self, other: object, /) -> bool:
+        """Check if the lenses are equal."""
+        return isinstance(other, PyTreeLens) and (
+            (self._keys, self._none_is_leaf, self._namespace)
+            == (other._keys, other._none_is_leaf, other._namespace)
+        )
+
+    def __hash__(self, /) -> int:
+        """Get the hash of the lens."""
+        return hash((self._keys, self._none_is_leaf, self._namespace))
+
+    def __repr__(self, /) -> str:
+        """Get the representation of the lens."""
+        path = ''.join(f'[{key!r}]' for key in self._keys)
+        return f'{self.__class__.__name__}(*{path})'
+
+    def get(self, tree: Any, /) -> Any:
+        """Get the focused subtree of the tree."""
+        return _rs.get_at_path(tree, self._keys, self._none_is_leaf, self._namespace)
+
+    def set(self, tree: Any, value: Any, /) -> Any:
+        """Return a new tree with the focused subtree replaced by ``value``.
+
+        Only the nodes along the path are rebuilt. All other subtrees are shared with the input, and
+        the dictionaries keep their key order. The dataclass nodes are rebuilt with
+        :func:`copy.replace` on Python 3.13+.
+        """
+        return _rs.replace_at_path(tree, self._keys, value, self._none_is_leaf, self._namespace)
+
+    def modify(self, tree: Any, func: Callable[[Any], Any], /) -> Any:
+        """Return a new tree with ``func`` applied to the focused subtree."""
+        return self.set(tree, func(self.get(tree)))
+
+
+def lens(
+    path: PyTreeAccessor | tuple[Any, ...] = (),
+    /,
+    *,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> PyTreeLens:
+    """Create a lens focusing on the given path.
+
+    The path can be a tuple of keys or a :class:`PyTreeAccessor`. Use indexing on the returned lens
+    to focus further into the tree.
+
+    >>> tree = {'a': [1, (2, 3)], 'b': None}
+    >>> lens()['a'][1][0].get(tree)
+    2
+    >>> accessors, leaves, _ = tree_flatten_with_accessor(tree)
+    >>> lens(accessors[1]).set(tree, 20)
+    {'a': [1, (20, 3)], 'b': None}
+
+    Args:
+        path (PyTreeAccessor or tuple, optional): The initial path of the lens. (default: ``()``)
+        none_is_leaf (bool, optional): Whether to treat :data:`None` as a leaf. (default:
+            :data:`False`)
+        namespace (str, optional): The registry namespace used for custom pytree node types.
+            (default: :const:`''`, i.e., the global namespace)
+
+    Returns:
+        A :class:`PyTreeLens` instance.
+    """
+    if isinstance(path, PyTreeAccessor):
+        path = path.path
+    return PyTreeLens(tuple(path), none_is_leaf=none_is_leaf, namespace=namespace)
+
+
+def tree_replace(
+    tree: Any,
+    replacements: Mapping[Any, Any] | None = None,
+    /,
+    *,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+    **changes: Any,
+) -> Any:
+    """Return a new tree with the subtrees at the given paths replaced.
+
+    See also :func:`lens` and :func:`copy.replace`.
+
+    The ``replacements`` map the paths (tuples of keys or :class:`PyTreeAccessor` instances) to
+    the new subtrees, and a key that is not a tuple is a path of length one. The keyword arguments
+    replace the children of the root node by their keys, field names, or attribute names, similar to
+    :func:`copy.replace`. The replacements are applied in order, and only the nodes along the paths
+    are rebuilt.
+
+    >>> tree = {'encoder': {'layers': [{'w': 1}, {'w': 2}]}, 'decoder': None}
+    >>> tree_replace(tree, {('encoder', 'layers', 0, 'w'): 10, ('encoder', 'layers', 1): None})
+    {'encoder': {'layers': [{'w': 10}, None]}, 'decoder': None}
+    >>> tree_replace(tree, decoder={'w': 3})
+    {'encoder': {'layers': [{'w': 1}, {'w': 2}]}, 'decoder': {'w': 3}}
+
+    Args:
+        tree (pytree): A pytree to be updated.
+        replacements (mapping, optional): A mapping from the paths to the new subtrees.
+            (default: :data:`None`)
+        none_is_leaf (bool, optional): Whether to treat :data:`None` as a leaf. (default:
+            :data:`False`)
+        namespace (str, optional): The registry namespace used for custom pytree node types.
+            (default: :const:`''`, i.e., the global namespace)
+        **changes: The new children of the root node by their keys.
+
+    Returns:
+        A new pytree with the replaced subtrees. The input tree is not modified.
+    """
+    items = list((replacements or {}).items()) + [((key,), value) for key, value in changes.items()]
+    for path, value in items:
+        if not isinstance(path, (tuple, PyTreeAccessor)):
+            path = (path,)
+        tree = lens(path, none_is_leaf=none_is_leaf, namespace=namespace).set(tree, value)
+    return tree
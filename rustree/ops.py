@@ -16,21 +16,654 @@
 
 from __future__ import annotations
 
-from typing import TYPE_CHECKING, TypeVar
+from collections import OrderedDict, defaultdict
+from typing import TYPE_CHECKING, Any, TypeVar
 
 import rustree._rs as _rs
+from rustree.utils import total_order_sorted
 
 
 if TYPE_CHECKING:
-    from collections.abc import Callable
+    from collections.abc import Callable, Hashable, Iterable, Mapping
+
+    from rustree.accessors import PyTreeAccessor
+    from rustree.typing import PyTreeSpec, RavelBackend, Unraveler
 
 
 __all__ = [
+    'tree_flatten',
+    'tree_flatten_into',
+    'tree_flatten_multiple',
+    'tree_flatten_with_accessor',
+    'tree_flatten_with_path',
+    'tree_unflatten',
+    'tree_leaves',
+    'tree_structure',
     'tree_is_leaf',
+    'all_leaves',
+    'tree_map',
+    'tree_map_with_path',
+    'tree_map_with_path_',
+    'tree_partition',
+    'tree_filter',
+    'tree_broadcast_map',
+    'tree_broadcast_map_with_path',
+    'tree_broadcast_prefix',
+    'broadcast_prefix',
+    'prefix_errors',
+    'tree_broadcast_common',
+    'tree_reduce_with_path',
+    'tree_count',
+    'tree_sum',
+    'tree_mean',
+    'tree_max',
+    'tree_min',
+    'tree_all',
+    'tree_any',
+    'tree_leaf_depths',
+    'tree_shared_leaves',
+    'tree_type_counts',
+    'tree_copy',
+    'tree_deepcopy',
+    'tree_replace_nones',
+    'tree_hash',
+    'tree_format',
+    'tree_align',
+    'tree_transpose',
+    'tree_transpose_map',
+    'tree_ravel',
+    'treespec_leaf',
+    'treespec_none',
+    'treespec_tuple',
+    'treespec_list',
+    'treespec_dict',
+    'treespec_ordereddict',
+    'treespec_defaultdict',
+    'treespec_deque',
+    'treespec_from_collection',
 ]
 
 
 _T = TypeVar('_T')
+_U = TypeVar('_U')
+
+
+__MISSING: Any = object()
+# The immutable builtin scalars, which are cached or interned by the interpreter
+__SCALAR_TYPES: tuple[type, ...] = (type(None), bool, int, float, complex, str, bytes)
+
+
+def tree_flatten(
+    tree: _T,
+    /,
+    is_leaf: Callable[[_T], bool] | None = None,
+    *,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+    prune_none: bool = False,
+    sort_dict_keys: bool | Callable[[Any], Any] | None = None,
+    shared_subtrees: bool = False,
+    weak_leaves: bool = False,
+    cache: bool = False,
+    max_nodes: int | None = None,
+    max_leaves: int | None = None,
+) -> tuple[list[_T], PyTreeSpec]:
+    """Flatten a pytree.
+
+    See also :func:`tree_flatten_with_accessor` and :func:`tree_unflatten`.
+
+    The flattening order (i.e., the order of elements in the output list) is deterministic,
+    corresponding to a left-to-right depth-first tree traversal.
+
+    >>> tree = {'b': (2, [3, 4]), 'a': 1, 'c': None, 'd': 5}
+    >>> tree_flatten(tree)  # doctest: +IGNORE_WHITESPACE
+    (
+        [1, 2, 3, 4, 5],
+        PyTreeSpec({'a': *, 'b': (*, [*, *]), 'c': None, 'd': *})
+    )
+    >>> tree_flatten(tree, none_is_leaf=True)  # doctest: +IGNORE_WHITESPACE
+    (
+        [1, 2, 3, 4, None, 5],
+        PyTreeSpec({'a': *, 'b': (*, [*, *]), 'c': *, 'd': *}, NoneIsLeaf)
+    )
+    >>> tree_flatten(1)
+    ([1], PyTreeSpec(*))
+    >>> tree_flatten(None)
+    ([], PyTreeSpec(None))
+    >>> tree_flatten(None, none_is_leaf=True)
+    ([None], PyTreeSpec(*, NoneIsLeaf))
+
+    With ``prune_none=True``, the :data:`None` values are skipped from the leaves list but recorded
+    in the treespec, so :func:`tree_unflatten` reinserts them. This matches the common convention
+    of using :data:`None` for missing values (e.g., parameters without gradients).
+
+    >>> tree_flatten(tree, none_is_leaf=True, prune_none=True)  # doctest: +IGNORE_WHITESPACE
+    (
+        [1, 2, 3, 4, 5],
+        PyTreeSpec({'a': *, 'b': (*, [*, *]), 'c': None, 'd': *}, NoneIsLeaf)
+    )
+
+    For unordered dictionaries, :class:`dict` and :class:`collections.defaultdict`, the order is
+    dependent on the **sorted** keys in the dictionary. Please use :class:`collections.OrderedDict`
+    if you want to keep the keys in the insertion order, or pass ``sort_dict_keys`` to change the
+    order for a single call.
+
+    >>> tree_flatten({'b': 2, 'a': 1}, sort_dict_keys=False)
+    ([2, 1], PyTreeSpec({'b': *, 'a': *}))
+    >>> tree_flatten({'bb': 2, 'a': 1, 'ccc': 3}, sort_dict_keys=len)
+    ([1, 2, 3], PyTreeSpec({'a': *, 'bb': *, 'ccc': *}))
+
+    With ``shared_subtrees=True``, a container object that occurs more than once in the tree (by
+    identity) is flattened only at its first occurrence. The repeated occurrences are recorded as
+    references in the treespec, and :func:`tree_unflatten` restores the sharing. The empty
+    containers are not shared since the interpreter may reuse them (e.g., the empty tuple).
+
+    >>> shared = {'x': 1, 'y': 2}
+    >>> leaves, treespec = tree_flatten({'a': shared, 'b': shared}, shared_subtrees=True)
+    >>> leaves, treespec
+    ([1, 2], PyTreeSpec({'a': {'x': *, 'y': *}, 'b': <shared ('a',)>}))
+    >>> tree = tree_unflatten(treespec, [3, 4])
+    >>> tree
+    {'a': {'x': 3, 'y': 4}, 'b': {'x': 3, 'y': 4}}
+    >>> tree['a'] is tree['b']
+    True
+    >>> treespec.flatten({'a': {'x': 3, 'y': 4}, 'b': {'x': 5, 'y': 6}})
+    Traceback (most recent call last):
+        ...
+    ValueError: Tree structure does not match the PyTreeSpec at path ('b',); expected the shared subtree at path ('a',), got {'x': 5, 'y': 6}.
+
+    With ``cache=True``, the treespecs are cached by the layout of the containers (i.e., the node
+    types, the arities, and the identities of the dictionary keys), so flattening the structurally
+    identical trees repeatedly (e.g., the batches from a data loader) returns the same treespec
+    object. The layout is collected along with the leaves, so a cached treespec is returned without
+    building the nodes. A layout that is not cached yet costs a second walk to build the treespec.
+    The trees with custom nodes or shared subtrees are not cached. See also
+    :func:`treespec_cache_clear`.
+
+    >>> _, treespec1 = tree_flatten({'x': 1, 'y': [2, 3]}, cache=True)
+    >>> _, treespec2 = tree_flatten({'x': 4, 'y': [5, 6]}, cache=True)
+    >>> treespec1 is treespec2
+    True
+
+    The untrusted inputs can be guarded with ``max_nodes`` and ``max_leaves``, which abort the
+    flattening as soon as the limits are exceeded.
+
+    >>> tree_flatten([[1, 2], [3, 4]], max_leaves=3)
+    Traceback (most recent call last):
+        ...
+    ValueError: Maximum number of leaves (3) exceeded during flattening the tree.
+
+    Args:
+        tree (pytree): A pytree to flatten.
+        is_leaf (callable, optional): An optionally specified function that will be called at each
+            flattening step. It should return a boolean, with :data:`True` stopping the traversal
+            and the whole subtree being treated as a leaf, and :data:`False` indicating the
+            flattening should traverse the current object.
+        none_is_leaf (bool, optional): Whether to treat :data:`None` as a leaf. If :data:`False`,
+            :data:`None` is a non-leaf node with arity 0. Thus :data:`None` is contained in the
+            treespec rather than in the leaves list. (default: :data:`False`)
+        namespace (str, optional): The registry namespace used for custom pytree node types.
+            (default: :const:`''`, i.e., the global namespace)
+        prune_none (bool, optional): Whether to skip the :data:`None` values from the leaves list
+            and record them in the treespec instead, even if ``none_is_leaf=True`` or ``is_leaf``
+            returns :data:`True` for them. (default: :data:`False`)
+        sort_dict_keys (bool or callable, optional): The traversal order of the keys of
+            :class:`dict` and :class:`collections.defaultdict` for this call only. :data:`True`
+            sorts the keys, :data:`False` keeps the insertion order, and a callable is used as the
+            ``key`` function to sort the keys. :class:`collections.OrderedDict` always keeps the
+            insertion order. (default: :data:`None`, i.e., follow the dictionary insertion order
+            mode of the namespace, see :func:`dict_insertion_ordered`)
+        shared_subtrees (bool, optional): Whether to flatten the non-leaf nodes that occur more than
+            once in the tree (by identity) only once and record the repeated occurrences as
+            references to the first one in the treespec. (default: :data:`False`)
+        weak_leaves (bool, optional): Whether to return :class:`weakref.ref` objects to the leaves
+            instead of the leaves themselves, so the caller does not extend the lifetime of the
+            leaves. The leaves that do not support weak references (e.g., :class:`int` and
+            :class:`str`) are returned as is. (default: :data:`False`)
+        cache (bool, optional): Whether to look up the treespec in the process-wide treespec cache
+            keyed by the layout of the containers, and cache the new treespec if it is not found.
+            (default: :data:`False`)
+        max_nodes (int, optional): The maximum number of nodes (including the leaves) in the tree.
+            A :exc:`ValueError` is raised if the tree has more nodes. (default: :data:`None`, i.e.,
+            no limit)
+        max_leaves (int, optional): The maximum number of leaves in the tree. A :exc:`ValueError`
+            is raised if the tree has more leaves. (default: :data:`None`, i.e., no limit)
+
+    Returns:
+        A pair ``(leaves, treespec)`` where the first element is a list of leaf values and the
+        second element is a treespec representing the structure of the pytree.
+    """
+    return _rs.flatten(
+        tree,
+        is_leaf,
+        none_is_leaf,
+        namespace,
+        prune_none=prune_none,
+        sort_dict_keys=sort_dict_keys,
+        shared_subtrees=shared_subtrees,
+        weak_leaves=weak_leaves,
+        cache=cache,
+        max_nodes=max_nodes,
+        max_leaves=max_leaves,
+    )
+
+
+def tree_flatten_into(
+    tree: _T,
+    leaves: list[_T],
+    /,
+    is_leaf: Callable[[_T], bool] | None = None,
+    *,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+    prune_none: bool = False,
+    sort_dict_keys: bool | Callable[[Any], Any] | None = None,
+    max_nodes: int | None = None,
+    max_leaves: int | None = None,
+) -> PyTreeSpec:
+    """Flatten a pytree and append the leaves to an existing list.
+
+    See also :func:`tree_flatten`.
+
+    This is useful for accumulating the leaves of many trees into a single buffer without building
+    and concatenating the intermediate lists. The list is left unchanged if the flattening fails.
+
+    >>> leaves = []
+    >>> tree_flatten_into({'b': 2, 'a': 1}, leaves)
+    PyTreeSpec({'a': *, 'b': *})
+    >>> tree_flatten_into([3, (4, None)], leaves)
+    PyTreeSpec([*, (*, None)])
+    >>> leaves
+    [1, 2, 3, 4]
+
+    Args:
+        tree (pytree): A pytree to flatten.
+        leaves (list): The list to append the leaves to.
+        is_leaf (callable, optional): An optionally specified function that will be called at each
+            flattening step. It should return a boolean, with :data:`True` stopping the traversal
+            and the whole subtree being treated as a leaf, and :data:`False` indicating the
+            flattening should traverse the current object.
+        none_is_leaf (bool, optional): Whether to treat :data:`None` as a leaf. If :data:`False`,
+            :data:`None` is a non-leaf node with arity 0. Thus :data:`None` is contained in the
+            treespec rather than in the leaves list. (default: :data:`False`)
+        namespace (str, optional): The registry namespace used for custom pytree node types.
+            (default: :const:`''`, i.e., the global namespace)
+        prune_none (bool, optional): Whether to skip the :data:`None` values from the leaves list
+            and record them in the treespec instead. (default: :data:`False`)
+        sort_dict_keys (bool or callable, optional): The traversal order of the keys of
+            :class:`dict` and :class:`collections.defaultdict` for this call only, see
+            :func:`tree_flatten`. (default: :data:`None`)
+        max_nodes (int, optional): The maximum number of nodes (including the leaves) in the tree,
+            see :func:`tree_flatten`. (default: :data:`None`, i.e., no limit)
+        max_leaves (int, optional): The maximum number of leaves in the tree, see
+            :func:`tree_flatten`. (default: :data:`None`, i.e., no limit)
+
+    Returns:
+        The treespec representing the structure of the pytree.
+    """
+    return _rs.flatten_into(
+        tree,
+        leaves,
+        is_leaf,
+        none_is_leaf,
+        namespace,
+        prune_none=prune_none,
+        sort_dict_keys=sort_dict_keys,
+        max_nodes=max_nodes,
+        max_leaves=max_leaves,
+    )
+
+
+def tree_flatten_multiple(
+    trees: Iterable[_T],
+    /,
+    is_leaf: Callable[[_T], bool] | None = None,
+    *,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> tuple[list[list[_T]], PyTreeSpec]:
+    """Flatten several pytrees that share the same structure.
+
+    See also :func:`tree_flatten`.
+
+    The first tree is flattened and the other trees are checked against its treespec during the
+    traversal, which is much cheaper than flattening them separately and comparing the treespecs
+    afterwards. The error message reports the first path where the structures diverge.
+
+    >>> tree_flatten_multiple([{'a': 1, 'b': (2, 3)}, {'a': 4, 'b': (5, 6)}])
+    ([[1, 2, 3], [4, 5, 6]], PyTreeSpec({'a': *, 'b': (*, *)}))
+    >>> tree_flatten_multiple([(1, [2]), (3, (4,))])
+    Traceback (most recent call last):
+        ...
+    ValueError: Tree structure does not match the PyTreeSpec at path (1,); expected [*], got (4,).
+
+    Args:
+        trees (iterable of pytree): The pytrees to flatten. At least one tree is required.
+        is_leaf (callable, optional): An optionally specified function that will be called at each
+            flattening step. It should return a boolean, with :data:`True` stopping the traversal
+            and the whole subtree being treated as a leaf, and :data:`False` indicating the
+            flattening should traverse the current object.
+        none_is_leaf (bool, optional): Whether to treat :data:`None` as a leaf. If :data:`False`,
+            :data:`None` is a non-leaf node with arity 0. Thus :data:`None` is contained in the
+            treespec rather than in the leaves list. (default: :data:`False`)
+        namespace (str, optional): The registry namespace used for custom pytree node types.
+            (default: :const:`''`, i.e., the global namespace)
+
+    Returns:
+        A pair ``(leaves_lists, treespec)`` where the first element is a list of the leaves lists
+        of the trees and the second element is the treespec shared by all the trees.
+    """
+    return _rs.flatten_multiple(trees, is_leaf, none_is_leaf, namespace)
+
+
+def tree_flatten_with_accessor(
+    tree: _T,
+    /,
+    is_leaf: Callable[[_T], bool] | None = None,
+    *,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+    prune_none: bool = False,
+    sort_dict_keys: bool | Callable[[Any], Any] | None = None,
+    max_nodes: int | None = None,
+    max_leaves: int | None = None,
+) -> tuple[list[PyTreeAccessor], list[_T], PyTreeSpec]:
+    """Flatten a pytree and additionally record the accessors to the leaves.
+
+    See also :func:`tree_flatten` and :func:`tree_unflatten`.
+
+    The path entries of each accessor are created from the ``path_entry_type`` of the node types.
+    For custom pytree node types, the ``path_entry_type`` given to :func:`register_pytree_node` is
+    used (default: :class:`AutoEntry`).
+
+    >>> tree = {'b': (2, [3, 4]), 'a': 1, 'c': None, 'd': 5}
+    >>> tree_flatten_with_accessor(tree)  # doctest: +IGNORE_WHITESPACE,ELLIPSIS
+    (
+        [
+            PyTreeAccessor(*['a'], (MappingEntry(key='a', type=<class 'dict'>),)),
+            PyTreeAccessor(*['b'][0], (MappingEntry(key='b', type=<class 'dict'>), SequenceEntry(index=0, type=<class 'tuple'>))),
+            ...
+            PyTreeAccessor(*['d'], (MappingEntry(key='d', type=<class 'dict'>),))
+        ],
+        [1, 2, 3, 4, 5],
+        PyTreeSpec({'a': *, 'b': (*, [*, *]), 'c': None, 'd': *})
+    )
+    >>> tree_flatten_with_accessor(1)
+    ([PyTreeAccessor(*, ())], [1], PyTreeSpec(*))
+
+    Args:
+        tree (pytree): A pytree to flatten.
+        is_leaf (callable, optional): An optionally specified function that will be called at each
+            flattening step. It should return a boolean, with :data:`True` stopping the traversal
+            and the whole subtree being treated as a leaf, and :data:`False` indicating the
+            flattening should traverse the current object.
+        none_is_leaf (bool, optional): Whether to treat :data:`None` as a leaf. If :data:`False`,
+            :data:`None` is a non-leaf node with arity 0. Thus :data:`None` is contained in the
+            treespec rather than in the leaves list. (default: :data:`False`)
+        namespace (str, optional): The registry namespace used for custom pytree node types.
+            (default: :const:`''`, i.e., the global namespace)
+        prune_none (bool, optional): Whether to skip the :data:`None` values from the leaves list
+            and record them in the treespec instead, even if ``none_is_leaf=True`` or ``is_leaf``
+            returns :data:`True` for them. (default: :data:`False`)
+        sort_dict_keys (bool or callable, optional): The traversal order of the keys of
+            :class:`dict` and :class:`collections.defaultdict` for this call only. :data:`True`
+            sorts the keys, :data:`False` keeps the insertion order, and a callable is used as the
+            ``key`` function to sort the keys. :class:`collections.OrderedDict` always keeps the
+            insertion order. (default: :data:`None`, i.e., follow the dictionary insertion order
+            mode of the namespace, see :func:`dict_insertion_ordered`)
+        max_nodes (int, optional): The maximum number of nodes (including the leaves) in the tree,
+            see :func:`tree_flatten`. (default: :data:`None`, i.e., no limit)
+        max_leaves (int, optional): The maximum number of leaves in the tree, see
+            :func:`tree_flatten`. (default: :data:`None`, i.e., no limit)
+
+    Returns:
+        A triple ``(accessors, leaves, treespec)``. The first element is a list of accessors to the
+        leaves. The second element is a list of leaf values and the last element is a treespec
+        representing the structure of the pytree.
+    """  # pylint: disable=line-too-long
+    return _rs.flatten_with_accessor(
+        tree,
+        is_leaf,
+        none_is_leaf,
+        namespace,
+        prune_none=prune_none,
+        sort_dict_keys=sort_dict_keys,
+        max_nodes=max_nodes,
+        max_leaves=max_leaves,
+    )
+
+
+def tree_flatten_with_path(
+    tree: _T,
+    /,
+    is_leaf: Callable[[_T], bool] | None = None,
+    *,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+    prune_none: bool = False,
+    sort_dict_keys: bool | Callable[[Any], Any] | None = None,
+    max_nodes: int | None = None,
+    max_leaves: int | None = None,
+) -> tuple[list[tuple[tuple[Any, ...], _T]], PyTreeSpec]:
+    """Flatten a pytree and pair each leaf with its path.
+
+    See also :func:`tree_flatten`, :func:`tree_flatten_with_accessor`, and
+    :meth:`PyTreeSpec.paths`.
+
+    The paths are recorded in the same traversal as the leaves, so they always follow the flatten
+    order of the treespec, including the order of the dictionary keys.
+
+    >>> tree = {'b': (2, [3, 4]), 'a': 1, 'c': None}
+    >>> tree_flatten_with_path(tree)  # doctest: +IGNORE_WHITESPACE
+    (
+        [(('a',), 1), (('b', 0), 2), (('b', 1, 0), 3), (('b', 1, 1), 4)],
+        PyTreeSpec({'a': *, 'b': (*, [*, *]), 'c': None})
+    )
+    >>> tree_flatten_with_path(tree, sort_dict_keys=False)[0]
+    [(('b', 0), 2), (('b', 1, 0), 3), (('b', 1, 1), 4), (('a',), 1)]
+    >>> tree_flatten_with_path(1)
+    ([((), 1)], PyTreeSpec(*))
+
+    Args:
+        tree (pytree): A pytree to flatten.
+        is_leaf (callable, optional): An optionally specified function that will be called at each
+            flattening step. It should return a boolean, with :data:`True` stopping the traversal
+            and the whole subtree being treated as a leaf, and :data:`False` indicating the
+            flattening should traverse the current object.
+        none_is_leaf (bool, optional): Whether to treat :data:`None` as a leaf. If :data:`False`,
+            :data:`None` is a non-leaf node with arity 0. Thus :data:`None` is contained in the
+            treespec rather than in the leaves list. (default: :data:`False`)
+        namespace (str, optional): The registry namespace used for custom pytree node types.
+            (default: :const:`''`, i.e., the global namespace)
+        prune_none (bool, optional): Whether to skip the :data:`None` values from the leaves list
+            and record them in the treespec instead, even if ``none_is_leaf=True`` or ``is_leaf``
+            returns :data:`True` for them. (default: :data:`False`)
+        sort_dict_keys (bool or callable, optional): The traversal order of the keys of
+            :class:`dict` and :class:`collections.defaultdict` for this call only. :data:`True`
+            sorts the keys, :data:`False` keeps the insertion order, and a callable is used as the
+            ``key`` function to sort the keys. :class:`collections.OrderedDict` always keeps the
+            insertion order. (default: :data:`None`, i.e., follow the dictionary insertion order
+            mode of the namespace, see :func:`dict_insertion_ordered`)
+        max_nodes (int, optional): The maximum number of nodes (including the leaves) in the tree,
+            see :func:`tree_flatten`. (default: :data:`None`, i.e., no limit)
+        max_leaves (int, optional): The maximum number of leaves in the tree, see
+            :func:`tree_flatten`. (default: :data:`None`, i.e., no limit)
+
+    Returns:
+        A pair ``(items, treespec)``. The first element is a list of ``(path, leaf)`` pairs where
+        ``path`` is a tuple of path entries, and the second element is a treespec representing the
+        structure of the pytree.
+    """
+    return _rs.flatten_with_path(
+        tree,
+        is_leaf,
+        none_is_leaf,
+        namespace,
+        prune_none=prune_none,
+        sort_dict_keys=sort_dict_keys,
+        max_nodes=max_nodes,
+        max_leaves=max_leaves,
+    )
+
+
+def tree_unflatten(
+    treespec: PyTreeSpec,
+    leaves: Iterable[Any],
+    *,
+    validate: Callable[[int, tuple[Any, ...], Any], Any] | None = None,
+) -> Any:
+    """Reconstruct a pytree from the treespec and the leaves.
+
+    The inverse of :func:`tree_flatten`.
+
+    >>> tree = {'b': (2, [3, 4]), 'a': 1, 'c': None, 'd': 5}
+    >>> leaves, treespec = tree_flatten(tree)
+    >>> tree == tree_unflatten(treespec, leaves)
+    True
+
+    The leaves can be checked or converted while the tree is rebuilt by passing ``validate``. It is
+    called with the index, the path, and the value of each leaf before the insertion, and the
+    return value is inserted in place of the leaf.
+
+    >>> def validate(index, path, leaf):
+    ...     if not isinstance(leaf, int):
+    ...         raise TypeError(f'Expected an int at path {path!r}, got {leaf!r}.')
+    ...     return float(leaf)
+    >>> tree_unflatten(treespec, leaves, validate=validate)
+    {'a': 1.0, 'b': (2.0, [3.0, 4.0]), 'c': None, 'd': 5.0}
+    >>> tree_unflatten(treespec, [1, 2, '3', 4, 5], validate=validate)
+    Traceback (most recent call last):
+        ...
+    TypeError: Expected an int at path ('b', 1, 0), got '3'.
+
+    Args:
+        treespec (PyTreeSpec): The treespec to reconstruct.
+        leaves (iterable): The list of leaves to use for reconstruction. The list must match the
+            number of leaves of the treespec.
+        validate (callable, optional): A function ``validate(index, path, leaf)`` to be called on
+            each leaf before the insertion. It may raise an exception to reject the leaf, and its
+            return value is inserted in place of the leaf. The path entries of custom pytree nodes
+            are the positions of the children. (default: :data:`None`)
+
+    Returns:
+        The reconstructed pytree, containing the ``leaves`` placed in the structure described by
+        ``treespec``.
+    """
+    return treespec.unflatten(leaves, validate)
+
+
+def tree_leaves(
+    tree: _T,
+    /,
+    is_leaf: Callable[[_T], bool] | None = None,
+    *,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+    of_type: type | tuple[type, ...] | None = None,
+    exact_type: bool = False,
+    weak_leaves: bool = False,
+) -> list[_T]:
+    """Get the leaves of a pytree.
+
+    See also :func:`tree_flatten`.
+
+    The leaves are collected without building the treespec. With ``of_type``, only the leaves that
+    are instances of the given types are collected, which avoids a second filtering pass over the
+    leaves in Python.
+
+    >>> tree = {'b': (2, [3.0, 4]), 'a': 1, 'c': None, 'd': True}
+    >>> tree_leaves(tree)
+    [1, 2, 3.0, 4, True]
+    >>> tree_leaves(tree, of_type=int)
+    [1, 2, 4, True]
+    >>> tree_leaves(tree, of_type=int, exact_type=True)
+    [1, 2, 4]
+    >>> tree_leaves(tree, of_type=(float, bool))
+    [3.0, True]
+
+    With ``weak_leaves=True``, the leaves that support weak references are returned as
+    :class:`weakref.ref` objects, which is useful for inspecting the leaves without keeping them
+    alive.
+
+    >>> tree = {'a': {1, 2}, 'b': 3}
+    >>> leaves = tree_leaves(tree, weak_leaves=True)
+    >>> leaves[0]() is tree['a'], leaves[1]
+    (True, 3)
+
+    Args:
+        tree (pytree): A pytree to get the leaves from.
+        is_leaf (callable, optional): An optionally specified function that will be called at each
+            flattening step. It should return a boolean, with :data:`True` stopping the traversal
+            and the whole subtree being treated as a leaf, and :data:`False` indicating the
+            flattening should traverse the current object.
+        none_is_leaf (bool, optional): Whether to treat :data:`None` as a leaf. If :data:`False`,
+            :data:`None` is a non-leaf node with arity 0. Thus :data:`None` is contained in the
+            treespec rather than in the leaves list. (default: :data:`False`)
+        namespace (str, optional): The registry namespace used for custom pytree node types.
+            (default: :const:`''`, i.e., the global namespace)
+        of_type (type or tuple of types, optional): Only collect the leaves that are instances of
+            the given types. (default: :data:`None`, i.e., collect all leaves)
+        exact_type (bool, optional): Whether to match the exact types of the leaves rather than
+            using :func:`isinstance`, i.e., the instances of the subclasses are not collected.
+            (default: :data:`False`)
+        weak_leaves (bool, optional): Whether to return :class:`weakref.ref` objects to the leaves
+            instead of the leaves themselves, so the caller does not extend the lifetime of the
+            leaves. The leaves that do not support weak references (e.g., :class:`int` and
+            :class:`str`) are returned as is. (default: :data:`False`)
+
+    Returns:
+        A list of leaf values.
+    """
+    return _rs.leaves(
+        tree,
+        is_leaf,
+        none_is_leaf,
+        namespace,
+        of_type=of_type,
+        exact_type=exact_type,
+        weak_leaves=weak_leaves,
+    )
+
+
+def tree_structure(
+    tree: _T,
+    /,
+    is_leaf: Callable[[_T], bool] | None = None,
+    *,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> PyTreeSpec:
+    """Get the treespec of a pytree.
+
+    See also :func:`tree_flatten` and :func:`tree_leaves`.
+
+    Only the treespec is built and the references to the leaves are not collected, which is faster
+    than :func:`tree_flatten` for the code that only needs the structure (e.g., to compare the
+    structures of two pytrees).
+
+    >>> tree_structure({'b': (2, [3, 4]), 'a': 1, 'c': None, 'd': 5})
+    PyTreeSpec({'a': *, 'b': (*, [*, *]), 'c': None, 'd': *})
+    >>> tree_structure({'b': (2, [3, 4]), 'a': 1, 'c': None, 'd': 5}, none_is_leaf=True)
+    PyTreeSpec({'a': *, 'b': (*, [*, *]), 'c': *, 'd': *}, NoneIsLeaf)
+    >>> tree_structure([1, 2]) == tree_structure([3, 4])
+    True
+
+    Args:
+        tree (pytree): A pytree to get the treespec of.
+        is_leaf (callable, optional): An optionally specified function that will be called at each
+            flattening step. It should return a boolean, with :data:`True` stopping the traversal
+            and the whole subtree being treated as a leaf, and :data:`False` indicating the
+            flattening should traverse the current object.
+        none_is_leaf (bool, optional): Whether to treat :data:`None` as a leaf. If :data:`False`,
+            :data:`None` is a non-leaf node with arity 0. Thus :data:`None` is contained in the
+            treespec rather than in the leaves list. (default: :data:`False`)
+        namespace (str, optional): The registry namespace used for custom pytree node types.
+            (default: :const:`''`, i.e., the global namespace)
+
+    Returns:
+        A treespec object representing the structure of the pytree.
+    """
+    return _rs.structure(tree, is_leaf, none_is_leaf, namespace)
 
 
 def tree_is_leaf(
@@ -70,3 +703,2048 @@ def tree_is_leaf(
         A boolean indicating if the given object is a leaf node.
     """
     return _rs.is_leaf(tree, is_leaf, none_is_leaf, namespace)
+
+
+def all_leaves(
+    iterable: Iterable[_T],
+    /,
+    is_leaf: Callable[[_T], bool] | None = None,
+    *,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> bool:
+    """Test whether all elements in the given iterable are all leaves.
+
+    See also :func:`tree_is_leaf` and :func:`tree_unflatten`.
+
+    This is useful to validate a flat list of arguments before passing it to
+    :func:`tree_unflatten`.
+
+    >>> tree = {'a': [1, 2, 3]}
+    >>> all_leaves(tree_leaves(tree))
+    True
+    >>> all_leaves([tree])
+    False
+    >>> all_leaves(tree)  # Iterate over the keys of the dict
+    True
+    >>> all_leaves([None])
+    False
+    >>> all_leaves([None], none_is_leaf=True)
+    True
+    >>> all_leaves([tree], is_leaf=lambda x: isinstance(x, dict))
+    True
+
+    Args:
+        iterable (iterable): An iterable of objects to check if they are all leaves.
+        is_leaf (callable, optional): An optionally specified function that will be called at each
+            flattening step. It should return a boolean, with :data:`True` stopping the traversal
+            and the whole subtree being treated as a leaf, and :data:`False` indicating the
+            flattening should traverse the current object.
+        none_is_leaf (bool, optional): Whether to treat :data:`None` as a leaf. If :data:`False`,
+            :data:`None` is a non-leaf node with arity 0. Thus :data:`None` is contained in the
+            treespec rather than a leaf. (default: :data:`False`)
+        namespace (str, optional): The registry namespace used for custom pytree node types.
+            (default: :const:`''`, i.e., the global namespace)
+
+    Returns:
+        A boolean indicating if all elements in the input iterable are leaves.
+    """
+    return _rs.all_leaves(iterable, is_leaf, none_is_leaf, namespace)
+
+
+def tree_map(
+    func: Callable[..., _U],
+    tree: _T,
+    /,
+    *rests: Any,
+    is_leaf: Callable[[_T], bool] | None = None,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+    sort_dict_keys: bool | Callable[[Any], Any] | None = None,
+) -> Any:
+    """Map a multi-input function over pytree args to produce a new pytree.
+
+    See also :func:`tree_flatten` and :func:`tree_unflatten`.
+
+    >>> tree_map(lambda x: x + 1, {'x': 7, 'y': (42, 64)})
+    {'x': 8, 'y': (43, 65)}
+    >>> tree_map(lambda x: x + 1, {'x': 7, 'y': (42, 64), 'z': None})
+    {'x': 8, 'y': (43, 65), 'z': None}
+    >>> tree_map(lambda x: x is None, {'x': 7, 'y': (42, 64), 'z': None}, none_is_leaf=True)
+    {'x': False, 'y': (False, False), 'z': True}
+    >>> tree_map(lambda x, y: x * y, [1, (2, 3)], [4, (5, 6)])
+    [4, (10, 18)]
+
+    Args:
+        func (callable): A function that takes ``1 + len(rests)`` arguments, to be applied at the
+            corresponding leaves of the pytrees.
+        tree (pytree): A pytree to be mapped over, with each leaf providing the first positional
+            argument to function ``func``.
+        rests (tuple of pytree): A tuple of pytrees, each of which has the same structure as
+            ``tree``.
+        is_leaf (callable, optional): An optionally specified function that will be called at each
+            flattening step. It should return a boolean, with :data:`True` stopping the traversal
+            and the whole subtree being treated as a leaf, and :data:`False` indicating the
+            flattening should traverse the current object.
+        none_is_leaf (bool, optional): Whether to treat :data:`None` as a leaf. If :data:`False`,
+            :data:`None` is a non-leaf node with arity 0. Thus :data:`None` is contained in the
+            treespec rather than in the leaves list and :data:`None` will be remain in the result
+            pytree. (default: :data:`False`)
+        namespace (str, optional): The registry namespace used for custom pytree node types.
+            (default: :const:`''`, i.e., the global namespace)
+        sort_dict_keys (bool or callable, optional): The traversal order of the keys of
+            :class:`dict` and :class:`collections.defaultdict` for this call only. :data:`True`
+            sorts the keys, :data:`False` keeps the insertion order, and a callable is used as the
+            ``key`` function to sort the keys. :class:`collections.OrderedDict` always keeps the
+            insertion order. (default: :data:`None`, i.e., follow the dictionary insertion order
+            mode of the namespace, see :func:`dict_insertion_ordered`)
+
+    Returns:
+        A new pytree with the same structure as ``tree`` but with the value at each leaf given by
+        ``func(x, *xs)`` where ``x`` is the value at the corresponding leaf in ``tree`` and ``xs``
+        is the tuple of values at corresponding nodes in ``rests``.
+    """
+    return _rs.map(
+        func,
+        tree,
+        *rests,
+        leaf_predicate=is_leaf,
+        none_is_leaf=none_is_leaf,
+        namespace=namespace,
+        sort_dict_keys=sort_dict_keys,
+    )
+
+
+def tree_map_with_path(
+    func: Callable[..., _U],
+    tree: _T,
+    /,
+    *rests: Any,
+    is_leaf: Callable[[_T], bool] | None = None,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+    sort_dict_keys: bool | Callable[[Any], Any] | None = None,
+) -> Any:
+    """Map a multi-input function over pytree args as well as the tree paths to produce a new pytree.
+
+    See also :func:`tree_map`, :func:`tree_map_with_path_`, and :func:`tree_flatten_with_accessor`.
+
+    >>> tree_map_with_path(lambda p, x: (len(p), x), {'x': 7, 'y': (42, 64)})
+    {'x': (1, 7), 'y': ((2, 42), (2, 64))}
+    >>> tree_map_with_path(lambda p, x: x + len(p), {'x': 7, 'y': (42, 64), 'z': None})
+    {'x': 8, 'y': (44, 66), 'z': None}
+    >>> tree_map_with_path(lambda p, x: p, {'x': 7, 'y': (42, 64), 'z': {1.5: None}})
+    {'x': ('x',), 'y': (('y', 0), ('y', 1)), 'z': {1.5: None}}
+    >>> tree_map_with_path(lambda p, x: p, {'x': 7, 'y': (42, 64), 'z': {1.5: None}}, none_is_leaf=True)
+    {'x': ('x',), 'y': (('y', 0), ('y', 1)), 'z': {1.5: ('z', 1.5)}}
+
+    Args:
+        func (callable): A function that takes ``2 + len(rests)`` arguments, to be applied at the
+            corresponding leaves of the pytrees with extra paths.
+        tree (pytree): A pytree to be mapped over, with each leaf providing the second positional
+            argument and the corresponding path providing the first positional argument to function
+            ``func``.
+        rests (tuple of pytree): A tuple of pytrees, each of which has the same structure as
+            ``tree``.
+        is_leaf (callable, optional): An optionally specified function that will be called at each
+            flattening step. It should return a boolean, with :data:`True` stopping the traversal
+            and the whole subtree being treated as a leaf, and :data:`False` indicating the
+            flattening should traverse the current object.
+        none_is_leaf (bool, optional): Whether to treat :data:`None` as a leaf. If :data:`False`,
+            :data:`None` is a non-leaf node with arity 0. Thus :data:`None` is contained in the
+            treespec rather than in the leaves list and :data:`None` will be remain in the result
+            pytree. (default: :data:`False`)
+        namespace (str, optional): The registry namespace used for custom pytree node types.
+            (default: :const:`''`, i.e., the global namespace)
+        sort_dict_keys (bool or callable, optional): The traversal order of the keys of
+            :class:`dict` and :class:`collections.defaultdict` for this call only. :data:`True`
+            sorts the keys, :data:`False` keeps the insertion order, and a callable is used as the
+            ``key`` function to sort the keys. :class:`collections.OrderedDict` always keeps the
+            insertion order. (default: :data:`None`, i.e., follow the dictionary insertion order
+            mode of the namespace, see :func:`dict_insertion_ordered`)
+
+    Returns:
+        A new pytree with the same structure as ``tree`` but with the value at each leaf given by
+        ``func(p, x, *xs)`` where ``(p, x)`` are the path and value at the corresponding leaf in
+        ``tree`` and ``xs`` is the tuple of values at corresponding nodes in ``rests``.
+    """  # pylint: disable=line-too-long
+    return _rs.map_with_path(
+        func,
+        tree,
+        *rests,
+        leaf_predicate=is_leaf,
+        none_is_leaf=none_is_leaf,
+        namespace=namespace,
+        sort_dict_keys=sort_dict_keys,
+    )
+
+
+def tree_map_with_path_(
+    func: Callable[..., Any],
+    tree: _T,
+    /,
+    *rests: Any,
+    is_leaf: Callable[[_T], bool] | None = None,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+    sort_dict_keys: bool | Callable[[Any], Any] | None = None,
+) -> _T:
+    """Like :func:`tree_map_with_path`, but do an inplace call on each leaf and return the original tree.
+
+    See also :func:`tree_map_with_path`.
+
+    The function is called for its side effects only (e.g., logging or collecting metrics), and the
+    containers are not reconstructed.
+
+    >>> names = []
+    >>> tree = {'x': 7, 'y': (42, 64)}
+    >>> tree_map_with_path_(lambda p, x: names.append('.'.join(map(str, p))), tree) is tree
+    True
+    >>> names
+    ['x', 'y.0', 'y.1']
+
+    Args:
+        func (callable): A function that takes ``2 + len(rests)`` arguments, to be applied at the
+            corresponding leaves of the pytrees with extra paths.
+        tree (pytree): A pytree to be mapped over, with each leaf providing the second positional
+            argument and the corresponding path providing the first positional argument to function
+            ``func``.
+        rests (tuple of pytree): A tuple of pytrees, each of which has the same structure as
+            ``tree``.
+        is_leaf (callable, optional): An optionally specified function that will be called at each
+            flattening step. It should return a boolean, with :data:`True` stopping the traversal
+            and the whole subtree being treated as a leaf, and :data:`False` indicating the
+            flattening should traverse the current object.
+        none_is_leaf (bool, optional): Whether to treat :data:`None` as a leaf. If :data:`False`,
+            :data:`None` is a non-leaf node with arity 0. Thus :data:`None` is contained in the
+            treespec rather than in the leaves list. (default: :data:`False`)
+        namespace (str, optional): The registry namespace used for custom pytree node types.
+            (default: :const:`''`, i.e., the global namespace)
+        sort_dict_keys (bool or callable, optional): The traversal order of the keys of
+            :class:`dict` and :class:`collections.defaultdict` for this call only. :data:`True`
+            sorts the keys, :data:`False` keeps the insertion order, and a callable is used as the
+            ``key`` function to sort the keys. :class:`collections.OrderedDict` always keeps the
+            insertion order. (default: :data:`None`, i.e., follow the dictionary insertion order
+            mode of the namespace, see :func:`dict_insertion_ordered`)
+
+    Returns:
+        The original ``tree``. The function ``func(p, x, *xs)`` is called for each leaf, where
+        ``(p, x)`` are the path and value at the corresponding leaf in ``tree`` and ``xs`` is the
+        tuple of values at corresponding nodes in ``rests``. The return values are discarded.
+    """  # pylint: disable=line-too-long
+    return _rs.map_with_path_(
+        func,
+        tree,
+        *rests,
+        leaf_predicate=is_leaf,
+        none_is_leaf=none_is_leaf,
+        namespace=namespace,
+        sort_dict_keys=sort_dict_keys,
+    )
+
+
+def tree_partition(
+    predicate: Callable[[_T], Any],
+    tree: _T,
+    /,
+    fill_value: Any = None,
+    is_leaf: Callable[[_T], bool] | None = None,
+    *,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> tuple[_T, _T]:
+    """Partition a pytree into the leaves that satisfy a predicate and the rest.
+
+    See also :func:`tree_filter` and :func:`tree_map`.
+
+    Both pytrees have the same structure as ``tree``. The leaves that do not belong to a pytree are
+    replaced by ``fill_value``, so the pytrees can be merged back leaf by leaf. This is useful to
+    split the trainable and the frozen parameters of a model.
+
+    >>> tree = {'a': 1, 'b': (2.0, [3, 'x'])}
+    >>> tree_partition(lambda x: isinstance(x, int), tree)
+    ({'a': 1, 'b': (None, [3, None])}, {'a': None, 'b': (2.0, [None, 'x'])})
+    >>> tree_partition(lambda x: isinstance(x, int), tree, fill_value=0)
+    ({'a': 1, 'b': (0, [3, 0])}, {'a': 0, 'b': (2.0, [0, 'x'])})
+
+    Args:
+        predicate (callable): A function that takes a leaf value and returns a truthy value if the
+            leaf belongs to the first pytree.
+        tree (pytree): A pytree to be partitioned.
+        fill_value (object, optional): The value to fill the positions of the leaves that do not
+            belong to a pytree. (default: :data:`None`)
+        is_leaf (callable, optional): An optionally specified function that will be called at each
+            flattening step. It should return a boolean, with :data:`True` stopping the traversal
+            and the whole subtree being treated as a leaf, and :data:`False` indicating the
+            flattening should traverse the current object.
+        none_is_leaf (bool, optional): Whether to treat :data:`None` as a leaf. If :data:`False`,
+            :data:`None` is a non-leaf node with arity 0. Thus :data:`None` is contained in the
+            treespec rather than in the leaves list and :data:`None` will be remain in the result
+            pytree. (default: :data:`False`)
+        namespace (str, optional): The registry namespace used for custom pytree node types.
+            (default: :const:`''`, i.e., the global namespace)
+
+    Returns:
+        A pair ``(left, right)`` of pytrees with the same structure as ``tree``. ``left`` holds the
+        leaves for which ``predicate(leaf)`` is truthy and ``right`` holds the others.
+    """
+    return _rs.partition(
+        predicate,
+        tree,
+        fill_value,
+        leaf_predicate=is_leaf,
+        none_is_leaf=none_is_leaf,
+        namespace=namespace,
+    )
+
+
+def tree_filter(
+    predicate: Callable[[_T], Any],
+    tree: _T,
+    /,
+    fill_value: Any = None,
+    is_leaf: Callable[[_T], bool] | None = None,
+    *,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> _T:
+    """Keep the leaves of a pytree that satisfy a predicate.
+
+    See also :func:`tree_partition`.
+
+    The result has the same structure as ``tree``, with the leaves that do not satisfy the
+    predicate replaced by ``fill_value``. With the default ``fill_value=None``, the leaves of the
+    result are exactly the selected leaves.
+
+    >>> tree = {'a': 1, 'b': (2.0, [3, 'x'])}
+    >>> tree_filter(lambda x: isinstance(x, int), tree)
+    {'a': 1, 'b': (None, [3, None])}
+    >>> tree_leaves(tree_filter(lambda x: isinstance(x, int), tree))
+    [1, 3]
+
+    Args:
+        predicate (callable): A function that takes a leaf value and returns a truthy value if the
+            leaf should be kept.
+        tree (pytree): A pytree to be filtered.
+        fill_value (object, optional): The value to fill the positions of the leaves that are not
+            kept. (default: :data:`None`)
+        is_leaf (callable, optional): An optionally specified function that will be called at each
+            flattening step. It should return a boolean, with :data:`True` stopping the traversal
+            and the whole subtree being treated as a leaf, and :data:`False` indicating the
+            flattening should traverse the current object.
+        none_is_leaf (bool, optional): Whether to treat :data:`None` as a leaf. If :data:`False`,
+            :data:`None` is a non-leaf node with arity 0. Thus :data:`None` is contained in the
+            treespec rather than in the leaves list and :data:`None` will be remain in the result
+            pytree. (default: :data:`False`)
+        namespace (str, optional): The registry namespace used for custom pytree node types.
+            (default: :const:`''`, i.e., the global namespace)
+
+    Returns:
+        A new pytree with the same structure as ``tree`` that keeps the leaves for which
+        ``predicate(leaf)`` is truthy.
+    """
+    return _rs.filter(
+        predicate,
+        tree,
+        fill_value,
+        leaf_predicate=is_leaf,
+        none_is_leaf=none_is_leaf,
+        namespace=namespace,
+    )
+
+
+def tree_broadcast_map(
+    func: Callable[..., _U],
+    tree: _T,
+    /,
+    *rests: Any,
+    is_leaf: Callable[[_T], bool] | None = None,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> Any:
+    """Map a multi-input function over pytree args to produce a new pytree, with broadcasting.
+
+    See also :func:`tree_map` and :func:`tree_broadcast_map_with_path`.
+
+    If only one input is provided, this function is the same as :func:`tree_map`. For multiple
+    inputs, the pytrees are broadcast to their common structure before mapping: a leaf in one tree
+    is repeated over the subtree at the corresponding position in the other trees. The non-leaf
+    nodes at the same position must be of the same type with the same metadata (e.g., the same keys
+    for dictionaries). This is useful to apply the per-subtree settings to the full pytrees.
+
+    >>> tree_broadcast_map(lambda x, y: x * y, {'lr': 0.1, 'layers': [1.0, 2.0]}, 2)
+    {'layers': [2.0, 4.0], 'lr': 0.2}
+    >>> tree_broadcast_map(
+    ...     lambda x, y: x * y,
+    ...     {'encoder': 0.5, 'decoder': 2},
+    ...     {'encoder': [1, 2], 'decoder': {'w': 3, 'b': None}},
+    ... )
+    {'decoder': {'b': None, 'w': 6}, 'encoder': [0.5, 1.0]}
+    >>> tree_broadcast_map(lambda x, y: x + y, [1, 2], [3, 4, 5])
+    Traceback (most recent call last):
+        ...
+    ValueError: Tree structures cannot be broadcast at path (); the node [1, 2] in tree 0 does not match the node [3, 4, 5] in tree 1.
+
+    Args:
+        func (callable): A function that takes ``1 + len(rests)`` arguments, to be applied at the
+            corresponding leaves of the broadcast pytrees.
+        tree (pytree): A pytree to be mapped over, with each leaf providing the first positional
+            argument to function ``func``.
+        rests (tuple of pytree): A tuple of pytrees, each of which has a common structure with
+            ``tree`` that all the pytrees can be broadcast to.
+        is_leaf (callable, optional): An optionally specified function that will be called at each
+            flattening step. It should return a boolean, with :data:`True` stopping the traversal
+            and the whole subtree being treated as a leaf, and :data:`False` indicating the
+            flattening should traverse the current object.
+        none_is_leaf (bool, optional): Whether to treat :data:`None` as a leaf. If :data:`False`,
+            :data:`None` is a non-leaf node with arity 0. Thus :data:`None` is contained in the
+            treespec rather than in the leaves list and :data:`None` will be remain in the result
+            pytree. (default: :data:`False`)
+        namespace (str, optional): The registry namespace used for custom pytree node types.
+            (default: :const:`''`, i.e., the global namespace)
+
+    Returns:
+        A new pytree with the common structure of ``tree`` and ``rests`` but with the value at each
+        leaf given by ``func(x, *xs)`` where ``x`` is the value at the corresponding leaf (may be
+        broadcast from a parent leaf) in ``tree`` and ``xs`` is the tuple of values at
+        corresponding leaves (may be broadcast from a parent leaf) in ``rests``.
+    """  # pylint: disable=line-too-long
+    return _rs.broadcast_map(
+        func,
+        tree,
+        *rests,
+        leaf_predicate=is_leaf,
+        none_is_leaf=none_is_leaf,
+        namespace=namespace,
+    )
+
+
+def tree_broadcast_map_with_path(
+    func: Callable[..., _U],
+    tree: _T,
+    /,
+    *rests: Any,
+    is_leaf: Callable[[_T], bool] | None = None,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> Any:
+    """Map a multi-input function over pytree args as well as the tree paths to produce a new pytree, with broadcasting.
+
+    See also :func:`tree_map_with_path`.
+
+    If only one input is provided, this function is the same as :func:`tree_map_with_path`. For
+    multiple inputs, the pytrees are broadcast to their common structure before mapping: a leaf in
+    one tree is repeated over the subtree at the corresponding position in the other trees. The
+    non-leaf nodes at the same position must be of the same type with the same metadata (e.g., the
+    same keys for dictionaries). The paths passed to ``func`` are the paths in the common structure.
+
+    >>> tree_broadcast_map_with_path(
+    ...     lambda p, x, y: ('.'.join(map(str, p)), x * y),
+    ...     {'lr': 0.1, 'layers': [1.0, 2.0]},
+    ...     {'lr': [1, 2], 'layers': 3},
+    ... )
+    {'layers': [('layers.0', 3.0), ('layers.1', 6.0)], 'lr': [('lr.0', 0.1), ('lr.1', 0.2)]}
+    >>> tree_broadcast_map_with_path(lambda p, x, y: p, 1, {'a': (2, None)})
+    {'a': (('a', 0), None)}
+    >>> tree_broadcast_map_with_path(lambda p, x, y: x + y, [1, 2], [3, 4, 5])
+    Traceback (most recent call last):
+        ...
+    ValueError: Tree structures cannot be broadcast at path (); the node [1, 2] in tree 0 does not match the node [3, 4, 5] in tree 1.
+
+    Args:
+        func (callable): A function that takes ``2 + len(rests)`` arguments, to be applied at the
+            corresponding leaves of the broadcast pytrees with extra paths.
+        tree (pytree): A pytree to be mapped over, with each leaf providing the second positional
+            argument and the corresponding path providing the first positional argument to function
+            ``func``.
+        rests (tuple of pytree): A tuple of pytrees, each of which has a common structure with
+            ``tree`` that all the pytrees can be broadcast to.
+        is_leaf (callable, optional): An optionally specified function that will be called at each
+            flattening step. It should return a boolean, with :data:`True` stopping the traversal
+            and the whole subtree being treated as a leaf, and :data:`False` indicating the
+            flattening should traverse the current object.
+        none_is_leaf (bool, optional): Whether to treat :data:`None` as a leaf. If :data:`False`,
+            :data:`None` is a non-leaf node with arity 0. Thus :data:`None` is contained in the
+            treespec rather than in the leaves list and :data:`None` will be remain in the result
+            pytree. (default: :data:`False`)
+        namespace (str, optional): The registry namespace used for custom pytree node types.
+            (default: :const:`''`, i.e., the global namespace)
+
+    Returns:
+        A new pytree with the common structure of ``tree`` and ``rests`` but with the value at each
+        leaf given by ``func(p, x, *xs)`` where ``(p, x)`` are the path and value at the
+        corresponding leaf (may be broadcast from a parent leaf) in ``tree`` and ``xs`` is the tuple
+        of values at corresponding leaves (may be broadcast from a parent leaf) in ``rests``.
+    """  # pylint: disable=line-too-long
+    return _rs.broadcast_map_with_path(
+        func,
+        tree,
+        *rests,
+        leaf_predicate=is_leaf,
+        none_is_leaf=none_is_leaf,
+        namespace=namespace,
+    )
+
+
+def tree_broadcast_prefix(
+    prefix_tree: Any,
+    full_tree: _T,
+    /,
+    is_leaf: Callable[[Any], bool] | None = None,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> _T:
+    """Return a pytree of same structure of ``full_tree`` with broadcasted subtrees in ``prefix_tree``.
+
+    See also :func:`tree_broadcast_map` and :meth:`PyTreeSpec.is_prefix`.
+
+    Each leaf in ``prefix_tree`` is repeated over the subtree at the corresponding position in
+    ``full_tree``. The non-leaf nodes in ``prefix_tree`` must match the nodes at the same positions
+    in ``full_tree``. This is useful to expand the per-subtree settings to the full pytrees.
+
+    >>> tree_broadcast_prefix(1, [2, 3, 4])
+    [1, 1, 1]
+    >>> tree_broadcast_prefix({'a': 0.1, 'b': 0.2}, {'a': [1, 2], 'b': {'w': 3, 'x': None}})
+    {'a': [0.1, 0.1], 'b': {'w': 0.2, 'x': None}}
+    >>> tree_broadcast_prefix([1, [2, 3]], [4, 5])
+    Traceback (most recent call last):
+        ...
+    ValueError: Tree structures cannot be broadcast at path (1,); the node [2, 3] in the prefix tree does not match the leaf 5 in the full tree.
+
+    Args:
+        prefix_tree (pytree): A pytree with the same structure as a prefix of ``full_tree``.
+        full_tree (pytree): A pytree with the structure to broadcast the prefix leaves to.
+        is_leaf (callable, optional): An optionally specified function that will be called at each
+            flattening step. It should return a boolean, with :data:`True` stopping the traversal
+            and the whole subtree being treated as a leaf, and :data:`False` indicating the
+            flattening should traverse the current object.
+        none_is_leaf (bool, optional): Whether to treat :data:`None` as a leaf. If :data:`False`,
+            :data:`None` is a non-leaf node with arity 0. Thus :data:`None` is contained in the
+            treespec rather than in the leaves list and :data:`None` will be remain in the result
+            pytree. (default: :data:`False`)
+        namespace (str, optional): The registry namespace used for custom pytree node types.
+            (default: :const:`''`, i.e., the global namespace)
+
+    Returns:
+        A pytree with the same structure as ``full_tree`` whose leaves are the leaves of
+        ``prefix_tree`` at the corresponding positions.
+
+    Raises:
+        ValueError: If ``prefix_tree`` is not a prefix of ``full_tree``.
+    """  # pylint: disable=line-too-long
+    return _rs.broadcast_prefix(
+        prefix_tree,
+        full_tree,
+        leaf_predicate=is_leaf,
+        none_is_leaf=none_is_leaf,
+        namespace=namespace,
+    )
+
+
+def broadcast_prefix(
+    prefix_tree: Any,
+    full_tree: Any,
+    /,
+    is_leaf: Callable[[Any], bool] | None = None,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> list[Any]:
+    """Return a list of broadcasted leaves in ``prefix_tree`` to match the number of leaves in ``full_tree``.
+
+    See also :func:`tree_broadcast_prefix` and :meth:`PyTreeSpec.is_prefix`.
+
+    Each leaf in ``prefix_tree`` is repeated as many times as the number of leaves in the subtree at
+    the corresponding position in ``full_tree``. The result has the same length as the leaves of
+    ``full_tree``, without rebuilding the broadcasted pytree.
+
+    >>> broadcast_prefix(1, [2, 3, 4])
+    [1, 1, 1]
+    >>> broadcast_prefix({'a': 0.1, 'b': 0.2}, {'a': [1, 2], 'b': {'w': 3, 'x': None}})
+    [0.1, 0.1, 0.2]
+    >>> broadcast_prefix({'a': 1, 'b': (2, 3)}, {'a': 4, 'b': [5, 6]})
+    Traceback (most recent call last):
+        ...
+    ValueError: Tree structures cannot be broadcast at path ('b',); the node (2, 3) in tree 0 does not match the node [5, 6] in tree 1.
+
+    Args:
+        prefix_tree (pytree): A pytree with the same structure as a prefix of ``full_tree``.
+        full_tree (pytree): A pytree with the structure to broadcast the prefix leaves to.
+        is_leaf (callable, optional): An optionally specified function that will be called at each
+            flattening step. It should return a boolean, with :data:`True` stopping the traversal
+            and the whole subtree being treated as a leaf, and :data:`False` indicating the
+            flattening should traverse the current object.
+        none_is_leaf (bool, optional): Whether to treat :data:`None` as a leaf. If :data:`False`,
+            :data:`None` is a non-leaf node with arity 0. Thus :data:`None` is contained in the
+            treespec rather than in the leaves list. (default: :data:`False`)
+        namespace (str, optional): The registry namespace used for custom pytree node types.
+            (default: :const:`''`, i.e., the global namespace)
+
+    Returns:
+        A list of the leaves of ``prefix_tree`` repeated to match the leaves of ``full_tree``.
+
+    Raises:
+        ValueError: If ``prefix_tree`` is not a prefix of ``full_tree``, with the path where the
+            structures diverge.
+    """  # pylint: disable=line-too-long
+    return _rs.broadcast_prefix_leaves(
+        prefix_tree,
+        full_tree,
+        leaf_predicate=is_leaf,
+        none_is_leaf=none_is_leaf,
+        namespace=namespace,
+    )
+
+
+def prefix_errors(
+    prefix_tree: Any,
+    full_tree: Any,
+    /,
+    is_leaf: Callable[[Any], bool] | None = None,
+    *,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> list[Callable[[str], ValueError]]:
+    """Return a list of error factories describing where ``prefix_tree`` is not a prefix of ``full_tree``.
+
+    See also :func:`broadcast_prefix` and :meth:`PyTreeSpec.is_prefix`.
+
+    Unlike :func:`broadcast_prefix` that stops at the first mismatch, all mismatched nodes are
+    reported, i.e., the nodes with different types, different dictionary keys, different numbers of
+    children, or different metadata. Each factory takes the name of the prefix tree for the error
+    message and returns a :exc:`ValueError` to be raised by the caller.
+
+    >>> prefix_errors({'a': 1, 'b': (2, 3)}, {'a': [4, 5], 'b': (6, 7)})
+    []
+    >>> errors = prefix_errors({'a': [1, 2], 'b': (2, 3)}, {'a': 4, 'b': (6, 7, 8)})
+    >>> errors
+    [PrefixError(path=('a',), reason='the prefix tree has a node of type list but the full tree has a leaf of type int'), PrefixError(path=('b',), reason='the prefix tree has a node of type tuple with 2 children but the full tree has 3 children')]
+    >>> raise errors[0]('in_axes')
+    Traceback (most recent call last):
+        ...
+    ValueError: Tree in_axes is not a prefix of the full tree at path ('a',); the prefix tree has a node of type list but the full tree has a leaf of type int.
+    >>> prefix_errors({'a': 1, 'b': 2}, {'a': 3, 'c': 4})[0]('in_axes')
+    ValueError("Tree in_axes is not a prefix of the full tree at path (); the prefix tree has a node of type dict with keys ['a', 'b'] but the full tree has keys ['a', 'c'].")
+
+    Args:
+        prefix_tree (pytree): A pytree to check if it is a prefix of ``full_tree``.
+        full_tree (pytree): A pytree with the full structure.
+        is_leaf (callable, optional): An optionally specified function that will be called at each
+            flattening step. It should return a boolean, with :data:`True` stopping the traversal
+            and the whole subtree being treated as a leaf, and :data:`False` indicating the
+            flattening should traverse the current object.
+        none_is_leaf (bool, optional): Whether to treat :data:`None` as a leaf. If :data:`False`,
+            :data:`None` is a non-leaf node with arity 0. Thus :data:`None` is contained in the
+            treespec rather than in the leaves list. (default: :data:`False`)
+        namespace (str, optional): The registry namespace used for custom pytree node types.
+            (default: :const:`''`, i.e., the global namespace)
+
+    Returns:
+        A list of callables, one for each mismatched node in the pre-order traversal, that take the
+        name of the prefix tree and return a :exc:`ValueError`. The list is empty if
+        ``prefix_tree`` is a prefix of ``full_tree``.
+    """  # pylint: disable=line-too-long
+    return _rs.prefix_errors(
+        prefix_tree,
+        full_tree,
+        leaf_predicate=is_leaf,
+        none_is_leaf=none_is_leaf,
+        namespace=namespace,
+    )
+
+
+def tree_broadcast_common(
+    tree: Any,
+    other_tree: Any,
+    /,
+    is_leaf: Callable[[Any], bool] | None = None,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> tuple[Any, Any]:
+    """Return two pytrees of common suffix structure of ``tree`` and ``other_tree`` with broadcasted subtrees.
+
+    See also :func:`tree_broadcast_prefix` and :meth:`PyTreeSpec.broadcast_to_common_suffix`.
+
+    A leaf in one tree is repeated over the subtree at the corresponding position in the other
+    tree. The non-leaf nodes at the same position must be of the same type with the same metadata
+    (e.g., the same keys for dictionaries).
+
+    >>> tree_broadcast_common({'a': 1, 'b': (2, 3)}, {'a': [4, 5, None], 'b': 6})
+    ({'a': [1, 1, None], 'b': (2, 3)}, {'a': [4, 5, None], 'b': (6, 6)})
+    >>> tree_broadcast_common({'a': [1]}, {'a': (2,)})
+    Traceback (most recent call last):
+        ...
+    ValueError: Tree structures cannot be broadcast at path ('a',); the node [1] in tree 0 does not match the node (2,) in tree 1.
+
+    Args:
+        tree (pytree): A pytree has a common suffix structure of ``other_tree``.
+        other_tree (pytree): A pytree has a common suffix structure of ``tree``.
+        is_leaf (callable, optional): An optionally specified function that will be called at each
+            flattening step. It should return a boolean, with :data:`True` stopping the traversal
+            and the whole subtree being treated as a leaf, and :data:`False` indicating the
+            flattening should traverse the current object.
+        none_is_leaf (bool, optional): Whether to treat :data:`None` as a leaf. If :data:`False`,
+            :data:`None` is a non-leaf node with arity 0. Thus :data:`None` is contained in the
+            treespec rather than in the leaves list and :data:`None` will be remain in the result
+            pytree. (default: :data:`False`)
+        namespace (str, optional): The registry namespace used for custom pytree node types.
+            (default: :const:`''`, i.e., the global namespace)
+
+    Returns:
+        Two pytrees of the common suffix structure of ``tree`` and ``other_tree`` with the leaves
+        broadcast from ``tree`` and ``other_tree`` respectively.
+
+    Raises:
+        ValueError: If the trees have incompatible nodes at the same position.
+    """  # pylint: disable=line-too-long
+    return _rs.broadcast_common(
+        tree,
+        other_tree,
+        leaf_predicate=is_leaf,
+        none_is_leaf=none_is_leaf,
+        namespace=namespace,
+    )
+
+
+def tree_reduce_with_path(
+    func: Callable[[Any, tuple[Any, ...], _T], Any],
+    tree: _T,
+    initial: Any = __MISSING,
+    /,
+    is_leaf: Callable[[_T], bool] | None = None,
+    *,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> Any:
+    """Traverse a pytree with a path-aware function to reduce its leaves to a single value.
+
+    See also :func:`tree_map_with_path`.
+
+    The leaves are folded in flatten order with ``func(acc, path, leaf)``, where ``path`` is the
+    tuple of path entries of the leaf. If ``initial`` is not given, the first leaf is used as the
+    initial accumulator and ``func`` is called from the second leaf on.
+
+    >>> tree = {'encoder': {'w': 1, 'b': 2}, 'decoder': {'w': 3, 'b': 4}}
+    >>> tree_reduce_with_path(lambda acc, p, x: acc + x if p[-1] == 'w' else acc, tree, 0)
+    4
+    >>> tree_reduce_with_path(lambda acc, p, x: acc + [p], tree, [])
+    [('decoder', 'b'), ('decoder', 'w'), ('encoder', 'b'), ('encoder', 'w')]
+    >>> tree_reduce_with_path(lambda acc, p, x: acc * x, (2, [3, 4]))
+    24
+    >>> tree_reduce_with_path(lambda acc, p, x: acc + x, {'a': None})
+    Traceback (most recent call last):
+        ...
+    TypeError: reduce_with_path() of empty tree with no initial value.
+
+    Args:
+        func (callable): A function that takes the accumulator, the path, and the leaf value, and
+            returns the new accumulator.
+        tree (pytree): A pytree to be traversed.
+        initial (object, optional): An initial value to start the reduction. If not provided, the
+            first leaf is used as the initial value and the tree must have at least one leaf.
+        is_leaf (callable, optional): An optionally specified function that will be called at each
+            flattening step. It should return a boolean, with :data:`True` stopping the traversal
+            and the whole subtree being treated as a leaf, and :data:`False` indicating the
+            flattening should traverse the current object.
+        none_is_leaf (bool, optional): Whether to treat :data:`None` as a leaf. If :data:`False`,
+            :data:`None` is a non-leaf node with arity 0. Thus :data:`None` is contained in the
+            treespec rather than in the leaves list. (default: :data:`False`)
+        namespace (str, optional): The registry namespace used for custom pytree node types.
+            (default: :const:`''`, i.e., the global namespace)
+
+    Returns:
+        The result of reducing the leaves of the pytree using ``func``.
+    """
+    if initial is __MISSING:
+        return _rs.reduce_with_path(
+            func,
+            tree,
+            leaf_predicate=is_leaf,
+            none_is_leaf=none_is_leaf,
+            namespace=namespace,
+        )
+    return _rs.reduce_with_path(
+        func,
+        tree,
+        initial,
+        leaf_predicate=is_leaf,
+        none_is_leaf=none_is_leaf,
+        namespace=namespace,
+    )
+
+
+def tree_count(
+    predicate: Callable[[_T], Any],
+    tree: _T,
+    /,
+    is_leaf: Callable[[_T], bool] | None = None,
+    *,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> int:
+    """Count the leaves of a pytree that satisfy a predicate.
+
+    See also :func:`tree_flatten`.
+
+    The leaves are visited in a single traversal without building the leaves list or the treespec.
+
+    >>> tree = {'a': 1, 'b': (2.0, [3, None]), 'c': 'x'}
+    >>> tree_count(lambda x: isinstance(x, int), tree)
+    2
+    >>> tree_count(lambda x: True, tree)
+    4
+    >>> tree_count(lambda x: x is None, tree, none_is_leaf=True)
+    1
+
+    Args:
+        predicate (callable): A function that takes a leaf value and returns a truthy value if the
+            leaf should be counted.
+        tree (pytree): A pytree to be traversed.
+        is_leaf (callable, optional): An optionally specified function that will be called at each
+            flattening step. It should return a boolean, with :data:`True` stopping the traversal
+            and the whole subtree being treated as a leaf, and :data:`False` indicating the
+            flattening should traverse the current object.
+        none_is_leaf (bool, optional): Whether to treat :data:`None` as a leaf. If :data:`False`,
+            :data:`None` is a non-leaf node with arity 0. Thus :data:`None` is contained in the
+            treespec rather than in the leaves list. (default: :data:`False`)
+        namespace (str, optional): The registry namespace used for custom pytree node types.
+            (default: :const:`''`, i.e., the global namespace)
+
+    Returns:
+        The number of leaves for which ``predicate(leaf)`` is truthy.
+    """
+    return _rs.count(predicate, tree, is_leaf, none_is_leaf, namespace)
+
+
+def tree_sum(
+    tree: _T,
+    /,
+    is_leaf: Callable[[_T], bool] | None = None,
+    *,
+    start: Any = 0,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> Any:
+    """Return the sum of ``start`` and the leaves of a pytree.
+
+    See also :func:`tree_mean` and :func:`tree_reduce_with_path`.
+
+    The leaves are added with the ``+`` operator in a single traversal without building the leaves
+    list. Like the built-in :func:`sum`, the leaves are added to ``start`` from left to right.
+
+    >>> tree = {'a': 1, 'b': (2, [3, None])}
+    >>> tree_sum(tree)
+    6
+    >>> tree_sum(tree, start=10)
+    16
+    >>> tree_sum({'x': [1], 'y': [2, 3]}, is_leaf=lambda x: isinstance(x, list), start=[])
+    [1, 2, 3]
+
+    Args:
+        tree (pytree): A pytree to be traversed.
+        is_leaf (callable, optional): An optionally specified function that will be called at each
+            flattening step. It should return a boolean, with :data:`True` stopping the traversal
+            and the whole subtree being treated as a leaf, and :data:`False` indicating the
+            flattening should traverse the current object.
+        start (object, optional): The initial value to add the leaves to. (default: :const:`0`)
+        none_is_leaf (bool, optional): Whether to treat :data:`None` as a leaf. If :data:`False`,
+            :data:`None` is a non-leaf node with arity 0. Thus :data:`None` is contained in the
+            treespec rather than in the leaves list. (default: :data:`False`)
+        namespace (str, optional): The registry namespace used for custom pytree node types.
+            (default: :const:`''`, i.e., the global namespace)
+
+    Returns:
+        The sum of ``start`` and the leaves of the pytree, or ``start`` if the pytree has no leaves.
+    """
+    return _rs.sum(tree, is_leaf, none_is_leaf, namespace, start=start)
+
+
+def tree_mean(
+    tree: _T,
+    /,
+    is_leaf: Callable[[_T], bool] | None = None,
+    *,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> Any:
+    """Return the arithmetic mean of the leaves of a pytree.
+
+    See also :func:`tree_sum`.
+
+    The leaves are added with the ``+`` operator starting from the first leaf, and the total is
+    divided by the number of leaves with the ``/`` operator.
+
+    >>> tree_mean({'a': 1, 'b': (2, [3, 4])})
+    2.5
+    >>> tree_mean({'a': None})
+    Traceback (most recent call last):
+        ...
+    ValueError: mean() arg is an empty tree.
+
+    Args:
+        tree (pytree): A pytree to be traversed.
+        is_leaf (callable, optional): An optionally specified function that will be called at each
+            flattening step. It should return a boolean, with :data:`True` stopping the traversal
+            and the whole subtree being treated as a leaf, and :data:`False` indicating the
+            flattening should traverse the current object.
+        none_is_leaf (bool, optional): Whether to treat :data:`None` as a leaf. If :data:`False`,
+            :data:`None` is a non-leaf node with arity 0. Thus :data:`None` is contained in the
+            treespec rather than in the leaves list. (default: :data:`False`)
+        namespace (str, optional): The registry namespace used for custom pytree node types.
+            (default: :const:`''`, i.e., the global namespace)
+
+    Returns:
+        The mean of the leaves of the pytree.
+
+    Raises:
+        ValueError: If the pytree has no leaves.
+    """
+    return _rs.mean(tree, is_leaf, none_is_leaf, namespace)
+
+
+def tree_max(
+    tree: _T,
+    /,
+    is_leaf: Callable[[_T], bool] | None = None,
+    *,
+    default: _T = __MISSING,
+    key: Callable[[_T], Any] | None = None,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> _T:
+    """Return the largest leaf of a pytree.
+
+    See also :func:`tree_min` and :func:`tree_reduce_with_path`.
+
+    The leaves are compared in a single traversal without building the leaves list. Like the
+    built-in :func:`max`, the first maximal leaf is returned if there are ties.
+
+    >>> tree = {'a': 3, 'b': (-7, [5, None])}
+    >>> tree_max(tree)
+    5
+    >>> tree_max(tree, key=abs)
+    -7
+    >>> tree_max({'w': [3, 4], 'b': [1]}, is_leaf=lambda x: isinstance(x, list), key=len)
+    [3, 4]
+    >>> tree_max({'a': None})
+    Traceback (most recent call last):
+        ...
+    ValueError: max() arg is an empty tree.
+    >>> tree_max({'a': None}, default=0)
+    0
+
+    Args:
+        tree (pytree): A pytree to be traversed.
+        is_leaf (callable, optional): An optionally specified function that will be called at each
+            flattening step. It should return a boolean, with :data:`True` stopping the traversal
+            and the whole subtree being treated as a leaf, and :data:`False` indicating the
+            flattening should traverse the current object.
+        default (object, optional): The value to return if the pytree has no leaves. If not
+            provided, an empty pytree raises :exc:`ValueError`.
+        key (callable, optional): A function of one argument that is used to extract a comparison
+            key from each leaf. (default: :data:`None`, i.e., compare the leaves directly)
+        none_is_leaf (bool, optional): Whether to treat :data:`None` as a leaf. If :data:`False`,
+            :data:`None` is a non-leaf node with arity 0. Thus :data:`None` is contained in the
+            treespec rather than in the leaves list. (default: :data:`False`)
+        namespace (str, optional): The registry namespace used for custom pytree node types.
+            (default: :const:`''`, i.e., the global namespace)
+
+    Returns:
+        The largest leaf of the pytree, or ``default`` if the pytree has no leaves.
+
+    Raises:
+        ValueError: If the pytree has no leaves and ``default`` is not given.
+    """
+    defaults = () if default is __MISSING else (default,)
+    return _rs.max(tree, is_leaf, none_is_leaf, namespace, *defaults, key=key)
+
+
+def tree_min(
+    tree: _T,
+    /,
+    is_leaf: Callable[[_T], bool] | None = None,
+    *,
+    default: _T = __MISSING,
+    key: Callable[[_T], Any] | None = None,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> _T:
+    """Return the smallest leaf of a pytree.
+
+    See also :func:`tree_max` and :func:`tree_reduce_with_path`.
+
+    The leaves are compared in a single traversal without building the leaves list. Like the
+    built-in :func:`min`, the first minimal leaf is returned if there are ties.
+
+    >>> tree = {'a': 3, 'b': (-7, [5, None])}
+    >>> tree_min(tree)
+    -7
+    >>> tree_min(tree, key=abs)
+    3
+    >>> tree_min({'w': [3, 4], 'b': [1]}, is_leaf=lambda x: isinstance(x, list), key=len)
+    [1]
+    >>> tree_min({'a': None})
+    Traceback (most recent call last):
+        ...
+    ValueError: min() arg is an empty tree.
+    >>> tree_min({'a': None}, default=0)
+    0
+
+    Args:
+        tree (pytree): A pytree to be traversed.
+        is_leaf (callable, optional): An optionally specified function that will be called at each
+            flattening step. It should return a boolean, with :data:`True` stopping the traversal
+            and the whole subtree being treated as a leaf, and :data:`False` indicating the
+            flattening should traverse the current object.
+        default (object, optional): The value to return if the pytree has no leaves. If not
+            provided, an empty pytree raises :exc:`ValueError`.
+        key (callable, optional): A function of one argument that is used to extract a comparison
+            key from each leaf. (default: :data:`None`, i.e., compare the leaves directly)
+        none_is_leaf (bool, optional): Whether to treat :data:`None` as a leaf. If :data:`False`,
+            :data:`None` is a non-leaf node with arity 0. Thus :data:`None` is contained in the
+            treespec rather than in the leaves list. (default: :data:`False`)
+        namespace (str, optional): The registry namespace used for custom pytree node types.
+            (default: :const:`''`, i.e., the global namespace)
+
+    Returns:
+        The smallest leaf of the pytree, or ``default`` if the pytree has no leaves.
+
+    Raises:
+        ValueError: If the pytree has no leaves and ``default`` is not given.
+    """
+    defaults = () if default is __MISSING else (default,)
+    return _rs.min(tree, is_leaf, none_is_leaf, namespace, *defaults, key=key)
+
+
+def tree_all(
+    tree: _T,
+    /,
+    is_leaf: Callable[[_T], bool] | None = None,
+    *,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> bool:
+    """Test whether all leaves of a pytree are truthy.
+
+    See also :func:`tree_any` and :func:`tree_count`.
+
+    The leaves are visited in flatten order and the traversal stops at the first falsy leaf, so the
+    rest of the pytree is not flattened. Like the built-in :func:`all`, an empty pytree gives
+    :data:`True`.
+
+    >>> tree_all({'a': 1, 'b': (2, [3, None])})
+    True
+    >>> tree_all({'a': 1, 'b': (0, [3, None])})
+    False
+    >>> tree_all({'a': 1, 'b': (2, [3, None])}, none_is_leaf=True)
+    False
+    >>> tree_all({})
+    True
+
+    Args:
+        tree (pytree): A pytree to be traversed.
+        is_leaf (callable, optional): An optionally specified function that will be called at each
+            flattening step. It should return a boolean, with :data:`True` stopping the traversal
+            and the whole subtree being treated as a leaf, and :data:`False` indicating the
+            flattening should traverse the current object.
+        none_is_leaf (bool, optional): Whether to treat :data:`None` as a leaf. If :data:`False`,
+            :data:`None` is a non-leaf node with arity 0. Thus :data:`None` is contained in the
+            treespec rather than in the leaves list. (default: :data:`False`)
+        namespace (str, optional): The registry namespace used for custom pytree node types.
+            (default: :const:`''`, i.e., the global namespace)
+
+    Returns:
+        :data:`True` if all leaves of the pytree are truthy or the pytree has no leaves, otherwise
+        :data:`False`.
+    """
+    return _rs.all(tree, is_leaf, none_is_leaf, namespace)
+
+
+def tree_any(
+    tree: _T,
+    /,
+    is_leaf: Callable[[_T], bool] | None = None,
+    *,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> bool:
+    """Test whether any leaf of a pytree is truthy.
+
+    See also :func:`tree_all` and :func:`tree_count`.
+
+    The leaves are visited in flatten order and the traversal stops at the first truthy leaf, so
+    the rest of the pytree is not flattened. Like the built-in :func:`any`, an empty pytree gives
+    :data:`False`.
+
+    >>> tree_any({'a': 0, 'b': (0, [3, None])})
+    True
+    >>> tree_any({'a': 0, 'b': (0, [0.0, None])})
+    False
+    >>> tree_any({})
+    False
+
+    Args:
+        tree (pytree): A pytree to be traversed.
+        is_leaf (callable, optional): An optionally specified function that will be called at each
+            flattening step. It should return a boolean, with :data:`True` stopping the traversal
+            and the whole subtree being treated as a leaf, and :data:`False` indicating the
+            flattening should traverse the current object.
+        none_is_leaf (bool, optional): Whether to treat :data:`None` as a leaf. If :data:`False`,
+            :data:`None` is a non-leaf node with arity 0. Thus :data:`None` is contained in the
+            treespec rather than in the leaves list. (default: :data:`False`)
+        namespace (str, optional): The registry namespace used for custom pytree node types.
+            (default: :const:`''`, i.e., the global namespace)
+
+    Returns:
+        :data:`True` if any leaf of the pytree is truthy, otherwise :data:`False`.
+    """
+    return _rs.any(tree, is_leaf, none_is_leaf, namespace)
+
+
+def tree_leaf_depths(
+    tree: _T,
+    /,
+    is_leaf: Callable[[_T], bool] | None = None,
+    *,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> list[int]:
+    """Return the nesting depth of every leaf of a pytree in flatten order.
+
+    See also :func:`tree_flatten`.
+
+    The depth of a leaf is the number of nodes on the path from the root to the leaf, i.e., the
+    length of the path of the leaf. The root is at depth 0.
+
+    >>> tree_leaf_depths({'b': (2, [3, 4]), 'a': 1, 'c': None, 'd': 5})
+    [1, 2, 3, 3, 1]
+    >>> tree_leaf_depths({'b': (2, [3, 4]), 'a': 1, 'c': None, 'd': 5}, none_is_leaf=True)
+    [1, 2, 3, 3, 1, 1]
+    >>> tree_leaf_depths(1)
+    [0]
+    >>> tree_leaf_depths([])
+    []
+
+    Args:
+        tree (pytree): A pytree to be traversed.
+        is_leaf (callable, optional): An optionally specified function that will be called at each
+            flattening step. It should return a boolean, with :data:`True` stopping the traversal
+            and the whole subtree being treated as a leaf, and :data:`False` indicating the
+            flattening should traverse the current object.
+        none_is_leaf (bool, optional): Whether to treat :data:`None` as a leaf. If :data:`False`,
+            :data:`None` is a non-leaf node with arity 0. Thus :data:`None` is contained in the
+            treespec rather than in the leaves list. (default: :data:`False`)
+        namespace (str, optional): The registry namespace used for custom pytree node types.
+            (default: :const:`''`, i.e., the global namespace)
+
+    Returns:
+        A list of the depths of the leaves, in the same order as the leaves of :func:`tree_flatten`.
+    """
+    return _rs.leaf_depths(tree, is_leaf, none_is_leaf, namespace)
+
+
+def tree_shared_leaves(
+    tree: _T,
+    /,
+    is_leaf: Callable[[_T], bool] | None = None,
+    *,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+    ignore_types: type | tuple[type, ...] | None = __SCALAR_TYPES,
+) -> list[list[tuple[Any, ...]]]:
+    """Find the groups of paths whose leaves are the same object.
+
+    See also :func:`tree_flatten_with_accessor`.
+
+    The leaves are compared by identity, which detects unintended sharing of mutable leaves (e.g.,
+    parameters) or the leaves that can be stored only once. The immutable builtin scalars, such as
+    small integers and short strings, are cached or interned by the interpreter, so they are skipped
+    by default.
+
+    >>> weight = object()
+    >>> tree_shared_leaves({'a': weight, 'b': (weight, [object()]), 'c': weight})
+    [[('a',), ('b', 0), ('c',)]]
+    >>> tree_shared_leaves({'a': object(), 'b': [object()]})
+    []
+    >>> tree_shared_leaves({'b': [0, 1, 1]})
+    []
+    >>> tree_shared_leaves({'b': [0, 1, 1]}, ignore_types=None)
+    [[('b', 1), ('b', 2)]]
+
+    Args:
+        tree (pytree): A pytree to check.
+        is_leaf (callable, optional): An optionally specified function that will be called at each
+            flattening step. It should return a boolean, with :data:`True` stopping the traversal
+            and the whole subtree being treated as a leaf, and :data:`False` indicating the
+            flattening should traverse the current object.
+        none_is_leaf (bool, optional): Whether to treat :data:`None` as a leaf. If :data:`False`,
+            :data:`None` is a non-leaf node with arity 0. Thus :data:`None` is contained in the
+            treespec rather than in the leaves list. (default: :data:`False`)
+        namespace (str, optional): The registry namespace used for custom pytree node types.
+            (default: :const:`''`, i.e., the global namespace)
+        ignore_types (type or tuple of types, optional): The types of the leaves to skip, or
+            :data:`None` to check all leaves. (default: :data:`None`, :class:`bool`, :class:`int`,
+            :class:`float`, :class:`complex`, :class:`str`, and :class:`bytes`)
+
+    Returns:
+        A list of groups of the paths to the same leaf, in the order of the first occurrences of the
+        leaves. Only the leaves that appear more than once are reported.
+    """
+    return _rs.shared_leaves(
+        tree,
+        is_leaf,
+        none_is_leaf,
+        namespace,
+        ignore_types=ignore_types,
+    )
+
+
+def tree_type_counts(
+    tree: _T,
+    /,
+    is_leaf: Callable[[_T], bool] | None = None,
+    *,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+    with_sizes: bool = False,
+) -> dict[type, int] | dict[type, tuple[int, int]]:
+    """Count the leaves of a pytree by their types.
+
+    See also :func:`tree_leaves`.
+
+    The leaves are counted by their exact types in a single traversal, which gives a quick overview
+    of what lives inside a nested state. With ``with_sizes=True``, the total sizes of the leaves
+    reported by :meth:`object.__sizeof__` are also collected.
+
+    >>> tree = {'b': (2, [3.0, 4]), 'a': 1, 'c': None, 'd': 'text'}
+    >>> tree_type_counts(tree)
+    {<class 'int'>: 3, <class 'float'>: 1, <class 'str'>: 1}
+    >>> tree_type_counts(tree, none_is_leaf=True)
+    {<class 'int'>: 3, <class 'float'>: 1, <class 'NoneType'>: 1, <class 'str'>: 1}
+    >>> tree_type_counts([1.0, 2.0], with_sizes=True)
+    {<class 'float'>: (2, 48)}
+
+    Args:
+        tree (pytree): A pytree to count the leaves of.
+        is_leaf (callable, optional): An optionally specified function that will be called at each
+            flattening step. It should return a boolean, with :data:`True` stopping the traversal
+            and the whole subtree being treated as a leaf, and :data:`False` indicating the
+            flattening should traverse the current object.
+        none_is_leaf (bool, optional): Whether to treat :data:`None` as a leaf. If :data:`False`,
+            :data:`None` is a non-leaf node with arity 0. Thus :data:`None` is contained in the
+            treespec rather than in the leaves list. (default: :data:`False`)
+        namespace (str, optional): The registry namespace used for custom pytree node types.
+            (default: :const:`''`, i.e., the global namespace)
+        with_sizes (bool, optional): Whether to also sum up the sizes of the leaves in bytes
+            reported by :meth:`object.__sizeof__`. (default: :data:`False`)
+
+    Returns:
+        A dictionary mapping the leaf types to the numbers of the leaves, in the order of the first
+        occurrences of the types. With ``with_sizes=True``, the values are pairs ``(count, size)``.
+    """
+    return _rs.type_counts(tree, is_leaf, none_is_leaf, namespace, with_sizes=with_sizes)
+
+
+def tree_copy(
+    tree: _T,
+    /,
+    is_leaf: Callable[[_T], bool] | None = None,
+    *,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> _T:
+    """Return a shallow copy of a pytree with fresh containers.
+
+    See also :func:`tree_map` and :func:`copy.copy`.
+
+    All the containers are rebuilt (including custom pytree nodes) in a single pass, while the leaf
+    objects are shared with the original tree. The structure of the copy can be mutated safely
+    without copying the leaves. The dictionaries keep the insertion order of the original tree.
+
+    >>> tree = {'b': (2, [3, 4]), 'a': 1, 'c': None, 'd': [5]}
+    >>> copied = tree_copy(tree)
+    >>> copied == tree, copied is tree, copied['d'] is tree['d']
+    (True, False, False)
+    >>> copied['d'].append(6)
+    >>> tree
+    {'b': (2, [3, 4]), 'a': 1, 'c': None, 'd': [5]}
+    >>> leaf = [5]
+    >>> tree_copy({'x': leaf}, is_leaf=lambda x: isinstance(x, list))['x'] is leaf
+    True
+
+    Args:
+        tree (pytree): A pytree to be copied.
+        is_leaf (callable, optional): An optionally specified function that will be called at each
+            flattening step. It should return a boolean, with :data:`True` stopping the traversal
+            and the whole subtree being treated as a leaf, and :data:`False` indicating the
+            flattening should traverse the current object.
+        none_is_leaf (bool, optional): Whether to treat :data:`None` as a leaf. If :data:`False`,
+            :data:`None` is a non-leaf node with arity 0. Thus :data:`None` is contained in the
+            treespec rather than in the leaves list. (default: :data:`False`)
+        namespace (str, optional): The registry namespace used for custom pytree node types.
+            (default: :const:`''`, i.e., the global namespace)
+
+    Returns:
+        A new pytree with the same structure as ``tree``, sharing the leaves with ``tree``.
+    """
+    return _rs.copy(tree, is_leaf, none_is_leaf, namespace)
+
+
+def tree_deepcopy(
+    tree: _T,
+    /,
+    is_leaf: Callable[[_T], bool] | None = None,
+    *,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+    copy_leaf: Callable[[Any, dict[int, Any]], Any] | None = None,
+    memo: dict[int, Any] | None = None,
+) -> _T:
+    """Return a deep copy of a pytree.
+
+    See also :func:`tree_copy` and :func:`copy.deepcopy`.
+
+    The containers are rebuilt in a single pass and the leaves are copied by ``copy_leaf``. Like
+    :func:`copy.deepcopy`, the objects that appear multiple times in the tree are copied only once,
+    so the copy keeps the sharing of the original tree. The node metadata (e.g., the auxiliary data
+    of custom pytree nodes) is shared with the original tree.
+
+    >>> leaf = [1, 2]
+    >>> tree = {'x': leaf, 'y': (leaf, 3)}
+    >>> copied = tree_deepcopy(tree, is_leaf=lambda x: isinstance(x, list))
+    >>> copied
+    {'x': [1, 2], 'y': ([1, 2], 3)}
+    >>> copied['x'] is leaf, copied['x'] is copied['y'][0]
+    (False, True)
+    >>> tree_deepcopy(tree, copy_leaf=lambda x, memo: x * 10)
+    {'x': [10, 20], 'y': ([10, 20], 30)}
+
+    Args:
+        tree (pytree): A pytree to be copied.
+        is_leaf (callable, optional): An optionally specified function that will be called at each
+            flattening step. It should return a boolean, with :data:`True` stopping the traversal
+            and the whole subtree being treated as a leaf, and :data:`False` indicating the
+            flattening should traverse the current object.
+        none_is_leaf (bool, optional): Whether to treat :data:`None` as a leaf. If :data:`False`,
+            :data:`None` is a non-leaf node with arity 0. Thus :data:`None` is contained in the
+            treespec rather than in the leaves list. (default: :data:`False`)
+        namespace (str, optional): The registry namespace used for custom pytree node types.
+            (default: :const:`''`, i.e., the global namespace)
+        copy_leaf (callable, optional): A function ``copy_leaf(leaf, memo)`` to copy each distinct
+            leaf. (default: :func:`copy.deepcopy`)
+        memo (dict, optional): The memo dictionary of :func:`copy.deepcopy`, mapping the ids of the
+            original objects to their copies. It is shared with ``copy_leaf``, so it can be used to
+            copy the tree within a :meth:`__deepcopy__` method. (default: a new dictionary)
+
+    Returns:
+        A new pytree with the same structure as ``tree``, with the copied leaves.
+    """
+    return _rs.deepcopy(
+        tree,
+        is_leaf,
+        none_is_leaf,
+        namespace,
+        copy_leaf=copy_leaf,
+        memo=memo,
+    )
+
+
+def tree_replace_nones(
+    sentinel: Any,
+    tree: _T | None,
+    /,
+    is_leaf: Callable[[Any], bool] | None = None,
+    *,
+    namespace: str = '',
+) -> Any:
+    """Replace the :data:`None` values in a pytree with a sentinel.
+
+    See also :func:`tree_copy`.
+
+    Every :data:`None` in the pytree is replaced, whether it is a leaf or a node with arity 0, and
+    the containers are rebuilt in a single traversal. The leaves selected by ``is_leaf`` are kept
+    as is unless they are :data:`None`.
+
+    >>> tree_replace_nones(0, {'a': 1, 'b': (None, [None, 2])})
+    {'a': 1, 'b': (0, [0, 2])}
+    >>> tree_replace_nones(0, None)
+    0
+
+    Args:
+        sentinel (object): The value to replace the :data:`None` values with.
+        tree (pytree): A pytree to be transformed.
+        is_leaf (callable, optional): An optionally specified function that will be called at each
+            flattening step. It should return a boolean, with :data:`True` stopping the traversal
+            and the whole subtree being treated as a leaf, and :data:`False` indicating the
+            flattening should traverse the current object.
+        namespace (str, optional): The registry namespace used for custom pytree node types.
+            (default: :const:`''`, i.e., the global namespace)
+
+    Returns:
+        A new pytree with the same structure as ``tree`` with the :data:`None` values replaced by
+        ``sentinel``.
+    """
+    return _rs.replace_nones(sentinel, tree, is_leaf, namespace)
+
+
+def tree_hash(
+    tree: _T,
+    /,
+    is_leaf: Callable[[_T], bool] | None = None,
+    *,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+    leaf_hash: Callable[[_T], Hashable] | None = None,
+) -> int:
+    """Return a combined hash of the structure and the leaves of a pytree.
+
+    See also :func:`tree_flatten`.
+
+    The structure is hashed in Rust and the hashing of the leaves is delegated to ``leaf_hash``.
+    Two pytrees that are equal (with equal treespecs and equal leaves) have the same hash, which
+    is useful for change detection and caching of nested configurations. The hash does not depend
+    on the insertion order of :class:`dict` keys.
+
+    >>> tree_hash({'b': (2, [3, 4]), 'a': 1}) == tree_hash({'a': 1, 'b': (2, [3, 4])})
+    True
+    >>> tree_hash({'a': 1, 'b': (2, [3, 4])}) == tree_hash({'a': 1, 'b': [2, [3, 4]]})
+    False
+    >>> tree_hash({'a': [1, 2]}, is_leaf=lambda x: isinstance(x, list), leaf_hash=tuple) == (
+    ...     tree_hash({'a': [1, 2]}, is_leaf=lambda x: isinstance(x, list), leaf_hash=tuple)
+    ... )
+    True
+
+    Args:
+        tree (pytree): A pytree to be hashed.
+        is_leaf (callable, optional): An optionally specified function that will be called at each
+            flattening step. It should return a boolean, with :data:`True` stopping the traversal
+            and the whole subtree being treated as a leaf, and :data:`False` indicating the
+            flattening should traverse the current object.
+        none_is_leaf (bool, optional): Whether to treat :data:`None` as a leaf. If :data:`False`,
+            :data:`None` is a non-leaf node with arity 0. Thus :data:`None` is contained in the
+            treespec rather than in the leaves list. (default: :data:`False`)
+        namespace (str, optional): The registry namespace used for custom pytree node types.
+            (default: :const:`''`, i.e., the global namespace)
+        leaf_hash (callable, optional): A function that returns a hashable value (e.g., a digest
+            of the content of an array) for each leaf. (default: :data:`None`, i.e., use
+            :func:`hash` on the leaves)
+
+    Returns:
+        An integer hash of the pytree.
+    """
+    return _rs.hash(tree, is_leaf, none_is_leaf, namespace, leaf_hash=leaf_hash)
+
+
+def tree_format(
+    tree: _T,
+    /,
+    is_leaf: Callable[[_T], bool] | None = None,
+    *,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+    max_depth: int | None = None,
+    max_children: int | None = None,
+    leaf_repr: Callable[[_T], str] | None = None,
+    max_leaf_width: int | None = None,
+    indent: int = 4,
+) -> str:
+    """Format a pytree as an indented string, one node or leaf per line.
+
+    See also :meth:`PyTreeSpec.repr`.
+
+    The containers deeper than ``max_depth`` are elided as ``...`` in their brackets and only the
+    first ``max_children`` children are rendered for each container. The leaves are rendered by
+    ``leaf_repr``, e.g., a function that summarizes the shapes and dtypes of the arrays, which
+    keeps the output small when formatting huge states for logs. The renderings longer than
+    ``max_leaf_width`` characters are elided in the middle like :mod:`reprlib`.
+
+    >>> tree = {'b': (2, [3, 4]), 'a': 1, 'c': None, 'd': 5}
+    >>> print(tree_format(tree))
+    {
+        'a': 1,
+        'b': (
+            2,
+            [
+                3,
+                4,
+            ],
+        ),
+        'c': None,
+        'd': 5,
+    }
+    >>> print(tree_format(tree, max_depth=1, max_children=2, leaf_repr=lambda x: f'<int {x}>'))
+    {
+        'a': <int 1>,
+        'b': (...),
+        ...
+    }
+    >>> print(tree_format({'name': 'x' * 100}, max_leaf_width=20))
+    {
+        'name': 'xxxxxxx...xxxxxxxx',
+    }
+
+    Args:
+        tree (pytree): A pytree to be formatted.
+        is_leaf (callable, optional): An optionally specified function that will be called at each
+            flattening step. It should return a boolean, with :data:`True` stopping the traversal
+            and the whole subtree being treated as a leaf, and :data:`False` indicating the
+            flattening should traverse the current object.
+        none_is_leaf (bool, optional): Whether to treat :data:`None` as a leaf. If :data:`False`,
+            :data:`None` is a non-leaf node with arity 0. Thus :data:`None` is contained in the
+            treespec rather than in the leaves list. (default: :data:`False`)
+        namespace (str, optional): The registry namespace used for custom pytree node types.
+            (default: :const:`''`, i.e., the global namespace)
+        max_depth (int, optional): The maximum depth of the containers to expand.
+            (default: :data:`None`, i.e., no limit)
+        max_children (int, optional): The maximum number of children to render for each
+            container. (default: :data:`None`, i.e., no limit)
+        leaf_repr (callable, optional): A function that returns the string rendering of a leaf.
+            (default: :func:`repr`)
+        max_leaf_width (int, optional): The maximum number of characters of a leaf rendering.
+            (default: :data:`None`, i.e., no limit)
+        indent (int, optional): The number of spaces per indentation level. (default: :const:`4`)
+
+    Returns:
+        The formatted string of the pytree.
+    """
+    return _rs.format(
+        tree,
+        is_leaf,
+        none_is_leaf,
+        namespace,
+        max_depth=max_depth,
+        max_children=max_children,
+        leaf_repr=leaf_repr,
+        max_leaf_width=max_leaf_width,
+        indent=indent,
+    )
+
+
+def tree_align(tree: Any, treespec: PyTreeSpec, /) -> Any:
+    """Rebuild a pytree in the structure of a treespec.
+
+    See also :func:`tree_flatten` and :meth:`PyTreeSpec.unflatten`.
+
+    The children of the mappings are matched by their keys rather than their order, so the keys of
+    :class:`dict` are reordered and :class:`collections.OrderedDict` and :class:`dict` are converted
+    into each other. The sequences (e.g., :class:`tuple`, :class:`list`, and
+    :class:`collections.deque`) are converted into each other if they have the same length. The
+    fields of namedtuples and PyStructSequences are matched by their names if the node in the tree
+    has keys, otherwise by their positions. The leaves are kept as is. This is useful to feed the
+    trees saved by one library into the structures defined by another.
+
+    >>> from collections import OrderedDict
+    >>> treespec = tree_flatten(OrderedDict(b=[0, 0], a=0))[1]
+    >>> tree_align({'a': 1, 'b': (2, 3)}, treespec)
+    OrderedDict([('b', [2, 3]), ('a', 1)])
+    >>> tree_align({'a': 1, 'c': 2}, treespec)
+    Traceback (most recent call last):
+        ...
+    ValueError: Cannot align the tree to the PyTreeSpec at path (); missing keys ['b'], unexpected keys ['c'].
+    >>> tree_align({'a': 1, 'b': [2]}, treespec)
+    Traceback (most recent call last):
+        ...
+    ValueError: Cannot align the tree to the PyTreeSpec at path ('b',); expected [*, *], got [2].
+
+    Args:
+        tree (pytree): A pytree to be aligned.
+        treespec (PyTreeSpec): The treespec of the target structure.
+
+    Returns:
+        A pytree with the same leaves as ``tree`` and the structure of ``treespec``.
+
+    Raises:
+        ValueError: If the tree cannot be aligned to the treespec, e.g., the keys or the numbers of
+            the children do not match.
+    """
+    return _rs.align(tree, treespec)
+
+
+def tree_transpose(
+    outer_treespec: PyTreeSpec,
+    inner_treespec: PyTreeSpec,
+    tree: Any,
+    /,
+    is_leaf: Callable[[Any], bool] | None = None,
+) -> Any:
+    """Transform a tree having tree structure (outer, inner) into one having structure (inner, outer).
+
+    See also :func:`tree_flatten` and :meth:`PyTreeSpec.compose`.
+
+    >>> outer_treespec = tree_flatten({'a': 1, 'b': 2, 'c': (3, 4)})[1]
+    >>> outer_treespec
+    PyTreeSpec({'a': *, 'b': *, 'c': (*, *)})
+    >>> inner_treespec = tree_flatten([1, 2])[1]
+    >>> inner_treespec
+    PyTreeSpec([*, *])
+    >>> tree = {'a': [1, 2], 'b': [3, 4], 'c': ([5, 6], [7, 8])}
+    >>> tree_transpose(outer_treespec, inner_treespec, tree)
+    [{'a': 1, 'b': 3, 'c': (5, 7)}, {'a': 2, 'b': 4, 'c': (6, 8)}]
+    >>> tree_transpose(outer_treespec, inner_treespec, {'a': [1, 2], 'b': 3, 'c': 4})
+    Traceback (most recent call last):
+        ...
+    ValueError: Tree structure does not match the PyTreeSpec at path ('b',); expected [*, *], got 3.
+
+    Args:
+        outer_treespec (PyTreeSpec): A treespec object representing the outer structure of the
+            pytree.
+        inner_treespec (PyTreeSpec): A treespec object representing the inner structure of the
+            pytree.
+        tree (pytree): A pytree to be transposed.
+        is_leaf (callable, optional): An optionally specified function that will be called at each
+            flattening step. It should return a boolean, with :data:`True` stopping the traversal
+            and the whole subtree being treated as a leaf, and :data:`False` indicating the
+            flattening should traverse the current object.
+
+    Returns:
+        A new pytree with the structure ``inner_treespec`` whose leaves are the pytrees with the
+        structure ``outer_treespec``.
+
+    Raises:
+        ValueError: If the structure of ``tree`` is not the composition of ``outer_treespec`` and
+            ``inner_treespec``, or if ``inner_treespec`` has no leaves.
+    """  # pylint: disable=line-too-long
+    return _rs.transpose(outer_treespec, inner_treespec, tree, leaf_predicate=is_leaf)
+
+
+def tree_transpose_map(
+    func: Callable[..., Any],
+    tree: _T,
+    /,
+    *rests: Any,
+    inner_treespec: PyTreeSpec | None = None,
+    is_leaf: Callable[[_T], bool] | None = None,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> Any:
+    """Map a multi-input function over pytree args to produce a new pytree with transposed structure.
+
+    See also :func:`tree_map` and :func:`tree_transpose`.
+
+    The function ``func`` returns a pytree with the inner structure for each leaf, and the results
+    are transposed into a pytree with the inner structure whose leaves are pytrees with the
+    structure of ``tree``.
+
+    >>> tree = {'b': (2, [3, 4]), 'a': 1, 'c': (5, 6)}
+    >>> tree_transpose_map(lambda x: {'identity': x, 'double': 2 * x}, tree)
+    {'double': {'a': 2, 'b': (4, [6, 8]), 'c': (10, 12)}, 'identity': {'a': 1, 'b': (2, [3, 4]), 'c': (5, 6)}}
+    >>> tree_transpose_map(lambda x: (x, 0), tree, inner_treespec=tree_flatten((0, [0]))[1])
+    Traceback (most recent call last):
+        ...
+    ValueError: Tree structure does not match the PyTreeSpec at path (1,); expected [*], got 0.
+
+    Args:
+        func (callable): A function that takes ``1 + len(rests)`` arguments, to be applied at the
+            corresponding leaves of the pytrees.
+        tree (pytree): A pytree to be mapped over, with each leaf providing the first positional
+            argument to function ``func``.
+        rests (tuple of pytree): A tuple of pytrees, each of which has the same structure as
+            ``tree``.
+        inner_treespec (PyTreeSpec, optional): The treespec object representing the inner structure
+            of the result pytree. If not specified, the inner structure is inferred from the result
+            of the function ``func`` on the first leaf. (default: :data:`None`)
+        is_leaf (callable, optional): An optionally specified function that will be called at each
+            flattening step. It should return a boolean, with :data:`True` stopping the traversal
+            and the whole subtree being treated as a leaf, and :data:`False` indicating the
+            flattening should traverse the current object.
+        none_is_leaf (bool, optional): Whether to treat :data:`None` as a leaf. If :data:`False`,
+            :data:`None` is a non-leaf node with arity 0. Thus :data:`None` is contained in the
+            treespec rather than in the leaves list and :data:`None` will be remain in the result
+            pytree. (default: :data:`False`)
+        namespace (str, optional): The registry namespace used for custom pytree node types.
+            (default: :const:`''`, i.e., the global namespace)
+
+    Returns:
+        A new pytree with the inner structure whose leaves are pytrees with the same structure as
+        ``tree``, i.e., the transposition of the results of ``func(x, *xs)`` where ``x`` is the
+        value at the corresponding leaf in ``tree`` and ``xs`` is the tuple of values at
+        corresponding nodes in ``rests``.
+
+    Raises:
+        ValueError: If the inner structure cannot be inferred because ``tree`` has no leaves, or
+            the results of ``func`` do not have the inner structure.
+    """  # pylint: disable=line-too-long
+    return _rs.transpose_map(
+        func,
+        tree,
+        *rests,
+        inner_treespec=inner_treespec,
+        leaf_predicate=is_leaf,
+        none_is_leaf=none_is_leaf,
+        namespace=namespace,
+    )
+
+
+class _NumpyRavelBackend:
+    """The default backend of :func:`tree_ravel` for NumPy arrays."""
+
+    def __init__(self) -> None:
+        import numpy as np  # pylint: disable=import-outside-toplevel
+
+        self.np = np
+
+    def ravel(self, leaf: Any, /) -> tuple[Any, Any, Any]:
+        array = self.np.asarray(leaf)
+        return array.ravel(), array.shape, array.dtype
+
+    def concatenate(self, flats: list[Any], /) -> Any:
+        if not flats:
+            return self.np.zeros((0,))
+        return self.np.concatenate(flats)
+
+    def unravel(self, flat: Any, shape: Any, dtype: Any, /) -> Any:
+        return flat.reshape(shape).astype(dtype, copy=False)
+
+
+def tree_ravel(
+    tree: _T,
+    /,
+    is_leaf: Callable[[_T], bool] | None = None,
+    *,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+    backend: RavelBackend | None = None,
+) -> tuple[Any, Unraveler]:
+    """Ravel the array leaves of a pytree into one flat buffer.
+
+    See also :func:`tree_flatten` and :class:`rustree.typing.RavelBackend`.
+
+    The leaves are raveled to one-dimensional arrays and concatenated in the flattening order. The
+    returned :class:`Unraveler` records the treespec and the shapes, dtypes, and offsets of the
+    leaves, and restores a flat buffer of the same size to a pytree with the same structure.
+
+    >>> import numpy as np  # doctest: +SKIP
+    >>> tree = {'b': np.zeros((2, 3)), 'a': np.arange(2, dtype=np.int32)}  # doctest: +SKIP
+    >>> flat, unravel = tree_ravel(tree)  # doctest: +SKIP
+    >>> flat  # doctest: +SKIP
+    array([0., 1., 0., 0., 0., 0., 0., 0.])
+    >>> unravel(flat + 1)  # doctest: +SKIP
+    {'a': array([1, 2], dtype=int32), 'b': array([[1., 1., 1.],
+           [1., 1., 1.]])}
+
+    Args:
+        tree (pytree): A pytree whose leaves are arrays to ravel.
+        is_leaf (callable, optional): An optionally specified function that will be called at each
+            flattening step. It should return a boolean, with :data:`True` stopping the traversal
+            and the whole subtree being treated as a leaf, and :data:`False` indicating the
+            flattening should traverse the current object.
+        none_is_leaf (bool, optional): Whether to treat :data:`None` as a leaf. If :data:`False`,
+            :data:`None` is a non-leaf node with arity 0. Thus :data:`None` is contained in the
+            treespec rather than in the leaves list. (default: :data:`False`)
+        namespace (str, optional): The registry namespace used for custom pytree node types.
+            (default: :const:`''`, i.e., the global namespace)
+        backend (RavelBackend, optional): The hooks to ravel, concatenate, and restore the leaves.
+            If not specified, the leaves are converted to NumPy arrays. (default: :data:`None`)
+
+    Returns:
+        A pair ``(flat, unravel)`` where ``flat`` is the one-dimensional buffer of all leaves and
+        ``unravel`` is a callable restoring a flat buffer of the same size to the pytree.
+    """
+    if backend is None:
+        backend = _NumpyRavelBackend()
+    return _rs.ravel(
+        tree,
+        backend,
+        leaf_predicate=is_leaf,
+        none_is_leaf=none_is_leaf,
+        namespace=namespace,
+    )
+
+
+def treespec_leaf(*, none_is_leaf: bool = False) -> PyTreeSpec:
+    """Make a treespec representing a leaf node.
+
+    See also :func:`tree_flatten` and :func:`treespec_none`.
+
+    >>> treespec_leaf()
+    PyTreeSpec(*)
+    >>> treespec_leaf(none_is_leaf=True)
+    PyTreeSpec(*, NoneIsLeaf)
+    >>> treespec_leaf().unflatten([1])
+    1
+
+    Args:
+        none_is_leaf (bool, optional): Whether to treat :data:`None` as a leaf. If :data:`False`,
+            :data:`None` is a non-leaf node with arity 0. (default: :data:`False`)
+
+    Returns:
+        A treespec representing a leaf node.
+    """
+    return _rs.make_leaf(none_is_leaf)
+
+
+def treespec_none(*, none_is_leaf: bool = False) -> PyTreeSpec:
+    """Make a treespec representing a :data:`None` node.
+
+    See also :func:`tree_flatten` and :func:`treespec_leaf`.
+
+    >>> treespec_none()
+    PyTreeSpec(None)
+    >>> treespec_none(none_is_leaf=True)
+    PyTreeSpec(*, NoneIsLeaf)
+    >>> treespec_none().unflatten([])
+
+    Args:
+        none_is_leaf (bool, optional): Whether to treat :data:`None` as a leaf. If :data:`False`,
+            :data:`None` is a non-leaf node with arity 0. (default: :data:`False`)
+
+    Returns:
+        A treespec representing a :data:`None` node, or a leaf if ``none_is_leaf`` is :data:`True`.
+    """
+    return _rs.make_none(none_is_leaf)
+
+
+def treespec_tuple(
+    iterable: Iterable[PyTreeSpec] = (),
+    /,
+    *,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> PyTreeSpec:
+    """Make a tuple treespec from an iterable of child treespecs.
+
+    See also :func:`treespec_list` and :func:`treespec_leaf`.
+
+    >>> treespec_tuple([treespec_leaf(), treespec_leaf()])
+    PyTreeSpec((*, *))
+    >>> treespec_tuple([treespec_leaf(), tree_flatten([1, 2])[1]])
+    PyTreeSpec((*, [*, *]))
+    >>> treespec_tuple()
+    PyTreeSpec(())
+
+    Args:
+        iterable (iterable of PyTreeSpec, optional): The treespecs of the children.
+            (default: :const:`()`)
+        none_is_leaf (bool, optional): Whether to treat :data:`None` as a leaf. It must be the same
+            as the value of the children. (default: :data:`False`)
+        namespace (str, optional): The registry namespace of the custom nodes in the children.
+            (default: :const:`''`)
+
+    Returns:
+        A treespec representing a tuple node with the given children.
+    """
+    return _rs.make_container(_rs.PyTreeKind.TUPLE, iterable, None, none_is_leaf, namespace)
+
+
+def treespec_list(
+    iterable: Iterable[PyTreeSpec] = (),
+    /,
+    *,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> PyTreeSpec:
+    """Make a list treespec from an iterable of child treespecs.
+
+    See also :func:`treespec_tuple` and :func:`treespec_leaf`.
+
+    >>> treespec_list([treespec_leaf(), treespec_none()])
+    PyTreeSpec([*, None])
+
+    Args:
+        iterable (iterable of PyTreeSpec, optional): The treespecs of the children.
+            (default: :const:`()`)
+        none_is_leaf (bool, optional): Whether to treat :data:`None` as a leaf. It must be the same
+            as the value of the children. (default: :data:`False`)
+        namespace (str, optional): The registry namespace of the custom nodes in the children.
+            (default: :const:`''`)
+
+    Returns:
+        A treespec representing a list node with the given children.
+    """
+    return _rs.make_container(_rs.PyTreeKind.LIST, iterable, None, none_is_leaf, namespace)
+
+
+def _ordered_keys(dct: Mapping[Any, Any], /, namespace: str) -> list[Any]:
+    # Follow the key order of the dictionaries in `tree_flatten`
+    if _rs.is_dict_insertion_ordered(namespace):
+        return list(dct)
+    return total_order_sorted(dct)
+
+
+def treespec_dict(
+    mapping: Mapping[Any, PyTreeSpec] | Iterable[tuple[Any, PyTreeSpec]] = (),
+    /,
+    *,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+    **kwargs: PyTreeSpec,
+) -> PyTreeSpec:
+    """Make a dict treespec from a mapping of child treespecs.
+
+    See also :func:`treespec_ordereddict` and :func:`treespec_defaultdict`.
+
+    The keys are sorted as in :func:`tree_flatten`, unless the dictionary insertion order is kept
+    for the namespace (see :func:`dict_insertion_ordered`).
+
+    >>> treespec_dict({'b': treespec_leaf(), 'a': treespec_none()})
+    PyTreeSpec({'a': None, 'b': *})
+    >>> treespec_dict(a=treespec_leaf()) == tree_flatten({'a': 1})[1]
+    True
+
+    Args:
+        mapping (mapping of PyTreeSpec, optional): The treespecs of the children by their keys.
+            (default: :const:`()`)
+        none_is_leaf (bool, optional): Whether to treat :data:`None` as a leaf. It must be the same
+            as the value of the children. (default: :data:`False`)
+        namespace (str, optional): The registry namespace of the custom nodes in the children.
+            (default: :const:`''`)
+        **kwargs (PyTreeSpec, optional): Additional children by their keys.
+
+    Returns:
+        A treespec representing a dict node with the given children.
+    """
+    dct = dict(mapping, **kwargs)
+    keys = _ordered_keys(dct, namespace=namespace)
+    return _rs.make_container(
+        _rs.PyTreeKind.DICT,
+        [dct[key] for key in keys],
+        keys,
+        none_is_leaf,
+        namespace,
+    )
+
+
+def treespec_ordereddict(
+    mapping: Mapping[Any, PyTreeSpec] | Iterable[tuple[Any, PyTreeSpec]] = (),
+    /,
+    *,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+    **kwargs: PyTreeSpec,
+) -> PyTreeSpec:
+    """Make an OrderedDict treespec from a mapping of child treespecs.
+
+    See also :func:`treespec_dict` and :func:`treespec_defaultdict`.
+
+    >>> treespec_ordereddict({'b': treespec_leaf(), 'a': treespec_none()})
+    PyTreeSpec(OrderedDict({'b': *, 'a': None}))
+
+    Args:
+        mapping (mapping of PyTreeSpec, optional): The treespecs of the children by their keys, in
+            order. (default: :const:`()`)
+        none_is_leaf (bool, optional): Whether to treat :data:`None` as a leaf. It must be the same
+            as the value of the children. (default: :data:`False`)
+        namespace (str, optional): The registry namespace of the custom nodes in the children.
+            (default: :const:`''`)
+        **kwargs (PyTreeSpec, optional): Additional children by their keys.
+
+    Returns:
+        A treespec representing an OrderedDict node with the given children.
+    """
+    dct = OrderedDict(mapping, **kwargs)
+    return _rs.make_container(
+        _rs.PyTreeKind.ORDEREDDICT,
+        list(dct.values()),
+        list(dct),
+        none_is_leaf,
+        namespace,
+    )
+
+
+def treespec_defaultdict(
+    default_factory: Callable[[], Any] | None = None,
+    mapping: Mapping[Any, PyTreeSpec] | Iterable[tuple[Any, PyTreeSpec]] = (),
+    /,
+    *,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+    **kwargs: PyTreeSpec,
+) -> PyTreeSpec:
+    """Make a defaultdict treespec from a mapping of child treespecs.
+
+    See also :func:`treespec_dict` and :func:`treespec_ordereddict`.
+
+    The keys are sorted as in :func:`tree_flatten`, unless the dictionary insertion order is kept
+    for the namespace (see :func:`dict_insertion_ordered`).
+
+    >>> treespec_defaultdict(list, {'b': treespec_leaf(), 'a': treespec_none()})
+    PyTreeSpec(defaultdict(<class 'list'>, {'a': None, 'b': *}))
+
+    Args:
+        default_factory (callable or None, optional): The default factory of the defaultdict.
+            (default: :data:`None`)
+        mapping (mapping of PyTreeSpec, optional): The treespecs of the children by their keys.
+            (default: :const:`()`)
+        none_is_leaf (bool, optional): Whether to treat :data:`None` as a leaf. It must be the same
+            as the value of the children. (default: :data:`False`)
+        namespace (str, optional): The registry namespace of the custom nodes in the children.
+            (default: :const:`''`)
+        **kwargs (PyTreeSpec, optional): Additional children by their keys.
+
+    Returns:
+        A treespec representing a defaultdict node with the given children.
+    """
+    dct = defaultdict(default_factory, mapping, **kwargs)
+    keys = _ordered_keys(dct, namespace=namespace)
+    return _rs.make_container(
+        _rs.PyTreeKind.DEFAULTDICT,
+        [dct[key] for key in keys],
+        (default_factory, keys),
+        none_is_leaf,
+        namespace,
+    )
+
+
+def treespec_deque(
+    iterable: Iterable[PyTreeSpec] = (),
+    /,
+    maxlen: int | None = None,
+    *,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> PyTreeSpec:
+    """Make a deque treespec from an iterable of child treespecs.
+
+    See also :func:`treespec_list` and :func:`treespec_tuple`.
+
+    >>> treespec_deque([treespec_leaf(), treespec_leaf()], maxlen=5)
+    PyTreeSpec(deque([*, *], maxlen=5))
+
+    Args:
+        iterable (iterable of PyTreeSpec, optional): The treespecs of the children.
+            (default: :const:`()`)
+        maxlen (int or None, optional): The maximum length of the deque. (default: :data:`None`)
+        none_is_leaf (bool, optional): Whether to treat :data:`None` as a leaf. It must be the same
+            as the value of the children. (default: :data:`False`)
+        namespace (str, optional): The registry namespace of the custom nodes in the children.
+            (default: :const:`''`)
+
+    Returns:
+        A treespec representing a deque node with the given children.
+    """
+    return _rs.make_container(_rs.PyTreeKind.DEQUE, iterable, maxlen, none_is_leaf, namespace)
+
+
+def treespec_from_collection(
+    collection: Any,
+    /,
+    *,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> PyTreeSpec:
+    """Make a treespec from a one-level collection of child treespecs.
+
+    See also :func:`treespec_tuple`, :func:`treespec_dict`, and :func:`tree_flatten`.
+
+    The collection is flattened by one level as in :func:`tree_flatten`, including the custom node
+    types registered in the namespace. The children that are treespecs are used as the subtrees,
+    and the other children are leaves. If the collection itself is a leaf, a leaf treespec is
+    returned.
+
+    >>> treespec_from_collection({'b': treespec_leaf(), 'a': treespec_tuple()})
+    PyTreeSpec({'a': (), 'b': *})
+    >>> treespec_from_collection([1, treespec_none(), tree_flatten((1, 2))[1]])
+    PyTreeSpec([*, None, (*, *)])
+    >>> treespec_from_collection(1)
+    PyTreeSpec(*)
+
+    Args:
+        collection (object): A one-level collection of treespecs and leaves.
+        none_is_leaf (bool, optional): Whether to treat :data:`None` as a leaf. It must be the same
+            as the value of the children. (default: :data:`False`)
+        namespace (str, optional): The registry namespace used for custom pytree node types.
+            (default: :const:`''`, i.e., the global namespace)
+
+    Returns:
+        A treespec with the node of the collection as the root.
+    """
+    return _rs.make_from_collection(collection, none_is_leaf, namespace)
@@ -0,0 +1,777 @@
+# Copyright 2024-2025 Xuehai Pan. All Rights Reserved.
+#
+# Licensed under the Apache License, Version 2.0 (the "License");
+# you may not use this file except in compliance with the License.
+# You may obtain a copy of the License at
+#
+#     http://www.apache.org/licenses/LICENSE-2.0
+#
+# Unless required by applicable law or agreed to in writing, software
+# distributed under the License is distributed on an "AS IS" BASIS,
+# WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+# See the License for the specific language governing permissions and
+# limitations under the License.
+# ==============================================================================
+"""An :mod:`optree`-compatible API backed by the RusTree implementations.
+
+The functions in this module have the same names, parameter names, and defaults as the public API
+of :mod:`optree`, so the existing code can switch with a one-line import change:
+
+>>> import rustree.optree_compat as optree
+>>> optree.tree_map(lambda x: x + 1, {'b': (2, [3, 4]), 'a': 1})
+{'a': 2, 'b': (3, [4, 5])}
+
+The :mod:`optree` functions that are not supported yet raise :exc:`AttributeError` with a message
+naming the gap:
+
+>>> optree.register_pytree_node_namespace  # doctest: +ELLIPSIS
+Traceback (most recent call last):
+    ...
+AttributeError: rustree.optree_compat does not support optree.register_pytree_node_namespace yet...
+"""
+
+from __future__ import annotations
+
+from typing import TYPE_CHECKING, Any, TypeVar
+
+import rustree._rs as _rs
+from rustree.accessors import (
+    AutoEntry,
+    DataclassEntry,
+    FlattenedEntry,
+    GetAttrEntry,
+    GetItemEntry,
+    MappingEntry,
+    NamedTupleEntry,
+    PyTreeAccessor,
+    PyTreeEntry,
+    SequenceEntry,
+    StructSequenceEntry,
+)
+from rustree.ops import (
+    all_leaves,
+    broadcast_prefix,
+    prefix_errors,
+    tree_broadcast_common,
+    tree_broadcast_map,
+    tree_broadcast_map_with_path,
+    tree_broadcast_prefix,
+    tree_flatten_with_accessor,
+    tree_is_leaf,
+    tree_partition,
+    tree_transpose,
+    tree_transpose_map,
+    tree_unflatten,
+    treespec_defaultdict,
+    treespec_deque,
+    treespec_dict,
+    treespec_from_collection,
+    treespec_list,
+    treespec_ordereddict,
+    treespec_tuple,
+)
+from rustree.registry import (
+    dict_insertion_ordered,
+    register_pytree_node,
+    register_pytree_node_class,
+    unregister_pytree_node,
+)
+from rustree.typing import (
+    PyTreeKind,
+    PyTreeSpec,
+    is_namedtuple,
+    is_namedtuple_class,
+    is_namedtuple_instance,
+    is_structseq,
+    is_structseq_class,
+    is_structseq_instance,
+    namedtuple_fields,
+    structseq_fields,
+)
+
+
+if TYPE_CHECKING:
+    from collections.abc import Callable, Iterable, Iterator
+
+
+__all__ = [
+    # Tree operations
+    'all_leaves',
+    'tree_flatten',
+    'tree_flatten_with_path',
+    'tree_flatten_with_accessor',
+    'tree_unflatten',
+    'tree_iter',
+    'tree_leaves',
+    'tree_structure',
+    'tree_paths',
+    'tree_accessors',
+    'tree_is_leaf',
+    'tree_map',
+    'tree_map_',
+    'tree_map_with_path',
+    'tree_map_with_path_',
+    'tree_map_with_accessor',
+    'tree_replace_nones',
+    'tree_partition',
+    'tree_broadcast_map',
+    'tree_broadcast_map_with_path',
+    'tree_broadcast_prefix',
+    'tree_broadcast_common',
+    'broadcast_prefix',
+    'prefix_errors',
+    'broadcast_common',
+    'tree_transpose',
+    'tree_transpose_map',
+    'tree_reduce',
+    'tree_sum',
+    'tree_max',
+    'tree_min',
+    'tree_all',
+    'tree_any',
+    # Treespec
+    'treespec_children',
+    'treespec_child',
+    'treespec_entries',
+    'treespec_entry',
+    'treespec_paths',
+    'treespec_accessors',
+    'treespec_is_leaf',
+    'treespec_is_strict_leaf',
+    'treespec_is_prefix',
+    'treespec_is_suffix',
+    'treespec_transform',
+    'treespec_leaf',
+    'treespec_none',
+    'treespec_tuple',
+    'treespec_list',
+    'treespec_dict',
+    'treespec_ordereddict',
+    'treespec_defaultdict',
+    'treespec_deque',
+    'treespec_namedtuple',
+    'treespec_structseq',
+    'treespec_from_collection',
+    # Registry
+    'register_pytree_node',
+    'register_pytree_node_class',
+    'unregister_pytree_node',
+    'dict_insertion_ordered',
+    # Typing
+    'PyTreeSpec',
+    'PyTreeKind',
+    'is_namedtuple',
+    'is_namedtuple_class',
+    'is_namedtuple_instance',
+    'namedtuple_fields',
+    'is_structseq',
+    'is_structseq_instance',
+    'is_structseq_class',
+    'structseq_fields',
+    # Accessor
+    'PyTreeEntry',
+    'GetAttrEntry',
+    'GetItemEntry',
+    'FlattenedEntry',
+    'AutoEntry',
+    'SequenceEntry',
+    'MappingEntry',
+    'NamedTupleEntry',
+    'StructSequenceEntry',
+    'DataclassEntry',
+    'PyTreeAccessor',
+]
+
+
+# The public API of optree that has no counterpart in RusTree yet
+_UNSUPPORTED: frozenset[str] = frozenset(
+    {
+        'tree_transpose_map_with_path',
+        'tree_transpose_map_with_accessor',
+        'tree_broadcast_map_with_accessor',
+        'tree_map_with_accessor_',
+        'tree_flatten_one_level',
+        'treespec_one_level',
+        'treespec_is_one_level',
+        'register_pytree_node_namespace',
+    },
+)
+
+
+def __getattr__(name: str, /) -> Any:
+    """Report the :mod:`optree` API that is not supported yet."""
+    if name in _UNSUPPORTED:
+        raise AttributeError(
+            f'rustree.optree_compat does not support optree.{name} yet. '
+            f'Please report the gap to the RusTree issue tracker.',
+        )
+    raise AttributeError(f'module {__name__!r} has no attribute {name!r}')
+
+
+_T = TypeVar('_T')
+_U = TypeVar('_U')
+
+
+__MISSING: Any = object()
+
+
+def tree_flatten(
+    tree: _T,
+    /,
+    is_leaf: Callable[[_T], bool] | None = None,
+    *,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> tuple[list[_T], PyTreeSpec]:
+    """Flatten a pytree.
+
+    See :func:`rustree.tree_flatten`.
+    """
+    return _rs.flatten(tree, is_leaf, none_is_leaf, namespace)
+
+
+def tree_flatten_with_path(
+    tree: _T,
+    /,
+    is_leaf: Callable[[_T], bool] | None = None,
+    *,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> tuple[list[tuple[Any, ...]], list[_T], PyTreeSpec]:
+    """Flatten a pytree and additionally record the paths.
+
+    >>> tree_flatten_with_path({'b': (2, [3, 4]), 'a': 1})  # doctest: +IGNORE_WHITESPACE
+    (
+        [('a',), ('b', 0), ('b', 1, 0), ('b', 1, 1)],
+        [1, 2, 3, 4],
+        PyTreeSpec({'a': *, 'b': (*, [*, *])})
+    )
+    """
+    items, treespec = _rs.flatten_with_path(tree, is_leaf, none_is_leaf, namespace)
+    return [path for path, _ in items], [leaf for _, leaf in items], treespec
+
+
+def tree_iter(
+    tree: _T,
+    /,
+    is_leaf: Callable[[_T], bool] | None = None,
+    *,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> Iterator[_T]:
+    """Get an iterator over the leaves of a pytree."""
+    return iter(_rs.leaves(tree, is_leaf, none_is_leaf, namespace))
+
+
+def tree_leaves(
+    tree: _T,
+    /,
+    is_leaf: Callable[[_T], bool] | None = None,
+    *,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> list[_T]:
+    """Get the leaves of a pytree.
+
+    See :func:`rustree.tree_leaves`.
+    """
+    return _rs.leaves(tree, is_leaf, none_is_leaf, namespace)
+
+
+def tree_structure(
+    tree: _T,
+    /,
+    is_leaf: Callable[[_T], bool] | None = None,
+    *,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> PyTreeSpec:
+    """Get the treespec for a pytree.
+
+    >>> tree_structure({'b': (2, [3, 4]), 'a': 1, 'c': None})
+    PyTreeSpec({'a': *, 'b': (*, [*, *]), 'c': None})
+    """
+    return _rs.structure(tree, is_leaf, none_is_leaf, namespace)
+
+
+def tree_paths(
+    tree: _T,
+    /,
+    is_leaf: Callable[[_T], bool] | None = None,
+    *,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> list[tuple[Any, ...]]:
+    """Get the path entries to the leaves of a pytree."""
+    return tree_flatten_with_path(
+        tree,
+        is_leaf,
+        none_is_leaf=none_is_leaf,
+        namespace=namespace,
+    )[0]
+
+
+def tree_accessors(
+    tree: _T,
+    /,
+    is_leaf: Callable[[_T], bool] | None = None,
+    *,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> list[PyTreeAccessor]:
+    """Get the accessors to the leaves of a pytree."""
+    return _rs.flatten_with_accessor(tree, is_leaf, none_is_leaf, namespace)[0]
+
+
+def tree_map(
+    func: Callable[..., _U],
+    tree: _T,
+    /,
+    *rests: Any,
+    is_leaf: Callable[[_T], bool] | None = None,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> Any:
+    """Map a multi-input function over pytree args to produce a new pytree.
+
+    See :func:`rustree.tree_map`.
+    """
+    return _rs.map(
+        func,
+        tree,
+        *rests,
+        leaf_predicate=is_leaf,
+        none_is_leaf=none_is_leaf,
+        namespace=namespace,
+    )
+
+
+def tree_map_(
+    func: Callable[..., Any],
+    tree: _T,
+    /,
+    *rests: Any,
+    is_leaf: Callable[[_T], bool] | None = None,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> _T:
+    """Like :func:`tree_map`, but do an inplace call on each leaf and return the original tree.
+
+    >>> seen = []
+    >>> tree_map_(seen.append, {'b': (2, [3, 4]), 'a': 1})
+    {'b': (2, [3, 4]), 'a': 1}
+    >>> seen
+    [1, 2, 3, 4]
+    """
+    return _rs.map_with_path_(
+        lambda _, *xs: func(*xs),
+        tree,
+        *rests,
+        leaf_predicate=is_leaf,
+        none_is_leaf=none_is_leaf,
+        namespace=namespace,
+    )
+
+
+def tree_map_with_path(
+    func: Callable[..., _U],
+    tree: _T,
+    /,
+    *rests: Any,
+    is_leaf: Callable[[_T], bool] | None = None,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> Any:
+    """Map a multi-input function over pytree args as well as the tree paths to produce a new pytree.
+
+    See :func:`rustree.tree_map_with_path`.
+    """
+    return _rs.map_with_path(
+        func,
+        tree,
+        *rests,
+        leaf_predicate=is_leaf,
+        none_is_leaf=none_is_leaf,
+        namespace=namespace,
+    )
+
+
+def tree_map_with_path_(
+    func: Callable[..., Any],
+    tree: _T,
+    /,
+    *rests: Any,
+    is_leaf: Callable[[_T], bool] | None = None,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> _T:
+    """Like :func:`tree_map_with_path`, but do an inplace call on each leaf and return the original tree.
+
+    See :func:`rustree.tree_map_with_path_`.
+    """
+    return _rs.map_with_path_(
+        func,
+        tree,
+        *rests,
+        leaf_predicate=is_leaf,
+        none_is_leaf=none_is_leaf,
+        namespace=namespace,
+    )
+
+
+def tree_map_with_accessor(
+    func: Callable[..., _U],
+    tree: _T,
+    /,
+    *rests: Any,
+    is_leaf: Callable[[_T], bool] | None = None,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> Any:
+    """Map a multi-input function over pytree args as well as the tree accessors to produce a new pytree.
+
+    >>> tree_map_with_accessor(lambda a, x: a.path, {'b': (2, [3]), 'a': 1})
+    {'a': ('a',), 'b': (('b', 0), [('b', 1, 0)])}
+    """
+    accessors, leaves, treespec = _rs.flatten_with_accessor(
+        tree,
+        is_leaf,
+        none_is_leaf,
+        namespace,
+    )
+    flat_args = [leaves] + [treespec.flatten(r, is_leaf) for r in rests]
+    return treespec.unflatten(map(func, accessors, *flat_args))
+
+
+def tree_replace_nones(sentinel: Any, tree: _T | None, /, namespace: str = '') -> Any:
+    """Replace :data:`None` in ``tree`` with ``sentinel``.
+
+    >>> tree_replace_nones(0, {'a': 1, 'b': None, 'c': (2, None)})
+    {'a': 1, 'b': 0, 'c': (2, 0)}
+    """
+    return _rs.replace_nones(sentinel, tree, None, namespace)
+
+
+def broadcast_common(
+    tree: Any,
+    other_tree: Any,
+    /,
+    is_leaf: Callable[[Any], bool] | None = None,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> tuple[list[Any], list[Any]]:
+    """Return the leaves of two pytrees broadcast to their common suffix structure.
+
+    >>> broadcast_common({'a': 1, 'b': (2, 3)}, {'a': [4, 5], 'b': 6})
+    ([1, 1, 2, 3], [4, 5, 6, 6])
+    """
+    broadcasted_tree, other_broadcasted_tree = tree_broadcast_common(
+        tree,
+        other_tree,
+        is_leaf=is_leaf,
+        none_is_leaf=none_is_leaf,
+        namespace=namespace,
+    )
+    return (
+        _rs.leaves(broadcasted_tree, is_leaf, none_is_leaf, namespace),
+        _rs.leaves(other_broadcasted_tree, is_leaf, none_is_leaf, namespace),
+    )
+
+
+def tree_reduce(
+    func: Callable[[_T, _T], _T],
+    tree: _T,
+    initial: _T = __MISSING,
+    /,
+    is_leaf: Callable[[_T], bool] | None = None,
+    *,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> _T:
+    """Traverse a pytree and reduce its leaves to a single value.
+
+    >>> tree_reduce(lambda x, y: x + y, {'x': 1, 'y': (2, 3)})
+    6
+    >>> tree_reduce(lambda x, y: x + y, {'x': 1, 'y': (2, 3)}, 10)
+    16
+    """
+    initial = () if initial is __MISSING else (initial,)
+    return _rs.reduce_with_path(
+        lambda acc, _, leaf: func(acc, leaf),
+        tree,
+        *initial,
+        leaf_predicate=is_leaf,
+        none_is_leaf=none_is_leaf,
+        namespace=namespace,
+    )
+
+
+def tree_sum(
+    tree: _T,
+    /,
+    start: _T = 0,  # type: ignore[assignment]
+    is_leaf: Callable[[_T], bool] | None = None,
+    *,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> _T:
+    """Sum ``start`` and leaf values in ``tree`` in reverse order and return the total.
+
+    >>> tree_sum({'x': 1, 'y': (2, 3)})
+    6
+    >>> tree_sum({'x': 1, 'y': (2, 3)}, 10)
+    16
+    """
+    return _rs.sum(tree, is_leaf, none_is_leaf, namespace, start=start)
+
+
+def tree_max(
+    tree: _T,
+    /,
+    default: _T = __MISSING,
+    key: Callable[[_T], Any] | None = None,
+    is_leaf: Callable[[_T], bool] | None = None,
+    *,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> _T:
+    """Return the maximum leaf value in ``tree``.
+
+    >>> tree_max({'x': 1, 'y': (2, 3)})
+    3
+    >>> tree_max({}, default=0)
+    0
+    """
+    defaults = () if default is __MISSING else (default,)
+    return _rs.max(tree, is_leaf, none_is_leaf, namespace, *defaults, key=key)
+
+
+def tree_min(
+    tree: _T,
+    /,
+    default: _T = __MISSING,
+    key: Callable[[_T], Any] | None = None,
+    is_leaf: Callable[[_T], bool] | None = None,
+    *,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> _T:
+    """Return the minimum leaf value in ``tree``.
+
+    >>> tree_min({'x': 1, 'y': (2, 3)})
+    1
+    >>> tree_min({}, default=0)
+    0
+    """
+    defaults = () if default is __MISSING else (default,)
+    return _rs.min(tree, is_leaf, none_is_leaf, namespace, *defaults, key=key)
+
+
+def tree_all(
+    tree: _T,
+    /,
+    is_leaf: Callable[[_T], bool] | None = None,
+    *,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> bool:
+    """Test whether all leaves in ``tree`` are true (or if ``tree`` is empty).
+
+    >>> tree_all({'x': 1, 'y': (2, 3)}), tree_all({'x': 1, 'y': (2, 0)}), tree_all({})
+    (True, False, True)
+    """
+    return _rs.all(tree, is_leaf, none_is_leaf, namespace)
+
+
+def tree_any(
+    tree: _T,
+    /,
+    is_leaf: Callable[[_T], bool] | None = None,
+    *,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> bool:
+    """Test whether any leaves in ``tree`` are true (or :data:`False` if ``tree`` is empty).
+
+    >>> tree_any({'x': 0, 'y': (2, 0)}), tree_any({'x': 0, 'y': (0, 0)}), tree_any({})
+    (True, False, False)
+    """
+    return _rs.any(tree, is_leaf, none_is_leaf, namespace)
+
+
+def treespec_children(treespec: PyTreeSpec, /) -> list[PyTreeSpec]:
+    """Return a list of treespecs for the children of the root node.
+
+    >>> treespec_children(tree_structure({'b': (2, [3, 4]), 'a': 1}))
+    [PyTreeSpec(*), PyTreeSpec((*, [*, *]))]
+    """
+    return treespec.children()
+
+
+def treespec_child(treespec: PyTreeSpec, index: int, /) -> PyTreeSpec:
+    """Return the treespec for the child at the given index of the root node.
+
+    >>> treespec_child(tree_structure({'b': (2, [3, 4]), 'a': 1}), -1)
+    PyTreeSpec((*, [*, *]))
+    """
+    return treespec.child(index)
+
+
+def treespec_entries(treespec: PyTreeSpec, /) -> list[Any]:
+    """Return a list of path entries for the children of the root node.
+
+    >>> treespec_entries(tree_structure({'b': (2, [3, 4]), 'a': 1}))
+    ['a', 'b']
+    """
+    return treespec.entries()
+
+
+def treespec_entry(treespec: PyTreeSpec, index: int, /) -> Any:
+    """Return the path entry for the child at the given index of the root node.
+
+    >>> treespec_entry(tree_structure({'b': (2, [3, 4]), 'a': 1}), 1)
+    'b'
+    """
+    return treespec.entry(index)
+
+
+def treespec_paths(treespec: PyTreeSpec, /) -> list[tuple[Any, ...]]:
+    """Return a list of paths to the leaves of a treespec.
+
+    >>> treespec_paths(tree_structure({'b': (2, [3, 4]), 'a': 1}))
+    [('a',), ('b', 0), ('b', 1, 0), ('b', 1, 1)]
+    """
+    return treespec.paths()
+
+
+def treespec_accessors(treespec: PyTreeSpec, /) -> list[PyTreeAccessor]:
+    """Return a list of accessors to the leaves of a treespec.
+
+    >>> treespec_accessors(tree_structure({'b': (2, [3]), 'a': 1}))  # doctest: +ELLIPSIS
+    [PyTreeAccessor(*['a'], ...), PyTreeAccessor(*['b'][0], ...), PyTreeAccessor(*['b'][1][0], ...)]
+    """
+    return treespec.accessors()
+
+
+def treespec_is_leaf(treespec: PyTreeSpec, /, strict: bool = True) -> bool:
+    """Return whether the treespec is a leaf that has no children.
+
+    >>> treespec_is_leaf(tree_structure(1)), treespec_is_leaf(tree_structure([1]))
+    (True, False)
+    >>> treespec_is_leaf(tree_structure(None)), treespec_is_leaf(tree_structure(None), strict=False)
+    (False, True)
+    """
+    return treespec.is_leaf(strict=strict)
+
+
+def treespec_is_strict_leaf(treespec: PyTreeSpec, /) -> bool:
+    """Return whether the treespec is a strict leaf.
+
+    >>> treespec_is_strict_leaf(tree_structure(1)), treespec_is_strict_leaf(tree_structure(()))
+    (True, False)
+    """
+    return treespec.is_leaf(strict=True)
+
+
+def treespec_is_prefix(
+    treespec: PyTreeSpec,
+    other_treespec: PyTreeSpec,
+    /,
+    strict: bool = False,
+) -> bool:
+    """Return whether ``treespec`` is a prefix of ``other_treespec``.
+
+    >>> treespec_is_prefix(tree_structure([1, 2]), tree_structure([1, (2, 3)]))
+    True
+    >>> treespec_is_prefix(tree_structure([1, 2]), tree_structure([1, 2]), strict=True)
+    False
+    """
+    return treespec.is_prefix(other_treespec, strict=strict)
+
+
+def treespec_is_suffix(
+    treespec: PyTreeSpec,
+    other_treespec: PyTreeSpec,
+    /,
+    strict: bool = False,
+) -> bool:
+    """Return whether ``treespec`` is a suffix of ``other_treespec``.
+
+    >>> treespec_is_suffix(tree_structure([1, (2, 3)]), tree_structure([1, 2]))
+    True
+    """
+    return treespec.is_suffix(other_treespec, strict=strict)
+
+
+def treespec_transform(
+    treespec: PyTreeSpec,
+    /,
+    f_node: Callable[[PyTreeSpec], PyTreeSpec] | None = None,
+    f_leaf: Callable[[PyTreeSpec], PyTreeSpec] | None = None,
+) -> PyTreeSpec:
+    """Transform a treespec by applying functions to its node and leaf treespecs.
+
+    >>> treespec_transform(tree_structure((1, [2, 3])), f_leaf=lambda _: tree_structure((1, 2)))
+    PyTreeSpec(((*, *), [(*, *), (*, *)]))
+    """
+    return treespec.transform(f_node, f_leaf)
+
+
+def treespec_leaf(
+    none_is_leaf: bool = False,
+    namespace: str = '',  # pylint: disable=unused-argument
+) -> PyTreeSpec:
+    """Make a treespec representing a leaf node.
+
+    The namespace is only recorded for the treespecs with custom nodes, so it is ignored here.
+
+    >>> treespec_leaf()
+    PyTreeSpec(*)
+    """
+    return _rs.make_leaf(none_is_leaf)
+
+
+def treespec_none(
+    none_is_leaf: bool = False,
+    namespace: str = '',  # pylint: disable=unused-argument
+) -> PyTreeSpec:
+    """Make a treespec representing a :data:`None` node.
+
+    The namespace is only recorded for the treespecs with custom nodes, so it is ignored here.
+
+    >>> treespec_none()
+    PyTreeSpec(None)
+    """
+    return _rs.make_none(none_is_leaf)
+
+
+def treespec_namedtuple(
+    namedtuple: tuple[PyTreeSpec, ...],
+    /,
+    *,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> PyTreeSpec:
+    """Make a namedtuple treespec from a namedtuple of child treespecs.
+
+    >>> from collections import namedtuple
+    >>> Point = namedtuple('Point', ['x', 'y'])
+    >>> treespec_namedtuple(Point(x=treespec_leaf(), y=treespec_none()))
+    PyTreeSpec(Point(x=*, y=None))
+    """
+    if not is_namedtuple_instance(namedtuple):
+        raise ValueError(f'Expected a namedtuple of PyTreeSpec(s), got {namedtuple!r}.')
+    return treespec_from_collection(namedtuple, none_is_leaf=none_is_leaf, namespace=namespace)
+
+
+def treespec_structseq(
+    structseq: tuple[PyTreeSpec, ...],
+    /,
+    *,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> PyTreeSpec:
+    """Make a PyStructSequence treespec from a PyStructSequence of child treespecs."""
+    if not is_structseq_instance(structseq):
+        raise ValueError(f'Expected a PyStructSequence of PyTreeSpec(s), got {structseq!r}.')
+    return treespec_from_collection(structseq, none_is_leaf=none_is_leaf, namespace=namespace)
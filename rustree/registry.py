@@ -20,16 +20,19 @@ from __future__ import annotations
 
 import contextlib
 import dataclasses
+import functools
 import inspect
 import sys
 from collections import OrderedDict, defaultdict, deque, namedtuple
-from operator import itemgetter, methodcaller
+from operator import itemgetter
 from threading import Lock
 from typing import TYPE_CHECKING, Any, Callable, ClassVar, Generic, NamedTuple, TypeVar, overload
 
 import rustree._rs as _rs
 from rustree.accessors import (
     AutoEntry,
+    DataclassEntry,
+    GetAttrEntry,
     MappingEntry,
     NamedTupleEntry,
     PyTreeEntry,
@@ -43,6 +46,7 @@ from rustree.utils import safe_zip, total_order_sorted, unzip2
 if TYPE_CHECKING:
     import builtins
     from collections.abc import Collection, Generator, Iterable
+    from typing import Literal
 
     from rustree.typing import KT, VT, CustomTreeNode, FlattenFunc, UnflattenFunc
 
@@ -53,14 +57,41 @@ if TYPE_CHECKING:
 __all__ = [
     'register_pytree_node',
     'register_pytree_node_class',
+    'register_node_class',
+    'register_dataclass',
+    'register_attrs',
+    'register_pydantic',
     'unregister_pytree_node',
+    'RegistrySnapshot',
+    'registry_snapshot',
+    'registry_restore',
+    'scoped_registrations',
     'dict_insertion_ordered',
+    'deserialization_limits',
+    'set_default_leaf_predicate',
+    'get_default_leaf_predicate',
+    'is_leaf_type',
+    'RegisteredType',
+    'registered_types',
+    'is_registered',
+    'load_registrations',
+    'metrics',
+    'reset_metrics',
+    'treespec_cache_clear',
+    'treespec_cache_size',
 ]
 
 
 SLOTS = {'slots': True} if sys.version_info >= (3, 10) else {}  # Python 3.10+
 
 
+_KIND_HINT_PATH_ENTRY_TYPES: dict[str, type[PyTreeEntry]] = {
+    'mapping': MappingEntry,
+    'sequence': SequenceEntry,
+    'object': GetAttrEntry,
+}
+
+
 @dataclasses.dataclass(init=True, repr=True, eq=True, frozen=True, **SLOTS)
 class PyTreeNodeRegistryEntry(Generic[T]):
     """A dataclass that stores the information of a pytree node type."""
@@ -75,7 +106,33 @@ class PyTreeNodeRegistryEntry(Generic[T]):
     path_entry_type: builtins.type[PyTreeEntry] = AutoEntry
     kind: PyTreeKind = PyTreeKind.CUSTOM
     namespace: str = ''
+    kind_hint: Literal['mapping', 'sequence', 'object'] | None = None
+
+
+@dataclasses.dataclass(init=True, repr=False, eq=False, frozen=True, **SLOTS)
+class RegistrySnapshot:
+    """An opaque snapshot of the pytree node type registrations, see :func:`registry_snapshot`."""
+
+    registrations: _rs.RegistrySnapshot
+    entries: dict[type | tuple[str, type], PyTreeNodeRegistryEntry]
 
+    def __len__(self) -> int:
+        """Return the number of the custom node types registered in all namespaces."""
+        return len(self.registrations)
+
+    def __repr__(self) -> str:
+        """Return a string representation of the snapshot."""
+        return repr(self.registrations)
+
+
+
+class RegisteredType(NamedTuple):
+    """A registered pytree node type, see :func:`registered_types`."""
+
+    type: type
+    kind: PyTreeKind
+    builtin: bool
+    namespace: str
 
 del SLOTS
 
@@ -195,7 +252,9 @@ def pytree_node_registry_get(  # noqa: C901
         cls (type or None, optional): The class of the pytree node to retrieve. If not provided, all
             the registered pytree nodes in the namespace are returned.
         namespace (str, optional): The namespace of the registry to retrieve. If not provided, the
-            global namespace is used.
+            global namespace is used. The wildcard namespace ``'*'`` looks up the ``cls`` in the
+            only namespace it is registered in. The built-in types always resolve to their global
+            registrations under the wildcard namespace.
 
     Returns:
         If the ``cls`` is not provided, a dictionary of all the registered pytree nodes in the
@@ -223,11 +282,33 @@ def pytree_node_registry_get(  # noqa: C901
                 if handler.namespace in namespaces
             }
         if _rs.is_dict_insertion_ordered(namespace):
-            registry[dict] = _DICT_INSERTION_ORDERED_REGISTRY_ENTRY
-            registry[defaultdict] = _DEFAULTDICT_INSERTION_ORDERED_REGISTRY_ENTRY
+            # Keep the overrides of the built-in types in the namespace
+            if registry[dict].namespace == '':
+                registry[dict] = _DICT_INSERTION_ORDERED_REGISTRY_ENTRY
+            if registry[defaultdict].namespace == '':
+                registry[defaultdict] = _DEFAULTDICT_INSERTION_ORDERED_REGISTRY_ENTRY
         return registry
 
-    if namespace != '':
+    if namespace == '*':
+        handler = _NODETYPE_REGISTRY.get(cls)
+        if handler is not None and handler.kind != PyTreeKind.CUSTOM:
+            # The overrides of the built-in types in the named namespaces are not visible
+            return handler
+        with __REGISTRY_LOCK:
+            handlers = [
+                handler
+                for key, handler in _NODETYPE_REGISTRY.items()
+                if isinstance(key, tuple) and key[1] is cls
+            ]
+        if len(handlers) > 1:
+            namespaces = ', '.join(sorted(repr(handler.namespace) for handler in handlers))
+            raise ValueError(
+                f'PyTree type {cls!r} is registered in multiple namespaces ({namespaces}), '
+                f"cannot resolve it with the wildcard namespace '*'.",
+            )
+        if handlers:
+            return handlers[0]
+    elif namespace != '':
         handler = _NODETYPE_REGISTRY.get((namespace, cls))
         if handler is not None:
             return handler
@@ -248,6 +329,24 @@ def pytree_node_registry_get(  # noqa: C901
     return None
 
 
+def _kind_hint_path_entry_type(
+    path_entry_type: type[PyTreeEntry],
+    kind_hint: str | None,
+    /,
+) -> type[PyTreeEntry]:
+    """Return the default path entry type of the kind hint if the path entry type is automatic."""
+    if kind_hint is None:
+        return path_entry_type
+    if kind_hint not in _KIND_HINT_PATH_ENTRY_TYPES:
+        raise ValueError(
+            "Expected the kind hint to be one of 'mapping', 'sequence', or 'object', "
+            f'got {kind_hint!r}.',
+        )
+    if path_entry_type is AutoEntry:
+        return _KIND_HINT_PATH_ENTRY_TYPES[kind_hint]
+    return path_entry_type
+
+
 @_add_get(pytree_node_registry_get)
 def register_pytree_node(
     cls: type[Collection[T]],
@@ -257,6 +356,7 @@ def register_pytree_node(
     *,
     path_entry_type: type[PyTreeEntry] = AutoEntry,
     namespace: str,
+    kind_hint: Literal['mapping', 'sequence', 'object'] | None = None,
 ) -> type[Collection[T]]:
     """Extend the set of types that are considered internal nodes in pytrees.
 
@@ -265,7 +365,9 @@ def register_pytree_node(
     The ``namespace`` argument is used to avoid collisions that occur when different libraries
     register the same Python type with different behaviors. It is recommended to add a unique prefix
     to the namespace to avoid conflicts with other libraries. Namespaces can also be used to specify
-    the same class in different namespaces for different use cases.
+    the same class in different namespaces for different use cases. The built-in types (e.g.,
+    :class:`dict` and :class:`list`) can also be registered in a namespace to override how they are
+    flattened in that namespace only, while the global namespace is not affected.
 
     .. warning::
         For safety reasons, a ``namespace`` must be specified while registering a custom type. It is
@@ -287,7 +389,15 @@ def register_pytree_node(
             (default: :class:`AutoEntry`)
         namespace (str): A non-empty string that uniquely identifies the namespace of the type registry.
             This is used to isolate the registry from other modules that might register a different
-            custom behavior for the same type.
+            custom behavior for the same type. The namespace ``'*'`` is reserved for the wildcard
+            lookup, which resolves a type in the only namespace it is registered in. The overrides
+            of the built-in types are not visible to the wildcard lookup.
+        kind_hint (str, optional): How the nodes are presented, one of ``'mapping'``,
+            ``'sequence'``, and ``'object'``. The path entries returned by ``flatten_func`` are
+            rendered as the keys of a mapping or the attribute names of an object in the repr of
+            the treespecs, and the default path entry type becomes :class:`MappingEntry`,
+            :class:`SequenceEntry`, or :class:`GetAttrEntry` respectively. (default: :data:`None`,
+            i.e., present the children by their positions)
 
     Returns:
         The same type as the input ``cls``.
@@ -297,6 +407,9 @@ def register_pytree_node(
         TypeError: If the path entry class is not a subclass of :class:`PyTreeEntry`.
         TypeError: If the namespace is not a string.
         ValueError: If the namespace is an empty string.
+        ValueError: If the namespace is the wildcard namespace ``'*'``.
+        ValueError: If the kind hint is not one of ``'mapping'``, ``'sequence'``, and ``'object'``.
+        ValueError: If the type is a built-in type and the namespace is the global namespace.
         ValueError: If the type is already registered in the registry.
 
     Examples:
@@ -309,6 +422,25 @@ def register_pytree_node(
         ... )
         <class 'set'>
 
+        >>> # Override a built-in type in a namespace, e.g., only accept string keys for dicts
+        >>> def flatten_str_dict(dct):
+        ...     if not all(isinstance(key, str) for key in dct):
+        ...         raise TypeError(f'Expected string keys, got {list(dct)!r}.')
+        ...     return list(dct.values()), list(dct), list(dct)
+        ...
+        ... register_pytree_node(
+        ...     dict,
+        ...     flatten_func=flatten_str_dict,
+        ...     unflatten_func=lambda keys, values: dict(zip(keys, values)),
+        ...     path_entry_type=MappingEntry,
+        ...     namespace='str-dict',
+        ... )
+        <class 'dict'>
+        >>> tree_flatten({'b': 2, 'a': 1}, namespace='str-dict')
+        ([2, 1], PyTreeSpec(CustomTreeNode(dict[['b', 'a']], [*, *]), namespace='str-dict'))
+        >>> tree_flatten({'b': 2, 'a': 1})  # the global namespace is not affected
+        ([1, 2], PyTreeSpec({'a': *, 'b': *}))
+
         >>> # Register a Python type into a namespace
         >>> import torch
         >>> register_pytree_node(
@@ -383,6 +515,7 @@ def register_pytree_node(
         raise TypeError(f'The namespace must be a string, got {namespace!r}.')
     if namespace == '':
         raise ValueError('The namespace cannot be an empty string.')
+    path_entry_type = _kind_hint_path_entry_type(path_entry_type, kind_hint)
 
     registration_key: type | tuple[str, type]
     if namespace is __GLOBAL_NAMESPACE:
@@ -398,6 +531,7 @@ def register_pytree_node(
             unflatten_func,
             path_entry_type,
             namespace,
+            kind_hint=kind_hint,
         )
         _NODETYPE_REGISTRY[registration_key] = PyTreeNodeRegistryEntry(
             cls,
@@ -405,6 +539,7 @@ def register_pytree_node(
             unflatten_func,
             path_entry_type=path_entry_type,
             namespace=namespace,
+            kind_hint=kind_hint,
         )
     return cls
 
@@ -419,6 +554,7 @@ def register_pytree_node_class(
     *,
     path_entry_type: type[PyTreeEntry] | None = None,
     namespace: str | None = None,
+    kind_hint: Literal['mapping', 'sequence', 'object'] | None = None,
 ) -> Callable[[CustomTreeNodeType], CustomTreeNodeType]: ...
 
 
@@ -429,6 +565,7 @@ def register_pytree_node_class(
     *,
     path_entry_type: type[PyTreeEntry] | None,
     namespace: str,
+    kind_hint: Literal['mapping', 'sequence', 'object'] | None = None,
 ) -> CustomTreeNodeType: ...
 
 
@@ -438,6 +575,7 @@ def register_pytree_node_class(  # noqa: C901
     *,
     path_entry_type: type[PyTreeEntry] | None = None,
     namespace: str | None = None,
+    kind_hint: Literal['mapping', 'sequence', 'object'] | None = None,
 ) -> CustomTreeNodeType | Callable[[CustomTreeNodeType], CustomTreeNodeType]:
     """Extend the set of types that are considered internal nodes in pytrees.
 
@@ -460,6 +598,9 @@ def register_pytree_node_class(  # noqa: C901
         namespace (str, optional): A non-empty string that uniquely identifies the namespace of the
             type registry. This is used to isolate the registry from other modules that might
             register a different custom behavior for the same type.
+        kind_hint (str, optional): How the nodes are presented, one of ``'mapping'``,
+            ``'sequence'``, and ``'object'``. See :func:`register_pytree_node` for more details.
+            (default: the ``TREE_KIND_HINT`` class attribute if present, otherwise :data:`None`)
 
     Returns:
         The same type as the input ``cls`` if the argument presents. Otherwise, return a decorator
@@ -469,6 +610,7 @@ def register_pytree_node_class(  # noqa: C901
         TypeError: If the path entry class is not a subclass of :class:`PyTreeEntry`.
         TypeError: If the namespace is not a string.
         ValueError: If the namespace is an empty string.
+        ValueError: If the kind hint is not one of ``'mapping'``, ``'sequence'``, and ``'object'``.
         ValueError: If the type is already registered in the registry.
 
     This function is a thin wrapper around :func:`register_pytree_node`, and provides a
@@ -521,6 +663,114 @@ def register_pytree_node_class(  # noqa: C901
                 cls,
                 path_entry_type=path_entry_type,
                 namespace=namespace,
+                kind_hint=kind_hint,
+            )
+
+        return decorator
+
+    return register_node_class(
+        cls,
+        path_entry_type=path_entry_type,
+        namespace='' if namespace is __GLOBAL_NAMESPACE else namespace,
+        kind_hint=kind_hint,
+    )
+
+
+@overload
+def register_node_class(
+    cls: None = None,
+    /,
+    *,
+    path_entry_type: type[PyTreeEntry] | None = None,
+    namespace: str = '',
+    kind_hint: Literal['mapping', 'sequence', 'object'] | None = None,
+) -> Callable[[CustomTreeNodeType], CustomTreeNodeType]: ...
+
+
+@overload
+def register_node_class(
+    cls: CustomTreeNodeType,
+    /,
+    *,
+    path_entry_type: type[PyTreeEntry] | None = None,
+    namespace: str = '',
+    kind_hint: Literal['mapping', 'sequence', 'object'] | None = None,
+) -> CustomTreeNodeType: ...
+
+
+def register_node_class(
+    cls: CustomTreeNodeType | None = None,
+    /,
+    *,
+    path_entry_type: type[PyTreeEntry] | None = None,
+    namespace: str = '',
+    kind_hint: Literal['mapping', 'sequence', 'object'] | None = None,
+) -> CustomTreeNodeType | Callable[[CustomTreeNodeType], CustomTreeNodeType]:
+    """Register a class that defines the ``tree_flatten`` and ``tree_unflatten`` methods as a pytree node type.
+
+    See also :func:`register_pytree_node_class` and :func:`unregister_pytree_node`.
+
+    The flatten and unflatten functions are taken from the class directly, i.e., the instance method
+    ``cls.tree_flatten`` and the class method ``cls.tree_unflatten``, without wrapping them in
+    lambdas. Unlike :func:`register_pytree_node_class`, the class is registered in the global
+    namespace if ``namespace`` is not given. It can be used as a decorator with or without
+    arguments.
+
+    >>> @register_node_class(namespace='point')
+    ... class Point:
+    ...     def __init__(self, x, y):
+    ...         self.x = x
+    ...         self.y = y
+    ...
+    ...     def tree_flatten(self):
+    ...         return (self.x, self.y), None, ('x', 'y')
+    ...
+    ...     @classmethod
+    ...     def tree_unflatten(cls, metadata, children):
+    ...         return cls(*children)
+    ...
+    ...     def __repr__(self):
+    ...         return f'Point(x={self.x}, y={self.y})'
+    >>> tree_map(lambda x: x + 1, Point(1, 2), namespace='point')
+    Point(x=2, y=3)
+    >>> _ = unregister_pytree_node(Point, namespace='point')
+    >>> register_node_class(int, namespace='point')
+    Traceback (most recent call last):
+        ...
+    TypeError: Expected a class with a callable `tree_flatten` attribute, got <class 'int'>.
+
+    Args:
+        cls (type, optional): A Python class to treat as an internal pytree node. If not given, a
+            decorator is returned.
+        path_entry_type (type, optional): The type of the path entry to be used in the treespec. If
+            not given, the ``TREE_PATH_ENTRY_TYPE`` attribute of the class is used if present.
+            (default: :class:`AutoEntry`)
+        namespace (str, optional): The registry namespace to register the class in.
+            (default: :const:`''`, i.e., the global namespace)
+        kind_hint (str, optional): The kind of the node, one of ``'mapping'``, ``'sequence'``, and
+            ``'object'``. If not given, the ``TREE_KIND_HINT`` attribute of the class is used if
+            present. (default: :data:`None`)
+
+    Returns:
+        The same type as the input ``cls``, or a decorator if ``cls`` is not given.
+
+    Raises:
+        TypeError: If the class does not define callable ``tree_flatten`` and ``tree_unflatten``.
+        TypeError: If the path entry class is not a subclass of :class:`PyTreeEntry`.
+        ValueError: If the kind hint is not one of ``'mapping'``, ``'sequence'``, and ``'object'``.
+        ValueError: If the type is already registered in the registry.
+    """  # pylint: disable=line-too-long
+    if not isinstance(namespace, str):
+        raise TypeError(f'The namespace must be a string, got {namespace!r}.')
+
+    if cls is None:
+
+        def decorator(cls: CustomTreeNodeType, /) -> CustomTreeNodeType:
+            return register_node_class(
+                cls,
+                path_entry_type=path_entry_type,
+                namespace=namespace,
+                kind_hint=kind_hint,
             )
 
         return decorator
@@ -531,17 +781,394 @@ def register_pytree_node_class(  # noqa: C901
         path_entry_type = getattr(cls, 'TREE_PATH_ENTRY_TYPE', AutoEntry)
     if not (inspect.isclass(path_entry_type) and issubclass(path_entry_type, PyTreeEntry)):
         raise TypeError(f'Expected a subclass of PyTreeEntry, got {path_entry_type!r}.')
+    if kind_hint is None:
+        kind_hint = getattr(cls, 'TREE_KIND_HINT', None)
+    path_entry_type = _kind_hint_path_entry_type(path_entry_type, kind_hint)
+
+    registration_key = cls if namespace == '' else (namespace, cls)
+    with __REGISTRY_LOCK:
+        flatten_func, unflatten_func = _rs.register_node_class(
+            cls,
+            path_entry_type,
+            namespace,
+            kind_hint=kind_hint,
+        )
+        _NODETYPE_REGISTRY[registration_key] = PyTreeNodeRegistryEntry(
+            cls,
+            flatten_func,
+            unflatten_func,
+            path_entry_type=path_entry_type,
+            namespace=namespace,
+            kind_hint=kind_hint,
+        )
+    return cls
+
+
+@overload
+def register_dataclass(
+    cls: None = None,
+    /,
+    *,
+    data_fields: Iterable[str] | None = None,
+    meta_fields: Iterable[str] | None = None,
+    path_entry_type: type[PyTreeEntry] = DataclassEntry,
+    namespace: str = '',
+) -> Callable[[type[T]], type[T]]: ...
+
+
+@overload
+def register_dataclass(
+    cls: type[T],
+    /,
+    *,
+    data_fields: Iterable[str] | None = None,
+    meta_fields: Iterable[str] | None = None,
+    path_entry_type: type[PyTreeEntry] = DataclassEntry,
+    namespace: str = '',
+) -> type[T]: ...
+
+
+def register_dataclass(
+    cls: type[T] | None = None,
+    /,
+    *,
+    data_fields: Iterable[str] | None = None,
+    meta_fields: Iterable[str] | None = None,
+    path_entry_type: type[PyTreeEntry] = DataclassEntry,
+    namespace: str = '',
+) -> type[T] | Callable[[type[T]], type[T]]:
+    """Register a dataclass as a pytree node type whose children are the dataclass fields.
+
+    See also :func:`register_node_class` and :func:`unregister_pytree_node`.
+
+    The fields that are arguments of ``__init__`` are split into the data fields, which are the
+    children of the node with the field names as the path entries, and the metadata fields, which
+    are carried in the treespec as the node data. The metadata fields are compared when checking
+    the treespec equality, so they should be hashable static values, e.g., the activation names.
+    If neither ``data_fields`` nor ``meta_fields`` is given, the fields with ``pytree_node=False``
+    in the field metadata are the metadata fields. If only one of them is given, the other fields
+    are the rest. The field names are cached in the registry, so the fields are read and passed to
+    the constructor without calling Python flatten and unflatten functions. The class is
+    registered in the global namespace if ``namespace`` is not given. It can be used as a decorator
+    with or without arguments.
+
+    >>> @register_dataclass(namespace='point')
+    ... @dataclasses.dataclass
+    ... class Point:
+    ...     x: int
+    ...     y: int
+    >>> tree_flatten_with_path(Point(1, 2), namespace='point')
+    ([(('x',), 1), (('y',), 2)], PyTreeSpec(CustomTreeNode(Point[None], [*, *]), namespace='point'))
+    >>> tree_map(lambda x: x + 1, Point(1, 2), namespace='point')
+    Point(x=2, y=3)
+    >>> _ = unregister_pytree_node(Point, namespace='point')
+    >>> @register_dataclass(namespace='layer')
+    ... @dataclasses.dataclass
+    ... class Dense:
+    ...     weight: float
+    ...     activation: str = dataclasses.field(metadata={'pytree_node': False})
+    >>> tree_flatten(Dense(1.0, 'relu'), namespace='layer')
+    ([1.0], PyTreeSpec(CustomTreeNode(Dense[('relu',)], [*]), namespace='layer'))
+    >>> _ = unregister_pytree_node(Dense, namespace='layer')
+
+    Args:
+        cls (type, optional): A dataclass to treat as an internal pytree node. If not given, a
+            decorator is returned.
+        data_fields (iterable of str, optional): The names of the fields to be the children of the
+            node. (default: the fields that are not metadata fields)
+        meta_fields (iterable of str, optional): The names of the fields to be carried as the node
+            data. (default: the fields with ``pytree_node=False`` in the field metadata)
+        path_entry_type (type, optional): The type of the path entry to be used in the treespec.
+            (default: :class:`DataclassEntry`)
+        namespace (str, optional): The registry namespace to register the class in.
+            (default: :const:`''`, i.e., the global namespace)
+
+    Returns:
+        The same type as the input ``cls``, or a decorator if ``cls`` is not given.
+
+    Raises:
+        TypeError: If the class is not a dataclass.
+        TypeError: If the path entry class is not a subclass of :class:`PyTreeEntry`.
+        ValueError: If the data and metadata fields are not a partition of the ``__init__`` fields.
+        ValueError: If the type is already registered in the registry.
+    """
+    if not isinstance(namespace, str):
+        raise TypeError(f'The namespace must be a string, got {namespace!r}.')
+    if data_fields is not None:
+        data_fields = tuple(data_fields)
+    if meta_fields is not None:
+        meta_fields = tuple(meta_fields)
+
+    if cls is None:
+
+        def decorator(cls: type[T], /) -> type[T]:
+            return register_dataclass(
+                cls,
+                data_fields=data_fields,
+                meta_fields=meta_fields,
+                path_entry_type=path_entry_type,
+                namespace=namespace,
+            )
+
+        return decorator
+
+    if not (inspect.isclass(cls) and dataclasses.is_dataclass(cls)):
+        raise TypeError(f'Expected a dataclass, got {cls!r}.')
+    if not (inspect.isclass(path_entry_type) and issubclass(path_entry_type, PyTreeEntry)):
+        raise TypeError(f'Expected a subclass of PyTreeEntry, got {path_entry_type!r}.')
+
+    init_fields = [field for field in dataclasses.fields(cls) if field.init]
+    names = [field.name for field in init_fields]
+    if data_fields is None and meta_fields is None:
+        meta_fields = tuple(
+            field.name for field in init_fields if not field.metadata.get('pytree_node', True)
+        )
+    if data_fields is None:
+        excluded = set(meta_fields or ())
+        data_fields = tuple(name for name in names if name not in excluded)
+    if meta_fields is None:
+        excluded = set(data_fields)
+        meta_fields = tuple(name for name in names if name not in excluded)
+    if sorted(data_fields + meta_fields) != sorted(names):
+        raise ValueError(
+            f'The data fields {list(data_fields)!r} and the metadata fields {list(meta_fields)!r} '
+            f'must partition the __init__ fields {names!r} of {cls!r}.',
+        )
 
-    register_pytree_node(
+    _register_fields(
         cls,
-        methodcaller('tree_flatten'),
-        cls.tree_unflatten,
+        data_fields,
+        data_fields,
         path_entry_type=path_entry_type,
         namespace=namespace,
+        meta_fields=meta_fields,
+    )
+    return cls
+
+
+@overload
+def register_attrs(
+    cls: None = None,
+    /,
+    *,
+    path_entry_type: type[PyTreeEntry] = GetAttrEntry,
+    namespace: str = '',
+) -> Callable[[type[T]], type[T]]: ...
+
+
+@overload
+def register_attrs(
+    cls: type[T],
+    /,
+    *,
+    path_entry_type: type[PyTreeEntry] = GetAttrEntry,
+    namespace: str = '',
+) -> type[T]: ...
+
+
+def register_attrs(
+    cls: type[T] | None = None,
+    /,
+    *,
+    path_entry_type: type[PyTreeEntry] = GetAttrEntry,
+    namespace: str = '',
+) -> type[T] | Callable[[type[T]], type[T]]:
+    """Register an ``attrs`` class as a pytree node type whose children are the attributes.
+
+    See also :func:`register_dataclass` and :func:`unregister_pytree_node`.
+
+    The attributes are found in the ``__attrs_attrs__`` attribute of the class. All attributes that
+    are arguments of the generated ``__init__`` are the children of the node, and the path entries
+    are the attribute names. The private attributes are passed to ``__init__`` by their aliases,
+    e.g., ``_x`` by ``x``. The class is registered in the global namespace if ``namespace`` is not
+    given. It can be used as a decorator with or without arguments.
+
+    >>> import attrs  # doctest: +SKIP
+    >>> @register_attrs(namespace='point')  # doctest: +SKIP
+    ... @attrs.define
+    ... class Point:
+    ...     x: int
+    ...     y: int
+    >>> tree_flatten_with_path(Point(1, 2), namespace='point')  # doctest: +SKIP
+    ([(('x',), 1), (('y',), 2)], PyTreeSpec(CustomTreeNode(Point[None], [*, *]), namespace='point'))
+
+    Args:
+        cls (type, optional): An ``attrs`` class to treat as an internal pytree node. If not given,
+            a decorator is returned.
+        path_entry_type (type, optional): The type of the path entry to be used in the treespec.
+            (default: :class:`GetAttrEntry`)
+        namespace (str, optional): The registry namespace to register the class in.
+            (default: :const:`''`, i.e., the global namespace)
+
+    Returns:
+        The same type as the input ``cls``, or a decorator if ``cls`` is not given.
+
+    Raises:
+        TypeError: If the class is not an ``attrs`` class.
+        TypeError: If the path entry class is not a subclass of :class:`PyTreeEntry`.
+        ValueError: If the type is already registered in the registry.
+    """
+    if not isinstance(namespace, str):
+        raise TypeError(f'The namespace must be a string, got {namespace!r}.')
+
+    if cls is None:
+
+        def decorator(cls: type[T], /) -> type[T]:
+            return register_attrs(cls, path_entry_type=path_entry_type, namespace=namespace)
+
+        return decorator
+
+    if not (inspect.isclass(cls) and getattr(cls, '__attrs_attrs__', None) is not None):
+        raise TypeError(f'Expected an attrs class, got {cls!r}.')
+    if not (inspect.isclass(path_entry_type) and issubclass(path_entry_type, PyTreeEntry)):
+        raise TypeError(f'Expected a subclass of PyTreeEntry, got {path_entry_type!r}.')
+
+    attributes = [attribute for attribute in cls.__attrs_attrs__ if attribute.init]
+    fields = tuple(attribute.name for attribute in attributes)
+    # The `alias` attribute is added in attrs 22.2.0, the older versions strip the underscores
+    init_args = tuple(
+        getattr(attribute, 'alias', None) or attribute.name.lstrip('_') for attribute in attributes
     )
+    _register_fields(cls, fields, init_args, path_entry_type=path_entry_type, namespace=namespace)
     return cls
 
 
+@overload
+def register_pydantic(
+    cls: None = None,
+    /,
+    *,
+    path_entry_type: type[PyTreeEntry] = GetAttrEntry,
+    namespace: str = '',
+) -> Callable[[type[T]], type[T]]: ...
+
+
+@overload
+def register_pydantic(
+    cls: type[T],
+    /,
+    *,
+    path_entry_type: type[PyTreeEntry] = GetAttrEntry,
+    namespace: str = '',
+) -> type[T]: ...
+
+
+def register_pydantic(
+    cls: type[T] | None = None,
+    /,
+    *,
+    path_entry_type: type[PyTreeEntry] = GetAttrEntry,
+    namespace: str = '',
+) -> type[T] | Callable[[type[T]], type[T]]:
+    """Register a :class:`pydantic.BaseModel` subclass as a pytree node type whose children are the model fields.
+
+    See also :func:`register_dataclass` and :func:`unregister_pytree_node`.
+
+    The children of the node are the fields in ``cls.model_fields`` and the path entries are the
+    field names. The models are rebuilt with ``cls.model_construct``, which skips the validation
+    because the leaves may be replaced by values of other types, e.g., arrays or tracers. The extra
+    attributes that are not declared as fields are not kept. The registration is opt-in for each
+    model class, as the subclasses of a registered model are leaves unless registered as well. The
+    class is registered in the global namespace if ``namespace`` is not given. It can be used as a
+    decorator with or without arguments.
+
+    >>> import pydantic  # doctest: +SKIP
+    >>> @register_pydantic(namespace='config')  # doctest: +SKIP
+    ... class Optimizer(pydantic.BaseModel):
+    ...     lr: float
+    ...     betas: tuple[float, float]
+    >>> tree_map(lambda x: x * 10, Optimizer(lr=0.1, betas=(0.9, 0.99)), namespace='config')  # doctest: +SKIP
+    Optimizer(lr=1.0, betas=(9.0, 9.9))
+
+    Args:
+        cls (type, optional): A subclass of :class:`pydantic.BaseModel` to treat as an internal
+            pytree node. If not given, a decorator is returned.
+        path_entry_type (type, optional): The type of the path entry to be used in the treespec.
+            (default: :class:`GetAttrEntry`)
+        namespace (str, optional): The registry namespace to register the class in.
+            (default: :const:`''`, i.e., the global namespace)
+
+    Returns:
+        The same type as the input ``cls``, or a decorator if ``cls`` is not given.
+
+    Raises:
+        TypeError: If the class is not a pydantic model class.
+        TypeError: If the path entry class is not a subclass of :class:`PyTreeEntry`.
+        ValueError: If the type is already registered in the registry.
+    """  # pylint: disable=line-too-long
+    if not isinstance(namespace, str):
+        raise TypeError(f'The namespace must be a string, got {namespace!r}.')
+
+    if cls is None:
+
+        def decorator(cls: type[T], /) -> type[T]:
+            return register_pydantic(cls, path_entry_type=path_entry_type, namespace=namespace)
+
+        return decorator
+
+    if not (
+        inspect.isclass(cls)
+        and isinstance(getattr(cls, 'model_fields', None), dict)
+        and callable(getattr(cls, 'model_construct', None))
+    ):
+        raise TypeError(f'Expected a pydantic model class, got {cls!r}.')
+    if not (inspect.isclass(path_entry_type) and issubclass(path_entry_type, PyTreeEntry)):
+        raise TypeError(f'Expected a subclass of PyTreeEntry, got {path_entry_type!r}.')
+
+    fields = tuple(cls.model_fields)  # type: ignore[attr-defined]
+    _register_fields(
+        cls,
+        fields,
+        fields,
+        path_entry_type=path_entry_type,
+        namespace=namespace,
+        init_func=cls.model_construct,  # type: ignore[attr-defined]
+    )
+    return cls
+
+
+def _register_fields(
+    cls: type,
+    fields: tuple[str, ...],
+    init_args: tuple[str, ...],
+    /,
+    *,
+    path_entry_type: type[PyTreeEntry],
+    namespace: str,
+    meta_fields: tuple[str, ...] = (),
+    init_func: Callable[..., Any] | None = None,
+) -> None:
+    """Register a type whose children are the given fields, read and passed to ``__init__`` in Rust."""
+    # The functions are kept for the registry entry, the registry reads the fields directly
+    flatten_func = functools.partial(_fields_flatten, fields, meta_fields)
+    unflatten_func = functools.partial(
+        _fields_unflatten,
+        init_func or cls,
+        init_args,
+        meta_fields,
+    )
+    registration_key = cls if namespace == '' else (namespace, cls)
+    with __REGISTRY_LOCK:
+        _rs.register_node(
+            cls,
+            flatten_func,
+            unflatten_func,
+            path_entry_type,
+            namespace,
+            data_fields=fields,
+            init_args=init_args,
+            meta_fields=meta_fields,
+            init_func=init_func,
+        )
+        _NODETYPE_REGISTRY[registration_key] = PyTreeNodeRegistryEntry(
+            cls,
+            flatten_func,
+            unflatten_func,
+            path_entry_type=path_entry_type,
+            namespace=namespace,
+        )
+
+
 def unregister_pytree_node(cls: type, /, *, namespace: str) -> PyTreeNodeRegistryEntry:
     """Remove a type from the pytree node registry.
 
@@ -560,7 +1187,7 @@ def unregister_pytree_node(cls: type, /, *, namespace: str) -> PyTreeNodeRegistr
         TypeError: If the input type is not a class.
         TypeError: If the namespace is not a string.
         ValueError: If the namespace is an empty string.
-        ValueError: If the type is a built-in type that cannot be unregistered.
+        ValueError: If the type is a built-in type and the namespace is the global namespace.
         ValueError: If the type is not found in the registry.
 
     Examples:
@@ -595,6 +1222,96 @@ def unregister_pytree_node(cls: type, /, *, namespace: str) -> PyTreeNodeRegistr
         return _NODETYPE_REGISTRY.pop(registration_key)
 
 
+def registry_snapshot() -> RegistrySnapshot:
+    """Take a snapshot of the pytree node type registrations in all namespaces.
+
+    See also :func:`registry_restore` and :func:`scoped_registrations`.
+
+    The snapshot only holds the node type registrations. The dictionary sorting modes and the
+    default leaf predicates are not included.
+
+    >>> snapshot = registry_snapshot()
+    >>> register_pytree_node(
+    ...     set,
+    ...     lambda s: (sorted(s), None, None),
+    ...     lambda _, children: set(children),
+    ...     namespace='temp',
+    ... )
+    <class 'set'>
+    >>> tree_leaves({3, 1, 2}, namespace='temp')
+    [1, 2, 3]
+    >>> registry_restore(snapshot)
+    >>> tree_leaves({3, 1, 2}, namespace='temp')
+    [{1, 2, 3}]
+
+    Returns:
+        An opaque :class:`RegistrySnapshot` to be passed to :func:`registry_restore`.
+    """
+    with __REGISTRY_LOCK:
+        return RegistrySnapshot(_rs.registry_snapshot(), dict(_NODETYPE_REGISTRY))
+
+
+def registry_restore(snapshot: RegistrySnapshot, /) -> None:
+    """Restore the pytree node type registrations to a snapshot taken by :func:`registry_snapshot`.
+
+    See also :func:`scoped_registrations`.
+
+    The types registered after the snapshot are unregistered, and the types unregistered after the
+    snapshot are registered again with the same flatten and unflatten functions. The treespecs
+    created in between keep the registrations they were built with.
+
+    Args:
+        snapshot (RegistrySnapshot): The snapshot to restore.
+
+    Raises:
+        TypeError: If the input is not a :class:`RegistrySnapshot`.
+    """
+    if not isinstance(snapshot, RegistrySnapshot):
+        raise TypeError(f'Expected a RegistrySnapshot, got {snapshot!r}.')
+
+    with __REGISTRY_LOCK:
+        _rs.registry_restore(snapshot.registrations)
+        _NODETYPE_REGISTRY.clear()
+        _NODETYPE_REGISTRY.update(snapshot.entries)
+
+
+@contextlib.contextmanager
+def scoped_registrations() -> Generator[RegistrySnapshot]:
+    """Context manager to undo the pytree node type registrations made in the context.
+
+    See also :func:`registry_snapshot` and :func:`registry_restore`.
+
+    The registrations are restored on exit even if an exception is raised, so the types can be
+    registered in tests or libraries without unregistering them one by one.
+
+    >>> with scoped_registrations():
+    ...     register_pytree_node(
+    ...         set,
+    ...         lambda s: (sorted(s), None, None),
+    ...         lambda _, children: set(children),
+    ...         namespace='temp',
+    ...     )
+    ...     tree_leaves({3, 1, 2}, namespace='temp')
+    <class 'set'>
+    [1, 2, 3]
+    >>> tree_leaves({3, 1, 2}, namespace='temp')
+    [{1, 2, 3}]
+
+    .. warning::
+        The registry is a global setting and is **not thread-safe**. The registrations made by
+        other threads in the context are undone as well. It is recommended to use this context
+        manager in a single-threaded environment.
+
+    Yields:
+        The :class:`RegistrySnapshot` taken on entry.
+    """
+    snapshot = registry_snapshot()
+    try:
+        yield snapshot
+    finally:
+        registry_restore(snapshot)
+
+
 @contextlib.contextmanager
 def dict_insertion_ordered(mode: bool, /, *, namespace: str) -> Generator[None]:
     """Context manager to temporarily set the dictionary sorting mode.
@@ -642,10 +1359,374 @@ def dict_insertion_ordered(mode: bool, /, *, namespace: str) -> Generator[None]:
             _rs.set_dict_insertion_ordered(prev, namespace)
 
 
+@contextlib.contextmanager
+def deserialization_limits(
+    *,
+    max_nodes: int | None = None,
+    max_arity: int | None = None,
+    max_depth: int | None = 1000,
+    trusted: bool = False,
+) -> Generator[None]:
+    """Context manager to temporarily set the limits on the treespecs loaded from untrusted data.
+
+    The limits are checked when a :class:`PyTreeSpec` is unpickled or loaded by
+    :meth:`PyTreeSpec.from_bytes`, so a service can refuse the oversized structure metadata from the
+    untrusted clients before building the treespec. Outside of this context manager, only the depth
+    is limited to ``1000``.
+
+    A pickled treespec is loaded as :meth:`PyTreeSpec.from_bytes` does, so by default the custom node
+    types are only resolved from the types registered in the namespace of the treespec, the
+    namedtuple and PyStructSequence classes are only resolved from the loaded modules without
+    importing them, and the node data and the entries cannot refer to the arbitrary classes and
+    callables. Pass ``trusted=True`` to unpickle the treespecs from the trusted data with the
+    unregistered types in the node data.
+
+    >>> import pickle
+    >>> treespec = tree_flatten([[1, 2], [3, [4, 5]]])[1]
+    >>> data = pickle.dumps(treespec)
+    >>> pickle.loads(data) == treespec
+    True
+    >>> with deserialization_limits(max_arity=2, max_depth=2):
+    ...     pickle.loads(data)
+    Traceback (most recent call last):
+        ...
+    ValueError: Maximum depth (2) exceeded during loading the PyTreeSpec.
+
+    >>> import fractions
+    >>> register_pytree_node(
+    ...     set,
+    ...     lambda s: (sorted(s), fractions.Fraction(len(s)), None),
+    ...     lambda n, c: set(c),
+    ...     namespace='doctest-limits',
+    ... )
+    <class 'set'>
+    >>> data = pickle.dumps(tree_flatten({1, 2}, namespace='doctest-limits')[1])
+    >>> pickle.loads(data)
+    Traceback (most recent call last):
+        ...
+    _pickle.UnpicklingError: Refusing to resolve fractions.Fraction from untrusted data, pass `trusted=True` to load it.
+    >>> with deserialization_limits(trusted=True):
+    ...     pickle.loads(data)
+    PyTreeSpec(CustomTreeNode(set[Fraction(2, 1)], [*, *]), namespace='doctest-limits')
+    >>> _ = unregister_pytree_node(set, namespace='doctest-limits')
+
+    .. warning::
+        The limits are a global setting and are **not thread-safe**. It is recommended to use this
+        context manager in a single-threaded environment.
+
+    .. note::
+        The :mod:`pickle` module still resolves the classes and callables outside of the pickled
+        treespecs by itself, so :func:`pickle.loads` is not safe for arbitrary data. Use
+        :meth:`PyTreeSpec.from_bytes` to load the treespecs from the untrusted data instead.
+
+    Args:
+        max_nodes (int, optional): The maximum number of nodes (including the leaves) in the
+            treespec. (default: :data:`None`, i.e., no limit)
+        max_arity (int, optional): The maximum number of children of a node. (default:
+            :data:`None`, i.e., no limit)
+        max_depth (int, optional): The maximum depth of the treespec, where a single leaf has depth
+            0. (default: :const:`1000`)
+        trusted (bool, optional): Whether to unpickle the treespecs that refer to the arbitrary
+            classes and callables. (default: :data:`False`)
+    """
+    for name, limit in (
+        ('max_nodes', max_nodes),
+        ('max_arity', max_arity),
+        ('max_depth', max_depth),
+    ):
+        if limit is not None and (not isinstance(limit, int) or limit < 0):
+            raise ValueError(f'The {name} must be a non-negative integer or None, got {limit!r}.')
+
+    with __REGISTRY_LOCK:
+        prev = _rs.get_load_limits()
+        _rs.set_load_limits(max_nodes, max_arity, max_depth, trusted)
+
+    try:
+        yield
+    finally:
+        with __REGISTRY_LOCK:
+            _rs.set_load_limits(*prev)
+
+
+def set_default_leaf_predicate(
+    leaf_predicate: Callable[[Any], bool] | None,
+    /,
+    *,
+    namespace: str,
+) -> Callable[[Any], bool] | None:
+    """Set the default leaf predicate of a namespace.
+
+    The default leaf predicate is used by the tree operations in the namespace when no
+    ``is_leaf`` predicate is given by the caller, so a framework can encode its leaf convention
+    once instead of at every call site. The treespecs flattened with the default leaf predicate
+    keep the namespace, so :meth:`PyTreeSpec.flatten` makes the same leaf decisions.
+
+    >>> tree = {'a': [1, 2], 'b': (3, 4)}
+    >>> set_default_leaf_predicate(lambda x: isinstance(x, list), namespace='lists-as-leaves')
+    >>> tree_flatten(tree, namespace='lists-as-leaves')
+    ([[1, 2], 3, 4], PyTreeSpec({'a': *, 'b': (*, *)}, namespace='lists-as-leaves'))
+    >>> tree_flatten(tree, is_leaf=lambda x: False, namespace='lists-as-leaves')
+    ([1, 2, 3, 4], PyTreeSpec({'a': [*, *], 'b': (*, *)}))
+    >>> set_default_leaf_predicate(None, namespace='lists-as-leaves')  # doctest: +ELLIPSIS
+    <function <lambda> at 0x...>
+
+    Args:
+        leaf_predicate (callable or None): A function that takes a subtree and returns a boolean
+            indicating whether it should be treated as a leaf. Pass :data:`None` to remove the
+            default leaf predicate of the namespace.
+        namespace (str): The namespace to set the default leaf predicate for. The global namespace
+            cannot have a default leaf predicate.
+
+    Returns:
+        The previous default leaf predicate of the namespace, or :data:`None` if there was none.
+    """
+    if not isinstance(namespace, str):
+        raise TypeError(f'The namespace must be a string, got {namespace!r}.')
+    if namespace == '':
+        raise ValueError('The namespace cannot be an empty string.')
+
+    with __REGISTRY_LOCK:
+        return _rs.set_leaf_predicate(leaf_predicate, namespace)
+
+
+def get_default_leaf_predicate(*, namespace: str) -> Callable[[Any], bool] | None:
+    """Get the default leaf predicate of a namespace.
+
+    >>> get_default_leaf_predicate(namespace='some-namespace') is None
+    True
+
+    Args:
+        namespace (str): The namespace to get the default leaf predicate for.
+
+    Returns:
+        The default leaf predicate of the namespace, or :data:`None` if it is not set.
+    """
+    if not isinstance(namespace, str):
+        raise TypeError(f'The namespace must be a string, got {namespace!r}.')
+    return _rs.get_leaf_predicate(namespace)
+
+
+def is_leaf_type(cls: type, /, *, none_is_leaf: bool = False, namespace: str = '') -> bool:
+    """Test whether the instances of a class would be treated as leaves.
+
+    The query is answered by the registry without requiring an instance of the class, so it can be
+    used to validate the types in user schemas statically. Note that ``is_leaf`` predicates given to
+    the tree operations are not taken into account as they are called on the instances.
+
+    >>> is_leaf_type(int)
+    True
+    >>> is_leaf_type(dict)
+    False
+    >>> is_leaf_type(type(None)), is_leaf_type(type(None), none_is_leaf=True)
+    (False, True)
+    >>> Point = namedtuple('Point', ['x', 'y'])
+    >>> is_leaf_type(Point)
+    False
+
+    Args:
+        cls (type): The class to query.
+        none_is_leaf (bool, optional): Whether to treat :data:`None` as a leaf. (default:
+            :data:`False`)
+        namespace (str, optional): The registry namespace used for custom pytree node types.
+            (default: :const:`''`, i.e., the global namespace)
+
+    Returns:
+        A boolean indicating if the instances of the class are leaves.
+    """
+    if not isinstance(cls, type):
+        raise TypeError(f'Expected a class, got {cls!r}.')
+    return _rs.is_leaf_type(cls, none_is_leaf, namespace)
+
+
+def registered_types(*, namespace: str = '') -> list[RegisteredType]:
+    """List the pytree node types registered in a namespace.
+
+    See also :func:`is_registered`.
+
+    The types registered in the namespace are listed along with the types registered in the global
+    namespace that are not overridden in the namespace. The wildcard namespace ``'*'`` lists the
+    registrations in all namespaces. The named tuples and the :class:`PyStructSequence` types are
+    treated as internal nodes without registrations, so they are not listed unless registered
+    explicitly. The result is sorted by namespace with the built-in types first, then by the
+    qualified names of the types.
+
+    >>> [entry.type.__name__ for entry in registered_types() if entry.builtin]
+    ['NoneType', 'OrderedDict', 'defaultdict', 'deque', 'dict', 'list', 'tuple']
+    >>> registered_types(namespace='empty') == registered_types()
+    True
+    >>> with scoped_registrations():
+    ...     register_pytree_node(
+    ...         set,
+    ...         lambda s: (sorted(s), None, None),
+    ...         lambda _, children: set(children),
+    ...         namespace='temp',
+    ...     )
+    ...     registered_types(namespace='temp')[-1]
+    <class 'set'>
+    RegisteredType(type=<class 'set'>, kind=PyTreeKind.CUSTOM, builtin=False, namespace='temp')
+
+    Args:
+        namespace (str, optional): The registry namespace to list the types of.
+            (default: :const:`''`, i.e., the global namespace)
+
+    Returns:
+        A list of :class:`RegisteredType` with the type, the node kind, whether the type is a
+        built-in node type, and the namespace it is registered in.
+    """
+    if not isinstance(namespace, str):
+        raise TypeError(f'The namespace must be a string, got {namespace!r}.')
+    return [RegisteredType(*entry) for entry in _rs.registered_types(namespace)]
+
+
+def is_registered(cls: type, /, *, namespace: str = '') -> bool:
+    """Test whether a class is registered as a pytree node type in a namespace.
+
+    See also :func:`registered_types` and :func:`is_leaf_type`.
+
+    The types registered in the global namespace are registered in all namespaces. The wildcard
+    namespace ``'*'`` tests the registrations in all namespaces. Unlike :func:`is_leaf_type`, the
+    named tuples and the :class:`PyStructSequence` types are not registered unless registered
+    explicitly.
+
+    >>> is_registered(dict), is_registered(int)
+    (True, False)
+    >>> Point = namedtuple('Point', ['x', 'y'])
+    >>> is_registered(Point), is_leaf_type(Point)
+    (False, False)
+
+    Args:
+        cls (type): The class to query.
+        namespace (str, optional): The registry namespace to look up the class in.
+            (default: :const:`''`, i.e., the global namespace)
+
+    Returns:
+        A boolean indicating if the class is registered in the namespace.
+    """
+    if not isinstance(cls, type):
+        raise TypeError(f'Expected a class, got {cls!r}.')
+    if not isinstance(namespace, str):
+        raise TypeError(f'The namespace must be a string, got {namespace!r}.')
+    return _rs.is_registered(cls, namespace)
+
+
+def load_registrations(group: str = 'rustree.register', /, *, reload: bool = False) -> list[str]:
+    """Discover and invoke the pytree node registration hooks advertised by installed packages.
+
+    Third-party packages can contribute custom node types without requiring users to import them
+    manually by advertising an entry point in the ``rustree.register`` group. For example, in the
+    ``pyproject.toml`` of the package:
+
+    .. code-block:: toml
+
+        [project.entry-points.'rustree.register']
+        mypackage = 'mypackage.pytree:register'
+
+    The entry point can refer to a callable that takes no arguments, which will be called to
+    register the node types, or to a module that registers the node types on import. Each entry
+    point is loaded at most once per process unless ``reload=True`` is given.
+
+    Args:
+        group (str, optional): The entry point group to discover the registration hooks from.
+            (default: :const:`'rustree.register'`)
+        reload (bool, optional): Whether to invoke the registration hooks that have already been
+            loaded. (default: :data:`False`)
+
+    Returns:
+        A list of the names of the entry points loaded by this call.
+
+    Raises:
+        RuntimeError: If an entry point fails to load. The original exception is chained as the
+            cause, and the entry points loaded before the failure remain loaded.
+    """
+    if not isinstance(group, str):
+        raise TypeError(f'The entry point group must be a string, got {group!r}.')
+    return _rs.load_registrations(group, reload=reload)
+
+
+def metrics() -> dict[str, Any]:
+    """Return the operation counters of the pytree node registry.
+
+    The returned dictionary contains the following entries:
+
+    - ``'registrations'``: the number of custom node types registered per namespace, where the
+      global namespace is ``''``.
+    - ``'lookups'``: the number of type lookups in the registry.
+    - ``'leaf_lookups'``: the number of type lookups that fell through to a leaf.
+    - ``'leaf_lookups_by_type'``: the number of leaf fall-throughs per type, most frequent first.
+    - ``'warnings'``: the number of warnings emitted by the registry.
+
+    The counters are process-wide and accumulate until :func:`reset_metrics` is called.
+
+    >>> reset_metrics()
+    >>> _ = tree_flatten({'a': 1, 'b': (2.0, None)})
+    >>> stats = metrics()
+    >>> stats['lookups'], stats['leaf_lookups']
+    (5, 2)
+    >>> stats['leaf_lookups_by_type']
+    {<class 'int'>: 1, <class 'float'>: 1}
+    """
+    return _rs.metrics()
+
+
+def reset_metrics() -> None:
+    """Reset the operation counters of the pytree node registry returned by :func:`metrics`."""
+    _rs.reset_metrics()
+
+
+def treespec_cache_clear() -> None:
+    """Clear the treespec cache used by ``tree_flatten(..., cache=True)``.
+
+    The cache holds at most 4096 layouts and does not evict them, so the cached treespecs keep their
+    dictionary keys and node types alive until the cache is cleared. The cache is also cleared when
+    a type is unregistered by :func:`unregister_pytree_node` or the registry is restored by
+    :func:`registry_restore`.
+
+    >>> treespec_cache_clear()
+    >>> _ = tree_flatten({'a': 1, 'b': (2, 3)}, cache=True)
+    >>> treespec_cache_size()
+    1
+    >>> treespec_cache_clear()
+    >>> treespec_cache_size()
+    0
+    """
+    _rs.treespec_cache_clear()
+
+
+def treespec_cache_size() -> int:
+    """Return the number of the treespecs in the cache used by ``tree_flatten(..., cache=True)``."""
+    return _rs.treespec_cache_size()
+
+
 def _sorted_items(items: Iterable[tuple[KT, VT]], /) -> list[tuple[KT, VT]]:
     return total_order_sorted(items, key=itemgetter(0))
 
 
+def _fields_flatten(
+    fields: tuple[str, ...],
+    meta_fields: tuple[str, ...],
+    obj: Any,
+    /,
+) -> tuple[tuple[Any, ...], tuple[Any, ...] | None, tuple[str, ...]]:
+    children = tuple(getattr(obj, name) for name in fields)
+    if not meta_fields:
+        return children, None, fields
+    return children, tuple(getattr(obj, name) for name in meta_fields), fields
+
+
+def _fields_unflatten(
+    init_func: Callable[..., T],
+    init_args: tuple[str, ...],
+    meta_fields: tuple[str, ...],
+    metadata: tuple[Any, ...] | None,
+    children: Iterable[Any],
+    /,
+) -> T:
+    kwargs = dict(safe_zip(init_args, children))
+    if meta_fields:
+        kwargs.update(safe_zip(meta_fields, metadata))  # type: ignore[arg-type]
+    return init_func(**kwargs)
+
+
 def _none_flatten(_: None, /) -> tuple[tuple[()], None]:
     return (), None
 
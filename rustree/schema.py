@@ -0,0 +1,194 @@
+# Copyright 2024-2025 Xuehai Pan. All Rights Reserved.
+#
+# Licensed under the Apache License, Version 2.0 (the "License");
+# you may not use this file except in compliance with the License.
+# You may obtain a copy of the License at
+#
+#     http://www.apache.org/licenses/LICENSE-2.0
+#
+# Unless required by applicable law or agreed to in writing, software
+# distributed under the License is distributed on an "AS IS" BASIS,
+# WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+# See the License for the specific language governing permissions and
+# limitations under the License.
+# ==============================================================================
+"""Structural schema validation for pytrees."""
+
+from __future__ import annotations
+
+from collections.abc import Mapping
+from typing import TYPE_CHECKING, Any, Callable, NamedTuple, Union
+from typing_extensions import TypeAlias  # Python 3.10+
+
+import rustree._rs as _rs
+from rustree.accessors import PyTreeAccessor
+from rustree.diff import tree_diff
+
+
+if TYPE_CHECKING:
+    from rustree.typing import PyTreeSpec
+
+
+__all__ = ['LeafValidator', 'SchemaViolation', 'TreeSchema']
+
+
+LeafValidator: TypeAlias = Union[type, tuple[type, ...], Callable[[Any], Any]]
+
+
+class SchemaViolation(NamedTuple):
+    """A violation of a :class:`TreeSchema` at a path of a pytree."""
+
+    path: tuple[Any, ...]
+    message: str
+
+
+class _Placeholder:  # pylint: disable=too-few-public-methods
+    __slots__: tuple[()] = ()
+
+    def __repr__(self, /) -> str:
+        return '*'
+
+
+_PLACEHOLDER: _Placeholder = _Placeholder()
+
+
+def _check_leaf(validator: LeafValidator, leaf: Any, /) -> str | None:
+    if isinstance(validator, type) or (
+        isinstance(validator, tuple) and all(isinstance(cls, type) for cls in validator)
+    ):
+        if isinstance(leaf, validator):
+            return None
+        expected = (
+            validator.__name__
+            if isinstance(validator, type)
+            else ' | '.join(cls.__name__ for cls in validator)
+        )
+        return f'Expected a leaf of type {expected}, got {type(leaf).__name__}: {leaf!r}.'
+    try:
+        result = validator(leaf)
+    except Exception as ex:  # noqa: BLE001 # pylint: disable=broad-exception-caught
+        return f'Validator {validator!r} raised {ex.__class__.__name__}: {ex}'
+    if result is False:
+        return f'Validator {validator!r} rejected leaf {leaf!r}.'
+    return None
+
+
+class TreeSchema:
+    """A schema for pytrees built from a treespec and per-path leaf validators.
+
+    The validators are keyed by the path of a leaf, as a tuple of path entry keys or a
+    :class:`PyTreeAccessor`. A validator can be a type or a tuple of types to check the leaf with
+    :func:`isinstance`, or a callable that fails if it returns :data:`False` or raises an exception.
+
+    >>> _, spec = tree_flatten({'lr': 0.1, 'layers': [64, 64], 'name': 'mlp'})
+    >>> schema = TreeSchema(
+    ...     spec,
+    ...     {('lr',): float, ('name',): str},
+    ...     default=lambda x: x > 0,
+    ... )
+    >>> schema.validate({'lr': 0.1, 'layers': [64, 32], 'name': 'mlp'})
+    []
+    >>> schema.validate({'lr': 1, 'layers': [64, -1], 'name': 'mlp'})  # doctest: +ELLIPSIS
+    [SchemaViolation(path=('layers', 1), message='Validator <function <lambda> at 0x...> rejected leaf -1.'), SchemaViolation(path=('lr',), message='Expected a leaf of type float, got int: 1.')]
+    >>> schema.validate({'lr': 0.1, 'layers': [64], 'name': 'mlp'})
+    [SchemaViolation(path=('layers', 1), message='Missing subtree *.')]
+    """  # pylint: disable=line-too-long
+
+    __slots__: tuple[str, ...] = ('_default', '_treespec', '_validators')
+
+    _treespec: PyTreeSpec
+    _validators: dict[tuple[Any, ...], LeafValidator]
+    _default: LeafValidator | None
+
+    def __init__(
+        self,
+        /,
+        treespec: PyTreeSpec,
+        validators: Mapping[tuple[Any, ...] | PyTreeAccessor, LeafValidator] | None = None,
+        *,
+        default: LeafValidator | None = None,
+    ) -> None:
+        """Initialize the schema from a treespec and per-path leaf validators.
+
+        Args:
+            treespec (PyTreeSpec): The expected structure of the pytrees.
+            validators (mapping, optional): A mapping from leaf paths to leaf validators. The paths
+                must be leaf paths of ``treespec``. (default: :data:`None`)
+            default (type, tuple of types, or callable, optional): The validator for leaves that
+                do not have a per-path validator. (default: :data:`None`)
+        """
+        paths = {accessor.path for accessor in treespec.accessors()}
+        self._validators = {}
+        for path, validator in (validators or {}).items():
+            if isinstance(path, PyTreeAccessor):
+                path = path.path
+            path = tuple(path)
+            if path not in paths:
+                raise ValueError(f'Path {path!r} is not a leaf path of {treespec!r}.')
+            self._validators[path] = validator
+        self._treespec = treespec
+        self._default = default
+
+    @property
+    def treespec(self, /) -> PyTreeSpec:
+        """Get the treespec of the schema."""
+        return self._treespec
+
+    def __repr__(self, /) -> str:
+        """Get the representation of the schema."""
+        return f'{self.__class__.__name__}({self._treespec!r}, {self._validators!r})'
+
+    def validate(self, tree: Any, /) -> list[SchemaViolation]:
+        """Validate the pytree against the schema and return all violations.
+
+        If the structure of the tree does not match the treespec, the structural violations are
+        returned and the leaves are not validated.
+        """
+        treespec = self._treespec
+        accessors, leaves, other_treespec = _rs.flatten_with_accessor(
+            tree,
+            None,
+            treespec.none_is_leaf,
+            treespec.namespace,
+        )
+        if other_treespec != treespec:
+            patch = tree_diff(
+                treespec.unflatten([_PLACEHOLDER] * treespec.num_leaves),
+                other_treespec.unflatten([_PLACEHOLDER] * other_treespec.num_leaves),
+                none_is_leaf=treespec.none_is_leaf,
+                namespace=treespec.namespace,
+            )
+            messages = {
+                'insert': 'Unexpected subtree {new!r}.',
+                'delete': 'Missing subtree {old!r}.',
+                'replace': 'Expected structure {old!r}, got {new!r}.',
+            }
+            return [
+                SchemaViolation(op.path, messages[op.op].format(old=op.old, new=op.new))
+                for op in patch
+            ]
+
+        violations = []
+        for accessor, leaf in zip(accessors, leaves):
+            path = accessor.path
+            validator = self._validators.get(path, self._default)
+            if validator is None:
+                continue
+            message = _check_leaf(validator, leaf)
+            if message is not None:
+                violations.append(SchemaViolation(path, message))
+        return violations
+
+    def is_valid(self, tree: Any, /) -> bool:
+        """Return whether the pytree satisfies the schema."""
+        return not self.validate(tree)
+
+    def check(self, tree: Any, /) -> None:
+        """Validate the pytree against the schema and raise on any violation."""
+        violations = self.validate(tree)
+        if violations:
+            details = '\n'.join(
+                f'  {violation.path!r}: {violation.message}'
+                for violation in violations
+            )
+            raise ValueError(f'PyTree does not match the schema:\n{details}')
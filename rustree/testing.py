@@ -0,0 +1,99 @@
+# Copyright 2024-2025 Xuehai Pan. All Rights Reserved.
+#
+# Licensed under the Apache License, Version 2.0 (the "License");
+# you may not use this file except in compliance with the License.
+# You may obtain a copy of the License at
+#
+#     http://www.apache.org/licenses/LICENSE-2.0
+#
+# Unless required by applicable law or agreed to in writing, software
+# distributed under the License is distributed on an "AS IS" BASIS,
+# WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+# See the License for the specific language governing permissions and
+# limitations under the License.
+# ==============================================================================
+"""Testing utilities for RusTree."""
+
+from __future__ import annotations
+
+from typing import TYPE_CHECKING, Any
+
+import rustree._rs as _rs
+
+
+if TYPE_CHECKING:
+    import random
+    from collections.abc import Callable, Iterable, Mapping
+
+    from rustree.typing import PyTreeKind
+
+
+__all__ = ['random_tree']
+
+
+def random_tree(
+    rng: random.Random,
+    /,
+    max_depth: int = 3,
+    container_weights: Mapping[PyTreeKind | type, float] | None = None,
+    leaf_factory: Callable[[random.Random], Any] | None = None,
+    *,
+    prototypes: Iterable[Any] | None = None,
+    max_children: int = 4,
+    none_is_leaf: bool = False,
+    namespace: str = '',
+) -> Any:
+    """Generate a random pytree for property-based testing.
+
+    The generated tree is fully determined by the state of ``rng``, so it can be driven by
+    `Hypothesis <https://hypothesis.readthedocs.io>`_ with ``st.randoms()``.
+
+    Built-in containers are generated with random children. Nodes of other types, e.g., namedtuples,
+    PyStructSequences, and registered custom pytree nodes, are generated from prototype instances:
+    the generated nodes have the same node type, metadata, and arity as the prototype, but with
+    random children.
+
+    >>> import random
+    >>> rng = random.Random(0)
+    >>> tree = random_tree(rng, max_depth=3, leaf_factory=lambda rng: rng.randrange(10))
+    >>> leaves, treespec = tree_flatten(tree)
+    >>> tree_unflatten(treespec, leaves) == tree
+    True
+    >>> random_tree(rng, container_weights={PyTreeKind.LEAF: 1.0}, leaf_factory=lambda rng: 42)
+    42
+
+    Args:
+        rng (random.Random): The random number generator.
+        max_depth (int, optional): The maximum depth of the generated tree. The nodes at the
+            maximum depth are always leaves. (default: :const:`3`)
+        container_weights (mapping, optional): A mapping from :class:`PyTreeKind` members or
+            prototype types to their relative weights of being chosen at each node. Only the
+            built-in kinds :attr:`PyTreeKind.LEAF`, :attr:`PyTreeKind.NONE`,
+            :attr:`PyTreeKind.TUPLE`, :attr:`PyTreeKind.LIST`, :attr:`PyTreeKind.DICT`,
+            :attr:`PyTreeKind.ORDEREDDICT`, :attr:`PyTreeKind.DEFAULTDICT`, and
+            :attr:`PyTreeKind.DEQUE` can be used as keys. Prototypes default to weight ``1.0``. If
+            not specified, all built-in kinds have weight ``1.0``. (default: :data:`None`)
+        leaf_factory (callable, optional): A function that takes ``rng`` and returns a leaf. If not
+            specified, leaves are random floats from ``rng.random()``. (default: :data:`None`)
+        prototypes (iterable, optional): Non-leaf nodes used as templates for generating nodes of
+            their types. (default: :data:`None`)
+        max_children (int, optional): The maximum number of children of generated built-in
+            containers. (default: :const:`4`)
+        none_is_leaf (bool, optional): Whether to treat :data:`None` as a leaf when flattening the
+            prototypes. (default: :data:`False`)
+        namespace (str, optional): The registry namespace used for custom pytree node types.
+            (default: :const:`''`, i.e., the global namespace)
+
+    Returns:
+        A randomly generated pytree.
+    """
+    return _rs.random_tree(
+        rng,
+        max_depth,
+        container_weights,
+        leaf_factory,
+        prototypes=prototypes,
+        max_children=max_children,
+        none_is_leaf=none_is_leaf,
+        namespace=namespace,
+    )
@@ -0,0 +1,92 @@
+# Copyright 2024-2025 Xuehai Pan. All Rights Reserved.
+#
+# Licensed under the Apache License, Version 2.0 (the "License");
+# you may not use this file except in compliance with the License.
+# You may obtain a copy of the License at
+#
+#     http://www.apache.org/licenses/LICENSE-2.0
+#
+# Unless required by applicable law or agreed to in writing, software
+# distributed under the License is distributed on an "AS IS" BASIS,
+# WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+# See the License for the specific language governing permissions and
+# limitations under the License.
+# ==============================================================================
+"""Tracing hooks for pytree traversals."""
+
+from __future__ import annotations
+
+import contextlib
+from typing import TYPE_CHECKING, Any
+
+import rustree._rs as _rs
+
+
+if TYPE_CHECKING:
+    from collections.abc import Generator
+
+    from rustree.typing import PyTreeKind
+
+
+__all__ = ['PyTreeTracer', 'trace', 'get_tracer']
+
+
+class PyTreeTracer:
+    """The base class for tracers of pytree traversals.
+
+    The callbacks are called in depth-first order during flattening, with the path of the node as a
+    tuple of path entry keys (see :func:`rustree.lens`), the kind of the node, and the node itself.
+    Subclasses override the callbacks they are interested in. A callback set to :data:`None` is not
+    called.
+
+    >>> class Printer(PyTreeTracer):
+    ...     def enter_node(self, path, kind, node):
+    ...         print('enter', path, kind)
+    ...
+    ...     def leaf(self, path, kind, leaf):
+    ...         print('leaf ', path, leaf)
+    >>> with trace(Printer()):
+    ...     tree_map(lambda x: x + 1, {'a': 1, 'b': [2]})
+    enter () PyTreeKind.DICT
+    leaf  ('a',) 1
+    enter ('b',) PyTreeKind.LIST
+    leaf  ('b', 0) 2
+    {'a': 2, 'b': [3]}
+    """
+
+    enter_node: Any = None
+    leaf: Any = None
+    exit_node: Any = None
+
+    if TYPE_CHECKING:
+
+        def enter_node(self, path: tuple[Any, ...], kind: PyTreeKind, node: Any, /) -> None:
+            """Called before the children of a non-leaf node are traversed."""
+
+        def leaf(self, path: tuple[Any, ...], kind: PyTreeKind, leaf: Any, /) -> None:
+            """Called on a leaf."""
+
+        def exit_node(self, path: tuple[Any, ...], kind: PyTreeKind, node: Any, /) -> None:
+            """Called after the children of a non-leaf node are traversed."""
+
+
+def get_tracer() -> Any | None:
+    """Get the tracer installed on the current thread."""
+    return _rs.get_tracer()
+
+
+@contextlib.contextmanager
+def trace(tracer: Any, /) -> Generator[Any]:
+    """Install a tracer on the current thread for the flatten and map calls within the context.
+
+    The tracer can be any object with the optional ``enter_node``, ``leaf``, and ``exit_node``
+    callbacks of :class:`PyTreeTracer`. Nested contexts restore the outer tracer on exit.
+
+    Tracing support can be compiled out by building the extension without the ``tracing`` cargo
+    feature, in which case installing a tracer raises a :exc:`RuntimeError`.
+    """
+    previous = _rs.set_tracer(tracer)
+    try:
+        yield tracer
+    finally:
+        _rs.set_tracer(previous)
@@ -53,7 +53,7 @@ from typing_extensions import (
 )
 
 import rustree._rs as _rs
-from rustree._rs import PyTreeKind
+from rustree._rs import PyTreeKind, PyTreeNode, PyTreeSpec, TreeSpecBuilder, Unraveler
 from rustree.accessors import (
     AutoEntry,
     DataclassEntry,
@@ -70,11 +70,16 @@ from rustree.accessors import (
 
 
 __all__ = [
+    'PyTreeSpec',
     'PyTreeKind',
+    'PyTreeNode',
+    'TreeSpecBuilder',
+    'Unraveler',
     'Children',
     'MetaData',
     'FlattenFunc',
     'UnflattenFunc',
+    'RavelBackend',
     'PyTreeEntry',
     'GetItemEntry',
     'GetAttrEntry',
@@ -168,6 +173,19 @@ class UnflattenFunc(Protocol[T]):  # pylint: disable=too-few-public-methods
         """Unflatten the children and metadata back into the container."""
 
 
+class RavelBackend(Protocol):
+    """The hooks for raveling the array leaves of a pytree into one flat buffer."""
+
+    def ravel(self, leaf: Any, /) -> tuple[Any, Any, Any]:
+        """Return the one-dimensional view of the leaf together with its shape and dtype."""
+
+    def concatenate(self, flats: list[Any], /) -> Any:
+        """Concatenate the one-dimensional arrays into one flat buffer."""
+
+    def unravel(self, flat: Any, shape: Any, dtype: Any, /) -> Any:
+        """Restore a slice of the flat buffer to the given shape and dtype."""
+
+
 def _override_with_(
     rust_implementation: Callable[P, T],
     /,
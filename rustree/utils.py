@@ -16,14 +16,59 @@
 
 from __future__ import annotations
 
+import io
+import os
+import pickle
+import sys
+from collections import OrderedDict, defaultdict, deque
 from collections.abc import Iterable, Sequence
 from typing import TYPE_CHECKING, Any, Callable, overload
 
+import rustree._rs as _rs
+
 
 if TYPE_CHECKING:
     from rustree.typing import S, T, U
 
 
+def get_include() -> str:
+    """Return the directory that contains the RusTree C API header ``rustree.h``.
+
+    Extension modules that use the C API should add this directory to the include paths, and call
+    ``RusTree_ImportCAPI()`` in the module initialization function.
+    """
+    return os.path.join(os.path.dirname(os.path.abspath(__file__)), 'include')
+
+
+def set_debug(enabled: bool, /, callback: Callable[[str, str], Any] | None = None) -> None:
+    """Enable or disable the debug logging of RusTree.
+
+    The debug logging reports registry mutations, type resolutions in the registry, and dispatches
+    to custom flatten functions. It is useful for diagnosing why a type is treated as a leaf. The
+    debug logging can also be enabled by setting the environment variable ``RUSTREE_DEBUG=1``
+    before importing RusTree.
+
+    >>> records = []
+    >>> set_debug(True, callback=lambda event, message: records.append(event))
+    >>> tree_flatten({'a': 1})
+    ([1], PyTreeSpec({'a': *}))
+    >>> records
+    ['resolve', 'resolve']
+    >>> set_debug(False)
+
+    Args:
+        enabled (bool): Whether to enable the debug logging.
+        callback (callable, optional): A function that takes the event name and the message. If not
+            specified, the messages are printed to :data:`sys.stderr`. (default: :data:`None`)
+    """
+    _rs.set_debug(enabled, callback)
+
+
+def is_debug_enabled() -> bool:
+    """Return whether the debug logging of RusTree is enabled."""
+    return _rs.is_debug_enabled()
+
+
 def total_order_sorted(
     iterable: Iterable[T],
     /,
@@ -115,3 +160,96 @@ def unzip2(xys: Iterable[tuple[T, S]], /) -> tuple[tuple[T, ...], tuple[S, ...]]
         xs.append(x)
         ys.append(y)
     return tuple(xs), tuple(ys)
+
+
+# The classes that are resolved when loading the untrusted data, they have no side effects
+_SAFE_CLASSES: frozenset[type] = frozenset(
+    {
+        bool,
+        int,
+        float,
+        complex,
+        str,
+        bytes,
+        bytearray,
+        tuple,
+        list,
+        dict,
+        set,
+        frozenset,
+        type(None),
+        OrderedDict,
+        defaultdict,
+        deque,
+    },
+)
+
+
+# The safe classes that `pickle` reconstructs by calling them, the other safe classes are only
+# reconstructed by the dedicated opcodes and cannot be called from the untrusted data
+_REDUCIBLE_CLASSES: frozenset[type] = frozenset(
+    {bytearray, complex, OrderedDict, defaultdict, deque},
+)
+
+
+# The pure-Python unpickler, so the opcodes that call the resolved classes can be checked
+class _RestrictedUnpickler(pickle._Unpickler):  # pylint: disable=protected-access
+    dispatch = pickle._Unpickler.dispatch.copy()  # pylint: disable=protected-access
+
+    def find_class(self, module: str, name: str, /) -> Any:
+        # Never import the modules, only resolve the builtin classes
+        obj: Any = sys.modules.get(module)
+        for attr in name.split('.'):
+            obj = getattr(obj, attr, None)
+        if isinstance(obj, type) and obj in _SAFE_CLASSES:
+            return obj
+        raise pickle.UnpicklingError(
+            f'Refusing to resolve {module}.{name} from untrusted data, '
+            f'pass `trusted=True` to load it.',
+        )
+
+    def check_call(self, cls: Any, args: tuple[Any, ...], /) -> None:
+        # The sizes are never passed to the constructors, e.g., `bytearray(10**12)`
+        if cls not in _REDUCIBLE_CLASSES or (
+            cls is bytearray and not all(isinstance(arg, bytes) for arg in args)
+        ):
+            raise pickle.UnpicklingError(
+                f'Refusing to call {cls.__qualname__} with {args!r} from untrusted data, '
+                f'pass `trusted=True` to load it.',
+            )
+
+    def refuse_opcode(self) -> None:
+        # `to_bytes()` always pickles with protocol 4, which never emits the protocol 0 and 1
+        # opcodes that call a class with the arguments on the stack
+        raise pickle.UnpicklingError(
+            'Refusing to call a class by the INST or OBJ opcode from untrusted data, '
+            'pass `trusted=True` to load it.',
+        )
+
+    def load_reduce(self) -> None:
+        self.check_call(self.stack[-2], self.stack[-1])
+        super().load_reduce()
+
+    def load_newobj(self) -> None:
+        self.check_call(self.stack[-2], self.stack[-1])
+        super().load_newobj()
+
+    def load_newobj_ex(self) -> None:
+        self.check_call(self.stack[-3], self.stack[-2])
+        super().load_newobj_ex()
+
+    dispatch[pickle.REDUCE[0]] = load_reduce
+    dispatch[pickle.NEWOBJ[0]] = load_newobj
+    dispatch[pickle.NEWOBJ_EX[0]] = load_newobj_ex
+    dispatch[pickle.INST[0]] = refuse_opcode
+    dispatch[pickle.OBJ[0]] = refuse_opcode
+
+
+def restricted_loads(data: bytes, /) -> Any:
+    """Unpickle the data without importing modules or resolving arbitrary callables.
+
+    Only the builtin containers and scalars can be resolved. They can only be called in the forms
+    that :mod:`pickle` writes for their instances, so the data cannot allocate by the sizes it
+    chooses.
+    """
+    return _RestrictedUnpickler(io.BytesIO(data)).load()
@@ -18,11 +18,37 @@ use pyo3::prelude::*;
 
 mod rustree;
 
+// The Rust API of the flatten/unflatten engine for embedding in other extension modules, the
+// `_rs` Python module below registers the same implementation.
+pub use rustree::capi::{RUSTREE_CAPI_VERSION, RusTreeCAPI};
+pub use rustree::treespec::{flatten, is_leaf};
+pub use rustree::{PyTreeKind, PyTreeSpec};
+pub use rustree::{get_leaf_predicate, set_leaf_predicate};
+pub use rustree::{is_dict_insertion_ordered, set_dict_insertion_ordered};
+pub use rustree::{register_node, register_node_class, unregister_node};
+
 #[pymodule]
 #[pyo3(name = "_rs")]
 fn build_extension(m: &Bound<PyModule>) -> PyResult<()> {
+    rustree::logging::init_from_env();
     m.add("Py_TPFLAGS_BASETYPE", ffi::Py_TPFLAGS_BASETYPE)?;
+    m.add("_C_API", rustree::capi::capsule(m.py())?)?;
     m.add_class::<rustree::PyTreeKind>()?;
+    m.add_class::<rustree::PyTreeEntry>()?;
+    m.add_class::<rustree::GetItemEntry>()?;
+    m.add_class::<rustree::GetAttrEntry>()?;
+    m.add_class::<rustree::FlattenedEntry>()?;
+    m.add_class::<rustree::SequenceEntry>()?;
+    m.add_class::<rustree::MappingEntry>()?;
+    m.add_class::<rustree::NamedTupleEntry>()?;
+    m.add_class::<rustree::StructSequenceEntry>()?;
+    m.add_class::<rustree::DataclassEntry>()?;
+    m.add_class::<rustree::PyTreeSpec>()?;
+    m.add_class::<rustree::PyTreeNode>()?;
+    m.add_class::<rustree::TreeSpecBuilder>()?;
+    m.add_class::<rustree::Unraveler>()?;
+    m.add_class::<rustree::PrefixError>()?;
+    m.add_class::<rustree::RegistrySnapshot>()?;
     m.add_function(wrap_pyfunction!(rustree::is_namedtuple, m)?)?;
     m.add_function(wrap_pyfunction!(rustree::is_namedtuple_instance, m)?)?;
     m.add_function(wrap_pyfunction!(rustree::is_namedtuple_class, m)?)?;
@@ -32,9 +58,90 @@ fn build_extension(m: &Bound<PyModule>) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(rustree::is_structseq_class, m)?)?;
     m.add_function(wrap_pyfunction!(rustree::structseq_fields, m)?)?;
     m.add_function(wrap_pyfunction!(rustree::register_node, m)?)?;
+    m.add_function(wrap_pyfunction!(rustree::register_node_class, m)?)?;
     m.add_function(wrap_pyfunction!(rustree::unregister_node, m)?)?;
+    m.add_function(wrap_pyfunction!(rustree::registry_snapshot, m)?)?;
+    m.add_function(wrap_pyfunction!(rustree::registry_restore, m)?)?;
+    m.add_function(wrap_pyfunction!(rustree::registered_types, m)?)?;
+    m.add_function(wrap_pyfunction!(rustree::is_registered, m)?)?;
     m.add_function(wrap_pyfunction!(rustree::is_dict_insertion_ordered, m)?)?;
     m.add_function(wrap_pyfunction!(rustree::set_dict_insertion_ordered, m)?)?;
+    m.add_function(wrap_pyfunction!(rustree::get_leaf_predicate, m)?)?;
+    m.add_function(wrap_pyfunction!(rustree::set_leaf_predicate, m)?)?;
     m.add_function(wrap_pyfunction!(rustree::treespec::is_leaf, m)?)?;
+    m.add_function(wrap_pyfunction!(rustree::treespec::is_leaf_type, m)?)?;
+    m.add_function(wrap_pyfunction!(rustree::treespec::are_all_leaves, m)?)?;
+    m.add_function(wrap_pyfunction!(rustree::treespec::flatten_tree, m)?)?;
+    m.add_function(wrap_pyfunction!(rustree::treespec::structure, m)?)?;
+    m.add_function(wrap_pyfunction!(rustree::treespec::flatten_into_list, m)?)?;
+    m.add_function(wrap_pyfunction!(rustree::treespec::flatten_multiple, m)?)?;
+    m.add_function(wrap_pyfunction!(
+        rustree::treespec::flatten_with_accessor,
+        m
+    )?)?;
+    m.add_function(wrap_pyfunction!(rustree::treespec::flatten_with_path, m)?)?;
+    m.add_function(wrap_pyfunction!(rustree::treespec::map_tree, m)?)?;
+    m.add_function(wrap_pyfunction!(rustree::treespec::map_with_path, m)?)?;
+    m.add_function(wrap_pyfunction!(rustree::treespec::map_with_path_, m)?)?;
+    m.add_function(wrap_pyfunction!(rustree::treespec::transpose_map, m)?)?;
+    m.add_function(wrap_pyfunction!(rustree::treespec::partition, m)?)?;
+    m.add_function(wrap_pyfunction!(rustree::treespec::filter, m)?)?;
+    m.add_function(wrap_pyfunction!(rustree::treespec::reduce_with_path, m)?)?;
+    m.add_function(wrap_pyfunction!(rustree::treespec::count, m)?)?;
+    m.add_function(wrap_pyfunction!(rustree::treespec::sum_leaves, m)?)?;
+    m.add_function(wrap_pyfunction!(rustree::treespec::mean_leaves, m)?)?;
+    m.add_function(wrap_pyfunction!(rustree::treespec::max_leaf, m)?)?;
+    m.add_function(wrap_pyfunction!(rustree::treespec::min_leaf, m)?)?;
+    m.add_function(wrap_pyfunction!(rustree::treespec::all_leaves, m)?)?;
+    m.add_function(wrap_pyfunction!(rustree::treespec::any_leaf, m)?)?;
+    m.add_function(wrap_pyfunction!(rustree::treespec::leaf_depths, m)?)?;
+    m.add_function(wrap_pyfunction!(rustree::treespec::leaves, m)?)?;
+    m.add_function(wrap_pyfunction!(rustree::treespec::shared_leaves, m)?)?;
+    m.add_function(wrap_pyfunction!(rustree::treespec::type_counts, m)?)?;
+    m.add_function(wrap_pyfunction!(rustree::treespec::copy_tree, m)?)?;
+    m.add_function(wrap_pyfunction!(rustree::treespec::deepcopy, m)?)?;
+    m.add_function(wrap_pyfunction!(rustree::treespec::replace_nones, m)?)?;
+    m.add_function(wrap_pyfunction!(rustree::treespec::hash_tree, m)?)?;
+    m.add_function(wrap_pyfunction!(rustree::treespec::format_tree, m)?)?;
+    m.add_function(wrap_pyfunction!(rustree::treespec::align_tree, m)?)?;
+    m.add_function(wrap_pyfunction!(rustree::treespec::transpose_tree, m)?)?;
+    m.add_function(wrap_pyfunction!(rustree::treespec::ravel_tree, m)?)?;
+    m.add_function(wrap_pyfunction!(rustree::treespec::make_leaf, m)?)?;
+    m.add_function(wrap_pyfunction!(rustree::treespec::make_none, m)?)?;
+    m.add_function(wrap_pyfunction!(rustree::treespec::make_container, m)?)?;
+    m.add_function(wrap_pyfunction!(
+        rustree::treespec::make_from_collection,
+        m
+    )?)?;
+    m.add_function(wrap_pyfunction!(rustree::treespec::broadcast_map, m)?)?;
+    m.add_function(wrap_pyfunction!(
+        rustree::treespec::broadcast_map_with_path,
+        m
+    )?)?;
+    m.add_function(wrap_pyfunction!(rustree::treespec::broadcast_prefix, m)?)?;
+    m.add_function(wrap_pyfunction!(
+        rustree::treespec::broadcast_prefix_leaves,
+        m
+    )?)?;
+    m.add_function(wrap_pyfunction!(rustree::treespec::broadcast_common, m)?)?;
+    m.add_function(wrap_pyfunction!(rustree::treespec::prefix_errors, m)?)?;
+    m.add_function(wrap_pyfunction!(rustree::testing::random_tree, m)?)?;
+    m.add_function(wrap_pyfunction!(rustree::tracing::set_tracer, m)?)?;
+    m.add_function(wrap_pyfunction!(rustree::tracing::get_tracer, m)?)?;
+    m.add_function(wrap_pyfunction!(rustree::logging::set_debug, m)?)?;
+    m.add_function(wrap_pyfunction!(rustree::logging::is_debug_enabled, m)?)?;
+    m.add_function(wrap_pyfunction!(rustree::metrics::metrics, m)?)?;
+    m.add_function(wrap_pyfunction!(rustree::metrics::reset_metrics, m)?)?;
+    m.add_function(wrap_pyfunction!(
+        rustree::treespec::treespec_cache_clear,
+        m
+    )?)?;
+    m.add_function(wrap_pyfunction!(rustree::treespec::treespec_cache_size, m)?)?;
+    m.add_function(wrap_pyfunction!(rustree::treespec::get_at_path, m)?)?;
+    m.add_function(wrap_pyfunction!(rustree::treespec::replace_at_path, m)?)?;
+    m.add_function(wrap_pyfunction!(rustree::treespec::get_load_limits, m)?)?;
+    m.add_function(wrap_pyfunction!(rustree::treespec::set_load_limits, m)?)?;
+    m.add_function(wrap_pyfunction!(rustree::plugins::load_registrations, m)?)?;
+    m.add_function(wrap_pyfunction!(rustree::profiling::profile, m)?)?;
     Ok(())
 }
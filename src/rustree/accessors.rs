@@ -0,0 +1,526 @@
+// Copyright 2024-2025 Xuehai Pan. All Rights Reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+// =============================================================================
+
+use pyo3::exceptions::{PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::*;
+
+use crate::rustree::pytypes::{namedtuple_fields, structseq_fields};
+use crate::rustree::registry::PyTreeKind;
+use crate::rustree::treespec::{new_accessor, pytree_accessor_type};
+
+// The base class of the path entries. The built-in node kinds use the subclasses below, and the
+// custom node types can subclass any of them in Python.
+#[pyclass(subclass, frozen, module = "rustree")]
+pub struct PyTreeEntry {
+    entry: Py<PyAny>,
+    node_type: Py<PyType>,
+    kind: PyTreeKind,
+}
+
+impl PyTreeEntry {
+    fn new(
+        entry: &Bound<'_, PyAny>,
+        node_type: &Bound<'_, PyType>,
+        kind: PyTreeKind,
+    ) -> PyResult<Self> {
+        match kind {
+            PyTreeKind::Leaf => Err(PyValueError::new_err("Cannot create a leaf path entry.")),
+            PyTreeKind::None => Err(PyValueError::new_err(
+                "Cannot create a path entry for None.",
+            )),
+            _ => Ok(PyTreeEntry {
+                entry: entry.clone().unbind(),
+                node_type: node_type.clone().unbind(),
+                kind,
+            }),
+        }
+    }
+}
+
+#[inline]
+fn entry_of<'py>(obj: &Bound<'py, PyAny>) -> PyResult<Bound<'py, PyAny>> {
+    Ok(obj
+        .downcast::<PyTreeEntry>()?
+        .get()
+        .entry
+        .bind(obj.py())
+        .clone())
+}
+
+// The representation with the entry shown as the given attribute, e.g., `index` or `key`
+fn entry_repr(obj: &Bound<'_, PyAny>, attr: &str) -> PyResult<String> {
+    Ok(std::format!(
+        "{}({}={}, type={})",
+        obj.get_type().name()?,
+        attr,
+        obj.getattr(attr)?.repr()?,
+        obj.downcast::<PyTreeEntry>()?
+            .get()
+            .node_type
+            .bind(obj.py())
+            .repr()?,
+    ))
+}
+
+// The methods of the path entry classes defined in Python are compared by their bytecode, so the
+// redefined classes (e.g., on module reload) are still equal
+fn method_key<'py>(cls: &Bound<'py, PyType>, name: &str) -> PyResult<Bound<'py, PyAny>> {
+    let method = cls.getattr(name)?;
+    match method.getattr("__code__") {
+        Ok(code) => code.getattr("co_code"),
+        Err(_) => Ok(method),
+    }
+}
+
+fn equality_key<'py>(obj: &Bound<'py, PyTreeEntry>) -> PyResult<Bound<'py, PyTuple>> {
+    let py = obj.py();
+    let entry = obj.get();
+    let cls = obj.get_type();
+    PyTuple::new(
+        py,
+        [
+            entry.entry.bind(py).clone(),
+            entry.node_type.bind(py).clone().into_any(),
+            Bound::new(py, entry.kind)?.into_any(),
+            method_key(&cls, "__call__")?,
+            method_key(&cls, "codify")?,
+        ],
+    )
+}
+
+#[pymethods]
+impl PyTreeEntry {
+    #[new]
+    #[pyo3(signature = (entry, r#type, kind))]
+    fn py_new(
+        entry: &Bound<'_, PyAny>,
+        r#type: &Bound<'_, PyType>,
+        kind: PyTreeKind,
+    ) -> PyResult<Self> {
+        PyTreeEntry::new(entry, r#type, kind)
+    }
+
+    #[getter]
+    fn entry<'py>(&self, py: Python<'py>) -> Bound<'py, PyAny> {
+        self.entry.bind(py).clone()
+    }
+
+    #[getter(r#type)]
+    fn get_type<'py>(&self, py: Python<'py>) -> Bound<'py, PyType> {
+        self.node_type.bind(py).clone()
+    }
+
+    #[getter]
+    fn kind(&self) -> PyTreeKind {
+        self.kind
+    }
+
+    fn __call__<'py>(
+        slf: &Bound<'py, Self>,
+        obj: &Bound<'py, PyAny>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let py = slf.py();
+        let entry = slf.get().entry.bind(py);
+        match obj.get_item(entry) {
+            Err(err) if err.is_instance_of::<PyTypeError>(py) => {
+                let new_err = PyTypeError::new_err(std::format!(
+                    "{} cannot access through {} via entry {}",
+                    slf.get_type().repr()?,
+                    obj.repr()?,
+                    entry.repr()?,
+                ));
+                new_err.set_cause(py, Some(err));
+                Err(new_err)
+            }
+            result => result,
+        }
+    }
+
+    fn __add__<'py>(
+        slf: &Bound<'py, Self>,
+        other: &Bound<'py, PyAny>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let py = slf.py();
+        let accessor_type = pytree_accessor_type(py)?;
+        let mut path = vec![slf.clone().into_any()];
+        if other.is_instance_of::<PyTreeEntry>() {
+            path.push(other.clone());
+        } else if other.is_instance(accessor_type)? {
+            path.extend(other.try_iter()?.collect::<PyResult<Vec<_>>>()?);
+        } else {
+            return Ok(py.NotImplemented().into_bound(py));
+        }
+        new_accessor(PyTuple::new(py, path)?)
+    }
+
+    fn __eq__(slf: &Bound<'_, Self>, other: &Bound<'_, PyAny>) -> PyResult<bool> {
+        match other.downcast::<PyTreeEntry>() {
+            Ok(other) => equality_key(slf)?.eq(equality_key(other)?),
+            Err(_) => Ok(false),
+        }
+    }
+
+    fn __hash__(slf: &Bound<'_, Self>) -> PyResult<isize> {
+        equality_key(slf)?.hash()
+    }
+
+    fn __repr__(slf: &Bound<'_, Self>) -> PyResult<String> {
+        entry_repr(slf.as_any(), "entry")
+    }
+
+    fn __reduce__<'py>(
+        slf: &Bound<'py, Self>,
+    ) -> (
+        Bound<'py, PyType>,
+        (Bound<'py, PyAny>, Bound<'py, PyType>, PyTreeKind),
+    ) {
+        let py = slf.py();
+        let entry = slf.get();
+        (
+            slf.get_type(),
+            (
+                entry.entry.bind(py).clone(),
+                entry.node_type.bind(py).clone(),
+                entry.kind,
+            ),
+        )
+    }
+
+    #[pyo3(signature = (node=""))]
+    fn codify(&self, py: Python<'_>, node: &str) -> PyResult<String> {
+        Ok(std::format!(
+            "{}[<flat index {}>]",
+            node,
+            self.entry.bind(py).repr()?
+        ))
+    }
+
+    #[classmethod]
+    fn __class_getitem__<'py>(
+        cls: &Bound<'py, PyType>,
+        item: &Bound<'py, PyAny>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        cls.py()
+            .import("types")?
+            .getattr("GenericAlias")?
+            .call1((cls, item))
+    }
+}
+
+// A generic path entry class for nodes that access their children by `__getitem__`
+#[pyclass(extends = PyTreeEntry, subclass, frozen, module = "rustree")]
+pub struct GetItemEntry;
+
+#[pymethods]
+impl GetItemEntry {
+    #[new]
+    #[pyo3(signature = (entry, r#type, kind))]
+    fn py_new(
+        entry: &Bound<'_, PyAny>,
+        r#type: &Bound<'_, PyType>,
+        kind: PyTreeKind,
+    ) -> PyResult<(Self, PyTreeEntry)> {
+        Ok((GetItemEntry, PyTreeEntry::new(entry, r#type, kind)?))
+    }
+
+    fn __call__<'py>(
+        slf: &Bound<'py, Self>,
+        obj: &Bound<'py, PyAny>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        obj.get_item(entry_of(slf.as_any())?)
+    }
+
+    #[pyo3(signature = (node=""))]
+    fn codify(slf: &Bound<'_, Self>, node: &str) -> PyResult<String> {
+        Ok(std::format!(
+            "{}[{}]",
+            node,
+            entry_of(slf.as_any())?.repr()?
+        ))
+    }
+}
+
+// A generic path entry class for nodes that access their children by `__getattr__`
+#[pyclass(extends = PyTreeEntry, subclass, frozen, module = "rustree")]
+pub struct GetAttrEntry;
+
+#[pymethods]
+impl GetAttrEntry {
+    #[new]
+    #[pyo3(signature = (entry, r#type, kind))]
+    fn py_new(
+        entry: &Bound<'_, PyAny>,
+        r#type: &Bound<'_, PyType>,
+        kind: PyTreeKind,
+    ) -> PyResult<(Self, PyTreeEntry)> {
+        Ok((GetAttrEntry, PyTreeEntry::new(entry, r#type, kind)?))
+    }
+
+    #[getter]
+    fn name<'py>(slf: &Bound<'py, Self>) -> PyResult<Bound<'py, PyAny>> {
+        entry_of(slf.as_any())
+    }
+
+    fn __call__<'py>(
+        slf: &Bound<'py, Self>,
+        obj: &Bound<'py, PyAny>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        obj.getattr(slf.getattr("name")?.downcast_into::<PyString>()?)
+    }
+
+    #[pyo3(signature = (node=""))]
+    fn codify(slf: &Bound<'_, Self>, node: &str) -> PyResult<String> {
+        Ok(std::format!("{}.{}", node, slf.getattr("name")?.str()?))
+    }
+}
+
+// A fallback path entry class for flattened objects
+#[pyclass(extends = PyTreeEntry, subclass, frozen, module = "rustree")]
+pub struct FlattenedEntry;
+
+#[pymethods]
+impl FlattenedEntry {
+    #[new]
+    #[pyo3(signature = (entry, r#type, kind))]
+    fn py_new(
+        entry: &Bound<'_, PyAny>,
+        r#type: &Bound<'_, PyType>,
+        kind: PyTreeKind,
+    ) -> PyResult<(Self, PyTreeEntry)> {
+        Ok((FlattenedEntry, PyTreeEntry::new(entry, r#type, kind)?))
+    }
+}
+
+#[pyclass(extends = GetItemEntry, subclass, frozen, module = "rustree")]
+pub struct SequenceEntry;
+
+#[pymethods]
+impl SequenceEntry {
+    #[new]
+    #[pyo3(signature = (entry, r#type, kind))]
+    fn py_new(
+        entry: &Bound<'_, PyAny>,
+        r#type: &Bound<'_, PyType>,
+        kind: PyTreeKind,
+    ) -> PyResult<PyClassInitializer<Self>> {
+        Ok(
+            PyClassInitializer::from(PyTreeEntry::new(entry, r#type, kind)?)
+                .add_subclass(GetItemEntry)
+                .add_subclass(SequenceEntry),
+        )
+    }
+
+    #[getter]
+    fn index<'py>(slf: &Bound<'py, Self>) -> PyResult<Bound<'py, PyAny>> {
+        entry_of(slf.as_any())
+    }
+
+    fn __call__<'py>(
+        slf: &Bound<'py, Self>,
+        obj: &Bound<'py, PyAny>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        obj.get_item(slf.getattr("index")?)
+    }
+
+    fn __repr__(slf: &Bound<'_, Self>) -> PyResult<String> {
+        entry_repr(slf.as_any(), "index")
+    }
+}
+
+#[pyclass(extends = GetItemEntry, subclass, frozen, module = "rustree")]
+pub struct MappingEntry;
+
+#[pymethods]
+impl MappingEntry {
+    #[new]
+    #[pyo3(signature = (entry, r#type, kind))]
+    fn py_new(
+        entry: &Bound<'_, PyAny>,
+        r#type: &Bound<'_, PyType>,
+        kind: PyTreeKind,
+    ) -> PyResult<PyClassInitializer<Self>> {
+        Ok(
+            PyClassInitializer::from(PyTreeEntry::new(entry, r#type, kind)?)
+                .add_subclass(GetItemEntry)
+                .add_subclass(MappingEntry),
+        )
+    }
+
+    #[getter]
+    fn key<'py>(slf: &Bound<'py, Self>) -> PyResult<Bound<'py, PyAny>> {
+        entry_of(slf.as_any())
+    }
+
+    fn __call__<'py>(
+        slf: &Bound<'py, Self>,
+        obj: &Bound<'py, PyAny>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        obj.get_item(slf.getattr("key")?)
+    }
+
+    fn __repr__(slf: &Bound<'_, Self>) -> PyResult<String> {
+        entry_repr(slf.as_any(), "key")
+    }
+}
+
+#[pyclass(extends = SequenceEntry, subclass, frozen, module = "rustree")]
+pub struct NamedTupleEntry;
+
+#[pymethods]
+impl NamedTupleEntry {
+    #[new]
+    #[pyo3(signature = (entry, r#type, kind))]
+    fn py_new(
+        entry: &Bound<'_, PyAny>,
+        r#type: &Bound<'_, PyType>,
+        kind: PyTreeKind,
+    ) -> PyResult<PyClassInitializer<Self>> {
+        Ok(
+            PyClassInitializer::from(PyTreeEntry::new(entry, r#type, kind)?)
+                .add_subclass(GetItemEntry)
+                .add_subclass(SequenceEntry)
+                .add_subclass(NamedTupleEntry),
+        )
+    }
+
+    #[getter]
+    fn fields<'py>(slf: &Bound<'py, Self>) -> PyResult<Bound<'py, PyTuple>> {
+        namedtuple_fields(slf.getattr("type")?.as_any())
+    }
+
+    #[getter]
+    fn field<'py>(slf: &Bound<'py, Self>) -> PyResult<Bound<'py, PyAny>> {
+        slf.getattr("fields")?.get_item(entry_of(slf.as_any())?)
+    }
+
+    fn __repr__(slf: &Bound<'_, Self>) -> PyResult<String> {
+        entry_repr(slf.as_any(), "field")
+    }
+
+    #[pyo3(signature = (node=""))]
+    fn codify(slf: &Bound<'_, Self>, node: &str) -> PyResult<String> {
+        Ok(std::format!("{}.{}", node, slf.getattr("field")?.str()?))
+    }
+}
+
+#[pyclass(extends = SequenceEntry, subclass, frozen, module = "rustree")]
+pub struct StructSequenceEntry;
+
+#[pymethods]
+impl StructSequenceEntry {
+    #[new]
+    #[pyo3(signature = (entry, r#type, kind))]
+    fn py_new(
+        entry: &Bound<'_, PyAny>,
+        r#type: &Bound<'_, PyType>,
+        kind: PyTreeKind,
+    ) -> PyResult<PyClassInitializer<Self>> {
+        Ok(
+            PyClassInitializer::from(PyTreeEntry::new(entry, r#type, kind)?)
+                .add_subclass(GetItemEntry)
+                .add_subclass(SequenceEntry)
+                .add_subclass(StructSequenceEntry),
+        )
+    }
+
+    #[getter]
+    fn fields<'py>(slf: &Bound<'py, Self>) -> PyResult<Bound<'py, PyTuple>> {
+        structseq_fields(slf.getattr("type")?.as_any())
+    }
+
+    #[getter]
+    fn field<'py>(slf: &Bound<'py, Self>) -> PyResult<Bound<'py, PyAny>> {
+        slf.getattr("fields")?.get_item(entry_of(slf.as_any())?)
+    }
+
+    fn __repr__(slf: &Bound<'_, Self>) -> PyResult<String> {
+        entry_repr(slf.as_any(), "field")
+    }
+
+    #[pyo3(signature = (node=""))]
+    fn codify(slf: &Bound<'_, Self>, node: &str) -> PyResult<String> {
+        Ok(std::format!("{}.{}", node, slf.getattr("field")?.str()?))
+    }
+}
+
+#[pyclass(extends = GetAttrEntry, subclass, frozen, module = "rustree")]
+pub struct DataclassEntry;
+
+// The names of the dataclass fields, optionally only the arguments of `__init__`
+fn dataclass_fields<'py>(
+    cls: &Bound<'py, PyAny>,
+    init_only: bool,
+) -> PyResult<Bound<'py, PyTuple>> {
+    let py = cls.py();
+    let mut names = Vec::new();
+    for field in py
+        .import("dataclasses")?
+        .call_method1("fields", (cls,))?
+        .try_iter()?
+    {
+        let field = field?;
+        if !init_only || field.getattr("init")?.is_truthy()? {
+            names.push(field.getattr("name")?);
+        }
+    }
+    PyTuple::new(py, names)
+}
+
+#[pymethods]
+impl DataclassEntry {
+    #[new]
+    #[pyo3(signature = (entry, r#type, kind))]
+    fn py_new(
+        entry: &Bound<'_, PyAny>,
+        r#type: &Bound<'_, PyType>,
+        kind: PyTreeKind,
+    ) -> PyResult<PyClassInitializer<Self>> {
+        Ok(
+            PyClassInitializer::from(PyTreeEntry::new(entry, r#type, kind)?)
+                .add_subclass(GetAttrEntry)
+                .add_subclass(DataclassEntry),
+        )
+    }
+
+    #[getter]
+    fn fields<'py>(slf: &Bound<'py, Self>) -> PyResult<Bound<'py, PyTuple>> {
+        dataclass_fields(&slf.getattr("type")?, false)
+    }
+
+    #[getter]
+    fn init_fields<'py>(slf: &Bound<'py, Self>) -> PyResult<Bound<'py, PyTuple>> {
+        dataclass_fields(&slf.getattr("type")?, true)
+    }
+
+    // The field is given by the name, or by the position in the `__init__` fields
+    #[getter]
+    fn field<'py>(slf: &Bound<'py, Self>) -> PyResult<Bound<'py, PyAny>> {
+        let entry = entry_of(slf.as_any())?;
+        match entry.is_instance_of::<PyInt>() {
+            true => slf.getattr("init_fields")?.get_item(entry),
+            false => Ok(entry),
+        }
+    }
+
+    #[getter]
+    fn name<'py>(slf: &Bound<'py, Self>) -> PyResult<Bound<'py, PyAny>> {
+        slf.getattr("field")
+    }
+
+    fn __repr__(slf: &Bound<'_, Self>) -> PyResult<String> {
+        entry_repr(slf.as_any(), "field")
+    }
+}
@@ -0,0 +1,163 @@
+// Copyright 2024-2025 Xuehai Pan. All Rights Reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+// =============================================================================
+
+// The C API exported as the `rustree._rs._C_API` capsule.
+// Keep in sync with `rustree/include/rustree.h`. All functions must be called with the GIL held.
+// On failure, they set a Python exception and return NULL or -1.
+
+use pyo3::exceptions::PyValueError;
+use pyo3::ffi;
+use pyo3::prelude::*;
+use pyo3::types::*;
+use std::ffi::{CStr, c_char, c_int, c_void};
+
+use crate::rustree::registry::PyTreeTypeRegistry;
+use crate::rustree::treespec::{PyTreeSpec, flatten};
+
+pub const RUSTREE_CAPI_VERSION: u32 = 1;
+
+const CAPSULE_NAME: &CStr = c"rustree._rs._C_API";
+
+#[repr(C)]
+pub struct RusTreeCAPI {
+    pub version: u32,
+    // Return a new reference to the list of leaves, and store a new reference to the treespec
+    pub flatten: unsafe extern "C" fn(
+        tree: *mut ffi::PyObject,
+        leaf_predicate: *mut ffi::PyObject,
+        none_is_leaf: c_int,
+        namespace: *const c_char,
+        treespec: *mut *mut ffi::PyObject,
+    ) -> *mut ffi::PyObject,
+    // Return a new reference to the reconstructed tree
+    pub unflatten: unsafe extern "C" fn(
+        treespec: *mut ffi::PyObject,
+        leaves: *mut ffi::PyObject,
+    ) -> *mut ffi::PyObject,
+    // Return the `PyTreeKind` value of the type
+    pub lookup: unsafe extern "C" fn(
+        cls: *mut ffi::PyObject,
+        none_is_leaf: c_int,
+        namespace: *const c_char,
+    ) -> c_int,
+}
+
+static RUSTREE_CAPI: RusTreeCAPI = RusTreeCAPI {
+    version: RUSTREE_CAPI_VERSION,
+    flatten: capi_flatten,
+    unflatten: capi_unflatten,
+    lookup: capi_lookup,
+};
+
+unsafe fn namespace_from_ptr<'a>(namespace: *const c_char) -> PyResult<&'a str> {
+    if namespace.is_null() {
+        return Ok("");
+    }
+    unsafe { CStr::from_ptr(namespace) }
+        .to_str()
+        .map_err(|err| PyValueError::new_err(std::format!("Invalid namespace: {}.", err)))
+}
+
+unsafe extern "C" fn capi_flatten(
+    tree: *mut ffi::PyObject,
+    leaf_predicate: *mut ffi::PyObject,
+    none_is_leaf: c_int,
+    namespace: *const c_char,
+    treespec: *mut *mut ffi::PyObject,
+) -> *mut ffi::PyObject {
+    Python::attach(|py| {
+        let result = (|| -> PyResult<(Bound<PyList>, Bound<PyTreeSpec>)> {
+            let tree = unsafe { Bound::from_borrowed_ptr(py, tree) };
+            let leaf_predicate = unsafe { Bound::from_borrowed_ptr_or_opt(py, leaf_predicate) }
+                .filter(|leaf_predicate| !leaf_predicate.is_none());
+            let namespace = unsafe { namespace_from_ptr(namespace) }?;
+            let (leaves, spec) =
+                flatten(&tree, leaf_predicate.as_ref(), none_is_leaf != 0, namespace)?;
+            Ok((PyList::new(py, leaves)?, Bound::new(py, spec)?))
+        })();
+        match result {
+            Ok((leaves, spec)) => {
+                if !treespec.is_null() {
+                    unsafe { *treespec = spec.into_ptr() };
+                }
+                leaves.into_ptr()
+            }
+            Err(err) => {
+                err.restore(py);
+                std::ptr::null_mut()
+            }
+        }
+    })
+}
+
+unsafe extern "C" fn capi_unflatten(
+    treespec: *mut ffi::PyObject,
+    leaves: *mut ffi::PyObject,
+) -> *mut ffi::PyObject {
+    Python::attach(|py| {
+        let result = (|| -> PyResult<Bound<PyAny>> {
+            let treespec = unsafe { Bound::from_borrowed_ptr(py, treespec) };
+            let leaves = unsafe { Bound::from_borrowed_ptr(py, leaves) };
+            treespec
+                .downcast::<PyTreeSpec>()?
+                .get()
+                .unflatten(&leaves, None)
+        })();
+        match result {
+            Ok(tree) => tree.into_ptr(),
+            Err(err) => {
+                err.restore(py);
+                std::ptr::null_mut()
+            }
+        }
+    })
+}
+
+unsafe extern "C" fn capi_lookup(
+    cls: *mut ffi::PyObject,
+    none_is_leaf: c_int,
+    namespace: *const c_char,
+) -> c_int {
+    Python::attach(|py| {
+        let result = (|| -> PyResult<c_int> {
+            let cls = unsafe { Bound::from_borrowed_ptr(py, cls) };
+            let namespace = unsafe { namespace_from_ptr(namespace) }?;
+            let (kind, _) = PyTreeTypeRegistry::get_type_kind(
+                cls.downcast::<PyType>()?,
+                none_is_leaf != 0,
+                namespace,
+            )?;
+            Ok(kind as c_int)
+        })();
+        match result {
+            Ok(kind) => kind,
+            Err(err) => {
+                err.restore(py);
+                -1
+            }
+        }
+    })
+}
+
+pub fn capsule(py: Python<'_>) -> PyResult<Bound<'_, PyCapsule>> {
+    let capsule = unsafe {
+        ffi::PyCapsule_New(
+            &RUSTREE_CAPI as *const RusTreeCAPI as *mut c_void,
+            CAPSULE_NAME.as_ptr(),
+            None,
+        )
+    };
+    Ok(unsafe { Bound::from_owned_ptr_or_err(py, capsule) }?.downcast_into::<PyCapsule>()?)
+}
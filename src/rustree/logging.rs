@@ -0,0 +1,107 @@
+// Copyright 2024-2025 Xuehai Pan. All Rights Reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+// =============================================================================
+
+use pyo3::prelude::*;
+use pyo3::types::*;
+use std::collections::HashSet;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static DEBUG: AtomicBool = AtomicBool::new(false);
+static CALLBACK: Mutex<Option<Py<PyAny>>> = Mutex::new(None);
+// Type resolutions are logged once per (type, namespace, none_is_leaf)
+static RESOLUTIONS: Mutex<Option<HashSet<(usize, String, bool)>>> = Mutex::new(None);
+
+pub(crate) fn init_from_env() {
+    if let Ok(value) = std::env::var("RUSTREE_DEBUG") {
+        let value = value.trim().to_ascii_lowercase();
+        DEBUG.store(
+            !matches!(value.as_str(), "" | "0" | "false" | "no" | "off"),
+            Ordering::Relaxed,
+        );
+    }
+}
+
+#[inline]
+pub(crate) fn is_enabled() -> bool {
+    DEBUG.load(Ordering::Relaxed)
+}
+
+#[inline]
+pub(crate) fn debug<F>(py: Python<'_>, event: &str, message: F) -> PyResult<()>
+where
+    F: FnOnce() -> PyResult<String>,
+{
+    if !is_enabled() {
+        return Ok(());
+    }
+    let message = message()?;
+    let callback = CALLBACK
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|callback| callback.clone_ref(py));
+    match callback {
+        Some(callback) => {
+            callback.bind(py).call1((event, message))?;
+        }
+        None => eprintln!("[rustree] {}: {}", event, message),
+    }
+    Ok(())
+}
+
+pub(crate) fn debug_resolution<F>(
+    cls: &Bound<'_, PyType>,
+    namespace: &str,
+    none_is_leaf: bool,
+    message: F,
+) -> PyResult<()>
+where
+    F: FnOnce() -> PyResult<String>,
+{
+    if !is_enabled() {
+        return Ok(());
+    }
+    let key = (cls.as_ptr() as usize, String::from(namespace), none_is_leaf);
+    if !RESOLUTIONS
+        .lock()
+        .unwrap()
+        .get_or_insert_with(HashSet::new)
+        .insert(key)
+    {
+        return Ok(());
+    }
+    debug(cls.py(), "resolve", message)
+}
+
+pub(crate) fn namespace_repr(py: Python<'_>, namespace: &str) -> PyResult<String> {
+    Ok(match namespace.is_empty() {
+        true => String::from("the global namespace"),
+        false => std::format!("namespace {}", PyString::new(py, namespace).repr()?),
+    })
+}
+
+#[pyfunction]
+#[pyo3(signature = (enabled, /, callback=None))]
+pub fn set_debug(enabled: bool, callback: Option<&Bound<PyAny>>) {
+    *CALLBACK.lock().unwrap() = callback.map(|callback| callback.clone().unbind());
+    *RESOLUTIONS.lock().unwrap() = None;
+    DEBUG.store(enabled, Ordering::Relaxed);
+}
+
+#[pyfunction]
+pub fn is_debug_enabled() -> bool {
+    is_enabled()
+}
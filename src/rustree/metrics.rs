@@ -0,0 +1,88 @@
+// Copyright 2024-2025 Xuehai Pan. All Rights Reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+// =============================================================================
+
+use pyo3::prelude::*;
+use pyo3::types::*;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::rustree::registry::PyTreeTypeRegistry;
+
+static LOOKUPS: AtomicU64 = AtomicU64::new(0);
+static LEAF_LOOKUPS: AtomicU64 = AtomicU64::new(0);
+static WARNINGS: AtomicU64 = AtomicU64::new(0);
+// Keyed by the address of the type object, the type is kept alive until the metrics are reset
+type LeafLookupCounts = HashMap<usize, (Py<PyType>, u64)>;
+static LEAF_LOOKUPS_BY_TYPE: Mutex<Option<LeafLookupCounts>> = Mutex::new(None);
+
+#[inline]
+pub(crate) fn record_lookup() {
+    LOOKUPS.fetch_add(1, Ordering::Relaxed);
+}
+
+#[inline]
+pub(crate) fn record_leaf_lookup(cls: &Bound<'_, PyType>) {
+    LEAF_LOOKUPS.fetch_add(1, Ordering::Relaxed);
+    LEAF_LOOKUPS_BY_TYPE
+        .lock()
+        .unwrap()
+        .get_or_insert_with(HashMap::new)
+        .entry(cls.as_ptr() as usize)
+        .or_insert_with(|| (cls.clone().unbind(), 0))
+        .1 += 1;
+}
+
+#[inline]
+pub(crate) fn record_warning() {
+    WARNINGS.fetch_add(1, Ordering::Relaxed);
+}
+
+#[pyfunction]
+pub fn metrics(py: Python<'_>) -> PyResult<Bound<'_, PyDict>> {
+    let registrations = PyDict::new(py);
+    let mut counts: Vec<_> = PyTreeTypeRegistry::registration_counts(py)
+        .into_iter()
+        .collect();
+    counts.sort();
+    for (namespace, count) in counts {
+        registrations.set_item(namespace, count)?;
+    }
+
+    let leaf_lookups_by_type = PyDict::new(py);
+    if let Some(by_type) = LEAF_LOOKUPS_BY_TYPE.lock().unwrap().as_ref() {
+        let mut by_type: Vec<_> = by_type.values().collect();
+        by_type.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        for (cls, count) in by_type {
+            leaf_lookups_by_type.set_item(cls.bind(py), *count)?;
+        }
+    }
+
+    let metrics = PyDict::new(py);
+    metrics.set_item("registrations", registrations)?;
+    metrics.set_item("lookups", LOOKUPS.load(Ordering::Relaxed))?;
+    metrics.set_item("leaf_lookups", LEAF_LOOKUPS.load(Ordering::Relaxed))?;
+    metrics.set_item("leaf_lookups_by_type", leaf_lookups_by_type)?;
+    metrics.set_item("warnings", WARNINGS.load(Ordering::Relaxed))?;
+    Ok(metrics)
+}
+
+#[pyfunction]
+pub fn reset_metrics() {
+    LOOKUPS.store(0, Ordering::Relaxed);
+    LEAF_LOOKUPS.store(0, Ordering::Relaxed);
+    WARNINGS.store(0, Ordering::Relaxed);
+    *LEAF_LOOKUPS_BY_TYPE.lock().unwrap() = None;
+}
@@ -13,12 +13,26 @@
 // limitations under the License.
 // =============================================================================
 
+mod accessors;
+pub mod capi;
+pub mod logging;
+pub mod metrics;
+pub mod plugins;
+pub mod profiling;
 mod pytypes;
 mod registry;
+pub mod testing;
+pub mod tracing;
 pub mod treespec;
 
+pub use accessors::{DataclassEntry, FlattenedEntry, GetAttrEntry, GetItemEntry, PyTreeEntry};
+pub use accessors::{MappingEntry, NamedTupleEntry, SequenceEntry, StructSequenceEntry};
 pub use pytypes::{is_namedtuple, is_namedtuple_class, is_namedtuple_instance, namedtuple_fields};
 pub use pytypes::{is_structseq, is_structseq_class, is_structseq_instance, structseq_fields};
 pub use registry::PyTreeKind;
+pub use registry::{RegistrySnapshot, registry_restore, registry_snapshot};
+pub use registry::{get_leaf_predicate, set_leaf_predicate};
 pub use registry::{is_dict_insertion_ordered, set_dict_insertion_ordered};
-pub use registry::{register_node, unregister_node};
+pub use registry::{is_registered, registered_types};
+pub use registry::{register_node, register_node_class, unregister_node};
+pub use treespec::{PrefixError, PyTreeNode, PyTreeSpec, TreeSpecBuilder, Unraveler};
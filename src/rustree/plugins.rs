@@ -0,0 +1,106 @@
+// Copyright 2024-2025 Xuehai Pan. All Rights Reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+// =============================================================================
+
+use pyo3::exceptions::{PyRuntimeError, PyTypeError};
+use pyo3::prelude::*;
+use pyo3::types::*;
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+use crate::rustree::logging::debug;
+
+pub const ENTRY_POINT_GROUP: &str = "rustree.register";
+
+// Entry points that have been loaded successfully, keyed by (group, name, value)
+static LOADED: Mutex<Option<HashSet<(String, String, String)>>> = Mutex::new(None);
+
+fn entry_points<'py>(py: Python<'py>, group: &str) -> PyResult<Vec<Bound<'py, PyAny>>> {
+    let metadata = py.import("importlib.metadata")?;
+    let kwargs = PyDict::new(py);
+    kwargs.set_item("group", group)?;
+    let entry_points = match metadata.getattr("entry_points")?.call((), Some(&kwargs)) {
+        Ok(entry_points) => entry_points,
+        // Python 3.9 does not support selecting entry points by group
+        Err(err) if err.is_instance_of::<PyTypeError>(py) => {
+            match metadata
+                .getattr("entry_points")?
+                .call0()?
+                .call_method1("get", (group, PyTuple::empty(py)))
+            {
+                Ok(entry_points) => entry_points,
+                Err(_) => return Err(err),
+            }
+        }
+        Err(err) => return Err(err),
+    };
+    entry_points.try_iter()?.collect()
+}
+
+#[pyfunction]
+#[pyo3(signature = (group=ENTRY_POINT_GROUP, /, *, reload=false))]
+pub fn load_registrations<'py>(
+    py: Python<'py>,
+    group: &str,
+    reload: bool,
+) -> PyResult<Bound<'py, PyList>> {
+    let loaded = PyList::empty(py);
+    for entry_point in entry_points(py, group)? {
+        let name: String = entry_point.getattr("name")?.extract()?;
+        let value: String = entry_point.getattr("value")?.extract()?;
+        let key = (String::from(group), name.clone(), value.clone());
+        if !reload
+            && LOADED
+                .lock()
+                .unwrap()
+                .as_ref()
+                .is_some_and(|cache| cache.contains(&key))
+        {
+            continue;
+        }
+
+        let result = entry_point.call_method0("load").and_then(|hook| {
+            // An entry point may refer to a module that registers its node types on import
+            if !hook.is_instance_of::<PyModule>() && hook.is_callable() {
+                hook.call0()?;
+            }
+            Ok(())
+        });
+        if let Err(err) = result {
+            let error = PyRuntimeError::new_err(std::format!(
+                "Failed to load PyTree registrations from entry point {} ({}) in group {}.",
+                PyString::new(py, &name).repr()?,
+                value,
+                PyString::new(py, group).repr()?,
+            ));
+            error.set_cause(py, Some(err));
+            return Err(error);
+        }
+        debug(py, "load_registrations", || {
+            Ok(std::format!(
+                "loaded entry point {} ({}) in group {}",
+                PyString::new(py, &name).repr()?,
+                value,
+                PyString::new(py, group).repr()?,
+            ))
+        })?;
+        LOADED
+            .lock()
+            .unwrap()
+            .get_or_insert_with(HashSet::new)
+            .insert(key);
+        loaded.append(name)?;
+    }
+    Ok(loaded)
+}
@@ -0,0 +1,177 @@
+// Copyright 2024-2025 Xuehai Pan. All Rights Reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+// =============================================================================
+
+use pyo3::exceptions::{PyRuntimeError, PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::*;
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Phase {
+    Lookup = 0,
+    Callback = 1,
+    Construction = 2,
+}
+
+#[derive(Default)]
+struct Profile {
+    // Exclusive time spent in each phase, nested phases are not double counted
+    durations: [Duration; 3],
+    counts: [u64; 3],
+    allocations: u64,
+    current: Option<(Phase, Instant)>,
+}
+
+// The number of threads with an active profile, checked first to keep the disabled path cheap
+static ACTIVE: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    static PROFILE: RefCell<Option<Profile>> = const { RefCell::new(None) };
+}
+
+const PROFILED_FUNCTIONS: [&str; 6] = [
+    "flatten",
+    "flatten_with_accessor",
+    "map",
+    "map_with_path",
+    "map_with_path_",
+    "is_leaf",
+];
+
+fn enter(phase: Phase) -> Option<Option<Phase>> {
+    PROFILE.with(|cell| {
+        let mut profile = cell.borrow_mut();
+        let profile = profile.as_mut()?;
+        let now = Instant::now();
+        let previous = profile.current.take().map(|(previous, start)| {
+            profile.durations[previous as usize] += now - start;
+            previous
+        });
+        profile.counts[phase as usize] += 1;
+        profile.current = Some((phase, now));
+        Some(previous)
+    })
+}
+
+fn exit(previous: Option<Phase>) {
+    PROFILE.with(|cell| {
+        if let Some(profile) = cell.borrow_mut().as_mut() {
+            let now = Instant::now();
+            if let Some((phase, start)) = profile.current.take() {
+                profile.durations[phase as usize] += now - start;
+            }
+            profile.current = previous.map(|previous| (previous, now));
+        }
+    })
+}
+
+#[inline]
+pub(crate) fn timed<T>(phase: Phase, f: impl FnOnce() -> T) -> T {
+    if ACTIVE.load(Ordering::Relaxed) == 0 {
+        return f();
+    }
+    let Some(previous) = enter(phase) else {
+        return f();
+    };
+    let result = f();
+    exit(previous);
+    result
+}
+
+#[inline]
+pub(crate) fn record_allocations(count: u64) {
+    if ACTIVE.load(Ordering::Relaxed) == 0 {
+        return;
+    }
+    PROFILE.with(|cell| {
+        if let Some(profile) = cell.borrow_mut().as_mut() {
+            profile.allocations += count;
+        }
+    })
+}
+
+#[pyfunction]
+#[pyo3(signature = (fn_name, tree, /, *args, **kwargs))]
+pub fn profile<'py>(
+    py: Python<'py>,
+    fn_name: &str,
+    tree: &Bound<'py, PyAny>,
+    args: &Bound<'py, PyTuple>,
+    kwargs: Option<&Bound<'py, PyDict>>,
+) -> PyResult<Bound<'py, PyDict>> {
+    if !PROFILED_FUNCTIONS.contains(&fn_name) {
+        return Err(PyValueError::new_err(std::format!(
+            "Cannot profile function {}; expected one of {}.",
+            PyString::new(py, fn_name).repr()?,
+            PyTuple::new(py, PROFILED_FUNCTIONS)?.repr()?,
+        )));
+    }
+    let func = py.import("rustree._rs")?.getattr(fn_name)?;
+    let call_args = match fn_name {
+        // `profile('map', tree, func, *rests)` calls `map(func, tree, *rests)`
+        "map" | "map_with_path" | "map_with_path_" => {
+            if args.is_empty() {
+                return Err(PyTypeError::new_err(
+                    "Profiling a map function requires the function to map as the third argument.",
+                ));
+            }
+            let mut call_args = vec![args.get_item(0)?, tree.clone()];
+            call_args.extend(args.iter().skip(1));
+            PyTuple::new(py, call_args)?
+        }
+        _ => {
+            let mut call_args = vec![tree.clone()];
+            call_args.extend(args.iter());
+            PyTuple::new(py, call_args)?
+        }
+    };
+
+    if PROFILE.with(|cell| cell.borrow().is_some()) {
+        return Err(PyRuntimeError::new_err(
+            "Cannot profile recursively on the same thread.",
+        ));
+    }
+    PROFILE.with(|cell| cell.replace(Some(Profile::default())));
+    ACTIVE.fetch_add(1, Ordering::Relaxed);
+    let start = Instant::now();
+    let result = func.call(call_args, kwargs);
+    let total = start.elapsed();
+    ACTIVE.fetch_sub(1, Ordering::Relaxed);
+    let profile = PROFILE.with(|cell| cell.take()).unwrap();
+    let result = result?;
+
+    let [lookup, callback, construction] = profile.durations;
+    let [lookups, callbacks, containers] = profile.counts;
+    let out = PyDict::new(py);
+    out.set_item("function", fn_name)?;
+    out.set_item("total", total.as_secs_f64())?;
+    out.set_item("registry_lookup", lookup.as_secs_f64())?;
+    out.set_item("python_callback", callback.as_secs_f64())?;
+    out.set_item("container_construction", construction.as_secs_f64())?;
+    out.set_item(
+        "traversal",
+        total
+            .saturating_sub(lookup + callback + construction)
+            .as_secs_f64(),
+    )?;
+    out.set_item("lookups", lookups)?;
+    out.set_item("callbacks", callbacks)?;
+    out.set_item("containers", containers)?;
+    out.set_item("allocations", profile.allocations)?;
+    out.set_item("result", result)?;
+    Ok(out)
+}
@@ -16,8 +16,28 @@
 use pyo3::exceptions::PyTypeError;
 use pyo3::ffi;
 use pyo3::prelude::*;
+use pyo3::sync::PyOnceLock;
 use pyo3::types::*;
 
+static ORDEREDDICT_TYPE: PyOnceLock<Py<PyType>> = PyOnceLock::new();
+static DEFAULTDICT_TYPE: PyOnceLock<Py<PyType>> = PyOnceLock::new();
+static DEQUE_TYPE: PyOnceLock<Py<PyType>> = PyOnceLock::new();
+
+#[inline]
+pub fn ordereddict_type(py: Python<'_>) -> PyResult<&Bound<'_, PyType>> {
+    ORDEREDDICT_TYPE.import(py, "collections", "OrderedDict")
+}
+
+#[inline]
+pub fn defaultdict_type(py: Python<'_>) -> PyResult<&Bound<'_, PyType>> {
+    DEFAULTDICT_TYPE.import(py, "collections", "defaultdict")
+}
+
+#[inline]
+pub fn deque_type(py: Python<'_>) -> PyResult<&Bound<'_, PyType>> {
+    DEQUE_TYPE.import(py, "collections", "deque")
+}
+
 #[inline]
 fn is_namedtuple_class_impl(cls: &Bound<PyType>) -> bool {
     // We can only identify namedtuples heuristically, here by the presence of a _fields attribute.
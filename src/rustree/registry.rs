@@ -13,7 +13,13 @@
 // limitations under the License.
 // =============================================================================
 
-use crate::rustree::pytypes::{is_namedtuple_class, is_structseq_class};
+use crate::rustree::logging::{debug, debug_resolution, namespace_repr};
+use crate::rustree::metrics::{record_leaf_lookup, record_lookup, record_warning};
+use crate::rustree::profiling::{Phase, timed};
+use crate::rustree::pytypes::{
+    defaultdict_type, deque_type, is_namedtuple_class, is_structseq_class, ordereddict_type,
+};
+use crate::rustree::treespec::treespec_cache_clear;
 use once_cell::sync::OnceCell;
 use pyo3::exceptions::{PyTypeError, PyValueError};
 use pyo3::prelude::*;
@@ -22,9 +28,10 @@ use pyo3::types::*;
 use std::collections::hash_map::Entry as HashMapEntry;
 use std::collections::{HashMap, HashSet};
 use std::ffi::CString;
+use std::sync::{Arc, Mutex};
 
-#[pyclass(eq, eq_int, module = "rustree", rename_all = "UPPERCASE")]
-#[derive(PartialEq, Eq, Clone, Copy)]
+#[pyclass(eq, eq_int, frozen, hash, module = "rustree", rename_all = "UPPERCASE")]
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
 pub enum PyTreeKind {
     Custom = 0,
     Leaf,
@@ -39,6 +46,95 @@ pub enum PyTreeKind {
     StructSequence,
 }
 
+impl PyTreeKind {
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            PyTreeKind::Custom => "CUSTOM",
+            PyTreeKind::Leaf => "LEAF",
+            PyTreeKind::None => "NONE",
+            PyTreeKind::Tuple => "TUPLE",
+            PyTreeKind::List => "LIST",
+            PyTreeKind::Dict => "DICT",
+            PyTreeKind::NamedTuple => "NAMEDTUPLE",
+            PyTreeKind::OrderedDict => "ORDEREDDICT",
+            PyTreeKind::DefaultDict => "DEFAULTDICT",
+            PyTreeKind::Deque => "DEQUE",
+            PyTreeKind::StructSequence => "STRUCTSEQUENCE",
+        }
+    }
+
+    // The node type of the kind, or `None` if the kind covers more than one type
+    pub(crate) fn builtin_node_type<'py>(
+        &self,
+        py: Python<'py>,
+    ) -> PyResult<Option<Bound<'py, PyType>>> {
+        Ok(Some(match self {
+            PyTreeKind::None => py.get_type::<PyNone>(),
+            PyTreeKind::Tuple => py.get_type::<PyTuple>(),
+            PyTreeKind::List => py.get_type::<PyList>(),
+            PyTreeKind::Dict => py.get_type::<PyDict>(),
+            PyTreeKind::OrderedDict => ordereddict_type(py)?.clone(),
+            PyTreeKind::DefaultDict => defaultdict_type(py)?.clone(),
+            PyTreeKind::Deque => deque_type(py)?.clone(),
+            PyTreeKind::Custom
+            | PyTreeKind::Leaf
+            | PyTreeKind::NamedTuple
+            | PyTreeKind::StructSequence => return Ok(None),
+        }))
+    }
+}
+
+#[pymethods]
+impl PyTreeKind {
+    #[classmethod]
+    #[pyo3(signature = (obj, /, leaf_predicate=None, none_is_leaf=false, namespace=""))]
+    fn of(
+        _cls: &Bound<'_, PyType>,
+        obj: &Bound<'_, PyAny>,
+        leaf_predicate: Option<&Bound<'_, PyAny>>,
+        none_is_leaf: Option<bool>,
+        namespace: Option<&str>,
+    ) -> PyResult<PyTreeKind> {
+        let namespace = namespace.unwrap_or("");
+        if let Some(leaf_predicate) =
+            PyTreeTypeRegistry::leaf_predicate(obj.py(), leaf_predicate, namespace)
+            && leaf_predicate.call1((obj,))?.is_truthy()?
+        {
+            return Ok(PyTreeKind::Leaf);
+        }
+        let (kind, _) =
+            PyTreeTypeRegistry::get_kind(obj, none_is_leaf.unwrap_or(false), namespace)?;
+        Ok(kind)
+    }
+
+    #[classmethod]
+    #[pyo3(signature = (cls, /, none_is_leaf=false, namespace=""))]
+    fn of_type(
+        _cls: &Bound<'_, PyType>,
+        cls: &Bound<'_, PyType>,
+        none_is_leaf: Option<bool>,
+        namespace: Option<&str>,
+    ) -> PyResult<PyTreeKind> {
+        let (kind, _) = PyTreeTypeRegistry::get_type_kind(
+            cls,
+            none_is_leaf.unwrap_or(false),
+            namespace.unwrap_or(""),
+        )?;
+        Ok(kind)
+    }
+
+    #[getter]
+    fn node_type<'py>(&self, py: Python<'py>) -> PyResult<Option<Bound<'py, PyType>>> {
+        self.builtin_node_type(py)
+    }
+
+    fn __reduce__<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyTuple>> {
+        let getattr = py.import("builtins")?.getattr("getattr")?;
+        let args = (py.get_type::<PyTreeKind>(), self.name()).into_pyobject(py)?;
+        PyTuple::new(py, [getattr, args.into_any()])
+    }
+}
+
 #[repr(transparent)]
 struct IdHashedPy<T>(Py<T>);
 
@@ -70,18 +166,123 @@ impl<T> std::hash::Hash for IdHashedPy<T> {
 static mut REGISTRY_NONE_IS_NODE: PyOnceLock<PyTreeTypeRegistry> = PyOnceLock::new();
 static mut REGISTRY_NONE_IS_LEAF: PyOnceLock<PyTreeTypeRegistry> = PyOnceLock::new();
 static mut DICT_INSERTION_ORDERED_NAMESPACES: OnceCell<HashSet<String>> = OnceCell::new();
+// The default leaf predicates of the named namespaces, used when the caller does not pass one
+static LEAF_PREDICATES: Mutex<Option<HashMap<String, Py<PyAny>>>> = Mutex::new(None);
+
+// Look up the type in the only namespace it is registered in, falling back to the global namespace
+pub(crate) const WILDCARD_NAMESPACE: &str = "*";
+
+// How the custom nodes are presented in the path entries and the repr of the treespecs
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum KindHint {
+    Mapping,
+    Sequence,
+    Object,
+}
+
+impl KindHint {
+    fn parse(py: Python<'_>, kind_hint: &str) -> PyResult<Self> {
+        match kind_hint {
+            "mapping" => Ok(KindHint::Mapping),
+            "sequence" => Ok(KindHint::Sequence),
+            "object" => Ok(KindHint::Object),
+            _ => Err(PyValueError::new_err(std::format!(
+                "Expected the kind hint to be one of 'mapping', 'sequence', or 'object', got {}.",
+                PyString::new(py, kind_hint).repr()?,
+            ))),
+        }
+    }
+}
 
 pub struct PyTreeTypeRegistration {
-    kind: PyTreeKind,
-    node_type: Py<PyType>,
-    flatten_func: Option<Py<PyAny>>,
-    unflatten_func: Option<Py<PyAny>>,
-    path_entry_type: Option<Py<PyType>>,
+    pub(crate) kind: PyTreeKind,
+    pub(crate) node_type: Py<PyType>,
+    pub(crate) flatten_func: Option<Py<PyAny>>,
+    pub(crate) unflatten_func: Option<Py<PyAny>>,
+    pub(crate) path_entry_type: Option<Py<PyType>>,
+    pub(crate) kind_hint: Option<KindHint>,
+    pub(crate) fields: Option<NodeFields>,
+}
+
+// The fields of a dataclass-like type, read and passed to the constructor directly
+pub struct NodeFields {
+    // The interned attribute names of the children
+    pub(crate) data: Py<PyTuple>,
+    // The argument names of the constructor for the children, in the same order
+    pub(crate) init: Py<PyTuple>,
+    // The interned attribute names carried as the node data, also passed to the constructor
+    pub(crate) meta: Py<PyTuple>,
+    // The constructor taking the children as keyword arguments, or the type itself if not given
+    pub(crate) init_func: Option<Py<PyAny>>,
+}
+
+impl NodeFields {
+    pub fn new(
+        py: Python<'_>,
+        data: &Bound<'_, PyAny>,
+        init: Option<&Bound<'_, PyAny>>,
+        meta: Option<&Bound<'_, PyAny>>,
+        init_func: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<Self> {
+        if let Some(init_func) = init_func
+            && !init_func.is_callable()
+        {
+            return Err(PyTypeError::new_err("'init_func' must be callable"));
+        }
+        let intern = |names: &Bound<'_, PyAny>| {
+            let names = names
+                .try_iter()?
+                .map(|name| {
+                    Ok(PyString::intern(
+                        py,
+                        name?.downcast::<PyString>()?.to_str()?,
+                    ))
+                })
+                .collect::<PyResult<Vec<_>>>()?;
+            PyTuple::new(py, names)
+        };
+        let data = intern(data)?;
+        let init = match init {
+            Some(init) => intern(init)?,
+            None => data.clone(),
+        };
+        if init.len() != data.len() {
+            return Err(PyValueError::new_err(std::format!(
+                "Expected {} constructor argument names for the data fields, got {}.",
+                data.len(),
+                init.len(),
+            )));
+        }
+        let meta = match meta {
+            Some(meta) => intern(meta)?,
+            None => PyTuple::empty(py),
+        };
+        Ok(NodeFields {
+            data: data.unbind(),
+            init: init.unbind(),
+            meta: meta.unbind(),
+            init_func: init_func.map(|init_func| init_func.clone().unbind()),
+        })
+    }
+
+    fn clone_ref(&self, py: Python<'_>) -> Self {
+        NodeFields {
+            data: self.data.clone_ref(py),
+            init: self.init.clone_ref(py),
+            meta: self.meta.clone_ref(py),
+            init_func: self
+                .init_func
+                .as_ref()
+                .map(|init_func| init_func.clone_ref(py)),
+        }
+    }
 }
 
 pub struct PyTreeTypeRegistry {
-    registrations: HashMap<IdHashedPy<PyType>, PyTreeTypeRegistration>,
-    named_registrations: HashMap<(String, IdHashedPy<PyType>), PyTreeTypeRegistration>,
+    registrations: HashMap<IdHashedPy<PyType>, Arc<PyTreeTypeRegistration>>,
+    named_registrations: HashMap<(String, IdHashedPy<PyType>), Arc<PyTreeTypeRegistration>>,
+    // The sorted namespaces of each type in the named registrations for the wildcard lookups
+    named_namespaces: HashMap<IdHashedPy<PyType>, Vec<String>>,
     builtin_types: HashSet<IdHashedPy<PyType>>,
 }
 
@@ -92,6 +293,7 @@ impl PyTreeTypeRegistry {
                 let mut singleton = PyTreeTypeRegistry {
                     registrations: HashMap::new(),
                     named_registrations: HashMap::new(),
+                    named_namespaces: HashMap::new(),
                     builtin_types: HashSet::new(),
                 };
                 let collections = py.import("collections").unwrap();
@@ -106,17 +308,21 @@ impl PyTreeTypeRegistry {
                     singleton
                         .registrations
                         .entry(node_type.clone_ref(py).into())
-                        .or_insert(PyTreeTypeRegistration {
-                            kind,
-                            node_type: node_type.clone_ref(py),
-                            flatten_func: None,
-                            unflatten_func: None,
-                            path_entry_type: None,
+                        .or_insert_with(|| {
+                            Arc::new(PyTreeTypeRegistration {
+                                kind,
+                                node_type: node_type.clone_ref(py),
+                                flatten_func: None,
+                                unflatten_func: None,
+                                path_entry_type: None,
+                                kind_hint: None,
+                                fields: None,
+                            })
                         });
                 };
 
-                if none_is_leaf {
-                    register(py.get_type::<PyNone>().unbind(), PyTreeKind::Leaf);
+                if !none_is_leaf {
+                    register(py.get_type::<PyNone>().unbind(), PyTreeKind::None);
                 }
                 register(py.get_type::<PyTuple>().unbind(), PyTreeKind::Tuple);
                 register(py.get_type::<PyList>().unbind(), PyTreeKind::List);
@@ -154,21 +360,72 @@ impl PyTreeTypeRegistry {
         Self::new(py, none_is_leaf)
     }
 
+    fn index_named(&mut self, py: Python<'_>) {
+        self.named_namespaces.clear();
+        for (namespace, key) in self.named_registrations.keys() {
+            self.named_namespaces
+                .entry(IdHashedPy(key.0.clone_ref(py)))
+                .or_default()
+                .push(namespace.clone());
+        }
+        for namespaces in self.named_namespaces.values_mut() {
+            namespaces.sort();
+        }
+    }
+
+    fn lookup_wildcard(
+        &'static self,
+        cls: &Bound<'_, PyType>,
+    ) -> PyResult<Option<Arc<PyTreeTypeRegistration>>> {
+        let key = IdHashedPy(cls.clone().unbind());
+        // The built-in types always flatten as in the global namespace, an override in any
+        // named namespace does not leak into the wildcard lookups
+        let namespaces = match self.named_namespaces.get(&key) {
+            Some(namespaces) if !self.builtin_types.contains(&key) => namespaces.as_slice(),
+            _ => &[],
+        };
+        match namespaces {
+            [] => Ok(self.registrations.get(&key).map(Arc::clone)),
+            [namespace] => Ok(self
+                .named_registrations
+                .get(&(namespace.clone(), key))
+                .map(Arc::clone)),
+            _ => {
+                let namespaces = namespaces
+                    .iter()
+                    .map(|namespace| Ok(PyString::new(cls.py(), namespace).repr()?.to_string()))
+                    .collect::<PyResult<Vec<_>>>()?;
+                Err(PyValueError::new_err(std::format!(
+                    "PyTree type {} is registered in multiple namespaces ({}), \
+                    cannot resolve it with the wildcard namespace {}.",
+                    cls.repr()?,
+                    namespaces.join(", "),
+                    PyString::new(cls.py(), WILDCARD_NAMESPACE).repr()?,
+                )))
+            }
+        }
+    }
+
     #[inline]
     fn lookup_impl(
         &'static self,
         cls: &Bound<'_, PyType>,
         namespace: &str,
-    ) -> Option<&'static PyTreeTypeRegistration> {
-        if !namespace.is_empty() {
-            if let Some(registration) = self
+    ) -> PyResult<Option<Arc<PyTreeTypeRegistration>>> {
+        if namespace == WILDCARD_NAMESPACE {
+            return self.lookup_wildcard(cls);
+        }
+        if !namespace.is_empty()
+            && let Some(registration) = self
                 .named_registrations
                 .get(&(String::from(namespace), cls.clone().unbind().into()))
-            {
-                return Some(registration);
-            }
+        {
+            return Ok(Some(Arc::clone(registration)));
         }
-        self.registrations.get(&cls.clone().unbind().into())
+        Ok(self
+            .registrations
+            .get(&cls.clone().unbind().into())
+            .map(Arc::clone))
     }
 
     #[inline]
@@ -176,11 +433,74 @@ impl PyTreeTypeRegistry {
         cls: &Bound<'_, PyType>,
         none_is_leaf: Option<bool>,
         namespace: Option<&str>,
-    ) -> Option<&'static PyTreeTypeRegistration> {
+    ) -> PyResult<Option<Arc<PyTreeTypeRegistration>>> {
         PyTreeTypeRegistry::get_singleton(cls.py(), none_is_leaf.unwrap_or(false))
             .lookup_impl(cls, namespace.unwrap_or(""))
     }
 
+    pub fn get_kind(
+        obj: &Bound<'_, PyAny>,
+        none_is_leaf: bool,
+        namespace: &str,
+    ) -> PyResult<(PyTreeKind, Option<Arc<PyTreeTypeRegistration>>)> {
+        timed(Phase::Lookup, || {
+            PyTreeTypeRegistry::get_type_kind(&obj.get_type(), none_is_leaf, namespace)
+        })
+    }
+
+    pub fn get_type_kind(
+        cls: &Bound<'_, PyType>,
+        none_is_leaf: bool,
+        namespace: &str,
+    ) -> PyResult<(PyTreeKind, Option<Arc<PyTreeTypeRegistration>>)> {
+        record_lookup();
+        if let Some(registration) =
+            PyTreeTypeRegistry::lookup(cls, Some(none_is_leaf), Some(namespace))?
+        {
+            debug_resolution(cls, namespace, none_is_leaf, || {
+                let py = cls.py();
+                let source = match namespace.is_empty()
+                    || PyTreeTypeRegistry::lookup(cls, Some(none_is_leaf), None)?
+                        .is_some_and(|global| Arc::ptr_eq(&global, &registration))
+                {
+                    true => namespace_repr(py, "")?,
+                    false => namespace_repr(py, namespace)?,
+                };
+                Ok(std::format!(
+                    "PyTree type {} with {} (none_is_leaf={}) resolved to {} from {}.",
+                    cls.repr()?,
+                    namespace_repr(py, namespace)?,
+                    none_is_leaf,
+                    registration.kind.name(),
+                    source,
+                ))
+            })?;
+            return Ok(match registration.kind {
+                PyTreeKind::Custom => (PyTreeKind::Custom, Some(registration)),
+                kind => (kind, None),
+            });
+        }
+        let kind = if is_structseq_class(cls)? {
+            PyTreeKind::StructSequence
+        } else if is_namedtuple_class(cls)? {
+            PyTreeKind::NamedTuple
+        } else {
+            record_leaf_lookup(cls);
+            PyTreeKind::Leaf
+        };
+        debug_resolution(cls, namespace, none_is_leaf, || {
+            Ok(std::format!(
+                "PyTree type {} with {} (none_is_leaf={}) is not registered, resolved to {}.",
+                cls.repr()?,
+                namespace_repr(cls.py(), namespace)?,
+                none_is_leaf,
+                kind.name(),
+            ))
+        })?;
+        Ok((kind, None))
+    }
+
+    #[allow(clippy::too_many_arguments)]
     fn register_impl<'py>(
         &'static mut self,
         cls: &Bound<'py, PyType>,
@@ -188,12 +508,16 @@ impl PyTreeTypeRegistry {
         unflatten_func: &Bound<'py, PyAny>,
         path_entry_type: &Bound<'py, PyType>,
         namespace: &str,
+        kind_hint: Option<KindHint>,
+        fields: Option<&NodeFields>,
     ) -> PyResult<()> {
         let py = cls.py();
         let key = IdHashedPy(cls.clone().unbind());
-        if self.builtin_types.contains(&key) {
+        // The built-in types can only be overridden in the named namespaces
+        if namespace.is_empty() && self.builtin_types.contains(&key) {
             return Err(PyValueError::new_err(std::format!(
-                "PyTree type {} is a built-in type and cannot be re-registered.",
+                "PyTree type {} is a built-in type and cannot be re-registered \
+                in the global namespace.",
                 cls.repr()?.to_cow().unwrap().as_ref()
             )));
         }
@@ -206,16 +530,19 @@ impl PyTreeTypeRegistry {
                     )));
                 }
                 HashMapEntry::Vacant(entry) => {
-                    entry.insert(PyTreeTypeRegistration {
+                    entry.insert(Arc::new(PyTreeTypeRegistration {
                         kind: PyTreeKind::Custom,
                         node_type: cls.clone().unbind(),
                         flatten_func: Some(flatten_func.clone().unbind()),
                         unflatten_func: Some(unflatten_func.clone().unbind()),
                         path_entry_type: Some(path_entry_type.clone().unbind()),
-                    });
+                        kind_hint,
+                        fields: fields.map(|fields| fields.clone_ref(py)),
+                    }));
                 }
             };
             if is_structseq_class(cls)? {
+                record_warning();
                 PyErr::warn(
                     py,
                     &py.get_type::<pyo3::exceptions::PyUserWarning>(),
@@ -228,6 +555,7 @@ impl PyTreeTypeRegistry {
                     2,
                 )?;
             } else if is_namedtuple_class(cls)? {
+                record_warning();
                 PyErr::warn(
                     py,
                     &py.get_type::<pyo3::exceptions::PyUserWarning>(),
@@ -241,7 +569,7 @@ impl PyTreeTypeRegistry {
                 )?;
             }
         } else {
-            let named_key = (String::from(namespace), key);
+            let named_key = (String::from(namespace), IdHashedPy(key.0.clone_ref(py)));
             match self.named_registrations.entry(named_key) {
                 HashMapEntry::Occupied(_) => {
                     return Err(PyValueError::new_err(std::format!(
@@ -255,16 +583,22 @@ impl PyTreeTypeRegistry {
                     )));
                 }
                 HashMapEntry::Vacant(entry) => {
-                    entry.insert(PyTreeTypeRegistration {
+                    entry.insert(Arc::new(PyTreeTypeRegistration {
                         kind: PyTreeKind::Custom,
                         node_type: cls.clone().unbind(),
                         flatten_func: Some(flatten_func.clone().unbind()),
                         unflatten_func: Some(unflatten_func.clone().unbind()),
                         path_entry_type: Some(path_entry_type.clone().unbind()),
-                    });
+                        kind_hint,
+                        fields: fields.map(|fields| fields.clone_ref(py)),
+                    }));
                 }
             };
+            let namespaces = self.named_namespaces.entry(key).or_default();
+            let index = namespaces.partition_point(|name| name.as_str() < namespace);
+            namespaces.insert(index, String::from(namespace));
             if is_structseq_class(cls)? {
+                record_warning();
                 PyErr::warn(
                     py,
                     &py.get_type::<pyo3::exceptions::PyUserWarning>(),
@@ -282,6 +616,7 @@ impl PyTreeTypeRegistry {
                     2,
                 )?;
             } else if is_namedtuple_class(cls)? {
+                record_warning();
                 PyErr::warn(
                     py,
                     &py.get_type::<pyo3::exceptions::PyUserWarning>(),
@@ -303,6 +638,104 @@ impl PyTreeTypeRegistry {
         Ok(())
     }
 
+    // The custom node types registered in the namespace or in the global namespace, where the
+    // wildcard namespace includes the registrations in all namespaces
+    pub(crate) fn custom_types<'py>(
+        py: Python<'py>,
+        none_is_leaf: bool,
+        namespace: &str,
+    ) -> Vec<Bound<'py, PyType>> {
+        let registry = PyTreeTypeRegistry::get_singleton(py, none_is_leaf);
+        let global = registry
+            .registrations
+            .values()
+            .filter(|registration| registration.kind == PyTreeKind::Custom);
+        let named = registry
+            .named_registrations
+            .iter()
+            .filter(|((name, _), _)| namespace == WILDCARD_NAMESPACE || name == namespace)
+            .map(|(_, registration)| registration);
+        global
+            .chain(named)
+            .map(|registration| registration.node_type.bind(py).clone())
+            .collect()
+    }
+
+    // The registered types visible in the namespace, where the wildcard namespace lists the
+    // registrations in all namespaces
+    pub(crate) fn registered_types<'py>(
+        py: Python<'py>,
+        namespace: &str,
+    ) -> PyResult<Vec<RegisteredType<'py>>> {
+        let registry = PyTreeTypeRegistry::get_singleton(py, false);
+        let named = registry
+            .named_registrations
+            .iter()
+            .filter(|((name, _), _)| {
+                namespace == WILDCARD_NAMESPACE || (!namespace.is_empty() && name == namespace)
+            })
+            .map(|((name, _), registration)| (name.as_str(), registration));
+        let mut types = registry
+            .registrations
+            .iter()
+            .filter(|(key, _)| {
+                // The global registrations overridden in the namespace are not visible
+                namespace.is_empty()
+                    || namespace == WILDCARD_NAMESPACE
+                    || !registry
+                        .named_registrations
+                        .contains_key(&(String::from(namespace), IdHashedPy(key.0.clone_ref(py))))
+            })
+            .map(|(_, registration)| ("", registration))
+            .chain(named)
+            .map(|(name, registration)| {
+                let node_type = registration.node_type.bind(py).clone();
+                let builtin = name.is_empty()
+                    && registry
+                        .builtin_types
+                        .contains(&IdHashedPy(node_type.clone().unbind()));
+                let qualname = node_type.fully_qualified_name()?.to_string();
+                Ok((
+                    (String::from(name), !builtin, qualname),
+                    (node_type, registration.kind, builtin, String::from(name)),
+                ))
+            })
+            .collect::<PyResult<Vec<_>>>()?;
+        // Sort by namespace with the built-in types first, then by the qualified name
+        types.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(types.into_iter().map(|(_, entry)| entry).collect())
+    }
+
+    pub(crate) fn is_registered(cls: &Bound<'_, PyType>, namespace: &str) -> bool {
+        let registry = PyTreeTypeRegistry::get_singleton(cls.py(), false);
+        let key = IdHashedPy(cls.clone().unbind());
+        if registry.registrations.contains_key(&key) {
+            return true;
+        }
+        match namespace {
+            "" => false,
+            WILDCARD_NAMESPACE => registry.named_namespaces.contains_key(&key),
+            _ => registry
+                .named_registrations
+                .contains_key(&(String::from(namespace), key)),
+        }
+    }
+
+    pub(crate) fn registration_counts(py: Python<'_>) -> HashMap<String, usize> {
+        let registry = PyTreeTypeRegistry::get_singleton(py, false);
+        let mut counts = HashMap::new();
+        let num_global = registry
+            .registrations
+            .values()
+            .filter(|registration| registration.kind == PyTreeKind::Custom)
+            .count();
+        counts.insert(String::new(), num_global);
+        for (namespace, _) in registry.named_registrations.keys() {
+            *counts.entry(namespace.clone()).or_insert(0) += 1;
+        }
+        counts
+    }
+
     #[inline]
     pub fn register<'py>(
         cls: &Bound<'py, PyType>,
@@ -310,6 +743,8 @@ impl PyTreeTypeRegistry {
         unflatten_func: &Bound<'py, PyAny>,
         path_entry_type: &Bound<'py, PyType>,
         namespace: Option<&str>,
+        kind_hint: Option<&str>,
+        fields: Option<NodeFields>,
     ) -> PyResult<()> {
         if !flatten_func.is_callable() {
             return Err(PyTypeError::new_err("'flatten_func' must be callable"));
@@ -319,12 +754,23 @@ impl PyTreeTypeRegistry {
         }
 
         let namespace = namespace.unwrap_or("");
+        if namespace == WILDCARD_NAMESPACE {
+            return Err(PyValueError::new_err(std::format!(
+                "The namespace {} is reserved for the wildcard lookup and cannot be registered.",
+                PyString::new(cls.py(), namespace).repr()?,
+            )));
+        }
+        let kind_hint = kind_hint
+            .map(|kind_hint| KindHint::parse(cls.py(), kind_hint))
+            .transpose()?;
         PyTreeTypeRegistry::get_singleton(cls.py(), false).register_impl(
             cls,
             flatten_func,
             unflatten_func,
             path_entry_type,
             namespace,
+            kind_hint,
+            fields.as_ref(),
         )?;
         PyTreeTypeRegistry::get_singleton(cls.py(), true).register_impl(
             cls,
@@ -332,8 +778,17 @@ impl PyTreeTypeRegistry {
             unflatten_func,
             path_entry_type,
             namespace,
+            kind_hint,
+            fields.as_ref(),
         )?;
-        Ok(())
+        debug(cls.py(), "register", || {
+            Ok(std::format!(
+                "PyTree type {} registered in {} with path entry type {}.",
+                cls.repr()?,
+                namespace_repr(cls.py(), namespace)?,
+                path_entry_type.repr()?,
+            ))
+        })
     }
 
     fn unregister_impl(
@@ -343,9 +798,10 @@ impl PyTreeTypeRegistry {
     ) -> PyResult<()> {
         let py = cls.py();
         let key = IdHashedPy(cls.clone().unbind());
-        if self.builtin_types.contains(&key) {
+        if namespace.is_empty() && self.builtin_types.contains(&key) {
             return Err(PyValueError::new_err(std::format!(
-                "PyTree type {} is a built-in type and cannot be unregistered.",
+                "PyTree type {} is a built-in type and cannot be unregistered \
+                from the global namespace.",
                 cls.repr()?.to_cow().unwrap().as_ref()
             )));
         }
@@ -371,8 +827,14 @@ impl PyTreeTypeRegistry {
                 return Err(PyValueError::new_err(message));
             }
         } else {
-            let named_key = (String::from(namespace), key);
+            let named_key = (String::from(namespace), IdHashedPy(key.0.clone_ref(py)));
             let registration = self.named_registrations.remove(&named_key);
+            if let Some(namespaces) = self.named_namespaces.get_mut(&key) {
+                namespaces.retain(|name| name != namespace);
+                if namespaces.is_empty() {
+                    self.named_namespaces.remove(&key);
+                }
+            }
             if registration.is_none() {
                 let mut message = String::new();
                 message.push_str("PyTree type ");
@@ -409,7 +871,15 @@ impl PyTreeTypeRegistry {
         let namespace = namespace.unwrap_or("");
         PyTreeTypeRegistry::get_singleton(cls.py(), false).unregister_impl(cls, namespace)?;
         PyTreeTypeRegistry::get_singleton(cls.py(), true).unregister_impl(cls, namespace)?;
-        Ok(())
+        // The cached treespecs may refer to the unregistered type
+        treespec_cache_clear();
+        debug(cls.py(), "unregister", || {
+            Ok(std::format!(
+                "PyTree type {} unregistered from {}.",
+                cls.repr()?,
+                namespace_repr(cls.py(), namespace)?,
+            ))
+        })
     }
 
     #[inline]
@@ -449,35 +919,205 @@ impl PyTreeTypeRegistry {
     }
 }
 
+impl PyTreeTypeRegistry {
+    // The leaf predicate given by the caller, or the default leaf predicate of the namespace
+    pub(crate) fn leaf_predicate<'py>(
+        py: Python<'py>,
+        leaf_predicate: Option<&Bound<'py, PyAny>>,
+        namespace: &str,
+    ) -> Option<Bound<'py, PyAny>> {
+        if let Some(leaf_predicate) = leaf_predicate {
+            return Some(leaf_predicate.clone());
+        }
+        PyTreeTypeRegistry::default_leaf_predicate(py, namespace)
+    }
+
+    #[inline]
+    pub(crate) fn default_leaf_predicate<'py>(
+        py: Python<'py>,
+        namespace: &str,
+    ) -> Option<Bound<'py, PyAny>> {
+        if namespace.is_empty() {
+            return None;
+        }
+        LEAF_PREDICATES
+            .lock()
+            .unwrap()
+            .as_ref()?
+            .get(namespace)
+            .map(|leaf_predicate| leaf_predicate.bind(py).clone())
+    }
+}
+
+// The registrations of the custom node types in both registries, the built-in types are fixed
+#[pyclass(frozen, module = "rustree")]
+pub struct RegistrySnapshot {
+    registries: Vec<RegistrySnapshotEntry>,
+}
+
+// A registered type as (type, kind, builtin, namespace)
+type RegisteredType<'py> = (Bound<'py, PyType>, PyTreeKind, bool, String);
+
+type RegistrySnapshotEntry = (
+    HashMap<IdHashedPy<PyType>, Arc<PyTreeTypeRegistration>>,
+    HashMap<(String, IdHashedPy<PyType>), Arc<PyTreeTypeRegistration>>,
+);
+
+#[pymethods]
+impl RegistrySnapshot {
+    fn __len__(&self) -> usize {
+        let (registrations, named_registrations) = &self.registries[0];
+        registrations
+            .values()
+            .filter(|registration| registration.kind == PyTreeKind::Custom)
+            .count()
+            + named_registrations.len()
+    }
+
+    fn __repr__(&self) -> String {
+        std::format!("RegistrySnapshot(num_registrations={})", self.__len__())
+    }
+}
+
+fn clone_registrations(
+    py: Python<'_>,
+    registrations: &HashMap<IdHashedPy<PyType>, Arc<PyTreeTypeRegistration>>,
+    named_registrations: &HashMap<(String, IdHashedPy<PyType>), Arc<PyTreeTypeRegistration>>,
+) -> RegistrySnapshotEntry {
+    let registrations = registrations
+        .iter()
+        .map(|(key, registration)| (IdHashedPy(key.0.clone_ref(py)), registration.clone()))
+        .collect();
+    let named_registrations = named_registrations
+        .iter()
+        .map(|((namespace, key), registration)| {
+            (
+                (namespace.clone(), IdHashedPy(key.0.clone_ref(py))),
+                registration.clone(),
+            )
+        })
+        .collect();
+    (registrations, named_registrations)
+}
+
+impl PyTreeTypeRegistry {
+    fn restore_impl(&'static mut self, py: Python<'_>, snapshot: &RegistrySnapshotEntry) {
+        let (registrations, named_registrations) =
+            clone_registrations(py, &snapshot.0, &snapshot.1);
+        // Release the replaced registrations after the registry is consistent again, the
+        // destructors of the flatten and unflatten functions may run arbitrary Python code
+        let replaced = (
+            std::mem::replace(&mut self.registrations, registrations),
+            std::mem::replace(&mut self.named_registrations, named_registrations),
+        );
+        self.index_named(py);
+        drop(replaced);
+    }
+
+    pub fn snapshot(py: Python<'_>) -> RegistrySnapshot {
+        RegistrySnapshot {
+            registries: [false, true]
+                .into_iter()
+                .map(|none_is_leaf| {
+                    let registry = PyTreeTypeRegistry::get_singleton(py, none_is_leaf);
+                    clone_registrations(py, &registry.registrations, &registry.named_registrations)
+                })
+                .collect(),
+        }
+    }
+
+    pub fn restore(py: Python<'_>, snapshot: &RegistrySnapshot) -> PyResult<()> {
+        PyTreeTypeRegistry::get_singleton(py, false).restore_impl(py, &snapshot.registries[0]);
+        PyTreeTypeRegistry::get_singleton(py, true).restore_impl(py, &snapshot.registries[1]);
+        treespec_cache_clear();
+        debug(py, "restore", || {
+            Ok(std::format!(
+                "PyTree type registry restored to {} registrations.",
+                snapshot.__len__(),
+            ))
+        })
+    }
+}
+
 impl Drop for PyTreeTypeRegistry {
     fn drop(&mut self) {
         Python::attach(|_py| {
             self.registrations.clear();
             self.named_registrations.clear();
+            self.named_namespaces.clear();
             self.builtin_types.clear();
         })
     }
 }
 
 #[pyfunction]
-#[pyo3(signature = (cls, /, flatten_func, unflatten_func, path_entry_type, namespace=""))]
+#[pyo3(
+    signature = (cls, /, flatten_func, unflatten_func, path_entry_type, namespace="", *, kind_hint=None, data_fields=None, init_args=None, meta_fields=None, init_func=None)
+)]
 #[inline]
+#[allow(clippy::too_many_arguments)]
 pub fn register_node<'py>(
     cls: &Bound<'py, PyType>,
     flatten_func: &Bound<'py, PyAny>,
     unflatten_func: &Bound<'py, PyAny>,
     path_entry_type: &Bound<'py, PyType>,
     namespace: Option<&str>,
+    kind_hint: Option<&str>,
+    data_fields: Option<&Bound<'py, PyAny>>,
+    init_args: Option<&Bound<'py, PyAny>>,
+    meta_fields: Option<&Bound<'py, PyAny>>,
+    init_func: Option<&Bound<'py, PyAny>>,
 ) -> PyResult<()> {
+    let fields = data_fields
+        .map(|data_fields| {
+            NodeFields::new(cls.py(), data_fields, init_args, meta_fields, init_func)
+        })
+        .transpose()?;
     PyTreeTypeRegistry::register(
         cls,
         flatten_func,
         unflatten_func,
         path_entry_type,
         namespace,
+        kind_hint,
+        fields,
     )
 }
 
+// Register a class implementing the `tree_flatten` / `tree_unflatten` protocol, and return the
+// synthesized flatten and unflatten functions
+#[pyfunction]
+#[pyo3(signature = (cls, /, path_entry_type, namespace="", *, kind_hint=None))]
+pub fn register_node_class<'py>(
+    cls: &Bound<'py, PyType>,
+    path_entry_type: &Bound<'py, PyType>,
+    namespace: Option<&str>,
+    kind_hint: Option<&str>,
+) -> PyResult<(Bound<'py, PyAny>, Bound<'py, PyAny>)> {
+    let method = |name: &str| match cls.getattr(name) {
+        Ok(method) if method.is_callable() => Ok(method),
+        _ => Err(PyTypeError::new_err(std::format!(
+            "Expected a class with a callable `{}` attribute, got {}.",
+            name,
+            cls.repr()?,
+        ))),
+    };
+    // The function `cls.tree_flatten` takes the instance as the first argument, which saves a
+    // method lookup per node, and `cls.tree_unflatten` is already bound to the class
+    let flatten_func = method("tree_flatten")?;
+    let unflatten_func = method("tree_unflatten")?;
+    PyTreeTypeRegistry::register(
+        cls,
+        &flatten_func,
+        &unflatten_func,
+        path_entry_type,
+        namespace,
+        kind_hint,
+        None,
+    )?;
+    Ok((flatten_func, unflatten_func))
+}
+
 #[pyfunction]
 #[pyo3(signature = (cls, /, namespace=""))]
 #[inline]
@@ -498,6 +1138,92 @@ pub fn is_dict_insertion_ordered(
 #[pyfunction]
 #[pyo3(signature = (mode, /, namespace=""))]
 #[inline]
-pub fn set_dict_insertion_ordered(mode: bool, namespace: Option<&str>) {
-    PyTreeTypeRegistry::set_dict_insertion_ordered(mode, namespace)
+pub fn set_dict_insertion_ordered(
+    py: Python<'_>,
+    mode: bool,
+    namespace: Option<&str>,
+) -> PyResult<()> {
+    PyTreeTypeRegistry::set_dict_insertion_ordered(mode, namespace);
+    debug(py, "dict_insertion_ordered", || {
+        Ok(std::format!(
+            "Dictionary insertion order mode set to {} in {}.",
+            mode,
+            namespace_repr(py, namespace.unwrap_or(""))?,
+        ))
+    })
+}
+
+#[pyfunction]
+#[pyo3(signature = (leaf_predicate, /, namespace))]
+pub fn set_leaf_predicate<'py>(
+    py: Python<'py>,
+    leaf_predicate: Option<&Bound<'py, PyAny>>,
+    namespace: &str,
+) -> PyResult<Option<Bound<'py, PyAny>>> {
+    if namespace.is_empty() || namespace == WILDCARD_NAMESPACE {
+        return Err(PyValueError::new_err(std::format!(
+            "The default leaf predicate can only be set for a named namespace, got {}.",
+            PyString::new(py, namespace).repr()?,
+        )));
+    }
+    if let Some(leaf_predicate) = leaf_predicate
+        && !leaf_predicate.is_callable()
+    {
+        return Err(PyTypeError::new_err(std::format!(
+            "Expected a callable leaf predicate, got {}.",
+            leaf_predicate.repr()?,
+        )));
+    }
+    let previous = {
+        let mut leaf_predicates = LEAF_PREDICATES.lock().unwrap();
+        let leaf_predicates = leaf_predicates.get_or_insert_with(HashMap::new);
+        match leaf_predicate {
+            Some(leaf_predicate) => {
+                leaf_predicates.insert(String::from(namespace), leaf_predicate.clone().unbind())
+            }
+            None => leaf_predicates.remove(namespace),
+        }
+    };
+    debug(py, "leaf_predicate", || {
+        Ok(std::format!(
+            "Default leaf predicate set to {} in {}.",
+            match leaf_predicate {
+                Some(leaf_predicate) => leaf_predicate.repr()?.to_string(),
+                None => String::from("None"),
+            },
+            namespace_repr(py, namespace)?,
+        ))
+    })?;
+    Ok(previous.map(|previous| previous.into_bound(py)))
+}
+
+#[pyfunction]
+#[pyo3(signature = (namespace=""))]
+pub fn registered_types<'py>(
+    py: Python<'py>,
+    namespace: &str,
+) -> PyResult<Vec<RegisteredType<'py>>> {
+    PyTreeTypeRegistry::registered_types(py, namespace)
+}
+
+#[pyfunction]
+#[pyo3(signature = (cls, /, namespace=""))]
+pub fn is_registered(cls: &Bound<'_, PyType>, namespace: &str) -> bool {
+    PyTreeTypeRegistry::is_registered(cls, namespace)
+}
+
+#[pyfunction]
+pub fn registry_snapshot(py: Python<'_>) -> RegistrySnapshot {
+    PyTreeTypeRegistry::snapshot(py)
+}
+
+#[pyfunction]
+pub fn registry_restore(snapshot: &Bound<'_, RegistrySnapshot>) -> PyResult<()> {
+    PyTreeTypeRegistry::restore(snapshot.py(), snapshot.get())
+}
+
+#[pyfunction]
+#[pyo3(signature = (namespace=""))]
+pub fn get_leaf_predicate<'py>(py: Python<'py>, namespace: &str) -> Option<Bound<'py, PyAny>> {
+    PyTreeTypeRegistry::default_leaf_predicate(py, namespace)
 }
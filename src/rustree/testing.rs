@@ -0,0 +1,275 @@
+// Copyright 2024-2025 Xuehai Pan. All Rights Reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+// =============================================================================
+
+use pyo3::exceptions::{PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::*;
+
+use crate::rustree::registry::{PyTreeKind, PyTreeTypeRegistry};
+use crate::rustree::treespec::{DictOrder, MAX_RECURSION_DEPTH, Node, get_children, make_node};
+
+const BUILTIN_KINDS: [PyTreeKind; 8] = [
+    PyTreeKind::Leaf,
+    PyTreeKind::None,
+    PyTreeKind::Tuple,
+    PyTreeKind::List,
+    PyTreeKind::Dict,
+    PyTreeKind::OrderedDict,
+    PyTreeKind::DefaultDict,
+    PyTreeKind::Deque,
+];
+
+enum Choice {
+    Kind(PyTreeKind),
+    // Nodes with the same type and metadata as the prototype, but with random children
+    Prototype(Node),
+}
+
+struct Generator<'a, 'py> {
+    rng: &'a Bound<'py, PyAny>,
+    max_depth: usize,
+    max_children: usize,
+    leaf_factory: Option<&'a Bound<'py, PyAny>>,
+    choices: Vec<(Choice, f64)>,
+    total_weight: f64,
+}
+
+impl<'a, 'py> Generator<'a, 'py> {
+    fn random(&self) -> PyResult<f64> {
+        self.rng.call_method0("random")?.extract()
+    }
+
+    fn randrange(&self, stop: usize) -> PyResult<usize> {
+        self.rng.call_method1("randrange", (stop,))?.extract()
+    }
+
+    fn leaf(&self) -> PyResult<Bound<'py, PyAny>> {
+        match self.leaf_factory {
+            Some(leaf_factory) => leaf_factory.call1((self.rng,)),
+            None => self.rng.call_method0("random"),
+        }
+    }
+
+    fn choose(&self) -> PyResult<&Choice> {
+        let mut threshold = self.random()? * self.total_weight;
+        for (choice, weight) in self.choices.iter() {
+            if threshold < *weight {
+                return Ok(choice);
+            }
+            threshold -= weight;
+        }
+        Ok(&self.choices.last().unwrap().0)
+    }
+
+    fn children(&self, arity: usize, depth: usize) -> PyResult<Vec<Bound<'py, PyAny>>> {
+        (0..arity).map(|_| self.generate(depth + 1)).collect()
+    }
+
+    fn generate(&self, depth: usize) -> PyResult<Bound<'py, PyAny>> {
+        let py = self.rng.py();
+        if depth >= self.max_depth {
+            return self.leaf();
+        }
+
+        let (node, children) = match self.choose()? {
+            Choice::Prototype(node) => (
+                Node {
+                    kind: node.kind,
+                    arity: node.arity,
+                    node_data: node.node_data.as_ref().map(|data| data.clone_ref(py)),
+                    custom: node.custom.clone(),
+                    num_leaves: 0,
+                    entries: None,
+                    reference: None,
+                },
+                self.children(node.arity, depth)?,
+            ),
+            Choice::Kind(PyTreeKind::Leaf) => return self.leaf(),
+            Choice::Kind(kind) => {
+                let kind = *kind;
+                let arity = match kind {
+                    PyTreeKind::None => 0,
+                    _ => self.randrange(self.max_children + 1)?,
+                };
+                let node_data = match kind {
+                    PyTreeKind::Dict | PyTreeKind::OrderedDict | PyTreeKind::DefaultDict => {
+                        let indices = self.rng.call_method1(
+                            "sample",
+                            (
+                                PyRange::new(py, 0, (4 * self.max_children) as isize)?,
+                                arity,
+                            ),
+                        )?;
+                        let keys = indices
+                            .try_iter()?
+                            .map(|index| Ok(std::format!("k{}", index?)))
+                            .collect::<PyResult<Vec<_>>>()?;
+                        let keys = PyList::new(py, keys)?.into_any();
+                        match kind {
+                            PyTreeKind::DefaultDict => Some(
+                                PyTuple::new(py, [py.get_type::<PyList>().into_any(), keys])?
+                                    .into_any()
+                                    .unbind(),
+                            ),
+                            _ => Some(keys.unbind()),
+                        }
+                    }
+                    PyTreeKind::Deque => Some(py.None()),
+                    _ => None,
+                };
+                (
+                    Node {
+                        kind,
+                        arity,
+                        node_data,
+                        custom: None,
+                        num_leaves: 0,
+                        entries: None,
+                        reference: None,
+                    },
+                    self.children(arity, depth)?,
+                )
+            }
+        };
+        make_node(py, &node, children)
+    }
+}
+
+#[pyfunction]
+#[pyo3(signature = (
+    rng,
+    /,
+    max_depth=3,
+    container_weights=None,
+    leaf_factory=None,
+    *,
+    prototypes=None,
+    max_children=4,
+    none_is_leaf=false,
+    namespace="",
+))]
+#[allow(clippy::too_many_arguments)]
+pub fn random_tree<'py>(
+    rng: &Bound<'py, PyAny>,
+    max_depth: usize,
+    container_weights: Option<&Bound<'py, PyAny>>,
+    leaf_factory: Option<&Bound<'py, PyAny>>,
+    prototypes: Option<&Bound<'py, PyAny>>,
+    max_children: usize,
+    none_is_leaf: bool,
+    namespace: &str,
+) -> PyResult<Bound<'py, PyAny>> {
+    if max_depth >= MAX_RECURSION_DEPTH {
+        return Err(PyValueError::new_err(std::format!(
+            "Maximum depth should be less than {}, got {}.",
+            MAX_RECURSION_DEPTH,
+            max_depth,
+        )));
+    }
+
+    let mut choices: Vec<(Choice, f64)> = Vec::new();
+    let mut prototype_types: Vec<Bound<'py, PyType>> = Vec::new();
+    if let Some(prototypes) = prototypes {
+        for prototype in prototypes.try_iter()? {
+            let prototype = prototype?;
+            let (kind, custom) = PyTreeTypeRegistry::get_kind(&prototype, none_is_leaf, namespace)?;
+            if kind == PyTreeKind::Leaf {
+                return Err(PyValueError::new_err(std::format!(
+                    "Expected a non-leaf prototype node, got {}.",
+                    prototype.repr()?,
+                )));
+            }
+            let node_children = get_children(
+                &prototype,
+                kind,
+                custom.as_ref(),
+                false,
+                &DictOrder::for_namespace(namespace),
+            )?;
+            let node = Node {
+                kind,
+                arity: node_children.children.len(),
+                node_data: node_children.node_data,
+                custom,
+                num_leaves: 0,
+                entries: None,
+                reference: None,
+            };
+            prototype_types.push(prototype.get_type());
+            choices.push((Choice::Prototype(node), 1.0));
+        }
+    }
+
+    match container_weights {
+        None => choices.extend(BUILTIN_KINDS.iter().map(|kind| (Choice::Kind(*kind), 1.0))),
+        Some(container_weights) => {
+            for item in container_weights.downcast::<PyDict>()?.items() {
+                let (key, weight) = item.extract::<(Bound<PyAny>, f64)>()?;
+                if !(weight >= 0.0 && weight.is_finite()) {
+                    return Err(PyValueError::new_err(std::format!(
+                        "Container weights should be non-negative finite numbers, got {} for {}.",
+                        weight,
+                        key.repr()?,
+                    )));
+                }
+                if let Ok(kind) = key.extract::<PyTreeKind>() {
+                    if !BUILTIN_KINDS.contains(&kind) {
+                        return Err(PyValueError::new_err(std::format!(
+                            "Cannot generate nodes of kind {} without a prototype.",
+                            key.repr()?,
+                        )));
+                    }
+                    choices.push((Choice::Kind(kind), weight));
+                } else if let Ok(cls) = key.downcast::<PyType>() {
+                    let mut found = false;
+                    for (i, prototype_type) in prototype_types.iter().enumerate() {
+                        if prototype_type.is(cls) {
+                            choices[i].1 = weight;
+                            found = true;
+                        }
+                    }
+                    if !found {
+                        return Err(PyValueError::new_err(std::format!(
+                            "No prototype node of type {} is provided.",
+                            cls.repr()?,
+                        )));
+                    }
+                } else {
+                    return Err(PyTypeError::new_err(std::format!(
+                        "Expected a PyTreeKind or a prototype type as the container weight key, got {}.",
+                        key.repr()?,
+                    )));
+                }
+            }
+        }
+    }
+
+    let total_weight: f64 = choices.iter().map(|(_, weight)| weight).sum();
+    if total_weight <= 0.0 {
+        return Err(PyValueError::new_err(
+            "Container weights should have a positive sum.",
+        ));
+    }
+
+    let generator = Generator {
+        rng,
+        max_depth,
+        max_children,
+        leaf_factory,
+        choices,
+        total_weight,
+    };
+    generator.generate(0)
+}
@@ -0,0 +1,129 @@
+// Copyright 2024-2025 Xuehai Pan. All Rights Reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+// =============================================================================
+
+#[cfg(not(feature = "tracing"))]
+use pyo3::exceptions::PyRuntimeError;
+use pyo3::prelude::*;
+use pyo3::types::*;
+#[cfg(feature = "tracing")]
+use std::cell::RefCell;
+
+use crate::rustree::profiling::{Phase, timed};
+use crate::rustree::registry::PyTreeKind;
+
+#[cfg(feature = "tracing")]
+thread_local! {
+    static TRACER: RefCell<Option<Py<PyAny>>> = const { RefCell::new(None) };
+}
+
+pub(crate) struct Tracer<'py> {
+    enter_node: Option<Bound<'py, PyAny>>,
+    leaf: Option<Bound<'py, PyAny>>,
+    exit_node: Option<Bound<'py, PyAny>>,
+}
+
+impl<'py> Tracer<'py> {
+    // Resolve the callbacks of the tracer installed on the current thread once per traversal
+    #[cfg(feature = "tracing")]
+    pub(crate) fn current(py: Python<'py>) -> PyResult<Option<Self>> {
+        let Some(tracer) = TRACER.with(|cell| cell.borrow().as_ref().map(|t| t.clone_ref(py)))
+        else {
+            return Ok(None);
+        };
+        let tracer = tracer.into_bound(py);
+        let callback = |name: &str| -> PyResult<Option<Bound<'py, PyAny>>> {
+            Ok(tracer
+                .getattr_opt(name)?
+                .filter(|callback| !callback.is_none()))
+        };
+        Ok(Some(Tracer {
+            enter_node: callback("enter_node")?,
+            leaf: callback("leaf")?,
+            exit_node: callback("exit_node")?,
+        }))
+    }
+
+    #[cfg(not(feature = "tracing"))]
+    #[inline(always)]
+    pub(crate) fn current(_py: Python<'py>) -> PyResult<Option<Self>> {
+        Ok(None)
+    }
+
+    #[inline]
+    fn call(
+        callback: Option<&Bound<'py, PyAny>>,
+        path: &[Bound<'py, PyAny>],
+        kind: PyTreeKind,
+        obj: &Bound<'py, PyAny>,
+    ) -> PyResult<()> {
+        if let Some(callback) = callback {
+            let path = PyTuple::new(obj.py(), path)?;
+            timed(Phase::Callback, || callback.call1((path, kind, obj)))?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn enter_node(
+        &self,
+        path: &[Bound<'py, PyAny>],
+        kind: PyTreeKind,
+        obj: &Bound<'py, PyAny>,
+    ) -> PyResult<()> {
+        Self::call(self.enter_node.as_ref(), path, kind, obj)
+    }
+
+    pub(crate) fn leaf(&self, path: &[Bound<'py, PyAny>], obj: &Bound<'py, PyAny>) -> PyResult<()> {
+        Self::call(self.leaf.as_ref(), path, PyTreeKind::Leaf, obj)
+    }
+
+    pub(crate) fn exit_node(
+        &self,
+        path: &[Bound<'py, PyAny>],
+        kind: PyTreeKind,
+        obj: &Bound<'py, PyAny>,
+    ) -> PyResult<()> {
+        Self::call(self.exit_node.as_ref(), path, kind, obj)
+    }
+}
+
+#[pyfunction]
+#[pyo3(signature = (tracer, /))]
+#[cfg(feature = "tracing")]
+pub fn set_tracer(tracer: Option<&Bound<PyAny>>) -> Option<Py<PyAny>> {
+    TRACER.with(|cell| cell.replace(tracer.map(|tracer| tracer.clone().unbind())))
+}
+
+#[pyfunction]
+#[pyo3(signature = (tracer, /))]
+#[cfg(not(feature = "tracing"))]
+pub fn set_tracer(tracer: Option<&Bound<PyAny>>) -> PyResult<Option<Py<PyAny>>> {
+    match tracer {
+        Some(_) => Err(PyRuntimeError::new_err(
+            "RusTree was built without the `tracing` feature.",
+        )),
+        None => Ok(None),
+    }
+}
+
+#[pyfunction]
+pub fn get_tracer(py: Python<'_>) -> Option<Py<PyAny>> {
+    #[cfg(feature = "tracing")]
+    return TRACER.with(|cell| cell.borrow().as_ref().map(|tracer| tracer.clone_ref(py)));
+    #[cfg(not(feature = "tracing"))]
+    {
+        let _ = py;
+        None
+    }
+}
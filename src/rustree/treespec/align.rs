@@ -0,0 +1,281 @@
+// Copyright 2024-2025 Xuehai Pan. All Rights Reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+// =============================================================================
+
+use pyo3::exceptions::{PyRecursionError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::*;
+use std::sync::Arc;
+
+use crate::rustree::pytypes::{namedtuple_fields, structseq_fields};
+use crate::rustree::registry::{KindHint, PyTreeKind, PyTreeTypeRegistration, PyTreeTypeRegistry};
+use crate::rustree::treespec::flatten::{
+    DictOrder, MAX_RECURSION_DEPTH, NodeChildren, get_children,
+};
+use crate::rustree::treespec::query::node_entries;
+use crate::rustree::treespec::serialization::repr_node_shape;
+use crate::rustree::treespec::{Node, PyTreeSpec, make_node};
+
+// Rebuild a tree in the structure of a treespec, matching the children by keys or by positions
+struct Aligner<'a, 'py> {
+    treespec: &'a PyTreeSpec,
+    num_nodes: Vec<usize>,
+    path: Vec<Bound<'py, PyAny>>,
+}
+
+// The children of a node by their keys, field names, or attribute names
+fn keyed_children<'py>(
+    obj: &Bound<'py, PyAny>,
+    kind: PyTreeKind,
+    custom: Option<&Arc<PyTreeTypeRegistration>>,
+) -> PyResult<Option<Bound<'py, PyDict>>> {
+    let py = obj.py();
+    let keys = match kind {
+        PyTreeKind::Dict | PyTreeKind::OrderedDict | PyTreeKind::DefaultDict => {
+            return Ok(Some(obj.downcast::<PyDict>()?.clone()));
+        }
+        PyTreeKind::NamedTuple => namedtuple_fields(obj)?.into_any(),
+        PyTreeKind::StructSequence => structseq_fields(obj)?.into_any(),
+        PyTreeKind::Custom
+            if custom.is_some_and(|registration| {
+                matches!(
+                    registration.kind_hint,
+                    Some(KindHint::Mapping | KindHint::Object)
+                )
+            }) =>
+        {
+            let NodeChildren {
+                children, entries, ..
+            } = get_children(obj, kind, custom, true, &DictOrder::Insertion)?;
+            let dict = PyDict::new(py);
+            for (key, child) in entries.unwrap().into_iter().zip(children) {
+                dict.set_item(key, child)?;
+            }
+            return Ok(Some(dict));
+        }
+        _ => return Ok(None),
+    };
+    let dict = PyDict::new(py);
+    for (key, child) in keys.try_iter()?.zip(obj.try_iter()?) {
+        dict.set_item(key?, child?)?;
+    }
+    Ok(Some(dict))
+}
+
+// The children of a sequence-like node by their positions
+fn positional_children<'py>(
+    obj: &Bound<'py, PyAny>,
+    kind: PyTreeKind,
+) -> PyResult<Option<Vec<Bound<'py, PyAny>>>> {
+    Ok(match kind {
+        PyTreeKind::Tuple
+        | PyTreeKind::List
+        | PyTreeKind::Deque
+        | PyTreeKind::NamedTuple
+        | PyTreeKind::StructSequence => Some(obj.try_iter()?.collect::<PyResult<Vec<_>>>()?),
+        _ => None,
+    })
+}
+
+fn repr_keys<'py>(keys: impl IntoIterator<Item = Bound<'py, PyAny>>) -> PyResult<String> {
+    let keys = keys
+        .into_iter()
+        .map(|key| Ok(key.repr()?.to_string()))
+        .collect::<PyResult<Vec<_>>>()?;
+    Ok(std::format!("[{}]", keys.join(", ")))
+}
+
+impl<'py> Aligner<'_, 'py> {
+    fn error(&self, py: Python<'py>, reason: String) -> PyResult<PyErr> {
+        Ok(PyValueError::new_err(std::format!(
+            "Cannot align the tree to the PyTreeSpec at path {}; {}.",
+            PyTuple::new(py, &self.path)?.repr()?,
+            reason,
+        )))
+    }
+
+    fn mismatch(&self, obj: &Bound<'py, PyAny>, node: &Node) -> PyResult<PyErr> {
+        let py = obj.py();
+        let expected = match node.kind {
+            PyTreeKind::Leaf => String::from("a leaf"),
+            _ => repr_node_shape(py, node)?,
+        };
+        self.error(
+            py,
+            std::format!("expected {}, got {}", expected, obj.repr()?),
+        )
+    }
+
+    // Look up the children in the order of the keys of the node
+    fn children_by_keys(
+        &self,
+        dict: &Bound<'py, PyDict>,
+        keys: &[Bound<'py, PyAny>],
+    ) -> PyResult<Vec<Bound<'py, PyAny>>> {
+        let py = dict.py();
+        let mut children = Vec::with_capacity(keys.len());
+        let mut missing = Vec::new();
+        for key in keys {
+            match dict.get_item(key)? {
+                Some(child) => children.push(child),
+                None => missing.push(key.clone()),
+            }
+        }
+        let expected = PySet::new(py, keys)?;
+        let mut unexpected = Vec::new();
+        for key in dict.keys() {
+            if !expected.contains(&key)? {
+                unexpected.push(key);
+            }
+        }
+        let mut reasons = Vec::new();
+        if !missing.is_empty() {
+            reasons.push(std::format!("missing keys {}", repr_keys(missing)?));
+        }
+        if !unexpected.is_empty() {
+            reasons.push(std::format!("unexpected keys {}", repr_keys(unexpected)?));
+        }
+        match reasons.is_empty() {
+            true => Ok(children),
+            false => Err(self.error(py, reasons.join(", "))?),
+        }
+    }
+
+    fn align(
+        &mut self,
+        obj: &Bound<'py, PyAny>,
+        index: usize,
+        depth: usize,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let py = obj.py();
+        if depth > MAX_RECURSION_DEPTH {
+            return Err(PyRecursionError::new_err(
+                "Maximum recursion depth exceeded during aligning the tree.",
+            ));
+        }
+
+        let treespec = self.treespec;
+        let node = &treespec.traversal[index];
+        if let Some(target) = node.reference {
+            return self.align(obj, target, depth);
+        }
+        let (kind, custom) =
+            PyTreeTypeRegistry::get_kind(obj, treespec.none_is_leaf, &treespec.namespace)?;
+        match node.kind {
+            PyTreeKind::Leaf if kind == PyTreeKind::Leaf => return Ok(obj.clone()),
+            PyTreeKind::None if obj.is_none() => return Ok(obj.clone()),
+            PyTreeKind::Leaf | PyTreeKind::None => return Err(self.mismatch(obj, node)?),
+            _ => {}
+        }
+
+        let entries = node_entries(py, node)?;
+        let (children, custom_node) = match node.kind {
+            PyTreeKind::Dict
+            | PyTreeKind::OrderedDict
+            | PyTreeKind::DefaultDict
+            | PyTreeKind::NamedTuple
+            | PyTreeKind::StructSequence => {
+                let keys = match node.kind {
+                    PyTreeKind::NamedTuple | PyTreeKind::StructSequence => {
+                        let cls = node.node_data.as_ref().unwrap().bind(py);
+                        let fields = match node.kind {
+                            PyTreeKind::NamedTuple => namedtuple_fields(cls)?,
+                            _ => structseq_fields(cls)?,
+                        };
+                        fields.iter().collect()
+                    }
+                    _ => entries.clone(),
+                };
+                let children = match keyed_children(obj, kind, custom.as_ref())? {
+                    Some(dict) => self.children_by_keys(&dict, &keys)?,
+                    None => match positional_children(obj, kind)? {
+                        // The fields of a namedtuple can also be given by the positions
+                        Some(children)
+                            if matches!(
+                                node.kind,
+                                PyTreeKind::NamedTuple | PyTreeKind::StructSequence
+                            ) && children.len() == node.arity =>
+                        {
+                            children
+                        }
+                        _ => return Err(self.mismatch(obj, node)?),
+                    },
+                };
+                (children, None)
+            }
+            PyTreeKind::Tuple | PyTreeKind::List | PyTreeKind::Deque => {
+                match positional_children(obj, kind)? {
+                    Some(children) if children.len() == node.arity => (children, None),
+                    _ => return Err(self.mismatch(obj, node)?),
+                }
+            }
+            PyTreeKind::Custom => {
+                let same_type = kind == PyTreeKind::Custom
+                    && custom.as_ref().unwrap().node_type.bind(py).is(node
+                        .custom
+                        .as_ref()
+                        .unwrap()
+                        .node_type
+                        .bind(py));
+                if !same_type {
+                    return Err(self.mismatch(obj, node)?);
+                }
+                // Keep the metadata of the custom node in the tree
+                let NodeChildren {
+                    children,
+                    node_data,
+                    ..
+                } = get_children(obj, kind, custom.as_ref(), false, &DictOrder::Insertion)?;
+                if children.len() != node.arity {
+                    return Err(self.mismatch(obj, node)?);
+                }
+                let custom_node = Node {
+                    kind,
+                    arity: children.len(),
+                    node_data,
+                    custom,
+                    num_leaves: 0,
+                    entries: None,
+                    reference: None,
+                };
+                (children, Some(custom_node))
+            }
+            PyTreeKind::Leaf | PyTreeKind::None => unreachable!(),
+        };
+
+        let child_indices = treespec.child_indices(&self.num_nodes, index);
+        let mut aligned = Vec::with_capacity(children.len());
+        for ((child, entry), child_index) in children.iter().zip(entries).zip(child_indices) {
+            self.path.push(entry);
+            let result = self.align(child, child_index, depth + 1);
+            self.path.pop();
+            aligned.push(result?);
+        }
+        make_node(py, custom_node.as_ref().unwrap_or(node), aligned)
+    }
+}
+
+#[pyfunction]
+#[pyo3(name = "align", signature = (tree, treespec, /))]
+pub fn align_tree<'py>(
+    tree: &Bound<'py, PyAny>,
+    treespec: &PyTreeSpec,
+) -> PyResult<Bound<'py, PyAny>> {
+    let mut aligner = Aligner {
+        treespec,
+        num_nodes: treespec.subtree_num_nodes(),
+        path: Vec::new(),
+    };
+    aligner.align(tree, treespec.traversal.len() - 1, 0)
+}
@@ -0,0 +1,478 @@
+// Copyright 2024-2025 Xuehai Pan. All Rights Reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+// =============================================================================
+
+use pyo3::exceptions::{PyBaseException, PyRecursionError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::*;
+
+use crate::rustree::profiling::{Phase, record_allocations, timed};
+use crate::rustree::registry::{PyTreeKind, PyTreeTypeRegistry};
+use crate::rustree::treespec::comparison::node_shape_eq;
+use crate::rustree::treespec::flatten::{
+    DictOrder, MAX_RECURSION_DEPTH, NodeChildren, get_children,
+};
+use crate::rustree::treespec::{Node, make_node};
+
+// A deferred error of where the prefix tree is not a prefix of the full tree
+#[pyclass(frozen, get_all, module = "rustree")]
+pub struct PrefixError {
+    path: Py<PyTuple>,
+    reason: String,
+}
+
+#[pymethods]
+impl PrefixError {
+    #[pyo3(signature = (name, /))]
+    fn __call__<'py>(&self, py: Python<'py>, name: &str) -> PyResult<Bound<'py, PyBaseException>> {
+        Ok(PyValueError::new_err(std::format!(
+            "Tree {} is not a prefix of the full tree at path {}; {}.",
+            name,
+            self.path.bind(py).repr()?,
+            self.reason,
+        ))
+        .into_value(py)
+        .into_bound(py))
+    }
+
+    fn __repr__(&self, py: Python<'_>) -> PyResult<String> {
+        Ok(std::format!(
+            "PrefixError(path={}, reason={})",
+            self.path.bind(py).repr()?,
+            PyString::new(py, &self.reason).repr()?,
+        ))
+    }
+}
+
+struct Expanded<'py> {
+    node: Node,
+    children: Vec<Bound<'py, PyAny>>,
+    entries: Vec<Bound<'py, PyAny>>,
+}
+
+struct Broadcaster<'a, 'py> {
+    // Without a function, the leaves are taken from the tree at the `select` index
+    func: Option<&'a Bound<'py, PyAny>>,
+    select: usize,
+    leaf_predicate: Option<Bound<'py, PyAny>>,
+    none_is_leaf: bool,
+    namespace: &'a str,
+    with_path: bool,
+    // The first tree of the two must be a prefix of the second one
+    prefix: bool,
+    path: Vec<Bound<'py, PyAny>>,
+    dict_order: DictOrder<'py>,
+    // Collect the selected leaves instead of rebuilding the tree
+    leaves: Option<Vec<Bound<'py, PyAny>>>,
+}
+
+impl<'py> Broadcaster<'_, 'py> {
+    // Flatten a non-leaf node by one level, or return `None` for a leaf
+    fn expand(
+        &self,
+        obj: &Bound<'py, PyAny>,
+        dict_order: &DictOrder<'py>,
+    ) -> PyResult<Option<Expanded<'py>>> {
+        if let Some(leaf_predicate) = &self.leaf_predicate
+            && timed(Phase::Callback, || leaf_predicate.call1((obj,)))?.is_truthy()?
+        {
+            return Ok(None);
+        }
+        let (kind, custom) = PyTreeTypeRegistry::get_kind(obj, self.none_is_leaf, self.namespace)?;
+        if kind == PyTreeKind::Leaf {
+            return Ok(None);
+        }
+        let NodeChildren {
+            children,
+            entries,
+            node_data,
+        } = get_children(obj, kind, custom.as_ref(), true, dict_order)?;
+        let node = Node {
+            kind,
+            arity: children.len(),
+            node_data,
+            custom,
+            num_leaves: 0,
+            entries: None,
+            reference: None,
+        };
+        Ok(Some(Expanded {
+            node,
+            children,
+            entries: entries.unwrap_or_default(),
+        }))
+    }
+
+    fn path_repr(&self, py: Python<'py>) -> PyResult<String> {
+        Ok(PyTuple::new(py, &self.path)?.repr()?.to_string())
+    }
+
+    // Collect all places where the first tree is not a prefix of the second one
+    fn prefix_errors(
+        &mut self,
+        prefix: &Bound<'py, PyAny>,
+        full: &Bound<'py, PyAny>,
+        depth: usize,
+        errors: &mut Vec<PrefixError>,
+    ) -> PyResult<()> {
+        let py = prefix.py();
+        if depth > MAX_RECURSION_DEPTH {
+            return Err(PyRecursionError::new_err(
+                "Maximum recursion depth exceeded during checking the prefix tree.",
+            ));
+        }
+
+        let Some(expanded) = self.expand(prefix, &self.dict_order)? else {
+            return Ok(());
+        };
+        let other = self.expand(full, &self.dict_order)?;
+        let prefix_type = prefix.get_type().name()?;
+        let node_data_repr = |node: &Node| match &node.node_data {
+            Some(node_data) => Ok(node_data.bind(py).repr()?.to_string()),
+            None => Ok::<_, PyErr>(String::from("None")),
+        };
+        let is_dict = matches!(
+            expanded.node.kind,
+            PyTreeKind::Dict | PyTreeKind::OrderedDict | PyTreeKind::DefaultDict
+        );
+        let reason = match &other {
+            None => Some(std::format!(
+                "the prefix tree has a node of type {} but the full tree has a leaf of type {}",
+                prefix_type,
+                full.get_type().name()?,
+            )),
+            Some(_) if !prefix.get_type().is(full.get_type()) => Some(std::format!(
+                "the prefix tree has a node of type {} but the full tree has a node of type {}",
+                prefix_type,
+                full.get_type().name()?,
+            )),
+            Some(other)
+                if is_dict
+                    && !PySet::new(py, &expanded.entries)?
+                        .eq(PySet::new(py, &other.entries)?)? =>
+            {
+                Some(std::format!(
+                    "the prefix tree has a node of type {} with keys {} \
+                    but the full tree has keys {}",
+                    prefix_type,
+                    PyList::new(py, &expanded.entries)?.repr()?,
+                    PyList::new(py, &other.entries)?.repr()?,
+                ))
+            }
+            Some(other) if expanded.node.arity != other.node.arity => Some(std::format!(
+                "the prefix tree has a node of type {} with {} children \
+                but the full tree has {} children",
+                prefix_type,
+                expanded.node.arity,
+                other.node.arity,
+            )),
+            Some(other) if !node_shape_eq(py, &expanded.node, &other.node)? => Some(std::format!(
+                "the prefix tree has a node of type {} with metadata {} \
+                but the full tree has metadata {}",
+                prefix_type,
+                node_data_repr(&expanded.node)?,
+                node_data_repr(&other.node)?,
+            )),
+            Some(_) => None,
+        };
+        if let Some(reason) = reason {
+            errors.push(PrefixError {
+                path: PyTuple::new(py, &self.path)?.unbind(),
+                reason,
+            });
+            return Ok(());
+        }
+
+        let other = other.unwrap();
+        for (index, (child, entry)) in expanded.children.iter().zip(&expanded.entries).enumerate() {
+            // The children of the dictionaries are matched by the keys
+            let other_child = match is_dict {
+                true => full.get_item(entry)?,
+                false => other.children[index].clone(),
+            };
+            self.path.push(entry.clone());
+            let result = self.prefix_errors(child, &other_child, depth + 1, errors);
+            self.path.pop();
+            result?;
+        }
+        Ok(())
+    }
+
+    fn broadcast(
+        &mut self,
+        objs: &[Bound<'py, PyAny>],
+        depth: usize,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let py = objs[0].py();
+        if depth > MAX_RECURSION_DEPTH {
+            return Err(PyRecursionError::new_err(
+                "Maximum recursion depth exceeded during broadcasting the trees.",
+            ));
+        }
+
+        // The dictionaries in the other trees follow the key order of the first non-leaf node
+        let mut expanded = Vec::with_capacity(objs.len());
+        let mut given: Option<DictOrder<'py>> = None;
+        for obj in objs {
+            let current = self.expand(obj, given.as_ref().unwrap_or(&self.dict_order))?;
+            if given.is_none()
+                && let Some(current) = &current
+            {
+                given =
+                    Some(DictOrder::of_node(py, &current.node)?.unwrap_or(self.dict_order.clone()));
+            }
+            expanded.push(current);
+        }
+        let Some(reference) = expanded.iter().position(Option::is_some) else {
+            let Some(func) = self.func else {
+                if let Some(leaves) = &mut self.leaves {
+                    leaves.push(objs[self.select].clone());
+                }
+                return Ok(objs[self.select].clone());
+            };
+            let mut args = Vec::with_capacity(objs.len() + 1);
+            if self.with_path {
+                args.push(PyTuple::new(py, &self.path)?.into_any());
+            }
+            args.extend(objs.iter().cloned());
+            let args = PyTuple::new(py, args)?;
+            record_allocations(1);
+            return timed(Phase::Callback, || func.call1(args));
+        };
+        if self.prefix && reference == 0 && expanded[1].is_none() {
+            return Err(PyValueError::new_err(std::format!(
+                "Tree structures cannot be broadcast at path {}; \
+                the node {} in the prefix tree does not match the leaf {} in the full tree.",
+                self.path_repr(py)?,
+                objs[0].repr()?,
+                objs[1].repr()?,
+            )));
+        }
+
+        let Expanded { node, entries, .. } = expanded[reference].as_ref().unwrap();
+        for (i, other) in expanded.iter().enumerate() {
+            if let Some(other) = other
+                && !node_shape_eq(py, node, &other.node)?
+            {
+                return Err(PyValueError::new_err(std::format!(
+                    "Tree structures cannot be broadcast at path {}; \
+                    the node {} in tree {} does not match the node {} in tree {}.",
+                    self.path_repr(py)?,
+                    objs[reference].repr()?,
+                    reference,
+                    objs[i].repr()?,
+                    i,
+                )));
+            }
+        }
+
+        let mut results = Vec::with_capacity(node.arity);
+        for (index, entry) in entries.iter().enumerate() {
+            // Leaves are repeated over all children of the nodes in the other trees
+            let children = objs
+                .iter()
+                .zip(expanded.iter())
+                .map(|(obj, expanded)| match expanded {
+                    Some(expanded) => expanded.children[index].clone(),
+                    None => obj.clone(),
+                })
+                .collect::<Vec<_>>();
+            // The path is also tracked without `with_path` for the error messages
+            self.path.push(entry.clone());
+            let result = self.broadcast(&children, depth + 1);
+            self.path.pop();
+            results.push(result?);
+        }
+        if self.leaves.is_some() {
+            return Ok(objs[self.select].clone());
+        }
+        make_node(py, node, results)
+    }
+}
+
+fn broadcast_all<'py>(
+    func: &Bound<'py, PyAny>,
+    tree: &Bound<'py, PyAny>,
+    rests: &Bound<'py, PyTuple>,
+    leaf_predicate: Option<&Bound<'py, PyAny>>,
+    none_is_leaf: Option<bool>,
+    namespace: Option<&str>,
+    with_path: bool,
+) -> PyResult<Bound<'py, PyAny>> {
+    let namespace = namespace.unwrap_or("");
+    let mut broadcaster = Broadcaster {
+        func: Some(func),
+        select: 0,
+        leaf_predicate: PyTreeTypeRegistry::leaf_predicate(tree.py(), leaf_predicate, namespace),
+        none_is_leaf: none_is_leaf.unwrap_or(false),
+        namespace,
+        with_path,
+        prefix: false,
+        path: Vec::new(),
+        dict_order: DictOrder::for_namespace(namespace),
+        leaves: None,
+    };
+    let mut objs = vec![tree.clone()];
+    objs.extend(rests.iter());
+    broadcaster.broadcast(&objs, 0)
+}
+
+#[pyfunction]
+#[pyo3(signature = (func, tree, /, *rests, leaf_predicate=None, none_is_leaf=false, namespace=""))]
+pub fn broadcast_map<'py>(
+    func: &Bound<'py, PyAny>,
+    tree: &Bound<'py, PyAny>,
+    rests: &Bound<'py, PyTuple>,
+    leaf_predicate: Option<&Bound<'py, PyAny>>,
+    none_is_leaf: Option<bool>,
+    namespace: Option<&str>,
+) -> PyResult<Bound<'py, PyAny>> {
+    broadcast_all(
+        func,
+        tree,
+        rests,
+        leaf_predicate,
+        none_is_leaf,
+        namespace,
+        false,
+    )
+}
+
+#[pyfunction]
+#[pyo3(signature = (func, tree, /, *rests, leaf_predicate=None, none_is_leaf=false, namespace=""))]
+pub fn broadcast_map_with_path<'py>(
+    func: &Bound<'py, PyAny>,
+    tree: &Bound<'py, PyAny>,
+    rests: &Bound<'py, PyTuple>,
+    leaf_predicate: Option<&Bound<'py, PyAny>>,
+    none_is_leaf: Option<bool>,
+    namespace: Option<&str>,
+) -> PyResult<Bound<'py, PyAny>> {
+    broadcast_all(
+        func,
+        tree,
+        rests,
+        leaf_predicate,
+        none_is_leaf,
+        namespace,
+        true,
+    )
+}
+
+fn prefix_broadcaster<'a, 'py>(
+    py: Python<'py>,
+    leaf_predicate: Option<&Bound<'py, PyAny>>,
+    none_is_leaf: Option<bool>,
+    namespace: &'a str,
+) -> Broadcaster<'a, 'py> {
+    Broadcaster {
+        func: None,
+        select: 0,
+        leaf_predicate: PyTreeTypeRegistry::leaf_predicate(py, leaf_predicate, namespace),
+        none_is_leaf: none_is_leaf.unwrap_or(false),
+        namespace,
+        with_path: false,
+        prefix: true,
+        path: Vec::new(),
+        dict_order: DictOrder::for_namespace(namespace),
+        leaves: None,
+    }
+}
+
+#[pyfunction]
+#[pyo3(signature = (prefix_tree, full_tree, /, leaf_predicate=None, none_is_leaf=false, namespace=""))]
+pub fn broadcast_prefix<'py>(
+    prefix_tree: &Bound<'py, PyAny>,
+    full_tree: &Bound<'py, PyAny>,
+    leaf_predicate: Option<&Bound<'py, PyAny>>,
+    none_is_leaf: Option<bool>,
+    namespace: Option<&str>,
+) -> PyResult<Bound<'py, PyAny>> {
+    let mut broadcaster = prefix_broadcaster(
+        prefix_tree.py(),
+        leaf_predicate,
+        none_is_leaf,
+        namespace.unwrap_or(""),
+    );
+    broadcaster.broadcast(&[prefix_tree.clone(), full_tree.clone()], 0)
+}
+
+#[pyfunction]
+#[pyo3(signature = (prefix_tree, full_tree, /, leaf_predicate=None, none_is_leaf=false, namespace=""))]
+pub fn broadcast_prefix_leaves<'py>(
+    prefix_tree: &Bound<'py, PyAny>,
+    full_tree: &Bound<'py, PyAny>,
+    leaf_predicate: Option<&Bound<'py, PyAny>>,
+    none_is_leaf: Option<bool>,
+    namespace: Option<&str>,
+) -> PyResult<Bound<'py, PyList>> {
+    let py = prefix_tree.py();
+    let mut broadcaster =
+        prefix_broadcaster(py, leaf_predicate, none_is_leaf, namespace.unwrap_or(""));
+    broadcaster.leaves = Some(Vec::new());
+    broadcaster.broadcast(&[prefix_tree.clone(), full_tree.clone()], 0)?;
+    PyList::new(py, broadcaster.leaves.unwrap())
+}
+
+#[pyfunction]
+#[pyo3(signature = (prefix_tree, full_tree, /, leaf_predicate=None, none_is_leaf=false, namespace=""))]
+pub fn prefix_errors<'py>(
+    prefix_tree: &Bound<'py, PyAny>,
+    full_tree: &Bound<'py, PyAny>,
+    leaf_predicate: Option<&Bound<'py, PyAny>>,
+    none_is_leaf: Option<bool>,
+    namespace: Option<&str>,
+) -> PyResult<Vec<PrefixError>> {
+    let mut broadcaster = prefix_broadcaster(
+        prefix_tree.py(),
+        leaf_predicate,
+        none_is_leaf,
+        namespace.unwrap_or(""),
+    );
+    let mut errors = Vec::new();
+    broadcaster.prefix_errors(prefix_tree, full_tree, 0, &mut errors)?;
+    Ok(errors)
+}
+
+#[pyfunction]
+#[pyo3(signature = (tree, other_tree, /, leaf_predicate=None, none_is_leaf=false, namespace=""))]
+pub fn broadcast_common<'py>(
+    tree: &Bound<'py, PyAny>,
+    other_tree: &Bound<'py, PyAny>,
+    leaf_predicate: Option<&Bound<'py, PyAny>>,
+    none_is_leaf: Option<bool>,
+    namespace: Option<&str>,
+) -> PyResult<Bound<'py, PyTuple>> {
+    let py = tree.py();
+    let namespace = namespace.unwrap_or("");
+    let objs = [tree.clone(), other_tree.clone()];
+    // Both trees are rebuilt in the same traversal order, so the dictionaries share the key order
+    let mut results = Vec::with_capacity(objs.len());
+    for select in 0..objs.len() {
+        let mut broadcaster = Broadcaster {
+            func: None,
+            select,
+            leaf_predicate: PyTreeTypeRegistry::leaf_predicate(py, leaf_predicate, namespace),
+            none_is_leaf: none_is_leaf.unwrap_or(false),
+            namespace,
+            with_path: false,
+            prefix: false,
+            path: Vec::new(),
+            dict_order: DictOrder::for_namespace(namespace),
+            leaves: None,
+        };
+        results.push(broadcaster.broadcast(&objs, 0)?);
+    }
+    PyTuple::new(py, results)
+}
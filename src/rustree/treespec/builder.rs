@@ -0,0 +1,342 @@
+// Copyright 2024-2025 Xuehai Pan. All Rights Reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+// =============================================================================
+
+use pyo3::exceptions::{PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::*;
+
+use crate::rustree::pytypes::{
+    is_namedtuple_class, is_structseq_class, namedtuple_fields, structseq_fields,
+};
+use crate::rustree::registry::{PyTreeKind, PyTreeTypeRegistry};
+use crate::rustree::treespec::{Node, PyTreeSpec};
+
+// Build a treespec from its post-order traversal without creating the containers
+#[pyclass(module = "rustree")]
+pub struct TreeSpecBuilder {
+    traversal: Vec<Node>,
+    // The number of leaves of the subtrees that are not yet attached to a parent node
+    pending: Vec<usize>,
+    none_is_leaf: bool,
+    namespace: String,
+    found_custom: bool,
+}
+
+impl TreeSpecBuilder {
+    // The number of keys must match the arity of the dictionary nodes
+    fn keys<'py>(keys: &Bound<'py, PyAny>, arity: usize) -> PyResult<Bound<'py, PyList>> {
+        let keys = PyList::new(keys.py(), keys.try_iter()?.collect::<PyResult<Vec<_>>>()?)?;
+        if keys.len() != arity {
+            return Err(PyValueError::new_err(std::format!(
+                "Expected {} keys for a dictionary node, got {}.",
+                arity,
+                keys.len(),
+            )));
+        }
+        Ok(keys)
+    }
+
+    fn node<'py>(
+        &mut self,
+        py: Python<'py>,
+        kind: PyTreeKind,
+        arity: usize,
+        metadata: Option<&Bound<'py, PyAny>>,
+        node_type: Option<&Bound<'py, PyType>>,
+        entries: Option<&Bound<'py, PyAny>>,
+    ) -> PyResult<Node> {
+        if node_type.is_some() && kind != PyTreeKind::Custom {
+            return Err(PyValueError::new_err(
+                "The node type can only be specified for custom nodes.",
+            ));
+        }
+        if entries.is_some() && kind != PyTreeKind::Custom {
+            return Err(PyValueError::new_err(
+                "The entries can only be specified for custom nodes.",
+            ));
+        }
+        let expect_no_metadata = || match metadata {
+            Some(metadata) if !metadata.is_none() => Err(PyValueError::new_err(std::format!(
+                "Expected no metadata for a node of kind {}, got {}.",
+                kind.name(),
+                metadata.repr()?,
+            ))),
+            _ => Ok(()),
+        };
+        let expect_arity = |expected: usize| match arity == expected {
+            true => Ok(()),
+            false => Err(PyValueError::new_err(std::format!(
+                "Expected arity {} for a node of kind {}, got {}.",
+                expected,
+                kind.name(),
+                arity,
+            ))),
+        };
+        let require_metadata = || {
+            metadata
+                .filter(|metadata| !metadata.is_none())
+                .ok_or_else(|| {
+                    PyValueError::new_err(std::format!(
+                        "Expected the metadata for a node of kind {}.",
+                        kind.name(),
+                    ))
+                })
+        };
+
+        let mut custom = None;
+        let mut node_entries = None;
+        let node_data = match kind {
+            PyTreeKind::Leaf => {
+                return Err(PyValueError::new_err(
+                    "Use `push_leaf()` to push the leaves.",
+                ));
+            }
+            PyTreeKind::None => {
+                if self.none_is_leaf {
+                    return Err(PyValueError::new_err(
+                        "None is a leaf for the builder with `none_is_leaf=True`, \
+                        use `push_leaf()` instead.",
+                    ));
+                }
+                expect_no_metadata()?;
+                expect_arity(0)?;
+                None
+            }
+            PyTreeKind::Tuple | PyTreeKind::List => {
+                expect_no_metadata()?;
+                None
+            }
+            PyTreeKind::Dict | PyTreeKind::OrderedDict => {
+                Some(Self::keys(require_metadata()?, arity)?.into_any().unbind())
+            }
+            PyTreeKind::DefaultDict => {
+                let metadata = require_metadata()?;
+                let (default_factory, keys) = metadata
+                    .extract::<(Bound<PyAny>, Bound<PyAny>)>()
+                    .map_err(|_| {
+                        PyTypeError::new_err(
+                            "Expected a pair `(default_factory, keys)` as the metadata for a node \
+                            of kind DEFAULTDICT.",
+                        )
+                    })?;
+                let keys = Self::keys(&keys, arity)?.into_any();
+                Some(
+                    PyTuple::new(py, [default_factory, keys])?
+                        .into_any()
+                        .unbind(),
+                )
+            }
+            PyTreeKind::NamedTuple | PyTreeKind::StructSequence => {
+                let cls = require_metadata()?;
+                let fields = match kind {
+                    PyTreeKind::NamedTuple if is_namedtuple_class(cls)? => namedtuple_fields(cls)?,
+                    PyTreeKind::StructSequence if is_structseq_class(cls)? => {
+                        structseq_fields(cls)?
+                    }
+                    _ => {
+                        return Err(PyTypeError::new_err(std::format!(
+                            "Expected a {} class as the metadata, got {}.",
+                            match kind {
+                                PyTreeKind::NamedTuple => "namedtuple",
+                                _ => "PyStructSequence",
+                            },
+                            cls.repr()?,
+                        )));
+                    }
+                };
+                expect_arity(fields.len())?;
+                Some(cls.clone().unbind())
+            }
+            PyTreeKind::Deque => Some(match metadata {
+                Some(maxlen) => maxlen.clone().unbind(),
+                None => py.None(),
+            }),
+            PyTreeKind::Custom => {
+                let cls = node_type.ok_or_else(|| {
+                    PyValueError::new_err("Expected the node type for a custom node.")
+                })?;
+                let (found, registration) =
+                    PyTreeTypeRegistry::get_type_kind(cls, self.none_is_leaf, &self.namespace)?;
+                if found != PyTreeKind::Custom {
+                    return Err(PyValueError::new_err(std::format!(
+                        "Expected a registered custom PyTree node type in namespace {}, got {}.",
+                        PyString::new(py, &self.namespace).repr()?,
+                        cls.repr()?,
+                    )));
+                }
+                if let Some(entries) = entries {
+                    let entries =
+                        PyTuple::new(py, entries.try_iter()?.collect::<PyResult<Vec<_>>>()?)?;
+                    if entries.len() != arity {
+                        return Err(PyValueError::new_err(std::format!(
+                            "Expected {} entries for a custom node, got {}.",
+                            arity,
+                            entries.len(),
+                        )));
+                    }
+                    node_entries = Some(entries.unbind());
+                }
+                custom = registration;
+                self.found_custom = true;
+                Some(match metadata {
+                    Some(metadata) => metadata.clone().unbind(),
+                    None => py.None(),
+                })
+            }
+        };
+        Ok(Node {
+            kind,
+            arity,
+            node_data,
+            custom,
+            num_leaves: 0,
+            entries: node_entries,
+            reference: None,
+        })
+    }
+
+    // Push a node with arity 0 that cannot be created by `push_node()`, i.e., a pruned `None` or a
+    // repeated occurrence of a shared subtree
+    pub(crate) fn push_empty(&mut self, reference: Option<usize>) -> PyResult<()> {
+        if let Some(target) = reference
+            && !self.traversal.get(target).is_some_and(|node| {
+                !matches!(node.kind, PyTreeKind::Leaf | PyTreeKind::None)
+                    && node.reference.is_none()
+            })
+        {
+            return Err(PyValueError::new_err(std::format!(
+                "Expected a reference to a preceding non-leaf node, got index {}.",
+                target,
+            )));
+        }
+        self.traversal.push(Node {
+            kind: PyTreeKind::None,
+            arity: 0,
+            node_data: None,
+            custom: None,
+            num_leaves: 0,
+            entries: None,
+            reference,
+        });
+        self.pending.push(0);
+        Ok(())
+    }
+
+    // Push a node that needs no validation, e.g., one built from the children of a Python object
+    pub(crate) fn push_validated(&mut self, mut node: Node) {
+        let size = self.pending.len();
+        node.num_leaves = self.pending.drain(size - node.arity..).sum();
+        self.found_custom |= node.kind == PyTreeKind::Custom;
+        self.pending.push(node.num_leaves);
+        self.traversal.push(node);
+    }
+
+    // Push a copy of the traversal of a treespec as a subtree
+    pub(crate) fn push_treespec(&mut self, py: Python<'_>, treespec: &PyTreeSpec) -> PyResult<()> {
+        if treespec.none_is_leaf != self.none_is_leaf {
+            return Err(PyValueError::new_err(std::format!(
+                "Expected a PyTreeSpec with none_is_leaf={}, got {}.",
+                match self.none_is_leaf {
+                    true => "True",
+                    false => "False",
+                },
+                treespec.repr_impl(py)?,
+            )));
+        }
+        if !treespec.namespace.is_empty() {
+            if treespec.namespace != self.namespace {
+                return Err(PyValueError::new_err(std::format!(
+                    "Expected a PyTreeSpec in namespace {}, got {}.",
+                    PyString::new(py, &self.namespace).repr()?,
+                    treespec.repr_impl(py)?,
+                )));
+            }
+            self.found_custom = true;
+        }
+        let start = self.traversal.len();
+        self.traversal.extend(treespec.traversal.iter().map(|node| {
+            let mut node = node.clone_ref(py);
+            node.reference = node.reference.map(|target| target + start);
+            node
+        }));
+        self.pending.push(treespec.num_leaves());
+        Ok(())
+    }
+}
+
+#[pymethods]
+impl TreeSpecBuilder {
+    #[new]
+    #[pyo3(signature = (none_is_leaf=false, namespace=""))]
+    pub(crate) fn new(none_is_leaf: bool, namespace: &str) -> Self {
+        TreeSpecBuilder {
+            traversal: Vec::new(),
+            pending: Vec::new(),
+            none_is_leaf,
+            namespace: String::from(namespace),
+            found_custom: false,
+        }
+    }
+
+    pub(crate) fn push_leaf(&mut self) {
+        self.traversal.push(Node::leaf());
+        self.pending.push(1);
+    }
+
+    #[pyo3(signature = (kind, arity=0, metadata=None, /, *, node_type=None, entries=None))]
+    pub(crate) fn push_node<'py>(
+        &mut self,
+        py: Python<'py>,
+        kind: PyTreeKind,
+        arity: usize,
+        metadata: Option<&Bound<'py, PyAny>>,
+        node_type: Option<&Bound<'py, PyType>>,
+        entries: Option<&Bound<'py, PyAny>>,
+    ) -> PyResult<()> {
+        // The children precede the node in the post-order traversal
+        if arity > self.pending.len() {
+            return Err(PyValueError::new_err(std::format!(
+                "Not enough subtrees for a node of arity {}, got {}.",
+                arity,
+                self.pending.len(),
+            )));
+        }
+        let mut node = self.node(py, kind, arity, metadata, node_type, entries)?;
+        let size = self.pending.len();
+        node.num_leaves = self.pending.drain(size - arity..).sum();
+        self.pending.push(node.num_leaves);
+        self.traversal.push(node);
+        Ok(())
+    }
+
+    pub(crate) fn build(&mut self) -> PyResult<PyTreeSpec> {
+        if self.pending.len() != 1 {
+            return Err(PyValueError::new_err(std::format!(
+                "Expected exactly one root to build the PyTreeSpec, got {} subtrees.",
+                self.pending.len(),
+            )));
+        }
+        self.pending.clear();
+        let namespace = match std::mem::take(&mut self.found_custom) {
+            true => self.namespace.clone(),
+            false => String::new(),
+        };
+        Ok(PyTreeSpec {
+            traversal: std::mem::take(&mut self.traversal),
+            none_is_leaf: self.none_is_leaf,
+            namespace,
+        })
+    }
+}
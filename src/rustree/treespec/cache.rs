@@ -0,0 +1,112 @@
+// Copyright 2024-2025 Xuehai Pan. All Rights Reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+// =============================================================================
+
+use pyo3::prelude::*;
+use pyo3::types::*;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::rustree::registry::PyTreeKind;
+use crate::rustree::treespec::PyTreeSpec;
+
+// The treespecs are keyed by (none_is_leaf, namespace, layout), where the layout is the post-order
+// sequence of the node kinds, the arities, and the identities of the node data (e.g., the keys of
+// the dictionaries). The cached treespecs hold the node data alive, so the identities in the keys
+// are never reused by other objects. The cache is never evicted, so the cached layouts keep their
+// keys and node types alive until the cache is cleared by `treespec_cache_clear()`, unregistering a
+// type, or restoring a registry snapshot.
+pub(crate) type CacheKey = (bool, String, Vec<usize>);
+
+static CACHE: Mutex<Option<HashMap<CacheKey, Py<PyTreeSpec>>>> = Mutex::new(None);
+
+// Stop caching new layouts beyond the limit until the cache is cleared
+const MAX_CACHE_SIZE: usize = 4096;
+
+// Append the layout of a node in the post-order traversal, return `false` if the node cannot be
+// cached. The layout is built during the flattening walk without allocating the nodes.
+pub(crate) fn push_layout(
+    layout: &mut Vec<usize>,
+    kind: PyTreeKind,
+    arity: usize,
+    node_data: Option<&Bound<'_, PyAny>>,
+) -> PyResult<bool> {
+    layout.push(kind as usize);
+    if kind == PyTreeKind::Leaf {
+        return Ok(true);
+    }
+    layout.push(arity);
+    match kind {
+        // The metadata of the custom nodes can be arbitrary objects that are not kept in the layout
+        PyTreeKind::Custom => return Ok(false),
+        PyTreeKind::Dict | PyTreeKind::OrderedDict => {
+            for key in node_data.unwrap().downcast::<PyList>()?.iter() {
+                layout.push(key.as_ptr() as usize);
+            }
+        }
+        PyTreeKind::DefaultDict => {
+            let node_data = node_data.unwrap();
+            layout.push(node_data.get_item(0)?.as_ptr() as usize);
+            for key in node_data.get_item(1)?.downcast::<PyList>()?.iter() {
+                layout.push(key.as_ptr() as usize);
+            }
+        }
+        _ => layout.push(node_data.map_or(0, |data| data.as_ptr() as usize)),
+    }
+    Ok(true)
+}
+
+// Look up the treespec with the same layout
+pub(crate) fn lookup<'py>(
+    py: Python<'py>,
+    none_is_leaf: bool,
+    namespace: &str,
+    layout: Vec<usize>,
+) -> (Option<Bound<'py, PyTreeSpec>>, CacheKey) {
+    let key = (none_is_leaf, String::from(namespace), layout);
+    let cache = CACHE.lock().unwrap();
+    let cached = cache
+        .as_ref()
+        .and_then(|cache| cache.get(&key))
+        .map(|cached| cached.bind(py).clone());
+    (cached, key)
+}
+
+// Cache the treespec built for a layout that is not found
+pub(crate) fn insert<'py>(
+    py: Python<'py>,
+    key: CacheKey,
+    treespec: PyTreeSpec,
+) -> PyResult<Bound<'py, PyTreeSpec>> {
+    let treespec = Bound::new(py, treespec)?;
+    let mut cache = CACHE.lock().unwrap();
+    let cache = cache.get_or_insert_with(HashMap::new);
+    if cache.len() < MAX_CACHE_SIZE {
+        cache.insert(key, treespec.clone().unbind());
+    }
+    Ok(treespec)
+}
+
+#[pyfunction]
+pub fn treespec_cache_clear() {
+    // Release the treespecs after unlocking the cache, the destructors of the node data may run
+    // arbitrary Python code
+    let cache = CACHE.lock().unwrap().take();
+    drop(cache);
+}
+
+#[pyfunction]
+pub fn treespec_cache_size() -> usize {
+    CACHE.lock().unwrap().as_ref().map_or(0, HashMap::len)
+}
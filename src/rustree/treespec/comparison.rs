@@ -0,0 +1,243 @@
+// Copyright 2024-2025 Xuehai Pan. All Rights Reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+// =============================================================================
+
+use pyo3::prelude::*;
+use pyo3::types::*;
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use crate::rustree::profiling::{Phase, timed};
+use crate::rustree::registry::PyTreeKind;
+use crate::rustree::treespec::flatten::{DictOrder, Flattener};
+use crate::rustree::treespec::{Node, PyTreeSpec};
+
+fn node_eq(py: Python<'_>, a: &Node, b: &Node) -> PyResult<bool> {
+    if a.num_leaves != b.num_leaves {
+        return Ok(false);
+    }
+    node_shape_eq(py, a, b)
+}
+
+// Compare the nodes by one level only, regardless of the leaves in the subtrees
+pub(crate) fn node_shape_eq(py: Python<'_>, a: &Node, b: &Node) -> PyResult<bool> {
+    if a.kind != b.kind || a.arity != b.arity || a.reference != b.reference {
+        return Ok(false);
+    }
+    match (&a.custom, &b.custom) {
+        (Some(a), Some(b)) if !a.node_type.bind(py).is(b.node_type.bind(py)) => return Ok(false),
+        (Some(_), None) | (None, Some(_)) => return Ok(false),
+        _ => {}
+    }
+    if let (Some(a), Some(b)) = (&a.entries, &b.entries)
+        && !a.bind(py).eq(b.bind(py))?
+    {
+        return Ok(false);
+    }
+    match (&a.node_data, &b.node_data) {
+        (None, None) => Ok(true),
+        (Some(a), Some(b)) => a.bind(py).eq(b.bind(py)),
+        _ => Ok(false),
+    }
+}
+
+// Hash the node by one level, consistent with `node_eq`, the entries are skipped as they are only
+// compared if both nodes have them
+fn node_hash(py: Python<'_>, node: &Node, state: &mut DefaultHasher) -> PyResult<()> {
+    node.kind.hash(state);
+    node.arity.hash(state);
+    node.num_leaves.hash(state);
+    node.reference.hash(state);
+    if let Some(custom) = &node.custom {
+        custom.node_type.bind(py).hash()?.hash(state);
+    }
+    let Some(node_data) = node.node_data.as_ref().map(|data| data.bind(py)) else {
+        return Ok(());
+    };
+    // The keys of the dictionaries are stored in lists, which are unhashable
+    let keys = match node.kind {
+        PyTreeKind::Dict | PyTreeKind::OrderedDict => node_data.clone(),
+        PyTreeKind::DefaultDict => {
+            node_data.get_item(0)?.hash()?.hash(state);
+            node_data.get_item(1)?
+        }
+        _ => {
+            node_data.hash()?.hash(state);
+            return Ok(());
+        }
+    };
+    for key in keys.try_iter()? {
+        key?.hash()?.hash(state);
+    }
+    Ok(())
+}
+
+impl PyTreeSpec {
+    // The reason why the treespecs cannot be combined, a treespec without custom nodes has an empty
+    // namespace and is compatible with any namespace
+    pub(crate) fn namespace_incompatibility(
+        &self,
+        py: Python<'_>,
+        other: &PyTreeSpec,
+    ) -> PyResult<Option<String>> {
+        if self.none_is_leaf != other.none_is_leaf {
+            return Ok(Some(std::format!(
+                "PyTreeSpecs should have the same none_is_leaf value, got {} vs. {}.",
+                match self.none_is_leaf {
+                    true => "True",
+                    false => "False",
+                },
+                match other.none_is_leaf {
+                    true => "True",
+                    false => "False",
+                },
+            )));
+        }
+        if !self.namespace.is_empty()
+            && !other.namespace.is_empty()
+            && self.namespace != other.namespace
+        {
+            return Ok(Some(std::format!(
+                "PyTreeSpecs should have the same namespace, got {} vs. {}.",
+                PyString::new(py, &self.namespace).repr()?,
+                PyString::new(py, &other.namespace).repr()?,
+            )));
+        }
+        Ok(None)
+    }
+
+    pub(crate) fn hash_impl(&self, py: Python<'_>, state: &mut DefaultHasher) -> PyResult<()> {
+        self.none_is_leaf.hash(state);
+        self.namespace.hash(state);
+        self.traversal.len().hash(state);
+        for node in self.traversal.iter() {
+            node_hash(py, node, state)?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn equal_impl(&self, py: Python<'_>, other: &PyTreeSpec) -> PyResult<bool> {
+        if self.none_is_leaf != other.none_is_leaf
+            || self.namespace != other.namespace
+            || self.traversal.len() != other.traversal.len()
+        {
+            return Ok(false);
+        }
+        for (a, b) in self.traversal.iter().zip(other.traversal.iter()) {
+            if !node_eq(py, a, b)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    // Whether the other treespec can be obtained by replacing some leaves with subtrees
+    pub(crate) fn is_prefix_impl(
+        &self,
+        py: Python<'_>,
+        other: &PyTreeSpec,
+        strict: bool,
+    ) -> PyResult<bool> {
+        if self.namespace_incompatibility(py, other)?.is_some() {
+            return Ok(false);
+        }
+        let num_nodes = self.subtree_num_nodes();
+        let other_num_nodes = other.subtree_num_nodes();
+        let is_prefix = self.is_prefix_at(
+            py,
+            &num_nodes,
+            self.traversal.len() - 1,
+            other,
+            &other_num_nodes,
+            other.traversal.len() - 1,
+        )?;
+        Ok(is_prefix && !(strict && self.equal_impl(py, other)?))
+    }
+
+    fn is_prefix_at(
+        &self,
+        py: Python<'_>,
+        num_nodes: &[usize],
+        index: usize,
+        other: &PyTreeSpec,
+        other_num_nodes: &[usize],
+        other_index: usize,
+    ) -> PyResult<bool> {
+        // Compare the shared subtrees at their first occurrences
+        let index = self.traversal[index].reference.unwrap_or(index);
+        let other_index = other.traversal[other_index]
+            .reference
+            .unwrap_or(other_index);
+        let node = &self.traversal[index];
+        if node.kind == PyTreeKind::Leaf {
+            return Ok(true);
+        }
+        if !node_shape_eq(py, node, &other.traversal[other_index])? {
+            return Ok(false);
+        }
+        for (child_index, other_child_index) in self
+            .child_indices(num_nodes, index)
+            .into_iter()
+            .zip(other.child_indices(other_num_nodes, other_index))
+        {
+            if !self.is_prefix_at(
+                py,
+                num_nodes,
+                child_index,
+                other,
+                other_num_nodes,
+                other_child_index,
+            )? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+#[pyfunction]
+#[pyo3(
+    name = "hash",
+    signature = (tree, /, leaf_predicate=None, none_is_leaf=false, namespace="", *, leaf_hash=None)
+)]
+pub fn hash_tree<'py>(
+    tree: &Bound<'py, PyAny>,
+    leaf_predicate: Option<&Bound<'py, PyAny>>,
+    none_is_leaf: Option<bool>,
+    namespace: Option<&str>,
+    leaf_hash: Option<&Bound<'py, PyAny>>,
+) -> PyResult<isize> {
+    let py = tree.py();
+    // Sort the keys so that the hash does not depend on the insertion order of the dictionaries
+    let mut flattener = Flattener::new(
+        py,
+        leaf_predicate,
+        none_is_leaf.unwrap_or(false),
+        namespace.unwrap_or(""),
+        false,
+    )?
+    .with_dict_order(Some(DictOrder::Sorted));
+    flattener.flatten_into(tree, 0)?;
+    let (_, leaves, treespec) = flattener.finish();
+
+    let mut state = DefaultHasher::new();
+    treespec.hash_impl(py, &mut state)?;
+    for leaf in leaves.iter() {
+        let hash = match leaf_hash {
+            Some(leaf_hash) => timed(Phase::Callback, || leaf_hash.call1((leaf,)))?.hash()?,
+            None => leaf.hash()?,
+        };
+        hash.hash(&mut state);
+    }
+    Ok(state.finish() as isize)
+}
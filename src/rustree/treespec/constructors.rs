@@ -0,0 +1,132 @@
+// Copyright 2024-2025 Xuehai Pan. All Rights Reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+// =============================================================================
+
+use pyo3::exceptions::PyTypeError;
+use pyo3::prelude::*;
+use pyo3::types::*;
+
+use crate::rustree::registry::{PyTreeKind, PyTreeTypeRegistry};
+use crate::rustree::treespec::flatten::{DictOrder, NodeChildren, get_children};
+use crate::rustree::treespec::{Node, PyTreeSpec, TreeSpecBuilder};
+
+#[pyfunction]
+#[pyo3(signature = (none_is_leaf=false))]
+pub fn make_leaf(none_is_leaf: bool) -> PyTreeSpec {
+    PyTreeSpec {
+        traversal: vec![Node::leaf()],
+        none_is_leaf,
+        namespace: String::new(),
+    }
+}
+
+#[pyfunction]
+#[pyo3(signature = (none_is_leaf=false))]
+pub fn make_none(none_is_leaf: bool) -> PyTreeSpec {
+    // `None` is a leaf rather than a node without children
+    if none_is_leaf {
+        return make_leaf(none_is_leaf);
+    }
+    PyTreeSpec {
+        traversal: vec![Node {
+            kind: PyTreeKind::None,
+            arity: 0,
+            node_data: None,
+            custom: None,
+            num_leaves: 0,
+            entries: None,
+            reference: None,
+        }],
+        none_is_leaf,
+        namespace: String::new(),
+    }
+}
+
+// Assemble a node from the treespecs of its children, the metadata is validated by the builder
+#[pyfunction]
+#[pyo3(signature = (kind, children, metadata=None, /, none_is_leaf=false, namespace=""))]
+pub fn make_container(
+    py: Python<'_>,
+    kind: PyTreeKind,
+    children: &Bound<'_, PyAny>,
+    metadata: Option<&Bound<'_, PyAny>>,
+    none_is_leaf: bool,
+    namespace: &str,
+) -> PyResult<PyTreeSpec> {
+    let mut builder = TreeSpecBuilder::new(none_is_leaf, namespace);
+    let mut arity = 0;
+    for child in children.try_iter()? {
+        let child = child?;
+        let Ok(treespec) = child.downcast::<PyTreeSpec>() else {
+            return Err(PyTypeError::new_err(std::format!(
+                "Expected a PyTreeSpec as the child of a node, got {}.",
+                child.repr()?,
+            )));
+        };
+        builder.push_treespec(py, treespec.get())?;
+        arity += 1;
+    }
+    builder.push_node(py, kind, arity, metadata, None, None)?;
+    builder.build()
+}
+
+// Flatten a collection by one level, the children that are not treespecs are leaves
+#[pyfunction]
+#[pyo3(signature = (collection, /, none_is_leaf=false, namespace=""))]
+pub fn make_from_collection(
+    collection: &Bound<'_, PyAny>,
+    none_is_leaf: bool,
+    namespace: &str,
+) -> PyResult<PyTreeSpec> {
+    let py = collection.py();
+    let (kind, custom) = PyTreeTypeRegistry::get_kind(collection, none_is_leaf, namespace)?;
+    match kind {
+        PyTreeKind::Leaf => return Ok(make_leaf(none_is_leaf)),
+        PyTreeKind::None => return Ok(make_none(none_is_leaf)),
+        _ => {}
+    }
+    let keep_entries = kind == PyTreeKind::Custom;
+    let NodeChildren {
+        children,
+        entries,
+        node_data,
+    } = get_children(
+        collection,
+        kind,
+        custom.as_ref(),
+        keep_entries,
+        &DictOrder::for_namespace(namespace),
+    )?;
+
+    let mut builder = TreeSpecBuilder::new(none_is_leaf, namespace);
+    for child in children.iter() {
+        match child.downcast::<PyTreeSpec>() {
+            Ok(treespec) => builder.push_treespec(py, treespec.get())?,
+            Err(_) => builder.push_leaf(),
+        }
+    }
+    builder.push_validated(Node {
+        kind,
+        arity: children.len(),
+        node_data,
+        custom,
+        num_leaves: 0,
+        entries: match keep_entries {
+            true => Some(PyTuple::new(py, entries.unwrap())?.unbind()),
+            false => None,
+        },
+        reference: None,
+    });
+    builder.build()
+}
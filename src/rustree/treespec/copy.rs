@@ -0,0 +1,211 @@
+// Copyright 2024-2025 Xuehai Pan. All Rights Reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+// =============================================================================
+
+use pyo3::exceptions::PyRecursionError;
+use pyo3::prelude::*;
+use pyo3::sync::PyOnceLock;
+use pyo3::types::*;
+
+use crate::rustree::profiling::{Phase, timed};
+use crate::rustree::registry::{PyTreeKind, PyTreeTypeRegistry};
+use crate::rustree::treespec::flatten::{
+    DictOrder, MAX_RECURSION_DEPTH, NodeChildren, get_children,
+};
+use crate::rustree::treespec::{Node, make_node};
+
+static DEEPCOPY: PyOnceLock<Py<PyAny>> = PyOnceLock::new();
+
+struct DeepCopy<'py> {
+    copy_leaf: Bound<'py, PyAny>,
+    // Map `id(obj)` to the copy of `obj`, shared with the leaf copy function
+    memo: Bound<'py, PyDict>,
+    // The originals are kept alive while the memo is alive, see `copy._keep_alive`
+    keep_alive: Bound<'py, PyList>,
+}
+
+// Rebuild the containers of a tree in a single pass
+struct Copier<'a, 'py> {
+    leaf_predicate: Option<Bound<'py, PyAny>>,
+    none_is_leaf: bool,
+    namespace: &'a str,
+    deep: Option<DeepCopy<'py>>,
+    // Replace the `None` values with the sentinel, whether they are leaves or nodes
+    sentinel: Option<Bound<'py, PyAny>>,
+}
+
+impl<'py> Copier<'_, 'py> {
+    fn copy(&self, obj: &Bound<'py, PyAny>, depth: usize) -> PyResult<Bound<'py, PyAny>> {
+        let Some(deep) = &self.deep else {
+            return self.copy_impl(obj, depth);
+        };
+        // Shared leaves and subtrees are copied only once
+        let key = obj.as_ptr() as usize;
+        if let Some(copied) = deep.memo.get_item(key)? {
+            return Ok(copied);
+        }
+        let copied = self.copy_impl(obj, depth)?;
+        if !copied.is(obj) {
+            deep.memo.set_item(key, &copied)?;
+            deep.keep_alive.append(obj)?;
+        }
+        Ok(copied)
+    }
+
+    fn copy_leaf(&self, leaf: &Bound<'py, PyAny>) -> PyResult<Bound<'py, PyAny>> {
+        match &self.deep {
+            Some(deep) => timed(Phase::Callback, || deep.copy_leaf.call1((leaf, &deep.memo))),
+            None => Ok(leaf.clone()),
+        }
+    }
+
+    fn copy_impl(&self, obj: &Bound<'py, PyAny>, depth: usize) -> PyResult<Bound<'py, PyAny>> {
+        let py = obj.py();
+        if depth > MAX_RECURSION_DEPTH {
+            return Err(PyRecursionError::new_err(
+                "Maximum recursion depth exceeded during copying the tree.",
+            ));
+        }
+
+        if let Some(sentinel) = &self.sentinel
+            && obj.is_none()
+        {
+            return Ok(sentinel.clone());
+        }
+        if let Some(leaf_predicate) = &self.leaf_predicate
+            && timed(Phase::Callback, || leaf_predicate.call1((obj,)))?.is_truthy()?
+        {
+            return self.copy_leaf(obj);
+        }
+        let (kind, custom) = PyTreeTypeRegistry::get_kind(obj, self.none_is_leaf, self.namespace)?;
+        if kind == PyTreeKind::Leaf {
+            return self.copy_leaf(obj);
+        }
+
+        // Keep the insertion order of the dictionaries in the copy
+        let NodeChildren {
+            children,
+            node_data,
+            ..
+        } = get_children(obj, kind, custom.as_ref(), false, &DictOrder::Insertion)?;
+        let children = children
+            .iter()
+            .map(|child| self.copy(child, depth + 1))
+            .collect::<PyResult<Vec<_>>>()?;
+        let node = Node {
+            kind,
+            arity: children.len(),
+            node_data,
+            custom,
+            num_leaves: 0,
+            entries: None,
+            reference: None,
+        };
+        make_node(py, &node, children)
+    }
+}
+
+#[pyfunction]
+#[pyo3(
+    name = "copy",
+    signature = (tree, /, leaf_predicate=None, none_is_leaf=false, namespace="")
+)]
+pub fn copy_tree<'py>(
+    tree: &Bound<'py, PyAny>,
+    leaf_predicate: Option<&Bound<'py, PyAny>>,
+    none_is_leaf: Option<bool>,
+    namespace: Option<&str>,
+) -> PyResult<Bound<'py, PyAny>> {
+    let namespace = namespace.unwrap_or("");
+    let copier = Copier {
+        leaf_predicate: PyTreeTypeRegistry::leaf_predicate(tree.py(), leaf_predicate, namespace),
+        none_is_leaf: none_is_leaf.unwrap_or(false),
+        namespace,
+        deep: None,
+        sentinel: None,
+    };
+    copier.copy(tree, 0)
+}
+
+#[pyfunction]
+#[pyo3(signature = (
+    tree,
+    /,
+    leaf_predicate=None,
+    none_is_leaf=false,
+    namespace="",
+    *,
+    copy_leaf=None,
+    memo=None,
+))]
+pub fn deepcopy<'py>(
+    py: Python<'py>,
+    tree: &Bound<'py, PyAny>,
+    leaf_predicate: Option<&Bound<'py, PyAny>>,
+    none_is_leaf: Option<bool>,
+    namespace: Option<&str>,
+    copy_leaf: Option<&Bound<'py, PyAny>>,
+    memo: Option<&Bound<'py, PyDict>>,
+) -> PyResult<Bound<'py, PyAny>> {
+    let copy_leaf = match copy_leaf {
+        Some(copy_leaf) => copy_leaf.clone(),
+        None => DEEPCOPY.import(py, "copy", "deepcopy")?.clone(),
+    };
+    let memo = match memo {
+        Some(memo) => memo.clone(),
+        None => PyDict::new(py),
+    };
+    // Follow the convention of `copy.deepcopy`, which stores the originals at `memo[id(memo)]`
+    let memo_key = memo.as_ptr() as usize;
+    let keep_alive = match memo.get_item(memo_key)? {
+        Some(keep_alive) => keep_alive.downcast_into::<PyList>()?,
+        None => {
+            let keep_alive = PyList::empty(py);
+            memo.set_item(memo_key, &keep_alive)?;
+            keep_alive
+        }
+    };
+    let namespace = namespace.unwrap_or("");
+    let copier = Copier {
+        leaf_predicate: PyTreeTypeRegistry::leaf_predicate(tree.py(), leaf_predicate, namespace),
+        none_is_leaf: none_is_leaf.unwrap_or(false),
+        namespace,
+        deep: Some(DeepCopy {
+            copy_leaf,
+            memo,
+            keep_alive,
+        }),
+        sentinel: None,
+    };
+    copier.copy(tree, 0)
+}
+
+#[pyfunction]
+#[pyo3(signature = (sentinel, tree, /, leaf_predicate=None, namespace=""))]
+pub fn replace_nones<'py>(
+    sentinel: &Bound<'py, PyAny>,
+    tree: &Bound<'py, PyAny>,
+    leaf_predicate: Option<&Bound<'py, PyAny>>,
+    namespace: Option<&str>,
+) -> PyResult<Bound<'py, PyAny>> {
+    let namespace = namespace.unwrap_or("");
+    let copier = Copier {
+        leaf_predicate: PyTreeTypeRegistry::leaf_predicate(tree.py(), leaf_predicate, namespace),
+        none_is_leaf: false,
+        namespace,
+        deep: None,
+        sentinel: Some(sentinel.clone()),
+    };
+    copier.copy(tree, 0)
+}
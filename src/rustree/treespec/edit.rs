@@ -0,0 +1,540 @@
+// Copyright 2024-2025 Xuehai Pan. All Rights Reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+// =============================================================================
+
+use pyo3::exceptions::{PyKeyError, PyTypeError, PyValueError};
+use pyo3::intern;
+use pyo3::prelude::*;
+use pyo3::types::*;
+
+use crate::rustree::accessors::DataclassEntry;
+use crate::rustree::registry::{PyTreeKind, PyTreeTypeRegistry};
+use crate::rustree::treespec::comparison::node_shape_eq;
+use crate::rustree::treespec::flatten::NodeChildren;
+use crate::rustree::treespec::query::node_entries;
+use crate::rustree::treespec::serialization::repr_node_shape;
+use crate::rustree::treespec::{
+    DictOrder, Node, PyTreeSpec, get_children, make_node, path_entry_type,
+};
+
+// Merge two treespecs into the smallest treespec that both of them are prefixes of
+struct SuffixBroadcaster<'a, 'py> {
+    treespecs: [&'a PyTreeSpec; 2],
+    num_nodes: [Vec<usize>; 2],
+    path: Vec<Bound<'py, PyAny>>,
+    traversal: Vec<Node>,
+}
+
+impl<'py> SuffixBroadcaster<'_, 'py> {
+    // Append a copy of the subtree of a treespec, the shared subtrees are expanded
+    fn copy_subtree(&mut self, py: Python<'py>, which: usize, index: usize) {
+        let subtree = self.treespecs[which].subtree(py, &self.num_nodes[which], index);
+        let start = self.traversal.len();
+        self.traversal
+            .extend(subtree.traversal.into_iter().map(|mut node| {
+                node.reference = node.reference.map(|target| target + start);
+                node
+            }));
+    }
+
+    fn broadcast(&mut self, py: Python<'py>, indices: [usize; 2]) -> PyResult<()> {
+        let [left, right] = self.treespecs;
+        let [index, other_index] = [
+            left.traversal[indices[0]].reference.unwrap_or(indices[0]),
+            right.traversal[indices[1]].reference.unwrap_or(indices[1]),
+        ];
+        let node = &left.traversal[index];
+        let other = &right.traversal[other_index];
+        if node.kind == PyTreeKind::Leaf {
+            self.copy_subtree(py, 1, other_index);
+            return Ok(());
+        }
+        if other.kind == PyTreeKind::Leaf {
+            self.copy_subtree(py, 0, index);
+            return Ok(());
+        }
+        if !node_shape_eq(py, node, other)? {
+            return Err(PyValueError::new_err(std::format!(
+                "PyTreeSpecs cannot be broadcast to a common suffix at path {}; \
+                the node {} does not match the node {}.",
+                PyTuple::new(py, &self.path)?.repr()?,
+                repr_node_shape(py, node)?,
+                repr_node_shape(py, other)?,
+            )));
+        }
+        let child_indices = left.child_indices(&self.num_nodes[0], index);
+        let other_child_indices = right.child_indices(&self.num_nodes[1], other_index);
+        for ((entry, child_index), other_child_index) in node_entries(py, node)?
+            .into_iter()
+            .zip(child_indices)
+            .zip(other_child_indices)
+        {
+            self.path.push(entry);
+            let result = self.broadcast(py, [child_index, other_child_index]);
+            self.path.pop();
+            result?;
+        }
+        let mut node = node.clone_ref(py);
+        node.reference = None;
+        self.traversal.push(node);
+        Ok(())
+    }
+}
+
+impl PyTreeSpec {
+    // The namespace of a treespec combined from the nodes of both treespecs
+    pub(crate) fn merged_namespace(&self, py: Python<'_>, other: &PyTreeSpec) -> PyResult<String> {
+        if let Some(reason) = self.namespace_incompatibility(py, other)? {
+            return Err(PyValueError::new_err(reason));
+        }
+        Ok(match self.namespace.is_empty() {
+            true => other.namespace.clone(),
+            false => self.namespace.clone(),
+        })
+    }
+
+    pub(crate) fn replace_subtree_impl(
+        &self,
+        py: Python<'_>,
+        path: &Bound<'_, PyAny>,
+        new_subspec: &PyTreeSpec,
+    ) -> PyResult<PyTreeSpec> {
+        if path.is_instance_of::<PyString>() {
+            return Err(PyTypeError::new_err(std::format!(
+                "Expected a path as a sequence of path entries, got {}.",
+                path.repr()?,
+            )));
+        }
+        let namespace = self.merged_namespace(py, new_subspec)?;
+        let (index, _) = self.locate(py, path)?;
+        // The subtree is a contiguous range of the post-order traversal ending at its root
+        let num_nodes = self.subtree_num_nodes();
+        let start = index + 1 - num_nodes[index];
+
+        let mut traversal: Vec<Node> = Vec::with_capacity(
+            self.traversal.len() - num_nodes[index] + new_subspec.traversal.len(),
+        );
+        traversal.extend(
+            self.traversal[..start]
+                .iter()
+                .map(|node| node.clone_ref(py)),
+        );
+        traversal.extend(new_subspec.traversal.iter().map(|node| {
+            let mut node = node.clone_ref(py);
+            node.reference = node.reference.map(|target| target + start);
+            node
+        }));
+        for node in self.traversal[index + 1..].iter() {
+            let mut node = node.clone_ref(py);
+            // Shift the references to the shared subtrees after the replaced subtree
+            node.reference = match node.reference {
+                Some(target) if target > index => {
+                    Some(target + new_subspec.traversal.len() - num_nodes[index])
+                }
+                Some(target) if target >= start => {
+                    return Err(PyValueError::new_err(std::format!(
+                        "Cannot replace the subtree at path {} that is shared by other occurrences.",
+                        path.repr()?,
+                    )));
+                }
+                reference => reference,
+            };
+            traversal.push(node);
+        }
+
+        // Recompute the leaf counts of the ancestors of the replaced subtree
+        let mut agenda: Vec<usize> = Vec::new();
+        for node in traversal.iter_mut() {
+            if node.kind != PyTreeKind::Leaf {
+                let size = agenda.len();
+                node.num_leaves = agenda.drain(size - node.arity..).sum();
+            }
+            agenda.push(node.num_leaves);
+        }
+        Ok(PyTreeSpec {
+            traversal,
+            none_is_leaf: self.none_is_leaf,
+            namespace,
+        })
+    }
+
+    // Replace every leaf with the inner treespec
+    pub(crate) fn compose_impl(&self, py: Python<'_>, inner: &PyTreeSpec) -> PyResult<PyTreeSpec> {
+        let namespace = self.merged_namespace(py, inner)?;
+        let mut traversal: Vec<Node> = Vec::with_capacity(
+            self.traversal.len() + self.num_leaves() * (inner.traversal.len() - 1),
+        );
+        // The new traversal indices of the nodes, for the references to the shared subtrees
+        let mut indices = Vec::with_capacity(self.traversal.len());
+        for node in self.traversal.iter() {
+            match node.kind {
+                PyTreeKind::Leaf => {
+                    let start = traversal.len();
+                    traversal.extend(inner.traversal.iter().map(|node| {
+                        let mut node = node.clone_ref(py);
+                        node.reference = node.reference.map(|target| target + start);
+                        node
+                    }));
+                }
+                _ => {
+                    let mut node = node.clone_ref(py);
+                    node.reference = node.reference.map(|target| indices[target]);
+                    node.num_leaves = match node.reference {
+                        Some(_) => 0,
+                        None => node.num_leaves * inner.num_leaves(),
+                    };
+                    traversal.push(node);
+                }
+            }
+            indices.push(traversal.len() - 1);
+        }
+        Ok(PyTreeSpec {
+            traversal,
+            none_is_leaf: self.none_is_leaf,
+            namespace,
+        })
+    }
+
+    pub(crate) fn transform_impl<'py>(
+        &self,
+        py: Python<'py>,
+        f_node: Option<&Bound<'py, PyAny>>,
+        f_leaf: Option<&Bound<'py, PyAny>>,
+    ) -> PyResult<PyTreeSpec> {
+        let mut namespace = self.namespace.clone();
+        // The replacement of each node, the references to the shared subtrees are kept as is
+        let mut replacements: Vec<Option<Bound<'py, PyTreeSpec>>> =
+            Vec::with_capacity(self.traversal.len());
+        for (index, node) in self.traversal.iter().enumerate() {
+            if node.reference.is_some() {
+                replacements.push(None);
+                continue;
+            }
+            let (callback, treespec) = match node.kind {
+                PyTreeKind::Leaf => (
+                    f_leaf,
+                    PyTreeSpec {
+                        traversal: vec![Node::leaf()],
+                        none_is_leaf: self.none_is_leaf,
+                        namespace: String::new(),
+                    },
+                ),
+                _ => (f_node, self.one_level(py, index)),
+            };
+            let Some(callback) = callback else {
+                replacements.push(Some(Bound::new(py, treespec)?));
+                continue;
+            };
+            let result = callback.call1((treespec,))?;
+            let replacement = match result.downcast::<PyTreeSpec>() {
+                Ok(replacement) => replacement.clone(),
+                Err(_) => {
+                    return Err(PyTypeError::new_err(std::format!(
+                        "Expected the {} callback to return a PyTreeSpec, got {}.",
+                        match node.kind {
+                            PyTreeKind::Leaf => "leaf",
+                            _ => "node",
+                        },
+                        result.repr()?,
+                    )));
+                }
+            };
+            let treespec = replacement.get();
+            if node.kind != PyTreeKind::Leaf && treespec.num_leaves() != node.arity {
+                return Err(PyValueError::new_err(std::format!(
+                    "Expected the node callback to return a PyTreeSpec with {} leaves, got {}.",
+                    node.arity,
+                    treespec.num_leaves(),
+                )));
+            }
+            if let Some(reason) = self.namespace_incompatibility(py, treespec)? {
+                return Err(PyValueError::new_err(reason));
+            }
+            if namespace.is_empty() {
+                namespace = treespec.namespace.clone();
+            } else if !treespec.namespace.is_empty() && treespec.namespace != namespace {
+                return Err(PyValueError::new_err(std::format!(
+                    "PyTreeSpecs should have the same namespace, got {} vs. {}.",
+                    PyString::new(py, &namespace).repr()?,
+                    PyString::new(py, &treespec.namespace).repr()?,
+                )));
+            }
+            replacements.push(Some(replacement));
+        }
+
+        let num_nodes = self.subtree_num_nodes();
+        let mut traversal = Vec::with_capacity(self.traversal.len());
+        let mut roots = vec![0; self.traversal.len()];
+        self.emit_transformed(
+            py,
+            &num_nodes,
+            &replacements,
+            self.traversal.len() - 1,
+            &mut traversal,
+            &mut roots,
+        );
+        let mut agenda: Vec<usize> = Vec::new();
+        for node in traversal.iter_mut() {
+            if node.kind != PyTreeKind::Leaf {
+                let size = agenda.len();
+                node.num_leaves = agenda.drain(size - node.arity..).sum();
+            }
+            agenda.push(node.num_leaves);
+        }
+        Ok(PyTreeSpec {
+            traversal,
+            none_is_leaf: self.none_is_leaf,
+            namespace,
+        })
+    }
+
+    // Emit the replacement of a node with the transformed subtrees of the children in place of its
+    // leaves
+    fn emit_transformed(
+        &self,
+        py: Python<'_>,
+        num_nodes: &[usize],
+        replacements: &[Option<Bound<'_, PyTreeSpec>>],
+        index: usize,
+        traversal: &mut Vec<Node>,
+        roots: &mut [usize],
+    ) {
+        let Some(replacement) = replacements[index].as_ref().map(Bound::get) else {
+            let mut node = self.traversal[index].clone_ref(py);
+            node.reference = node.reference.map(|target| roots[target]);
+            roots[index] = traversal.len();
+            traversal.push(node);
+            return;
+        };
+        let mut child_indices = match self.traversal[index].kind {
+            PyTreeKind::Leaf => Vec::new(),
+            _ => self.child_indices(num_nodes, index),
+        }
+        .into_iter();
+        // The new traversal indices of the nodes of the replacement
+        let mut indices = Vec::with_capacity(replacement.traversal.len());
+        for node in replacement.traversal.iter() {
+            match node.kind {
+                PyTreeKind::Leaf if let Some(child_index) = child_indices.next() => {
+                    self.emit_transformed(
+                        py,
+                        num_nodes,
+                        replacements,
+                        child_index,
+                        traversal,
+                        roots,
+                    );
+                }
+                _ => {
+                    let mut node = node.clone_ref(py);
+                    node.reference = node.reference.map(|target| indices[target]);
+                    traversal.push(node);
+                }
+            }
+            indices.push(traversal.len() - 1);
+        }
+        roots[index] = traversal.len() - 1;
+    }
+
+    pub(crate) fn broadcast_to_common_suffix_impl(
+        &self,
+        py: Python<'_>,
+        other: &PyTreeSpec,
+    ) -> PyResult<PyTreeSpec> {
+        let namespace = self.merged_namespace(py, other)?;
+        let mut broadcaster = SuffixBroadcaster {
+            treespecs: [self, other],
+            num_nodes: [self.subtree_num_nodes(), other.subtree_num_nodes()],
+            path: Vec::new(),
+            traversal: Vec::new(),
+        };
+        broadcaster.broadcast(py, [self.traversal.len() - 1, other.traversal.len() - 1])?;
+        let mut traversal = broadcaster.traversal;
+        let mut agenda: Vec<usize> = Vec::new();
+        for node in traversal.iter_mut() {
+            if node.kind != PyTreeKind::Leaf {
+                let size = agenda.len();
+                node.num_leaves = agenda.drain(size - node.arity..).sum();
+            }
+            agenda.push(node.num_leaves);
+        }
+        Ok(PyTreeSpec {
+            traversal,
+            none_is_leaf: self.none_is_leaf,
+            namespace,
+        })
+    }
+}
+
+// A node on the path of a lens, flattened by one level with the dictionary keys in the insertion
+// order so the rebuilt node keeps the key order recorded in its node data
+struct Focus<'py> {
+    obj: Bound<'py, PyAny>,
+    node: Node,
+    children: Vec<Bound<'py, PyAny>>,
+    index: usize,
+    // The field to pass to `copy.replace` for the dataclass nodes on Python 3.13+
+    field: Option<Bound<'py, PyAny>>,
+}
+
+fn focus<'py>(
+    obj: &Bound<'py, PyAny>,
+    key: &Bound<'py, PyAny>,
+    none_is_leaf: bool,
+    namespace: &str,
+) -> PyResult<Focus<'py>> {
+    let py = obj.py();
+    let is_leaf = match PyTreeTypeRegistry::leaf_predicate(py, None, namespace) {
+        Some(leaf_predicate) => leaf_predicate.call1((obj,))?.is_truthy()?,
+        None => false,
+    };
+    let (kind, custom) = match is_leaf {
+        true => (PyTreeKind::Leaf, None),
+        false => PyTreeTypeRegistry::get_kind(obj, none_is_leaf, namespace)?,
+    };
+    if kind == PyTreeKind::Leaf {
+        return Err(PyTypeError::new_err(std::format!(
+            "Cannot focus into a leaf node {}.",
+            obj.repr()?,
+        )));
+    }
+    let NodeChildren {
+        children,
+        entries,
+        node_data,
+    } = get_children(obj, kind, custom.as_ref(), true, &DictOrder::Insertion)?;
+    let entries = entries.unwrap_or_default();
+
+    // Match the key against the raw path entries first, then the field names of the entries
+    let mut index = None;
+    for (i, entry) in entries.iter().enumerate() {
+        if entry.eq(key)? {
+            index = Some(i);
+            break;
+        }
+    }
+    let entry_type = path_entry_type(py, kind, custom.as_ref())?;
+    let node_type = match &custom {
+        Some(registration) => registration.node_type.bind(py).clone(),
+        None => obj.get_type(),
+    };
+    let entry_at = |i: usize| entry_type.call1((&entries[i], &node_type, kind));
+    if index.is_none() {
+        for i in 0..entries.len() {
+            let entry = entry_at(i)?;
+            let name = match entry.getattr(intern!(py, "field")) {
+                Ok(field) => Some(field),
+                Err(_) => entry.getattr(intern!(py, "name")).ok(),
+            };
+            if let Some(name) = name
+                && name.eq(key)?
+            {
+                index = Some(i);
+                break;
+            }
+        }
+    }
+    if index.is_none()
+        && !key.is_instance_of::<PyBool>()
+        && let Ok(position) = key.extract::<isize>()
+        && (-(entries.len() as isize)..0).contains(&position)
+    {
+        index = Some((position + entries.len() as isize) as usize);
+    }
+    let Some(index) = index else {
+        return Err(PyKeyError::new_err(std::format!(
+            "Key {} is not found in node {}.",
+            key.repr()?,
+            obj.repr()?,
+        )));
+    };
+
+    let mut field = None;
+    if py.version_info() >= (3, 13) && entry_type.is_subclass_of::<DataclassEntry>()? {
+        let entry = entry_at(index)?;
+        let name = entry.getattr(intern!(py, "field"))?;
+        if entry.getattr(intern!(py, "init_fields"))?.contains(&name)? {
+            field = Some(name);
+        }
+    }
+    Ok(Focus {
+        obj: obj.clone(),
+        node: Node {
+            kind,
+            arity: children.len(),
+            node_data,
+            custom,
+            num_leaves: 0,
+            entries: None,
+            reference: None,
+        },
+        children,
+        index,
+        field,
+    })
+}
+
+#[pyfunction]
+#[pyo3(signature = (tree, path, /, none_is_leaf=false, namespace=""))]
+pub fn get_at_path<'py>(
+    tree: &Bound<'py, PyAny>,
+    path: &Bound<'py, PyAny>,
+    none_is_leaf: bool,
+    namespace: &str,
+) -> PyResult<Bound<'py, PyAny>> {
+    let mut obj = tree.clone();
+    for key in path.try_iter()? {
+        let mut focus = focus(&obj, &key?, none_is_leaf, namespace)?;
+        obj = focus.children.swap_remove(focus.index);
+    }
+    Ok(obj)
+}
+
+// Only the nodes along the path are rebuilt, all other subtrees are shared with the input
+#[pyfunction]
+#[pyo3(signature = (tree, path, value, /, none_is_leaf=false, namespace=""))]
+pub fn replace_at_path<'py>(
+    tree: &Bound<'py, PyAny>,
+    path: &Bound<'py, PyAny>,
+    value: &Bound<'py, PyAny>,
+    none_is_leaf: bool,
+    namespace: &str,
+) -> PyResult<Bound<'py, PyAny>> {
+    let py = tree.py();
+    let mut stack = Vec::new();
+    let mut obj = tree.clone();
+    for key in path.try_iter()? {
+        let focus = focus(&obj, &key?, none_is_leaf, namespace)?;
+        obj = focus.children[focus.index].clone();
+        stack.push(focus);
+    }
+
+    let mut child = value.clone();
+    while let Some(mut focus) = stack.pop() {
+        child = match focus.field {
+            Some(field) => {
+                let changes = PyDict::new(py);
+                changes.set_item(field, &child)?;
+                py.import("copy")?
+                    .getattr("replace")?
+                    .call((&focus.obj,), Some(&changes))?
+            }
+            None => {
+                focus.children[focus.index] = child;
+                make_node(py, &focus.node, focus.children)?
+            }
+        };
+    }
+    Ok(child)
+}
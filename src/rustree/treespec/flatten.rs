@@ -13,10 +13,672 @@
 // limitations under the License.
 // =============================================================================
 
+use pyo3::exceptions::{PyRecursionError, PyRuntimeError, PyTypeError, PyValueError};
 use pyo3::prelude::*;
+use pyo3::types::*;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 
-use crate::rustree::pytypes::{is_namedtuple_class, is_structseq_class};
-use crate::rustree::registry::PyTreeTypeRegistry;
+use crate::rustree::logging::{debug, namespace_repr};
+use crate::rustree::profiling::{Phase, record_allocations, timed};
+use crate::rustree::registry::{PyTreeKind, PyTreeTypeRegistration, PyTreeTypeRegistry};
+use crate::rustree::tracing::Tracer;
+use crate::rustree::treespec::cache::{insert, lookup, push_layout};
+use crate::rustree::treespec::comparison::node_shape_eq;
+use crate::rustree::treespec::serialization::repr_node_shape;
+use crate::rustree::treespec::{Node, PyTreeSpec, new_accessor, path_entry_type};
+
+pub(crate) const MAX_RECURSION_DEPTH: usize = 1000;
+
+// The namespace of the treespec and its layout for the treespec cache
+type NamespacedLayout = (String, Vec<usize>);
+
+pub(crate) struct NodeChildren<'py> {
+    pub(crate) children: Vec<Bound<'py, PyAny>>,
+    pub(crate) entries: Option<Vec<Bound<'py, PyAny>>>,
+    pub(crate) node_data: Option<Py<PyAny>>,
+}
+
+// A weak reference to the leaf, or the leaf itself if its type does not support weak references
+pub(crate) fn weak_leaf<'py>(leaf: &Bound<'py, PyAny>) -> PyResult<Bound<'py, PyAny>> {
+    match PyWeakrefReference::new(leaf) {
+        Ok(reference) => Ok(reference.into_any()),
+        Err(err) if err.is_instance_of::<PyTypeError>(leaf.py()) => Ok(leaf.clone()),
+        Err(err) => Err(err),
+    }
+}
+
+pub(crate) fn total_order_sorted<'py>(keys: &Bound<'py, PyList>) -> PyResult<Bound<'py, PyList>> {
+    let py = keys.py();
+    let sorted = PyList::new(py, keys)?;
+    match sorted.sort() {
+        Ok(()) => Ok(sorted),
+        Err(err) if err.is_instance_of::<PyTypeError>(py) => {
+            // Add `{obj.__class__.__module__}.{obj.__class__.__qualname__}` to the key order to make
+            // it sortable between different types (e.g., `int` vs. `str`)
+            let sort_key_fn = PyCFunction::new_closure(
+                py,
+                None,
+                None,
+                |args: &Bound<'_, PyTuple>,
+                 _kwargs: Option<&Bound<'_, PyDict>>|
+                 -> PyResult<Py<PyAny>> {
+                    let py = args.py();
+                    let obj = args.get_item(0)?;
+                    let cls = obj.get_type();
+                    let qualname = std::format!("{}.{}", cls.module()?, cls.qualname()?);
+                    Ok(
+                        PyTuple::new(py, [qualname.into_pyobject(py)?.into_any(), obj])?
+                            .into_any()
+                            .unbind(),
+                    )
+                },
+            )?;
+            let kwargs = PyDict::new(py);
+            kwargs.set_item("key", sort_key_fn)?;
+            let sorted = PyList::new(py, keys)?;
+            match sorted.call_method("sort", (), Some(&kwargs)) {
+                Ok(_) => Ok(sorted),
+                // Cannot sort the keys (e.g., user-defined types), fallback to the original order
+                Err(err) if err.is_instance_of::<PyTypeError>(py) => PyList::new(py, keys),
+                Err(err) => Err(err),
+            }
+        }
+        Err(err) => Err(err),
+    }
+}
+
+// The traversal order of the keys of `dict` and `defaultdict`, `OrderedDict` is always in insertion
+// order
+#[derive(Clone)]
+pub(crate) enum DictOrder<'py> {
+    Sorted,
+    Insertion,
+    // Sorted by a key function
+    Key(Bound<'py, PyAny>),
+    // Follow the key order recorded in a treespec if the keys match
+    Given(Bound<'py, PyAny>),
+}
+
+impl<'py> DictOrder<'py> {
+    // The default order set by `set_dict_insertion_ordered` for the namespace
+    pub(crate) fn for_namespace(namespace: &str) -> Self {
+        match PyTreeTypeRegistry::is_dict_insertion_ordered(Some(namespace), None) {
+            true => DictOrder::Insertion,
+            false => DictOrder::Sorted,
+        }
+    }
+
+    // Follow the key order recorded in a dictionary node
+    pub(crate) fn of_node(py: Python<'py>, node: &Node) -> PyResult<Option<Self>> {
+        let node_data = node.node_data.as_ref().map(|data| data.bind(py));
+        Ok(match node.kind {
+            PyTreeKind::Dict => Some(DictOrder::Given(node_data.unwrap().clone())),
+            PyTreeKind::DefaultDict => Some(DictOrder::Given(node_data.unwrap().get_item(1)?)),
+            _ => None,
+        })
+    }
+
+    // Parse the `sort_dict_keys` argument, `None` means the default order
+    pub(crate) fn from_arg(arg: Option<&Bound<'py, PyAny>>) -> PyResult<Option<Self>> {
+        let Some(arg) = arg.filter(|arg| !arg.is_none()) else {
+            return Ok(None);
+        };
+        if let Ok(sort) = arg.downcast::<PyBool>() {
+            return Ok(Some(match sort.is_true() {
+                true => DictOrder::Sorted,
+                false => DictOrder::Insertion,
+            }));
+        }
+        if arg.is_callable() {
+            return Ok(Some(DictOrder::Key(arg.clone())));
+        }
+        Err(PyTypeError::new_err(std::format!(
+            "Expected a bool or a callable for `sort_dict_keys`, got {}.",
+            arg.repr()?,
+        )))
+    }
+
+    fn order_keys(&self, dict: &Bound<'py, PyDict>) -> PyResult<Bound<'py, PyList>> {
+        let py = dict.py();
+        match self {
+            DictOrder::Sorted => total_order_sorted(&dict.keys()),
+            DictOrder::Insertion => Ok(dict.keys()),
+            DictOrder::Key(key) => {
+                let keys = dict.keys();
+                let kwargs = PyDict::new(py);
+                kwargs.set_item("key", key)?;
+                keys.call_method("sort", (), Some(&kwargs))?;
+                Ok(keys)
+            }
+            DictOrder::Given(keys) => {
+                let keys = PyList::new(py, keys.try_iter()?.collect::<PyResult<Vec<_>>>()?)?;
+                if keys.len() == dict.len() {
+                    let mut matched = true;
+                    for key in keys.iter() {
+                        if !dict.contains(key)? {
+                            matched = false;
+                            break;
+                        }
+                    }
+                    if matched {
+                        return Ok(keys);
+                    }
+                }
+                Ok(dict.keys())
+            }
+        }
+    }
+}
+
+pub(crate) fn get_children<'py>(
+    obj: &Bound<'py, PyAny>,
+    kind: PyTreeKind,
+    custom: Option<&Arc<PyTreeTypeRegistration>>,
+    with_entries: bool,
+    dict_order: &DictOrder<'py>,
+) -> PyResult<NodeChildren<'py>> {
+    let py = obj.py();
+    let index_entries = |arity: usize| -> PyResult<Option<Vec<Bound<'py, PyAny>>>> {
+        if !with_entries {
+            return Ok(None);
+        }
+        (0..arity)
+            .map(|i| Ok(i.into_pyobject(py)?.into_any()))
+            .collect::<PyResult<Vec<_>>>()
+            .map(Some)
+    };
+
+    Ok(match kind {
+        PyTreeKind::Leaf | PyTreeKind::None => NodeChildren {
+            children: Vec::new(),
+            entries: with_entries.then(Vec::new),
+            node_data: None,
+        },
+        PyTreeKind::Tuple | PyTreeKind::NamedTuple | PyTreeKind::StructSequence => {
+            let children: Vec<_> = obj.downcast::<PyTuple>()?.iter().collect();
+            let node_data = match kind {
+                PyTreeKind::Tuple => None,
+                _ => Some(obj.get_type().into_any().unbind()),
+            };
+            NodeChildren {
+                entries: index_entries(children.len())?,
+                children,
+                node_data,
+            }
+        }
+        PyTreeKind::List => {
+            let children: Vec<_> = obj.downcast::<PyList>()?.iter().collect();
+            NodeChildren {
+                entries: index_entries(children.len())?,
+                children,
+                node_data: None,
+            }
+        }
+        PyTreeKind::Dict | PyTreeKind::OrderedDict | PyTreeKind::DefaultDict => {
+            let dict = obj.downcast::<PyDict>()?;
+            let keys = match kind {
+                PyTreeKind::OrderedDict => dict.keys(),
+                _ => dict_order.order_keys(dict)?,
+            };
+            let children = keys
+                .iter()
+                .map(|key| dict.as_any().get_item(key))
+                .collect::<PyResult<Vec<_>>>()?;
+            let entries = with_entries.then(|| keys.iter().collect());
+            let node_data = match kind {
+                PyTreeKind::DefaultDict => {
+                    PyTuple::new(py, [obj.getattr("default_factory")?, keys.into_any()])?
+                        .into_any()
+                        .unbind()
+                }
+                _ => keys.into_any().unbind(),
+            };
+            NodeChildren {
+                children,
+                entries,
+                node_data: Some(node_data),
+            }
+        }
+        PyTreeKind::Deque => {
+            let children = obj.try_iter()?.collect::<PyResult<Vec<_>>>()?;
+            NodeChildren {
+                entries: index_entries(children.len())?,
+                children,
+                node_data: Some(obj.getattr("maxlen")?.unbind()),
+            }
+        }
+        PyTreeKind::Custom if custom.unwrap().fields.is_some() => {
+            let fields = custom.unwrap().fields.as_ref().unwrap();
+            let getattrs = |names: &Bound<'py, PyTuple>| {
+                names
+                    .iter()
+                    .map(|name| obj.getattr(name.downcast::<PyString>()?))
+                    .collect::<PyResult<Vec<_>>>()
+            };
+            let (data, meta) = (fields.data.bind(py), fields.meta.bind(py));
+            let children = getattrs(data)?;
+            NodeChildren {
+                entries: match with_entries {
+                    true => Some(data.iter().collect()),
+                    false => index_entries(children.len())?,
+                },
+                children,
+                // The metadata fields are compared and hashed with the treespec as a tuple
+                node_data: match meta.is_empty() {
+                    true => Some(py.None()),
+                    false => Some(PyTuple::new(py, getattrs(meta)?)?.into_any().unbind()),
+                },
+            }
+        }
+        PyTreeKind::Custom => {
+            let registration = custom.unwrap();
+            let out = timed(Phase::Callback, || {
+                registration
+                    .flatten_func
+                    .as_ref()
+                    .unwrap()
+                    .bind(py)
+                    .call1((obj,))
+            })?;
+            let out = match out.downcast::<PyTuple>() {
+                Ok(out) if out.len() == 2 || out.len() == 3 => out.clone(),
+                _ => {
+                    return Err(PyRuntimeError::new_err(std::format!(
+                        "PyTree custom flatten function for type {} should return a 2- or 3-tuple, got {}.",
+                        registration.node_type.bind(py).repr()?,
+                        out.repr()?,
+                    )));
+                }
+            };
+            let children = out.get_item(0)?.try_iter()?.collect::<PyResult<Vec<_>>>()?;
+            let node_data = out.get_item(1)?.unbind();
+            let node_entries = match out.len() {
+                3 if !out.get_item(2)?.is_none() => {
+                    let node_entries =
+                        out.get_item(2)?.try_iter()?.collect::<PyResult<Vec<_>>>()?;
+                    if node_entries.len() != children.len() {
+                        return Err(PyRuntimeError::new_err(std::format!(
+                            "PyTree custom flatten function for type {} returned inconsistent \
+                            number of children ({}) and number of entries ({}).",
+                            registration.node_type.bind(py).repr()?,
+                            children.len(),
+                            node_entries.len(),
+                        )));
+                    }
+                    Some(node_entries)
+                }
+                _ => None,
+            };
+            let entries = match node_entries {
+                Some(node_entries) if with_entries => Some(node_entries),
+                _ => index_entries(children.len())?,
+            };
+            NodeChildren {
+                children,
+                entries,
+                node_data: Some(node_data),
+            }
+        }
+    })
+}
+
+pub(crate) struct Flattener<'a, 'py> {
+    leaf_predicate: Option<Bound<'py, PyAny>>,
+    none_is_leaf: bool,
+    namespace: &'a str,
+    leaves: Vec<Bound<'py, PyAny>>,
+    // The leaves are counted even if they are not collected
+    num_leaves: usize,
+    collect_leaves: bool,
+    traversal: Vec<Node>,
+    found_custom: bool,
+    path: Option<Vec<Bound<'py, PyAny>>>,
+    accessors: Vec<Bound<'py, PyAny>>,
+    tracer: Option<Tracer<'py>>,
+    trace_path: Vec<Bound<'py, PyAny>>,
+    paths: Option<Vec<Bound<'py, PyTuple>>>,
+    prune_none: bool,
+    dict_order: DictOrder<'py>,
+    // Map `id(obj)` to the traversal index of the flattened subtree, the objects are kept alive so
+    // their ids are not reused by the temporary objects
+    shared: Option<HashMap<usize, (usize, Bound<'py, PyAny>)>>,
+    max_nodes: Option<usize>,
+    max_leaves: Option<usize>,
+    num_visited: usize,
+    // Only collect the leaves and the layout for the treespec cache without building the nodes, the
+    // layout is dropped if the tree cannot be cached
+    layout: Option<Vec<usize>>,
+    layout_only: bool,
+}
+
+impl<'a, 'py> Flattener<'a, 'py> {
+    pub(crate) fn new(
+        py: Python<'py>,
+        leaf_predicate: Option<&'a Bound<'py, PyAny>>,
+        none_is_leaf: bool,
+        namespace: &'a str,
+        with_accessor: bool,
+    ) -> PyResult<Self> {
+        Ok(Flattener {
+            leaf_predicate: PyTreeTypeRegistry::leaf_predicate(py, leaf_predicate, namespace),
+            none_is_leaf,
+            namespace,
+            leaves: Vec::new(),
+            num_leaves: 0,
+            collect_leaves: true,
+            traversal: Vec::new(),
+            // Keep the namespace in the treespec if its default leaf predicate is used
+            found_custom: leaf_predicate.is_none()
+                && PyTreeTypeRegistry::default_leaf_predicate(py, namespace).is_some(),
+            path: with_accessor.then(Vec::new),
+            accessors: Vec::new(),
+            tracer: Tracer::current(py)?,
+            trace_path: Vec::new(),
+            paths: None,
+            prune_none: false,
+            dict_order: DictOrder::for_namespace(namespace),
+            shared: None,
+            max_nodes: None,
+            max_leaves: None,
+            num_visited: 0,
+            layout: None,
+            layout_only: false,
+        })
+    }
+
+    // Walk the tree for the layout of the treespec cache instead of building the treespec
+    pub(crate) fn layout_only(mut self) -> Self {
+        self.layout = Some(Vec::new());
+        self.layout_only = true;
+        self
+    }
+
+    // Override the default traversal order of the dictionary keys
+    pub(crate) fn with_dict_order(mut self, dict_order: Option<DictOrder<'py>>) -> Self {
+        if let Some(dict_order) = dict_order {
+            self.dict_order = dict_order;
+        }
+        self
+    }
+
+    // Skip the `None` leaves from the leaves list but keep them in the treespec
+    pub(crate) fn with_prune_none(mut self, prune_none: bool) -> Self {
+        self.prune_none = prune_none;
+        self
+    }
+
+    // Flatten the subtrees that are the same object only once and record the repeated occurrences
+    // as references to the first occurrence
+    pub(crate) fn with_shared_subtrees(mut self, shared_subtrees: bool) -> Self {
+        self.shared = shared_subtrees.then(HashMap::new);
+        self
+    }
+
+    // Abort the flattening when the tree has more nodes (including the leaves) or leaves than the
+    // limits, to guard against the untrusted inputs
+    pub(crate) fn with_limits(
+        mut self,
+        max_nodes: Option<usize>,
+        max_leaves: Option<usize>,
+    ) -> Self {
+        self.max_nodes = max_nodes;
+        self.max_leaves = max_leaves;
+        self
+    }
+
+    // Only build the treespec without holding the references to the leaves
+    pub(crate) fn without_leaves(mut self) -> Self {
+        self.collect_leaves = false;
+        self
+    }
+
+    // Also record the path of each leaf as a tuple of raw path entries (i.e., `accessor.path`)
+    pub(crate) fn with_paths(mut self) -> Self {
+        self.paths = Some(Vec::new());
+        self
+    }
+
+    #[inline]
+    fn track_path(&self) -> bool {
+        self.tracer.is_some() || self.paths.is_some()
+    }
+
+    fn push_leaf(&mut self, obj: &Bound<'py, PyAny>) -> PyResult<()> {
+        if let Some(max_leaves) = self.max_leaves
+            && self.num_leaves >= max_leaves
+        {
+            return Err(PyValueError::new_err(std::format!(
+                "Maximum number of leaves ({}) exceeded during flattening the tree.",
+                max_leaves,
+            )));
+        }
+        if let Some(path) = &self.path {
+            let py = obj.py();
+            let accessor = new_accessor(PyTuple::new(py, path)?)?;
+            record_allocations(2);
+            self.accessors.push(accessor);
+        }
+        if let Some(tracer) = &self.tracer {
+            tracer.leaf(&self.trace_path, obj)?;
+        }
+        if let Some(paths) = self.paths.as_mut() {
+            paths.push(PyTuple::new(obj.py(), &self.trace_path)?);
+        }
+        if self.collect_leaves {
+            self.leaves.push(obj.clone());
+        }
+        self.num_leaves += 1;
+        match self.layout.as_mut() {
+            Some(layout) => layout.push(PyTreeKind::Leaf as usize),
+            None if self.layout_only => {}
+            None => self.traversal.push(Node::leaf()),
+        }
+        Ok(())
+    }
+
+    pub(crate) fn flatten_into(&mut self, obj: &Bound<'py, PyAny>, depth: usize) -> PyResult<()> {
+        // The walk for the layout is abandoned once the tree cannot be cached
+        if self.layout_only && self.layout.is_none() {
+            return Ok(());
+        }
+        if depth > MAX_RECURSION_DEPTH {
+            return Err(PyRecursionError::new_err(
+                "Maximum recursion depth exceeded during flattening the tree.",
+            ));
+        }
+        self.num_visited += 1;
+        if let Some(max_nodes) = self.max_nodes
+            && self.num_visited > max_nodes
+        {
+            return Err(PyValueError::new_err(std::format!(
+                "Maximum number of nodes ({}) exceeded during flattening the tree.",
+                max_nodes,
+            )));
+        }
+
+        let (kind, custom) = if self.prune_none && obj.is_none() {
+            // Record the pruned `None` in the treespec as a node with arity 0
+            (PyTreeKind::None, None)
+        } else {
+            if let Some(leaf_predicate) = &self.leaf_predicate
+                && timed(Phase::Callback, || leaf_predicate.call1((obj,)))?.is_truthy()?
+            {
+                return self.push_leaf(obj);
+            }
+            PyTreeTypeRegistry::get_kind(obj, self.none_is_leaf, self.namespace)?
+        };
+        if kind == PyTreeKind::Leaf {
+            return self.push_leaf(obj);
+        }
+        if kind != PyTreeKind::None
+            && let Some(shared) = &self.shared
+            && let Some((target, _)) = shared.get(&(obj.as_ptr() as usize))
+        {
+            self.traversal.push(Node {
+                kind: PyTreeKind::None,
+                arity: 0,
+                node_data: None,
+                custom: None,
+                num_leaves: 0,
+                entries: None,
+                reference: Some(*target),
+            });
+            return Ok(());
+        }
+        match kind {
+            PyTreeKind::Custom => self.found_custom = true,
+            // Keep the namespace in the treespec so the insertion order mode is reproducible
+            PyTreeKind::Dict | PyTreeKind::DefaultDict
+                if !self.namespace.is_empty()
+                    && matches!(self.dict_order, DictOrder::Insertion)
+                    && PyTreeTypeRegistry::is_dict_insertion_ordered(
+                        Some(self.namespace),
+                        Some(false),
+                    ) =>
+            {
+                self.found_custom = true;
+            }
+            _ => {}
+        }
+
+        // The metadata of the custom nodes can be arbitrary objects that are not kept in the layout
+        if self.layout_only && kind == PyTreeKind::Custom {
+            self.layout = None;
+            return Ok(());
+        }
+        let start_num_leaves = self.num_leaves;
+        let keep_entries = kind == PyTreeKind::Custom;
+        let NodeChildren {
+            children,
+            entries,
+            node_data,
+        } = get_children(
+            obj,
+            kind,
+            custom.as_ref(),
+            self.path.is_some() || self.track_path() || keep_entries,
+            &self.dict_order,
+        )?;
+        let node_entries = match keep_entries {
+            true => Some(PyTuple::new(obj.py(), entries.as_ref().unwrap())?.unbind()),
+            false => None,
+        };
+        if kind == PyTreeKind::Custom {
+            debug(obj.py(), "flatten", || {
+                Ok(std::format!(
+                    "Custom PyTree node of type {} in {} flattened into {} children.",
+                    obj.get_type().repr()?,
+                    namespace_repr(obj.py(), self.namespace)?,
+                    children.len(),
+                ))
+            })?;
+        }
+        if let Some(tracer) = &self.tracer {
+            tracer.enter_node(&self.trace_path, kind, obj)?;
+        }
+
+        match entries {
+            Some(entries) => {
+                let py = obj.py();
+                let entry_type = match self.path {
+                    Some(_) => {
+                        let node_type = match &custom {
+                            Some(registration) => registration.node_type.bind(py).clone(),
+                            None => obj.get_type(),
+                        };
+                        Some((path_entry_type(py, kind, custom.as_ref())?, node_type))
+                    }
+                    None => None,
+                };
+                for (child, entry) in children.iter().zip(entries) {
+                    if let Some((entry_type, node_type)) = &entry_type {
+                        let entry = entry_type.call1((&entry, node_type, kind))?;
+                        self.path.as_mut().unwrap().push(entry);
+                    }
+                    if self.track_path() {
+                        self.trace_path.push(entry);
+                    }
+                    self.flatten_into(child, depth + 1)?;
+                    if self.track_path() {
+                        self.trace_path.pop();
+                    }
+                    if let Some(path) = self.path.as_mut() {
+                        path.pop();
+                    }
+                }
+            }
+            None => {
+                for child in children.iter() {
+                    self.flatten_into(child, depth + 1)?;
+                }
+            }
+        }
+
+        if let Some(tracer) = &self.tracer {
+            tracer.exit_node(&self.trace_path, kind, obj)?;
+        }
+        if self.layout_only {
+            if let Some(layout) = self.layout.as_mut()
+                && !push_layout(
+                    layout,
+                    kind,
+                    children.len(),
+                    node_data.as_ref().map(|data| data.bind(obj.py())),
+                )?
+            {
+                self.layout = None;
+            }
+            return Ok(());
+        }
+        self.traversal.push(Node {
+            kind,
+            arity: children.len(),
+            node_data,
+            custom,
+            num_leaves: self.num_leaves - start_num_leaves,
+            entries: node_entries,
+            reference: None,
+        });
+        // The empty containers are skipped, e.g., the empty tuple is a singleton
+        if kind != PyTreeKind::None
+            && !children.is_empty()
+            && let Some(shared) = self.shared.as_mut()
+        {
+            shared.insert(
+                obj.as_ptr() as usize,
+                (self.traversal.len() - 1, obj.clone()),
+            );
+        }
+        Ok(())
+    }
+
+    pub(crate) fn take_paths(&mut self) -> Vec<Bound<'py, PyTuple>> {
+        self.paths.take().unwrap_or_default()
+    }
+
+    // The leaves and the namespace of the treespec with its layout, or `None` if it cannot be cached
+    pub(crate) fn finish_layout(self) -> (Vec<Bound<'py, PyAny>>, Option<NamespacedLayout>) {
+        let namespace = match self.found_custom {
+            true => String::from(self.namespace),
+            false => String::new(),
+        };
+        (self.leaves, self.layout.map(|layout| (namespace, layout)))
+    }
+
+    pub(crate) fn finish(self) -> (Vec<Bound<'py, PyAny>>, Vec<Bound<'py, PyAny>>, PyTreeSpec) {
+        let namespace = match self.found_custom {
+            true => String::from(self.namespace),
+            false => String::new(),
+        };
+        let treespec = PyTreeSpec {
+            traversal: self.traversal,
+            none_is_leaf: self.none_is_leaf,
+            namespace,
+        };
+        (self.accessors, self.leaves, treespec)
+    }
+}
 
 #[pyfunction]
 #[pyo3(signature = (obj, /, leaf_predicate=None, none_is_leaf=false, namespace=""))]
@@ -27,15 +689,525 @@ pub fn is_leaf(
     none_is_leaf: Option<bool>,
     namespace: Option<&str>,
 ) -> PyResult<bool> {
-    let cls = obj.get_type();
-    if leaf_predicate.is_some() {
-        let result = leaf_predicate.unwrap().call1((obj,))?;
-        if result.is_truthy()? {
-            return Ok(true);
+    let namespace = namespace.unwrap_or("");
+    if let Some(leaf_predicate) =
+        PyTreeTypeRegistry::leaf_predicate(obj.py(), leaf_predicate, namespace)
+        && timed(Phase::Callback, || leaf_predicate.call1((obj,)))?.is_truthy()?
+    {
+        return Ok(true);
+    }
+    let (kind, _) = PyTreeTypeRegistry::get_kind(obj, none_is_leaf.unwrap_or(false), namespace)?;
+    Ok(kind == PyTreeKind::Leaf)
+}
+
+#[pyfunction]
+#[pyo3(name = "all_leaves", signature = (iterable, /, leaf_predicate=None, none_is_leaf=false, namespace=""))]
+pub fn are_all_leaves(
+    iterable: &Bound<PyAny>,
+    leaf_predicate: Option<&Bound<PyAny>>,
+    none_is_leaf: Option<bool>,
+    namespace: Option<&str>,
+) -> PyResult<bool> {
+    let namespace = namespace.unwrap_or("");
+    let leaf_predicate =
+        PyTreeTypeRegistry::leaf_predicate(iterable.py(), leaf_predicate, namespace);
+    for obj in iterable.try_iter()? {
+        let obj = obj?;
+        if let Some(leaf_predicate) = &leaf_predicate
+            && timed(Phase::Callback, || leaf_predicate.call1((&obj,)))?.is_truthy()?
+        {
+            continue;
+        }
+        let (kind, _) =
+            PyTreeTypeRegistry::get_kind(&obj, none_is_leaf.unwrap_or(false), namespace)?;
+        if kind != PyTreeKind::Leaf {
+            return Ok(false);
         }
     }
-    if PyTreeTypeRegistry::lookup(&cls, none_is_leaf, namespace).is_some() {
-        return Ok(false);
+    Ok(true)
+}
+
+#[pyfunction]
+#[pyo3(signature = (cls, /, none_is_leaf=false, namespace=""))]
+pub fn is_leaf_type(
+    cls: &Bound<PyType>,
+    none_is_leaf: Option<bool>,
+    namespace: Option<&str>,
+) -> PyResult<bool> {
+    let (kind, _) = PyTreeTypeRegistry::get_type_kind(
+        cls,
+        none_is_leaf.unwrap_or(false),
+        namespace.unwrap_or(""),
+    )?;
+    Ok(kind == PyTreeKind::Leaf)
+}
+
+pub fn flatten<'py>(
+    tree: &Bound<'py, PyAny>,
+    leaf_predicate: Option<&Bound<'py, PyAny>>,
+    none_is_leaf: bool,
+    namespace: &str,
+) -> PyResult<(Vec<Bound<'py, PyAny>>, PyTreeSpec)> {
+    let mut flattener = Flattener::new(tree.py(), leaf_predicate, none_is_leaf, namespace, false)?;
+    flattener.flatten_into(tree, 0)?;
+    let (_, leaves, treespec) = flattener.finish();
+    Ok((leaves, treespec))
+}
+
+fn leaves_list<'py>(
+    py: Python<'py>,
+    leaves: Vec<Bound<'py, PyAny>>,
+    weak_leaves: bool,
+) -> PyResult<Bound<'py, PyList>> {
+    match weak_leaves {
+        true => PyList::new(
+            py,
+            leaves.iter().map(weak_leaf).collect::<PyResult<Vec<_>>>()?,
+        ),
+        false => PyList::new(py, leaves),
+    }
+}
+
+#[pyfunction]
+#[pyo3(
+    name = "flatten",
+    signature = (
+        tree,
+        /,
+        leaf_predicate=None,
+        none_is_leaf=false,
+        namespace="",
+        *,
+        prune_none=false,
+        sort_dict_keys=None,
+        shared_subtrees=false,
+        weak_leaves=false,
+        cache=false,
+        max_nodes=None,
+        max_leaves=None,
+    )
+)]
+#[allow(clippy::too_many_arguments)]
+pub fn flatten_tree<'py>(
+    tree: &Bound<'py, PyAny>,
+    leaf_predicate: Option<&Bound<'py, PyAny>>,
+    none_is_leaf: Option<bool>,
+    namespace: Option<&str>,
+    prune_none: bool,
+    sort_dict_keys: Option<&Bound<'py, PyAny>>,
+    shared_subtrees: bool,
+    weak_leaves: bool,
+    cache: bool,
+    max_nodes: Option<usize>,
+    max_leaves: Option<usize>,
+) -> PyResult<(Bound<'py, PyList>, Bound<'py, PyTreeSpec>)> {
+    let py = tree.py();
+    let none_is_leaf = none_is_leaf.unwrap_or(false);
+    let namespace = namespace.unwrap_or("");
+    let new_flattener = || -> PyResult<Flattener> {
+        Ok(
+            Flattener::new(py, leaf_predicate, none_is_leaf, namespace, false)?
+                .with_prune_none(prune_none)
+                .with_dict_order(DictOrder::from_arg(sort_dict_keys)?)
+                .with_shared_subtrees(shared_subtrees)
+                .with_limits(max_nodes, max_leaves),
+        )
+    };
+
+    // Look up the treespec by the layout collected along with the leaves, the nodes are only built
+    // if the layout is not cached yet. The shared subtrees and the traced walks are not cached.
+    let mut cache_key = None;
+    if cache && !shared_subtrees && Tracer::current(py)?.is_none() {
+        let mut flattener = new_flattener()?.layout_only();
+        flattener.flatten_into(tree, 0)?;
+        if let (leaves, Some((namespace, layout))) = flattener.finish_layout() {
+            match lookup(py, none_is_leaf, &namespace, layout) {
+                (Some(treespec), _) => {
+                    return Ok((leaves_list(py, leaves, weak_leaves)?, treespec));
+                }
+                (None, key) => cache_key = Some(key),
+            }
+        }
+    }
+
+    let mut flattener = new_flattener()?;
+    flattener.flatten_into(tree, 0)?;
+    let (_, leaves, treespec) = flattener.finish();
+    let treespec = match cache_key {
+        Some(key) => insert(py, key, treespec)?,
+        None => Bound::new(py, treespec)?,
+    };
+    Ok((leaves_list(py, leaves, weak_leaves)?, treespec))
+}
+
+#[pyfunction]
+#[pyo3(signature = (tree, /, leaf_predicate=None, none_is_leaf=false, namespace=""))]
+pub fn structure<'py>(
+    tree: &Bound<'py, PyAny>,
+    leaf_predicate: Option<&Bound<'py, PyAny>>,
+    none_is_leaf: Option<bool>,
+    namespace: Option<&str>,
+) -> PyResult<PyTreeSpec> {
+    let mut flattener = Flattener::new(
+        tree.py(),
+        leaf_predicate,
+        none_is_leaf.unwrap_or(false),
+        namespace.unwrap_or(""),
+        false,
+    )?
+    .without_leaves();
+    flattener.flatten_into(tree, 0)?;
+    Ok(flattener.finish().2)
+}
+
+#[pyfunction]
+#[pyo3(signature = (trees, /, leaf_predicate=None, none_is_leaf=false, namespace=""))]
+pub fn flatten_multiple<'py>(
+    trees: &Bound<'py, PyAny>,
+    leaf_predicate: Option<&Bound<'py, PyAny>>,
+    none_is_leaf: Option<bool>,
+    namespace: Option<&str>,
+) -> PyResult<(Bound<'py, PyList>, PyTreeSpec)> {
+    let py = trees.py();
+    let mut trees = trees.try_iter()?;
+    let Some(tree) = trees.next() else {
+        return Err(PyValueError::new_err(
+            "Expected at least one tree to flatten.",
+        ));
     };
-    Ok(!(is_namedtuple_class(&cls)? || is_structseq_class(&cls)?))
+    let (leaves, treespec) = flatten(
+        &tree?,
+        leaf_predicate,
+        none_is_leaf.unwrap_or(false),
+        namespace.unwrap_or(""),
+    )?;
+    let all_leaves = PyList::empty(py);
+    all_leaves.append(leaves)?;
+    // The other trees are checked against the treespec during the traversal
+    for tree in trees {
+        all_leaves.append(treespec.flatten_impl(&tree?, leaf_predicate)?)?;
+    }
+    Ok((all_leaves, treespec))
+}
+
+#[pyfunction]
+#[pyo3(
+    signature = (
+        tree,
+        /,
+        leaf_predicate=None,
+        none_is_leaf=false,
+        namespace="",
+        *,
+        prune_none=false,
+        sort_dict_keys=None,
+        max_nodes=None,
+        max_leaves=None,
+    )
+)]
+#[allow(clippy::too_many_arguments)]
+pub fn flatten_with_accessor<'py>(
+    tree: &Bound<'py, PyAny>,
+    leaf_predicate: Option<&Bound<'py, PyAny>>,
+    none_is_leaf: Option<bool>,
+    namespace: Option<&str>,
+    prune_none: bool,
+    sort_dict_keys: Option<&Bound<'py, PyAny>>,
+    max_nodes: Option<usize>,
+    max_leaves: Option<usize>,
+) -> PyResult<(Bound<'py, PyList>, Bound<'py, PyList>, PyTreeSpec)> {
+    let py = tree.py();
+    let mut flattener = Flattener::new(
+        py,
+        leaf_predicate,
+        none_is_leaf.unwrap_or(false),
+        namespace.unwrap_or(""),
+        true,
+    )?
+    .with_prune_none(prune_none)
+    .with_dict_order(DictOrder::from_arg(sort_dict_keys)?)
+    .with_limits(max_nodes, max_leaves);
+    flattener.flatten_into(tree, 0)?;
+    let (accessors, leaves, treespec) = flattener.finish();
+    Ok((
+        PyList::new(py, accessors)?,
+        PyList::new(py, leaves)?,
+        treespec,
+    ))
+}
+
+#[pyfunction]
+#[pyo3(
+    signature = (
+        tree,
+        /,
+        leaf_predicate=None,
+        none_is_leaf=false,
+        namespace="",
+        *,
+        prune_none=false,
+        sort_dict_keys=None,
+        max_nodes=None,
+        max_leaves=None,
+    )
+)]
+#[allow(clippy::too_many_arguments)]
+pub fn flatten_with_path<'py>(
+    tree: &Bound<'py, PyAny>,
+    leaf_predicate: Option<&Bound<'py, PyAny>>,
+    none_is_leaf: Option<bool>,
+    namespace: Option<&str>,
+    prune_none: bool,
+    sort_dict_keys: Option<&Bound<'py, PyAny>>,
+    max_nodes: Option<usize>,
+    max_leaves: Option<usize>,
+) -> PyResult<(Bound<'py, PyList>, PyTreeSpec)> {
+    let py = tree.py();
+    let mut flattener = Flattener::new(
+        py,
+        leaf_predicate,
+        none_is_leaf.unwrap_or(false),
+        namespace.unwrap_or(""),
+        false,
+    )?
+    .with_prune_none(prune_none)
+    .with_dict_order(DictOrder::from_arg(sort_dict_keys)?)
+    .with_limits(max_nodes, max_leaves)
+    .with_paths();
+    flattener.flatten_into(tree, 0)?;
+    let paths = flattener.take_paths();
+    let (_, leaves, treespec) = flattener.finish();
+    // Pair the paths with the leaves, both are recorded in the same traversal
+    let items = paths
+        .into_iter()
+        .zip(leaves)
+        .map(|(path, leaf)| PyTuple::new(py, [path.into_any(), leaf]))
+        .collect::<PyResult<Vec<_>>>()?;
+    Ok((PyList::new(py, items)?, treespec))
+}
+
+#[pyfunction]
+#[pyo3(
+    name = "flatten_into",
+    signature = (
+        tree,
+        leaves,
+        /,
+        leaf_predicate=None,
+        none_is_leaf=false,
+        namespace="",
+        *,
+        prune_none=false,
+        sort_dict_keys=None,
+        max_nodes=None,
+        max_leaves=None,
+    )
+)]
+#[allow(clippy::too_many_arguments)]
+pub fn flatten_into_list<'py>(
+    tree: &Bound<'py, PyAny>,
+    leaves: &Bound<'py, PyList>,
+    leaf_predicate: Option<&Bound<'py, PyAny>>,
+    none_is_leaf: Option<bool>,
+    namespace: Option<&str>,
+    prune_none: bool,
+    sort_dict_keys: Option<&Bound<'py, PyAny>>,
+    max_nodes: Option<usize>,
+    max_leaves: Option<usize>,
+) -> PyResult<PyTreeSpec> {
+    let mut flattener = Flattener::new(
+        tree.py(),
+        leaf_predicate,
+        none_is_leaf.unwrap_or(false),
+        namespace.unwrap_or(""),
+        false,
+    )?
+    .with_prune_none(prune_none)
+    .with_dict_order(DictOrder::from_arg(sort_dict_keys)?)
+    .with_limits(max_nodes, max_leaves);
+    flattener.flatten_into(tree, 0)?;
+    let (_, new_leaves, treespec) = flattener.finish();
+    // The list is only extended after a successful flatten
+    for leaf in new_leaves {
+        leaves.append(leaf)?;
+    }
+    Ok(treespec)
+}
+
+struct SpecFlattener<'a, 'py> {
+    treespec: &'a PyTreeSpec,
+    leaf_predicate: Option<Bound<'py, PyAny>>,
+    // The number of nodes in the subtree rooted at each node of the traversal
+    num_nodes: Vec<usize>,
+    path: Vec<Bound<'py, PyAny>>,
+    leaves: Vec<Bound<'py, PyAny>>,
+    // Collect the subtrees at the leaves of the treespec instead of requiring leaves
+    up_to: bool,
+    // The traversal indices of the shared subtrees referenced by the repeated occurrences
+    targets: HashSet<usize>,
+    // The first occurrences of the shared subtrees, with the path and the range of their leaves
+    shared: HashMap<usize, (Bound<'py, PyAny>, Bound<'py, PyTuple>, usize, usize)>,
+}
+
+impl<'py> SpecFlattener<'_, 'py> {
+    fn mismatch(&self, obj: &Bound<'py, PyAny>, expected: &str) -> PyResult<PyErr> {
+        let py = obj.py();
+        Ok(PyValueError::new_err(std::format!(
+            "Tree structure does not match the PyTreeSpec at path {}; expected {}, got {}.",
+            PyTuple::new(py, &self.path)?.repr()?,
+            expected,
+            obj.repr()?,
+        )))
+    }
+
+    fn flatten_into(&mut self, obj: &Bound<'py, PyAny>, index: usize) -> PyResult<()> {
+        let py = obj.py();
+        let node = &self.treespec.traversal[index];
+        // The leaves of a shared subtree are collected at its first occurrence, a repeated occurrence
+        // must be the same object or have the same structure and the same leaves
+        if let Some(target) = node.reference {
+            return self.check_shared(obj, target);
+        }
+        // A pruned `None` is recorded as a node even if `None` is a leaf
+        if node.kind == PyTreeKind::None && obj.is_none() {
+            return Ok(());
+        }
+        if self.up_to && node.kind == PyTreeKind::Leaf {
+            self.leaves.push(obj.clone());
+            return Ok(());
+        }
+        let is_leaf = match &self.leaf_predicate {
+            Some(leaf_predicate) => {
+                timed(Phase::Callback, || leaf_predicate.call1((obj,)))?.is_truthy()?
+            }
+            None => false,
+        };
+        let (kind, custom) = match is_leaf {
+            true => (PyTreeKind::Leaf, None),
+            false => PyTreeTypeRegistry::get_kind(
+                obj,
+                self.treespec.none_is_leaf,
+                &self.treespec.namespace,
+            )?,
+        };
+        if node.kind == PyTreeKind::Leaf {
+            if kind != PyTreeKind::Leaf {
+                return Err(self.mismatch(obj, "a leaf")?);
+            }
+            self.leaves.push(obj.clone());
+            return Ok(());
+        }
+
+        let expected = || -> PyResult<String> { repr_node_shape(py, node) };
+        if kind != node.kind {
+            return Err(self.mismatch(obj, &expected()?)?);
+        }
+        let dict_order = DictOrder::of_node(py, node)?.unwrap_or(DictOrder::Sorted);
+        let NodeChildren {
+            children,
+            entries,
+            node_data,
+        } = get_children(obj, kind, custom.as_ref(), true, &dict_order)?;
+        let actual = Node {
+            kind,
+            arity: children.len(),
+            node_data,
+            custom,
+            num_leaves: 0,
+            entries: None,
+            reference: None,
+        };
+        if !node_shape_eq(py, node, &actual)? {
+            return Err(self.mismatch(obj, &expected()?)?);
+        }
+
+        let start = self.leaves.len();
+        let child_indices = self.treespec.child_indices(&self.num_nodes, index);
+        for ((child, entry), child_index) in
+            children.iter().zip(entries.unwrap()).zip(child_indices)
+        {
+            self.path.push(entry);
+            self.flatten_into(child, child_index)?;
+            self.path.pop();
+        }
+        if self.targets.contains(&index) && !self.shared.contains_key(&index) {
+            let path = PyTuple::new(py, &self.path)?;
+            self.shared
+                .insert(index, (obj.clone(), path, start, self.leaves.len()));
+        }
+        Ok(())
+    }
+
+    fn check_shared(&mut self, obj: &Bound<'py, PyAny>, target: usize) -> PyResult<()> {
+        let (first, path, start, end) = self.shared[&target].clone();
+        if obj.is(&first) {
+            return Ok(());
+        }
+        let expected = std::format!("the shared subtree at path {}", path.repr()?);
+        let offset = self.leaves.len();
+        let depth = self.path.len();
+        match self.flatten_into(obj, target) {
+            Ok(()) => {}
+            Err(err) if err.is_instance_of::<PyValueError>(obj.py()) => {
+                self.path.truncate(depth);
+                return Err(self.mismatch(obj, &expected)?);
+            }
+            Err(err) => return Err(err),
+        }
+        let leaves = self.leaves.split_off(offset);
+        if leaves.len() != end - start
+            || leaves
+                .iter()
+                .zip(&self.leaves[start..end])
+                .any(|(leaf, expected)| !leaf.is(expected))
+        {
+            return Err(self.mismatch(obj, &expected)?);
+        }
+        Ok(())
+    }
+}
+
+impl PyTreeSpec {
+    pub(crate) fn flatten_impl<'py>(
+        &self,
+        tree: &Bound<'py, PyAny>,
+        leaf_predicate: Option<&Bound<'py, PyAny>>,
+    ) -> PyResult<Vec<Bound<'py, PyAny>>> {
+        self.flatten_with_spec(tree, leaf_predicate, false)
+    }
+
+    pub(crate) fn flatten_up_to_impl<'py>(
+        &self,
+        tree: &Bound<'py, PyAny>,
+    ) -> PyResult<Vec<Bound<'py, PyAny>>> {
+        self.flatten_with_spec(tree, None, true)
+    }
+
+    fn flatten_with_spec<'py>(
+        &self,
+        tree: &Bound<'py, PyAny>,
+        leaf_predicate: Option<&Bound<'py, PyAny>>,
+        up_to: bool,
+    ) -> PyResult<Vec<Bound<'py, PyAny>>> {
+        let mut flattener = SpecFlattener {
+            treespec: self,
+            leaf_predicate: PyTreeTypeRegistry::leaf_predicate(
+                tree.py(),
+                leaf_predicate,
+                &self.namespace,
+            ),
+            num_nodes: self.subtree_num_nodes(),
+            path: Vec::new(),
+            leaves: Vec::with_capacity(self.num_leaves()),
+            up_to,
+            targets: self
+                .traversal
+                .iter()
+                .filter_map(|node| node.reference)
+                .collect(),
+            shared: HashMap::new(),
+        };
+        flattener.flatten_into(tree, self.traversal.len() - 1)?;
+        Ok(flattener.leaves)
+    }
 }
@@ -0,0 +1,289 @@
+// Copyright 2024-2025 Xuehai Pan. All Rights Reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+// =============================================================================
+
+use pyo3::exceptions::PyRecursionError;
+use pyo3::prelude::*;
+use pyo3::types::*;
+
+use crate::rustree::profiling::{Phase, timed};
+use crate::rustree::pytypes::{namedtuple_fields, structseq_fields};
+use crate::rustree::registry::{PyTreeKind, PyTreeTypeRegistry};
+use crate::rustree::treespec::flatten::{
+    DictOrder, MAX_RECURSION_DEPTH, NodeChildren, get_children,
+};
+
+// The opening and closing brackets of a node, and the prefixes of the children
+struct Delimiters {
+    open: String,
+    close: String,
+    prefixes: Option<Vec<String>>,
+}
+
+fn delimiters(
+    obj: &Bound<'_, PyAny>,
+    kind: PyTreeKind,
+    node_data: Option<&Bound<'_, PyAny>>,
+) -> PyResult<Delimiters> {
+    let key_prefixes = |keys: &Bound<'_, PyAny>| -> PyResult<Option<Vec<String>>> {
+        Ok(Some(
+            keys.try_iter()?
+                .map(|key| Ok(std::format!("{}: ", key?.repr()?)))
+                .collect::<PyResult<Vec<_>>>()?,
+        ))
+    };
+    let field_prefixes = |fields: Bound<'_, PyTuple>| -> PyResult<Option<Vec<String>>> {
+        Ok(Some(
+            fields
+                .iter()
+                .map(|field| Ok(std::format!("{}=", field.extract::<String>()?)))
+                .collect::<PyResult<Vec<_>>>()?,
+        ))
+    };
+    let (open, close, prefixes) = match kind {
+        PyTreeKind::Tuple => (String::from("("), String::from(")"), None),
+        PyTreeKind::List => (String::from("["), String::from("]"), None),
+        PyTreeKind::Dict => (
+            String::from("{"),
+            String::from("}"),
+            key_prefixes(node_data.unwrap())?,
+        ),
+        PyTreeKind::OrderedDict => (
+            String::from("OrderedDict({"),
+            String::from("})"),
+            key_prefixes(node_data.unwrap())?,
+        ),
+        PyTreeKind::DefaultDict => {
+            let node_data = node_data.unwrap();
+            (
+                std::format!("defaultdict({}, {{", node_data.get_item(0)?.repr()?),
+                String::from("})"),
+                key_prefixes(&node_data.get_item(1)?)?,
+            )
+        }
+        PyTreeKind::NamedTuple => {
+            let cls = node_data.unwrap();
+            (
+                std::format!("{}(", cls.getattr("__name__")?),
+                String::from(")"),
+                field_prefixes(namedtuple_fields(cls)?)?,
+            )
+        }
+        PyTreeKind::StructSequence => {
+            let cls = node_data.unwrap().downcast::<PyType>()?;
+            let module = cls.module()?;
+            let qualname = cls.qualname()?;
+            let open = match module.to_cow()?.as_ref() {
+                "builtins" => std::format!("{}(", qualname),
+                module => std::format!("{}.{}(", module, qualname),
+            };
+            (
+                open,
+                String::from(")"),
+                field_prefixes(structseq_fields(cls)?)?,
+            )
+        }
+        PyTreeKind::Deque => {
+            let maxlen = node_data.unwrap();
+            let close = match maxlen.is_none() {
+                true => String::from("])"),
+                false => std::format!("], maxlen={})", maxlen),
+            };
+            (String::from("deque(["), close, None)
+        }
+        _ => (
+            std::format!("{}(", obj.get_type().name()?),
+            String::from(")"),
+            None,
+        ),
+    };
+    Ok(Delimiters {
+        open,
+        close,
+        prefixes,
+    })
+}
+
+struct Formatter<'a, 'py> {
+    leaf_predicate: Option<Bound<'py, PyAny>>,
+    none_is_leaf: bool,
+    namespace: &'a str,
+    dict_order: DictOrder<'py>,
+    max_depth: Option<usize>,
+    max_children: Option<usize>,
+    leaf_repr: Option<&'a Bound<'py, PyAny>>,
+    max_leaf_width: Option<usize>,
+    indent: usize,
+    lines: Vec<String>,
+}
+
+// Elide the middle of a long text like `reprlib`, keeping the head and the tail
+fn elide(text: String, width: usize) -> String {
+    let num_chars = text.chars().count();
+    if num_chars <= width {
+        return text;
+    }
+    let head = width.saturating_sub(3) / 2;
+    let tail = width.saturating_sub(3) - head;
+    let mut elided = text.chars().take(head).collect::<String>();
+    elided.push_str("...");
+    elided.extend(text.chars().skip(num_chars - tail));
+    elided
+}
+
+impl<'py> Formatter<'_, 'py> {
+    fn push(&mut self, depth: usize, text: &str) {
+        let indent = " ".repeat(depth * self.indent);
+        for line in text.split('\n') {
+            self.lines.push(std::format!("{}{}", indent, line));
+        }
+    }
+
+    // Append the text to the last line, e.g., a trailing comma
+    fn extend(&mut self, text: &str) {
+        if let Some(line) = self.lines.last_mut() {
+            line.push_str(text);
+        }
+    }
+
+    fn format_leaf(
+        &mut self,
+        leaf: &Bound<'py, PyAny>,
+        depth: usize,
+        prefix: &str,
+    ) -> PyResult<()> {
+        let text = match self.leaf_repr {
+            Some(leaf_repr) => {
+                let text = timed(Phase::Callback, || leaf_repr.call1((leaf,)))?;
+                match text.downcast::<PyString>() {
+                    Ok(text) => text.to_string(),
+                    Err(_) => text.str()?.to_string(),
+                }
+            }
+            None => leaf.repr()?.to_string(),
+        };
+        let text = match self.max_leaf_width {
+            Some(width) => elide(text, width),
+            None => text,
+        };
+        self.push(depth, &std::format!("{}{}", prefix, text));
+        Ok(())
+    }
+
+    fn format(&mut self, obj: &Bound<'py, PyAny>, depth: usize, prefix: &str) -> PyResult<()> {
+        if depth > MAX_RECURSION_DEPTH {
+            return Err(PyRecursionError::new_err(
+                "Maximum recursion depth exceeded during formatting the tree.",
+            ));
+        }
+
+        if let Some(leaf_predicate) = &self.leaf_predicate
+            && timed(Phase::Callback, || leaf_predicate.call1((obj,)))?.is_truthy()?
+        {
+            return self.format_leaf(obj, depth, prefix);
+        }
+        let (kind, custom) = PyTreeTypeRegistry::get_kind(obj, self.none_is_leaf, self.namespace)?;
+        match kind {
+            PyTreeKind::Leaf => return self.format_leaf(obj, depth, prefix),
+            PyTreeKind::None => {
+                self.push(depth, &std::format!("{}None", prefix));
+                return Ok(());
+            }
+            _ => {}
+        }
+
+        let NodeChildren {
+            children,
+            node_data,
+            ..
+        } = get_children(obj, kind, custom.as_ref(), false, &self.dict_order)?;
+        let node_data = node_data.map(|data| data.into_bound(obj.py()));
+        let Delimiters {
+            open,
+            close,
+            prefixes,
+        } = delimiters(obj, kind, node_data.as_ref())?;
+        if children.is_empty() {
+            self.push(depth, &std::format!("{}{}{}", prefix, open, close));
+            return Ok(());
+        }
+        if self.max_depth.is_some_and(|max_depth| depth >= max_depth) {
+            self.push(depth, &std::format!("{}{}...{}", prefix, open, close));
+            return Ok(());
+        }
+
+        self.push(depth, &std::format!("{}{}", prefix, open));
+        let num_children = self.max_children.map_or(children.len(), |max_children| {
+            max_children.min(children.len())
+        });
+        for (i, child) in children.iter().take(num_children).enumerate() {
+            let prefix = prefixes
+                .as_ref()
+                .map_or("", |prefixes| prefixes[i].as_str());
+            self.format(child, depth + 1, prefix)?;
+            self.extend(",");
+        }
+        if num_children < children.len() {
+            self.push(depth + 1, "...");
+        }
+        self.push(depth, &close);
+        Ok(())
+    }
+}
+
+#[pyfunction]
+#[pyo3(
+    name = "format",
+    signature = (
+        tree,
+        /,
+        leaf_predicate=None,
+        none_is_leaf=false,
+        namespace="",
+        *,
+        max_depth=None,
+        max_children=None,
+        leaf_repr=None,
+        max_leaf_width=None,
+        indent=4,
+    )
+)]
+#[allow(clippy::too_many_arguments)]
+pub fn format_tree<'py>(
+    tree: &Bound<'py, PyAny>,
+    leaf_predicate: Option<&Bound<'py, PyAny>>,
+    none_is_leaf: Option<bool>,
+    namespace: Option<&str>,
+    max_depth: Option<usize>,
+    max_children: Option<usize>,
+    leaf_repr: Option<&Bound<'py, PyAny>>,
+    max_leaf_width: Option<usize>,
+    indent: usize,
+) -> PyResult<String> {
+    let namespace = namespace.unwrap_or("");
+    let mut formatter = Formatter {
+        leaf_predicate: PyTreeTypeRegistry::leaf_predicate(tree.py(), leaf_predicate, namespace),
+        none_is_leaf: none_is_leaf.unwrap_or(false),
+        namespace,
+        dict_order: DictOrder::for_namespace(namespace),
+        max_depth,
+        max_children,
+        leaf_repr,
+        max_leaf_width,
+        indent,
+        lines: Vec::new(),
+    };
+    formatter.format(tree, 0, "")?;
+    Ok(formatter.lines.join("\n"))
+}
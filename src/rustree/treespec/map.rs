@@ -0,0 +1,386 @@
+// Copyright 2024-2025 Xuehai Pan. All Rights Reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+// =============================================================================
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::*;
+
+use crate::rustree::profiling::{Phase, record_allocations, timed};
+use crate::rustree::treespec::PyTreeSpec;
+use crate::rustree::treespec::flatten::{DictOrder, Flattener};
+use crate::rustree::treespec::transpose::{check_transposable, transpose_leaves};
+
+struct Flattened<'py> {
+    leaves: Vec<Bound<'py, PyAny>>,
+    paths: Vec<Bound<'py, PyTuple>>,
+    treespec: PyTreeSpec,
+    rest_leaves: Vec<Vec<Bound<'py, PyAny>>>,
+}
+
+fn flatten_all<'py>(
+    tree: &Bound<'py, PyAny>,
+    rests: &Bound<'py, PyTuple>,
+    leaf_predicate: Option<&Bound<'py, PyAny>>,
+    none_is_leaf: bool,
+    namespace: &str,
+    with_paths: bool,
+    dict_order: Option<DictOrder<'py>>,
+) -> PyResult<Flattened<'py>> {
+    let py = tree.py();
+    let dict_order = dict_order.unwrap_or_else(|| DictOrder::for_namespace(namespace));
+    let mut flattener = Flattener::new(py, leaf_predicate, none_is_leaf, namespace, false)?
+        .with_dict_order(Some(dict_order.clone()));
+    if with_paths {
+        flattener = flattener.with_paths();
+    }
+    flattener.flatten_into(tree, 0)?;
+    let paths = flattener.take_paths();
+    let (_, leaves, treespec) = flattener.finish();
+
+    let mut rest_leaves = Vec::with_capacity(rests.len());
+    for rest in rests.iter() {
+        // Unless the keys are sorted, the other trees follow the key order of the first tree
+        if !matches!(dict_order, DictOrder::Sorted) {
+            rest_leaves.push(treespec.flatten_impl(&rest, leaf_predicate)?);
+            continue;
+        }
+        let mut flattener = Flattener::new(py, leaf_predicate, none_is_leaf, namespace, false)?;
+        flattener.flatten_into(&rest, 0)?;
+        let (_, leaves, other) = flattener.finish();
+        if !treespec.equal_impl(py, &other)? {
+            return Err(PyValueError::new_err(std::format!(
+                "Tree structures do not match; expected {}, got {}.",
+                treespec.repr_impl(py)?,
+                other.repr_impl(py)?,
+            )));
+        }
+        rest_leaves.push(leaves);
+    }
+    Ok(Flattened {
+        leaves,
+        paths,
+        treespec,
+        rest_leaves,
+    })
+}
+
+// Call the function on the leaves, the references to the input leaves are moved into the
+// arguments and released as soon as each output has been produced
+fn call_leaves<'py>(
+    func: &Bound<'py, PyAny>,
+    flattened: &mut Flattened<'py>,
+    with_paths: bool,
+    mut output: impl FnMut(Bound<'py, PyAny>),
+) -> PyResult<()> {
+    let py = func.py();
+    let leaves = std::mem::take(&mut flattened.leaves);
+    let mut paths = std::mem::take(&mut flattened.paths).into_iter();
+    let mut rest_leaves = std::mem::take(&mut flattened.rest_leaves)
+        .into_iter()
+        .map(Vec::into_iter)
+        .collect::<Vec<_>>();
+    for leaf in leaves {
+        let mut args = Vec::with_capacity(rest_leaves.len() + 2);
+        if with_paths {
+            args.push(paths.next().unwrap().into_any());
+        }
+        args.push(leaf);
+        args.extend(rest_leaves.iter_mut().map(|leaves| leaves.next().unwrap()));
+        let args = PyTuple::new(py, args)?;
+        record_allocations(1);
+        output(timed(Phase::Callback, || func.call1(args))?);
+    }
+    Ok(())
+}
+
+#[pyfunction]
+#[pyo3(
+    name = "map",
+    signature = (
+        func,
+        tree,
+        /,
+        *rests,
+        leaf_predicate=None,
+        none_is_leaf=false,
+        namespace="",
+        sort_dict_keys=None,
+    )
+)]
+pub fn map_tree<'py>(
+    func: &Bound<'py, PyAny>,
+    tree: &Bound<'py, PyAny>,
+    rests: &Bound<'py, PyTuple>,
+    leaf_predicate: Option<&Bound<'py, PyAny>>,
+    none_is_leaf: Option<bool>,
+    namespace: Option<&str>,
+    sort_dict_keys: Option<&Bound<'py, PyAny>>,
+) -> PyResult<Bound<'py, PyAny>> {
+    let py = tree.py();
+    let mut flattened = flatten_all(
+        tree,
+        rests,
+        leaf_predicate,
+        none_is_leaf.unwrap_or(false),
+        namespace.unwrap_or(""),
+        false,
+        DictOrder::from_arg(sort_dict_keys)?,
+    )?;
+    let mut mapped = Vec::with_capacity(flattened.leaves.len());
+    call_leaves(func, &mut flattened, false, |leaf| mapped.push(leaf))?;
+    flattened
+        .treespec
+        .unflatten_impl(PyList::new(py, mapped)?.as_any(), None)
+}
+
+#[pyfunction]
+#[pyo3(signature = (
+        func,
+        tree,
+        /,
+        *rests,
+        leaf_predicate=None,
+        none_is_leaf=false,
+        namespace="",
+        sort_dict_keys=None,
+    ))]
+pub fn map_with_path<'py>(
+    func: &Bound<'py, PyAny>,
+    tree: &Bound<'py, PyAny>,
+    rests: &Bound<'py, PyTuple>,
+    leaf_predicate: Option<&Bound<'py, PyAny>>,
+    none_is_leaf: Option<bool>,
+    namespace: Option<&str>,
+    sort_dict_keys: Option<&Bound<'py, PyAny>>,
+) -> PyResult<Bound<'py, PyAny>> {
+    let py = tree.py();
+    let mut flattened = flatten_all(
+        tree,
+        rests,
+        leaf_predicate,
+        none_is_leaf.unwrap_or(false),
+        namespace.unwrap_or(""),
+        true,
+        DictOrder::from_arg(sort_dict_keys)?,
+    )?;
+    let mut mapped = Vec::with_capacity(flattened.leaves.len());
+    call_leaves(func, &mut flattened, true, |leaf| mapped.push(leaf))?;
+    flattened
+        .treespec
+        .unflatten_impl(PyList::new(py, mapped)?.as_any(), None)
+}
+
+#[pyfunction]
+#[pyo3(signature = (
+        func,
+        tree,
+        /,
+        *rests,
+        leaf_predicate=None,
+        none_is_leaf=false,
+        namespace="",
+        sort_dict_keys=None,
+    ))]
+pub fn map_with_path_<'py>(
+    func: &Bound<'py, PyAny>,
+    tree: &Bound<'py, PyAny>,
+    rests: &Bound<'py, PyTuple>,
+    leaf_predicate: Option<&Bound<'py, PyAny>>,
+    none_is_leaf: Option<bool>,
+    namespace: Option<&str>,
+    sort_dict_keys: Option<&Bound<'py, PyAny>>,
+) -> PyResult<Bound<'py, PyAny>> {
+    let mut flattened = flatten_all(
+        tree,
+        rests,
+        leaf_predicate,
+        none_is_leaf.unwrap_or(false),
+        namespace.unwrap_or(""),
+        true,
+        DictOrder::from_arg(sort_dict_keys)?,
+    )?;
+    // Only the side effects of the function are needed, skip reconstructing the containers
+    call_leaves(func, &mut flattened, true, drop)?;
+    Ok(tree.clone())
+}
+
+#[pyfunction]
+#[pyo3(signature = (
+        func,
+        tree,
+        /,
+        *rests,
+        inner_treespec=None,
+        leaf_predicate=None,
+        none_is_leaf=false,
+        namespace="",
+    ))]
+pub fn transpose_map<'py>(
+    func: &Bound<'py, PyAny>,
+    tree: &Bound<'py, PyAny>,
+    rests: &Bound<'py, PyTuple>,
+    inner_treespec: Option<PyRef<'py, PyTreeSpec>>,
+    leaf_predicate: Option<&Bound<'py, PyAny>>,
+    none_is_leaf: Option<bool>,
+    namespace: Option<&str>,
+) -> PyResult<Bound<'py, PyAny>> {
+    let py = tree.py();
+    let none_is_leaf = none_is_leaf.unwrap_or(false);
+    let namespace = namespace.unwrap_or("");
+    let mut flattened = flatten_all(
+        tree,
+        rests,
+        leaf_predicate,
+        none_is_leaf,
+        namespace,
+        false,
+        None,
+    )?;
+    let mut outputs = Vec::with_capacity(flattened.leaves.len());
+    call_leaves(func, &mut flattened, false, |output| outputs.push(output))?;
+    let outer_treespec = &flattened.treespec;
+
+    // The inner structure is inferred from the first output unless given
+    let inferred;
+    let inner_treespec = match &inner_treespec {
+        Some(inner_treespec) => &**inner_treespec,
+        None => {
+            let Some(first) = outputs.first() else {
+                return Err(PyValueError::new_err(std::format!(
+                    "The outer structure must have at least one leaf when the inner_treespec is \
+                    not given, got {}.",
+                    outer_treespec.repr_impl(py)?,
+                )));
+            };
+            let mut flattener = Flattener::new(py, leaf_predicate, none_is_leaf, namespace, false)?;
+            flattener.flatten_into(first, 0)?;
+            inferred = flattener.finish().2;
+            &inferred
+        }
+    };
+    check_transposable(py, outer_treespec, inner_treespec)?;
+
+    let mut leaves = Vec::with_capacity(outputs.len() * inner_treespec.num_leaves());
+    for output in outputs {
+        leaves.extend(inner_treespec.flatten_impl(&output, leaf_predicate)?);
+    }
+    transpose_leaves(py, outer_treespec, inner_treespec, &leaves)
+}
+
+// Flatten the tree and test each leaf with the predicate
+fn split_leaves<'py>(
+    predicate: &Bound<'py, PyAny>,
+    tree: &Bound<'py, PyAny>,
+    leaf_predicate: Option<&Bound<'py, PyAny>>,
+    none_is_leaf: bool,
+    namespace: &str,
+) -> PyResult<(Vec<Bound<'py, PyAny>>, Vec<bool>, PyTreeSpec)> {
+    let mut flattener = Flattener::new(tree.py(), leaf_predicate, none_is_leaf, namespace, false)?;
+    flattener.flatten_into(tree, 0)?;
+    let (_, leaves, treespec) = flattener.finish();
+    let matched = leaves
+        .iter()
+        .map(|leaf| timed(Phase::Callback, || predicate.call1((leaf,)))?.is_truthy())
+        .collect::<PyResult<Vec<_>>>()?;
+    Ok((leaves, matched, treespec))
+}
+
+#[pyfunction]
+#[pyo3(signature = (
+        predicate,
+        tree,
+        /,
+        fill_value=None,
+        leaf_predicate=None,
+        none_is_leaf=false,
+        namespace="",
+    ))]
+pub fn partition<'py>(
+    predicate: &Bound<'py, PyAny>,
+    tree: &Bound<'py, PyAny>,
+    fill_value: Option<&Bound<'py, PyAny>>,
+    leaf_predicate: Option<&Bound<'py, PyAny>>,
+    none_is_leaf: Option<bool>,
+    namespace: Option<&str>,
+) -> PyResult<(Bound<'py, PyAny>, Bound<'py, PyAny>)> {
+    let py = tree.py();
+    let fill_value = fill_value
+        .cloned()
+        .unwrap_or_else(|| py.None().into_bound(py));
+    let (leaves, matched, treespec) = split_leaves(
+        predicate,
+        tree,
+        leaf_predicate,
+        none_is_leaf.unwrap_or(false),
+        namespace.unwrap_or(""),
+    )?;
+    let mut left = Vec::with_capacity(leaves.len());
+    let mut right = Vec::with_capacity(leaves.len());
+    for (leaf, matched) in leaves.into_iter().zip(matched) {
+        match matched {
+            true => {
+                left.push(leaf);
+                right.push(fill_value.clone());
+            }
+            false => {
+                left.push(fill_value.clone());
+                right.push(leaf);
+            }
+        }
+    }
+    Ok((
+        treespec.unflatten_impl(PyList::new(py, left)?.as_any(), None)?,
+        treespec.unflatten_impl(PyList::new(py, right)?.as_any(), None)?,
+    ))
+}
+
+#[pyfunction]
+#[pyo3(signature = (
+        predicate,
+        tree,
+        /,
+        fill_value=None,
+        leaf_predicate=None,
+        none_is_leaf=false,
+        namespace="",
+    ))]
+pub fn filter<'py>(
+    predicate: &Bound<'py, PyAny>,
+    tree: &Bound<'py, PyAny>,
+    fill_value: Option<&Bound<'py, PyAny>>,
+    leaf_predicate: Option<&Bound<'py, PyAny>>,
+    none_is_leaf: Option<bool>,
+    namespace: Option<&str>,
+) -> PyResult<Bound<'py, PyAny>> {
+    let py = tree.py();
+    let fill_value = fill_value
+        .cloned()
+        .unwrap_or_else(|| py.None().into_bound(py));
+    let (leaves, matched, treespec) = split_leaves(
+        predicate,
+        tree,
+        leaf_predicate,
+        none_is_leaf.unwrap_or(false),
+        namespace.unwrap_or(""),
+    )?;
+    let leaves = leaves
+        .into_iter()
+        .zip(matched)
+        .map(|(leaf, matched)| match matched {
+            true => leaf,
+            false => fill_value.clone(),
+        });
+    treespec.unflatten_impl(PyList::new(py, leaves)?.as_any(), None)
+}
@@ -13,6 +13,514 @@
 // limitations under the License.
 // =============================================================================
 
+mod align;
+mod broadcast;
+mod builder;
+mod cache;
+mod comparison;
+mod constructors;
+mod copy;
+mod edit;
 mod flatten;
+mod format;
+mod map;
+mod query;
+mod ravel;
+mod reduce;
+mod serialization;
+mod transpose;
+mod traverse;
+mod unflatten;
 
-pub use flatten::is_leaf;
+use pyo3::exceptions::PyIndexError;
+use pyo3::intern;
+use pyo3::prelude::*;
+use pyo3::sync::PyOnceLock;
+use pyo3::types::*;
+use std::hash::{DefaultHasher, Hasher};
+use std::sync::Arc;
+
+use crate::rustree::accessors::{
+    MappingEntry, NamedTupleEntry, PyTreeEntry, SequenceEntry, StructSequenceEntry,
+};
+use crate::rustree::registry::{PyTreeKind, PyTreeTypeRegistration};
+use crate::rustree::treespec::query::{node_entries, node_entry};
+
+pub use align::align_tree;
+pub use broadcast::{
+    PrefixError, broadcast_common, broadcast_map, broadcast_map_with_path, broadcast_prefix,
+    broadcast_prefix_leaves, prefix_errors,
+};
+pub use builder::TreeSpecBuilder;
+pub use cache::{treespec_cache_clear, treespec_cache_size};
+pub use comparison::hash_tree;
+pub use constructors::{make_container, make_from_collection, make_leaf, make_none};
+pub use copy::{copy_tree, deepcopy, replace_nones};
+pub use edit::{get_at_path, replace_at_path};
+pub(crate) use flatten::{DictOrder, MAX_RECURSION_DEPTH, get_children};
+pub use flatten::{
+    are_all_leaves, flatten, flatten_into_list, flatten_multiple, flatten_tree,
+    flatten_with_accessor, flatten_with_path, is_leaf, is_leaf_type, structure,
+};
+pub use format::format_tree;
+pub use map::{filter, map_tree, map_with_path, map_with_path_, partition, transpose_map};
+pub use ravel::{Unraveler, ravel_tree};
+pub use reduce::{
+    all_leaves, any_leaf, count, leaf_depths, leaves, max_leaf, mean_leaves, min_leaf,
+    reduce_with_path, shared_leaves, sum_leaves, type_counts,
+};
+pub use serialization::{get_load_limits, set_load_limits};
+pub use transpose::transpose_tree;
+pub use traverse::PyTreeNode;
+pub(crate) use unflatten::make_node;
+
+static PYTREE_ACCESSOR_TYPE: PyOnceLock<Py<PyType>> = PyOnceLock::new();
+
+pub(crate) fn path_entry_type<'py>(
+    py: Python<'py>,
+    kind: PyTreeKind,
+    custom: Option<&Arc<PyTreeTypeRegistration>>,
+) -> PyResult<Bound<'py, PyType>> {
+    Ok(match kind {
+        PyTreeKind::Custom => {
+            if let Some(path_entry_type) = custom.and_then(|r| r.path_entry_type.as_ref()) {
+                return Ok(path_entry_type.bind(py).clone());
+            }
+            py.get_type::<PyTreeEntry>()
+        }
+        PyTreeKind::Tuple | PyTreeKind::List | PyTreeKind::Deque => py.get_type::<SequenceEntry>(),
+        PyTreeKind::Dict | PyTreeKind::OrderedDict | PyTreeKind::DefaultDict => {
+            py.get_type::<MappingEntry>()
+        }
+        PyTreeKind::NamedTuple => py.get_type::<NamedTupleEntry>(),
+        PyTreeKind::StructSequence => py.get_type::<StructSequenceEntry>(),
+        PyTreeKind::Leaf | PyTreeKind::None => py.get_type::<PyTreeEntry>(),
+    })
+}
+
+// The accessor class is defined in `rustree/accessors.py` as a subclass of `tuple`, which the
+// Rust classes cannot extend, and attached to this module on import. The accessors must stay
+// `tuple` instances since they are unpacked, sliced, and compared as the tuples of the path entries.
+#[inline]
+pub(crate) fn pytree_accessor_type(py: Python<'_>) -> PyResult<&Bound<'_, PyType>> {
+    PYTREE_ACCESSOR_TYPE.import(py, "rustree._rs", "PyTreeAccessor")
+}
+
+// Build an accessor with `tuple.__new__`, skipping the validation of the path entries in the Python
+// constructor since the entries are built in Rust
+pub(crate) fn new_accessor<'py>(path: Bound<'py, PyTuple>) -> PyResult<Bound<'py, PyAny>> {
+    let py = path.py();
+    py.get_type::<PyTuple>()
+        .call_method1(intern!(py, "__new__"), (pytree_accessor_type(py)?, path))
+}
+
+pub struct Node {
+    pub(crate) kind: PyTreeKind,
+    pub(crate) arity: usize,
+    pub(crate) node_data: Option<Py<PyAny>>,
+    pub(crate) custom: Option<Arc<PyTreeTypeRegistration>>,
+    pub(crate) num_leaves: usize,
+    // The path entries of the custom nodes, given by the flatten function or the positions of the
+    // children
+    pub(crate) entries: Option<Py<PyTuple>>,
+    // The traversal index of the first occurrence of a shared subtree, the repeated occurrences are
+    // recorded as empty nodes
+    pub(crate) reference: Option<usize>,
+}
+
+impl Node {
+    pub(crate) fn leaf() -> Self {
+        Node {
+            kind: PyTreeKind::Leaf,
+            arity: 0,
+            node_data: None,
+            custom: None,
+            num_leaves: 1,
+            entries: None,
+            reference: None,
+        }
+    }
+
+    pub(crate) fn clone_ref(&self, py: Python<'_>) -> Self {
+        Node {
+            kind: self.kind,
+            arity: self.arity,
+            node_data: self.node_data.as_ref().map(|data| data.clone_ref(py)),
+            custom: self.custom.clone(),
+            num_leaves: self.num_leaves,
+            entries: self.entries.as_ref().map(|entries| entries.clone_ref(py)),
+            reference: self.reference,
+        }
+    }
+
+    pub(crate) fn node_type<'py>(&self, py: Python<'py>) -> PyResult<Option<Bound<'py, PyType>>> {
+        Ok(Some(match self.kind {
+            PyTreeKind::Leaf => return Ok(None),
+            PyTreeKind::None
+            | PyTreeKind::Tuple
+            | PyTreeKind::List
+            | PyTreeKind::Dict
+            | PyTreeKind::OrderedDict
+            | PyTreeKind::DefaultDict
+            | PyTreeKind::Deque => return self.kind.builtin_node_type(py),
+            PyTreeKind::NamedTuple | PyTreeKind::StructSequence => self
+                .node_data
+                .as_ref()
+                .unwrap()
+                .bind(py)
+                .downcast::<PyType>()?
+                .clone(),
+            PyTreeKind::Custom => self.custom.as_ref().unwrap().node_type.bind(py).clone(),
+        }))
+    }
+}
+
+#[pyclass(frozen, module = "rustree")]
+pub struct PyTreeSpec {
+    pub(crate) traversal: Vec<Node>,
+    pub(crate) none_is_leaf: bool,
+    pub(crate) namespace: String,
+}
+
+impl PyTreeSpec {
+    #[inline]
+    pub(crate) fn root(&self) -> &Node {
+        self.traversal.last().unwrap()
+    }
+
+    #[inline]
+    pub fn num_leaves(&self) -> usize {
+        self.root().num_leaves
+    }
+
+    #[inline]
+    pub fn num_nodes(&self) -> usize {
+        self.traversal.len()
+    }
+
+    // The number of nodes in the subtree rooted at each node of the traversal
+    pub(crate) fn subtree_num_nodes(&self) -> Vec<usize> {
+        let mut num_nodes = Vec::with_capacity(self.traversal.len());
+        let mut agenda: Vec<usize> = Vec::new();
+        for node in self.traversal.iter() {
+            let size = agenda.len();
+            let count = 1 + agenda.drain(size - node.arity..).sum::<usize>();
+            num_nodes.push(count);
+            agenda.push(count);
+        }
+        num_nodes
+    }
+
+    // The position of a child of the root node, negative indices count from the end
+    pub(crate) fn child_position(&self, index: isize) -> PyResult<usize> {
+        let arity = self.root().arity as isize;
+        let position = if index < 0 { index + arity } else { index };
+        if !(0..arity).contains(&position) {
+            return Err(PyIndexError::new_err(std::format!(
+                "Child index {} out of range for the root node of arity {}.",
+                index,
+                arity,
+            )));
+        }
+        Ok(position as usize)
+    }
+
+    // The traversal indices of the children of a node, in order
+    pub(crate) fn child_indices(&self, num_nodes: &[usize], index: usize) -> Vec<usize> {
+        // The subtrees of the children precede the node in the post-order traversal
+        let arity = self.traversal[index].arity;
+        let mut child_indices = Vec::with_capacity(arity);
+        let mut child_index = index.wrapping_sub(1);
+        for _ in 0..arity {
+            child_indices.push(child_index);
+            child_index = child_index.wrapping_sub(num_nodes[child_index]);
+        }
+        child_indices.reverse();
+        child_indices
+    }
+}
+
+#[pymethods]
+impl PyTreeSpec {
+    #[pyo3(signature = (leaves, /, validate=None))]
+    pub fn unflatten<'py>(
+        &self,
+        leaves: &Bound<'py, PyAny>,
+        validate: Option<&Bound<'py, PyAny>>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        self.unflatten_impl(leaves, validate)
+    }
+
+    #[pyo3(signature = (leaves, /, f_node=None, f_leaf=None))]
+    fn walk<'py>(
+        &self,
+        leaves: &Bound<'py, PyAny>,
+        f_node: Option<&Bound<'py, PyAny>>,
+        f_leaf: Option<&Bound<'py, PyAny>>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        self.walk_impl(leaves, f_node, f_leaf)
+    }
+
+    // The missing leaves are filled by `default_factory(index, path)` if given, else by `default`
+    #[pyo3(signature = (leaves, /, default=None, *, default_factory=None))]
+    fn unflatten_partial<'py>(
+        &self,
+        py: Python<'py>,
+        leaves: &Bound<'py, PyAny>,
+        default: Option<&Bound<'py, PyAny>>,
+        default_factory: Option<&Bound<'py, PyAny>>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let default = default.cloned().unwrap_or_else(|| py.None().into_bound(py));
+        self.unflatten_partial_impl(py, leaves, &default, default_factory)
+    }
+
+    #[pyo3(signature = (tree, /, leaf_predicate=None))]
+    fn flatten<'py>(
+        &self,
+        tree: &Bound<'py, PyAny>,
+        leaf_predicate: Option<&Bound<'py, PyAny>>,
+    ) -> PyResult<Bound<'py, PyList>> {
+        PyList::new(tree.py(), self.flatten_impl(tree, leaf_predicate)?)
+    }
+
+    #[pyo3(signature = (tree, /))]
+    fn flatten_up_to<'py>(&self, tree: &Bound<'py, PyAny>) -> PyResult<Bound<'py, PyList>> {
+        PyList::new(tree.py(), self.flatten_up_to_impl(tree)?)
+    }
+
+    #[pyo3(signature = (path, new_subspec, /))]
+    fn replace_subtree(
+        &self,
+        py: Python<'_>,
+        path: &Bound<'_, PyAny>,
+        new_subspec: &PyTreeSpec,
+    ) -> PyResult<PyTreeSpec> {
+        self.replace_subtree_impl(py, path, new_subspec)
+    }
+
+    #[pyo3(signature = (path, /))]
+    fn index(&self, py: Python<'_>, path: &Bound<'_, PyAny>) -> PyResult<usize> {
+        self.leaf_index_at(py, path)
+    }
+
+    #[pyo3(signature = (path, /))]
+    fn leaf_range(&self, py: Python<'_>, path: &Bound<'_, PyAny>) -> PyResult<(usize, usize)> {
+        self.leaf_range_impl(py, path)
+    }
+
+    #[pyo3(signature = (path, /))]
+    fn entry_type_at<'py>(
+        &self,
+        py: Python<'py>,
+        path: &Bound<'_, PyAny>,
+    ) -> PyResult<Bound<'py, PyTuple>> {
+        self.entry_type_at_impl(py, path)
+    }
+
+    // A strict leaf is a single leaf, otherwise any treespec with a single node (e.g., `None` or an
+    // empty container) is also accepted
+    #[pyo3(signature = (strict=true))]
+    fn is_leaf(&self, strict: bool) -> bool {
+        match strict {
+            true => self.root().kind == PyTreeKind::Leaf,
+            false => self.traversal.len() == 1,
+        }
+    }
+
+    #[pyo3(signature = (other, /, strict=false))]
+    fn is_prefix(&self, py: Python<'_>, other: &PyTreeSpec, strict: bool) -> PyResult<bool> {
+        self.is_prefix_impl(py, other, strict)
+    }
+
+    #[pyo3(signature = (other, /, strict=false))]
+    fn is_suffix(&self, py: Python<'_>, other: &PyTreeSpec, strict: bool) -> PyResult<bool> {
+        other.is_prefix_impl(py, self, strict)
+    }
+
+    #[pyo3(signature = (inner, /))]
+    fn compose(&self, py: Python<'_>, inner: &PyTreeSpec) -> PyResult<PyTreeSpec> {
+        self.compose_impl(py, inner)
+    }
+
+    fn __add__(&self, py: Python<'_>, other: &PyTreeSpec) -> PyResult<PyTreeSpec> {
+        self.compose_impl(py, other)
+    }
+
+    #[pyo3(signature = (f_node=None, f_leaf=None))]
+    fn transform(
+        &self,
+        py: Python<'_>,
+        f_node: Option<&Bound<'_, PyAny>>,
+        f_leaf: Option<&Bound<'_, PyAny>>,
+    ) -> PyResult<PyTreeSpec> {
+        self.transform_impl(py, f_node, f_leaf)
+    }
+
+    #[pyo3(signature = (other, /))]
+    fn broadcast_to_common_suffix(
+        &self,
+        py: Python<'_>,
+        other: &PyTreeSpec,
+    ) -> PyResult<PyTreeSpec> {
+        self.broadcast_to_common_suffix_impl(py, other)
+    }
+
+    fn traverse<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyIterator>> {
+        self.traverse_impl(py)
+    }
+
+    fn children(&self, py: Python<'_>) -> Vec<PyTreeSpec> {
+        let num_nodes = self.subtree_num_nodes();
+        self.child_indices(&num_nodes, self.traversal.len() - 1)
+            .into_iter()
+            .map(|child_index| self.subtree(py, &num_nodes, child_index))
+            .collect()
+    }
+
+    #[pyo3(signature = (index, /))]
+    fn child(&self, py: Python<'_>, index: isize) -> PyResult<PyTreeSpec> {
+        let position = self.child_position(index)?;
+        let num_nodes = self.subtree_num_nodes();
+        let child_index = self.child_indices(&num_nodes, self.traversal.len() - 1)[position];
+        Ok(self.subtree(py, &num_nodes, child_index))
+    }
+
+    fn entries<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyList>> {
+        PyList::new(py, node_entries(py, self.root())?)
+    }
+
+    fn paths<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyList>> {
+        PyList::new(py, self.leaf_paths(py)?)
+    }
+
+    fn accessors<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyList>> {
+        PyList::new(py, self.leaf_accessors(py)?)
+    }
+
+    #[pyo3(signature = (index, /))]
+    fn entry<'py>(&self, py: Python<'py>, index: isize) -> PyResult<Bound<'py, PyAny>> {
+        node_entry(py, self.root(), self.child_position(index)?)
+    }
+
+    #[pyo3(signature = (other, /, *, return_reason=false))]
+    fn is_namespace_compatible<'py>(
+        &self,
+        py: Python<'py>,
+        other: &PyTreeSpec,
+        return_reason: bool,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let reason = self.namespace_incompatibility(py, other)?;
+        let compatible = PyBool::new(py, reason.is_none()).to_owned().into_any();
+        match return_reason {
+            true => Ok(PyTuple::new(py, [compatible, reason.into_pyobject(py)?])?.into_any()),
+            false => Ok(compatible),
+        }
+    }
+
+    #[getter(num_leaves)]
+    fn get_num_leaves(&self) -> usize {
+        self.num_leaves()
+    }
+
+    #[getter(num_nodes)]
+    fn get_num_nodes(&self) -> usize {
+        self.num_nodes()
+    }
+
+    #[getter]
+    fn num_children(&self) -> usize {
+        self.root().arity
+    }
+
+    #[getter]
+    fn none_is_leaf(&self) -> bool {
+        self.none_is_leaf
+    }
+
+    #[getter]
+    fn namespace(&self) -> &str {
+        &self.namespace
+    }
+
+    #[getter(r#type)]
+    fn get_type<'py>(&self, py: Python<'py>) -> PyResult<Option<Bound<'py, PyType>>> {
+        self.root().node_type(py)
+    }
+
+    #[getter]
+    fn kind(&self) -> PyTreeKind {
+        self.root().kind
+    }
+
+    fn __len__(&self) -> usize {
+        self.num_leaves()
+    }
+
+    #[pyo3(signature = (depth=None, max_children=None))]
+    fn repr(
+        &self,
+        py: Python<'_>,
+        depth: Option<usize>,
+        max_children: Option<usize>,
+    ) -> PyResult<String> {
+        self.repr_limited(py, depth, max_children, None)
+    }
+
+    fn __repr__(&self, py: Python<'_>) -> PyResult<String> {
+        self.repr_impl(py)
+    }
+
+    fn __eq__(&self, py: Python<'_>, other: &PyTreeSpec) -> PyResult<bool> {
+        self.equal_impl(py, other)
+    }
+
+    fn __ne__(&self, py: Python<'_>, other: &PyTreeSpec) -> PyResult<bool> {
+        Ok(!self.equal_impl(py, other)?)
+    }
+
+    // The treespec is pickled as its bytes, so the unpickling resolves the custom node types and the
+    // node data as `from_bytes()` instead of letting `pickle` import the arbitrary callables
+    fn __reduce__<'py>(
+        slf: &Bound<'py, Self>,
+    ) -> PyResult<(Bound<'py, PyAny>, (Bound<'py, PyBytes>,))> {
+        let py = slf.py();
+        Ok((
+            slf.get_type().getattr("_from_pickle")?,
+            (slf.get().to_bytes_impl(py)?,),
+        ))
+    }
+
+    #[classmethod]
+    #[pyo3(signature = (data, /))]
+    fn _from_pickle(cls: &Bound<'_, PyType>, data: &[u8]) -> PyResult<PyTreeSpec> {
+        PyTreeSpec::from_pickle_impl(cls.py(), data)
+    }
+
+    fn __copy__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    // Copy the node data without the pickle round trip, which refuses the unregistered classes
+    fn __deepcopy__<'py>(&self, py: Python<'py>, memo: &Bound<'py, PyAny>) -> PyResult<PyTreeSpec> {
+        let deepcopy = py.import("copy")?.getattr("deepcopy")?;
+        PyTreeSpec::from_state(&deepcopy.call1((self.state(py)?, memo))?)
+    }
+
+    fn to_bytes<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyBytes>> {
+        self.to_bytes_impl(py)
+    }
+
+    // Only the registered custom node types and the side-effect-free classes are resolved from the
+    // untrusted data
+    #[classmethod]
+    #[pyo3(signature = (data, /, *, trusted=false))]
+    fn from_bytes(cls: &Bound<'_, PyType>, data: &[u8], trusted: bool) -> PyResult<PyTreeSpec> {
+        PyTreeSpec::from_bytes_impl(cls.py(), data, trusted)
+    }
+
+    fn __hash__(&self, py: Python<'_>) -> PyResult<u64> {
+        let mut state = DefaultHasher::new();
+        self.hash_impl(py, &mut state)?;
+        Ok(state.finish())
+    }
+}
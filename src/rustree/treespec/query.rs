@@ -0,0 +1,379 @@
+// Copyright 2024-2025 Xuehai Pan. All Rights Reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+// =============================================================================
+
+use pyo3::exceptions::{PyKeyError, PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::*;
+use std::collections::HashMap;
+
+use crate::rustree::pytypes::{namedtuple_fields, structseq_fields};
+use crate::rustree::registry::PyTreeKind;
+use crate::rustree::treespec::serialization::repr_node_shape;
+use crate::rustree::treespec::{Node, PyTreeSpec, new_accessor, path_entry_type};
+
+// Resolve a path entry to the position of the child in the node
+fn child_position(py: Python<'_>, node: &Node, key: &Bound<'_, PyAny>) -> PyResult<Option<usize>> {
+    let node_data = node.node_data.as_ref().map(|data| data.bind(py));
+    let keys = match node.kind {
+        PyTreeKind::Dict | PyTreeKind::OrderedDict => Some(node_data.unwrap().clone()),
+        PyTreeKind::DefaultDict => Some(node_data.unwrap().get_item(1)?),
+        PyTreeKind::Custom => node
+            .entries
+            .as_ref()
+            .map(|entries| entries.bind(py).clone().into_any()),
+        _ => None,
+    };
+    if let Some(keys) = keys {
+        for (i, k) in keys.try_iter()?.enumerate() {
+            if k?.eq(key)? {
+                return Ok(Some(i));
+            }
+        }
+        return Ok(None);
+    }
+
+    if let Ok(field) = key.downcast::<PyString>() {
+        let fields = match node.kind {
+            PyTreeKind::NamedTuple => namedtuple_fields(node_data.unwrap())?,
+            PyTreeKind::StructSequence => structseq_fields(node_data.unwrap())?,
+            _ => return Ok(None),
+        };
+        for (i, f) in fields.iter().enumerate() {
+            if f.eq(field)? {
+                return Ok(Some(i));
+            }
+        }
+        return Ok(None);
+    }
+
+    // Sequence-like nodes and custom nodes are indexed by the position of the children
+    if key.is_instance_of::<PyBool>() {
+        return Ok(None);
+    }
+    let Ok(index) = key.extract::<isize>() else {
+        return Ok(None);
+    };
+    let arity = node.arity as isize;
+    let index = if index < 0 { index + arity } else { index };
+    Ok((0..arity).contains(&index).then_some(index as usize))
+}
+
+fn check_path(path: &Bound<'_, PyAny>) -> PyResult<()> {
+    if path.is_instance_of::<PyString>() {
+        return Err(PyTypeError::new_err(std::format!(
+            "Expected a path as a sequence of path entries, got {}.",
+            path.repr()?,
+        )));
+    }
+    Ok(())
+}
+
+// The path entries of the children of a node, custom nodes are indexed by the position of the
+// children unless their entries are recorded in the treespec
+pub(crate) fn node_entries<'py>(py: Python<'py>, node: &Node) -> PyResult<Vec<Bound<'py, PyAny>>> {
+    let node_data = node.node_data.as_ref().map(|data| data.bind(py));
+    if let Some(entries) = &node.entries {
+        return Ok(entries.bind(py).iter().collect());
+    }
+    match node.kind {
+        PyTreeKind::Dict | PyTreeKind::OrderedDict => node_data.unwrap().try_iter()?.collect(),
+        PyTreeKind::DefaultDict => node_data.unwrap().get_item(1)?.try_iter()?.collect(),
+        _ => (0..node.arity)
+            .map(|i| Ok(i.into_pyobject(py)?.into_any()))
+            .collect(),
+    }
+}
+
+// The path entry of the child at the position, without building the entries of the other children
+pub(crate) fn node_entry<'py>(
+    py: Python<'py>,
+    node: &Node,
+    position: usize,
+) -> PyResult<Bound<'py, PyAny>> {
+    let node_data = node.node_data.as_ref().map(|data| data.bind(py));
+    if let Some(entries) = &node.entries {
+        return entries.bind(py).get_item(position);
+    }
+    match node.kind {
+        PyTreeKind::Dict | PyTreeKind::OrderedDict => node_data.unwrap().get_item(position),
+        PyTreeKind::DefaultDict => node_data.unwrap().get_item(1)?.get_item(position),
+        _ => Ok(position.into_pyobject(py)?.into_any()),
+    }
+}
+
+impl PyTreeSpec {
+    // The paths to the leaves, in flatten order
+    pub(crate) fn leaf_paths<'py>(&self, py: Python<'py>) -> PyResult<Vec<Bound<'py, PyTuple>>> {
+        self.leaf_paths_impl(py, false)
+    }
+
+    // The accessors to the leaves, in flatten order
+    pub(crate) fn leaf_accessors<'py>(&self, py: Python<'py>) -> PyResult<Vec<Bound<'py, PyAny>>> {
+        self.leaf_paths_impl(py, true)?
+            .into_iter()
+            .map(new_accessor)
+            .collect()
+    }
+
+    // The paths of raw path entries, or of the typed path entries of the accessors
+    fn leaf_paths_impl<'py>(
+        &self,
+        py: Python<'py>,
+        typed: bool,
+    ) -> PyResult<Vec<Bound<'py, PyTuple>>> {
+        let num_nodes = self.subtree_num_nodes();
+        let mut paths = Vec::with_capacity(self.num_leaves());
+        let mut prefix: Vec<Bound<'py, PyAny>> = Vec::new();
+        self.collect_leaf_paths(
+            py,
+            &num_nodes,
+            self.traversal.len() - 1,
+            typed,
+            &mut prefix,
+            &mut paths,
+        )?;
+        Ok(paths)
+    }
+
+    fn collect_leaf_paths<'py>(
+        &self,
+        py: Python<'py>,
+        num_nodes: &[usize],
+        index: usize,
+        typed: bool,
+        prefix: &mut Vec<Bound<'py, PyAny>>,
+        paths: &mut Vec<Bound<'py, PyTuple>>,
+    ) -> PyResult<()> {
+        let node = &self.traversal[index];
+        if node.kind == PyTreeKind::Leaf {
+            paths.push(PyTuple::new(py, prefix.iter())?);
+            return Ok(());
+        }
+        let entry_type = match typed {
+            true => Some((
+                path_entry_type(py, node.kind, node.custom.as_ref())?,
+                node.node_type(py)?,
+            )),
+            false => None,
+        };
+        let entries = node_entries(py, node)?;
+        for (mut entry, child_index) in entries
+            .into_iter()
+            .zip(self.child_indices(num_nodes, index))
+        {
+            if let Some((entry_type, node_type)) = &entry_type {
+                entry = entry_type.call1((entry, node_type, node.kind))?;
+            }
+            prefix.push(entry);
+            let result = self.collect_leaf_paths(py, num_nodes, child_index, typed, prefix, paths);
+            prefix.pop();
+            result?;
+        }
+        Ok(())
+    }
+
+    // The treespec of the subtree rooted at the traversal index, the shared subtrees that are first
+    // flattened outside of it are copied in place of their first reference
+    pub(crate) fn subtree(&self, py: Python<'_>, num_nodes: &[usize], index: usize) -> PyTreeSpec {
+        let mut traversal = Vec::with_capacity(num_nodes[index]);
+        let mut copied = HashMap::new();
+        self.copy_subtree(py, num_nodes, index, &mut traversal, &mut copied);
+        // The copied shared subtrees add to the leaf counts of their ancestors
+        let mut agenda: Vec<usize> = Vec::new();
+        for node in traversal.iter_mut() {
+            if node.kind != PyTreeKind::Leaf {
+                let size = agenda.len();
+                node.num_leaves = agenda.drain(size - node.arity..).sum();
+            }
+            agenda.push(node.num_leaves);
+        }
+        PyTreeSpec {
+            traversal,
+            none_is_leaf: self.none_is_leaf,
+            namespace: self.namespace.clone(),
+        }
+    }
+
+    // The treespec of a non-leaf node with its children replaced by leaves
+    pub(crate) fn one_level(&self, py: Python<'_>, index: usize) -> PyTreeSpec {
+        let node = &self.traversal[index];
+        let mut traversal = Vec::with_capacity(node.arity + 1);
+        traversal.extend((0..node.arity).map(|_| Node::leaf()));
+        let mut node = node.clone_ref(py);
+        node.num_leaves = node.arity;
+        traversal.push(node);
+        PyTreeSpec {
+            traversal,
+            none_is_leaf: self.none_is_leaf,
+            namespace: match self.traversal[index].kind {
+                PyTreeKind::Custom => self.namespace.clone(),
+                _ => String::new(),
+            },
+        }
+    }
+
+    fn copy_subtree(
+        &self,
+        py: Python<'_>,
+        num_nodes: &[usize],
+        index: usize,
+        traversal: &mut Vec<Node>,
+        copied: &mut HashMap<usize, usize>,
+    ) {
+        let node = &self.traversal[index];
+        if let Some(target) = node.reference {
+            match copied.get(&target) {
+                Some(&copy) => {
+                    let mut node = node.clone_ref(py);
+                    node.reference = Some(copy);
+                    traversal.push(node);
+                }
+                None => self.copy_subtree(py, num_nodes, target, traversal, copied),
+            }
+            return;
+        }
+        for child_index in self.child_indices(num_nodes, index) {
+            self.copy_subtree(py, num_nodes, child_index, traversal, copied);
+        }
+        copied.insert(index, traversal.len());
+        traversal.push(node.clone_ref(py));
+    }
+
+    // Resolve a path to the traversal index of the subtree and the index of its first leaf
+    pub(crate) fn locate(
+        &self,
+        py: Python<'_>,
+        path: &Bound<'_, PyAny>,
+    ) -> PyResult<(usize, usize)> {
+        self.walk_path(py, path, |_| Ok(()))
+    }
+
+    // Resolve a path and visit the node indexed by each path entry
+    fn walk_path(
+        &self,
+        py: Python<'_>,
+        path: &Bound<'_, PyAny>,
+        mut visit: impl FnMut(&Node) -> PyResult<()>,
+    ) -> PyResult<(usize, usize)> {
+        let num_nodes = self.subtree_num_nodes();
+        let mut index = self.traversal.len() - 1;
+        let mut start: usize = 0;
+        let mut prefix: Vec<Bound<'_, PyAny>> = Vec::new();
+        let entry_type = path_entry_type(py, PyTreeKind::Leaf, None)?;
+        for key in path.try_iter()? {
+            let mut key = key?;
+            // Also accept the path entries of a `PyTreeAccessor`
+            if key.is_instance(&entry_type)? {
+                key = key.getattr("entry")?;
+            }
+            let node = &self.traversal[index];
+            if node.kind == PyTreeKind::Leaf {
+                return Err(PyKeyError::new_err(std::format!(
+                    "Cannot index into a leaf at path {} with path entry {}.",
+                    PyTuple::new(py, &prefix)?.repr()?,
+                    key.repr()?,
+                )));
+            }
+            let Some(position) = child_position(py, node, &key)? else {
+                return Err(PyKeyError::new_err(std::format!(
+                    "Path entry {} not found in node {} at path {}.",
+                    key.repr()?,
+                    repr_node_shape(py, node)?,
+                    PyTuple::new(py, &prefix)?.repr()?,
+                )));
+            };
+            visit(node)?;
+            let child_indices = self.child_indices(&num_nodes, index);
+            start += child_indices[..position]
+                .iter()
+                .map(|&child_index| self.traversal[child_index].num_leaves)
+                .sum::<usize>();
+            index = child_indices[position];
+            // Follow the reference to the first occurrence of a shared subtree, where its leaves are
+            if let Some(target) = self.traversal[index].reference {
+                index = target;
+                start = self.traversal[..target + 1 - num_nodes[target]]
+                    .iter()
+                    .filter(|node| node.kind == PyTreeKind::Leaf)
+                    .count();
+            }
+            prefix.push(key);
+        }
+        Ok((index, start))
+    }
+
+    // The path to the node at the traversal index
+    pub(crate) fn node_path<'py>(
+        &self,
+        py: Python<'py>,
+        num_nodes: &[usize],
+        index: usize,
+    ) -> PyResult<Bound<'py, PyTuple>> {
+        let mut path = Vec::new();
+        let mut current = self.traversal.len() - 1;
+        while current != index {
+            let node = &self.traversal[current];
+            let entries = node_entries(py, node)?;
+            let (entry, child_index) = entries
+                .into_iter()
+                .zip(self.child_indices(num_nodes, current))
+                .find(|(_, child_index)| {
+                    child_index + 1 - num_nodes[*child_index] <= index && index <= *child_index
+                })
+                .unwrap();
+            path.push(entry);
+            current = child_index;
+        }
+        PyTuple::new(py, path)
+    }
+
+    // The index of the leaf at the path in the flattened leaves
+    pub(crate) fn leaf_index_at(&self, py: Python<'_>, path: &Bound<'_, PyAny>) -> PyResult<usize> {
+        check_path(path)?;
+        let (index, start) = self.locate(py, path)?;
+        if self.traversal[index].kind != PyTreeKind::Leaf {
+            return Err(PyValueError::new_err(std::format!(
+                "Path {} does not point to a leaf.",
+                path.repr()?,
+            )));
+        }
+        Ok(start)
+    }
+
+    pub(crate) fn leaf_range_impl(
+        &self,
+        py: Python<'_>,
+        path: &Bound<'_, PyAny>,
+    ) -> PyResult<(usize, usize)> {
+        check_path(path)?;
+        let (index, start) = self.locate(py, path)?;
+        Ok((start, start + self.traversal[index].num_leaves))
+    }
+
+    // The path entry classes of the nodes indexed by each path entry
+    pub(crate) fn entry_type_at_impl<'py>(
+        &self,
+        py: Python<'py>,
+        path: &Bound<'_, PyAny>,
+    ) -> PyResult<Bound<'py, PyTuple>> {
+        check_path(path)?;
+        let mut entry_types = Vec::new();
+        self.walk_path(py, path, |node| {
+            entry_types.push(path_entry_type(py, node.kind, node.custom.as_ref())?);
+            Ok(())
+        })?;
+        PyTuple::new(py, entry_types)
+    }
+}
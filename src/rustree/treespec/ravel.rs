@@ -0,0 +1,144 @@
+// Copyright 2024-2025 Xuehai Pan. All Rights Reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+// =============================================================================
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::*;
+
+use crate::rustree::treespec::PyTreeSpec;
+use crate::rustree::treespec::flatten::Flattener;
+
+// Split a flat buffer back into the leaves of a raveled tree
+#[pyclass(frozen, module = "rustree")]
+pub struct Unraveler {
+    treespec: Py<PyTreeSpec>,
+    shapes: Vec<Py<PyAny>>,
+    dtypes: Vec<Py<PyAny>>,
+    // The leaf `i` is stored in `flat[offsets[i]:offsets[i + 1]]`
+    offsets: Vec<usize>,
+    backend: Py<PyAny>,
+}
+
+impl Unraveler {
+    fn size(&self) -> usize {
+        *self.offsets.last().unwrap()
+    }
+}
+
+#[pymethods]
+impl Unraveler {
+    fn __call__<'py>(&self, flat: &Bound<'py, PyAny>) -> PyResult<Bound<'py, PyAny>> {
+        let py = flat.py();
+        let size = flat.len()?;
+        if size != self.size() {
+            return Err(PyValueError::new_err(std::format!(
+                "Expected a flat buffer of size {}, got {}.",
+                self.size(),
+                size,
+            )));
+        }
+        let backend = self.backend.bind(py);
+        let mut leaves = Vec::with_capacity(self.shapes.len());
+        for ((window, shape), dtype) in self.offsets.windows(2).zip(&self.shapes).zip(&self.dtypes)
+        {
+            let chunk =
+                flat.get_item(PySlice::new(py, window[0] as isize, window[1] as isize, 1))?;
+            leaves.push(backend.call_method1("unravel", (chunk, shape.bind(py), dtype.bind(py)))?);
+        }
+        self.treespec
+            .get()
+            .unflatten_impl(PyList::new(py, leaves)?.as_any(), None)
+    }
+
+    #[getter]
+    fn treespec(&self, py: Python<'_>) -> Py<PyTreeSpec> {
+        self.treespec.clone_ref(py)
+    }
+
+    #[getter]
+    fn shapes<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyTuple>> {
+        PyTuple::new(py, &self.shapes)
+    }
+
+    #[getter]
+    fn dtypes<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyTuple>> {
+        PyTuple::new(py, &self.dtypes)
+    }
+
+    #[getter]
+    fn offsets<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyTuple>> {
+        PyTuple::new(py, &self.offsets)
+    }
+
+    #[getter(size)]
+    fn get_size(&self) -> usize {
+        self.size()
+    }
+
+    fn __repr__(&self, py: Python<'_>) -> PyResult<String> {
+        Ok(std::format!(
+            "Unraveler(size={}, treespec={})",
+            self.size(),
+            self.treespec.get().repr_impl(py)?,
+        ))
+    }
+}
+
+#[pyfunction]
+#[pyo3(name = "ravel", signature = (tree, backend, /, leaf_predicate=None, none_is_leaf=false, namespace=""))]
+pub fn ravel_tree<'py>(
+    tree: &Bound<'py, PyAny>,
+    backend: &Bound<'py, PyAny>,
+    leaf_predicate: Option<&Bound<'py, PyAny>>,
+    none_is_leaf: Option<bool>,
+    namespace: Option<&str>,
+) -> PyResult<(Bound<'py, PyAny>, Unraveler)> {
+    let py = tree.py();
+    let mut flattener = Flattener::new(
+        py,
+        leaf_predicate,
+        none_is_leaf.unwrap_or(false),
+        namespace.unwrap_or(""),
+        false,
+    )?;
+    flattener.flatten_into(tree, 0)?;
+    let (_, leaves, treespec) = flattener.finish();
+
+    let mut flats = Vec::with_capacity(leaves.len());
+    let mut shapes = Vec::with_capacity(leaves.len());
+    let mut dtypes = Vec::with_capacity(leaves.len());
+    let mut offsets = Vec::with_capacity(leaves.len() + 1);
+    offsets.push(0);
+    for leaf in &leaves {
+        let (flat, shape, dtype) = backend.call_method1("ravel", (leaf,))?.extract::<(
+            Bound<'py, PyAny>,
+            Bound<'py, PyAny>,
+            Bound<'py, PyAny>,
+        )>()?;
+        offsets.push(offsets.last().unwrap() + flat.len()?);
+        flats.push(flat);
+        shapes.push(shape.unbind());
+        dtypes.push(dtype.unbind());
+    }
+    let flat = backend.call_method1("concatenate", (PyList::new(py, flats)?,))?;
+    let unraveler = Unraveler {
+        treespec: Py::new(py, treespec)?,
+        shapes,
+        dtypes,
+        offsets,
+        backend: backend.clone().unbind(),
+    };
+    Ok((flat, unraveler))
+}
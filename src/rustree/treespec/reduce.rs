@@ -0,0 +1,586 @@
+// Copyright 2024-2025 Xuehai Pan. All Rights Reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+// =============================================================================
+
+use pyo3::exceptions::{PyRecursionError, PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::pyclass::CompareOp;
+use pyo3::types::*;
+use std::collections::HashMap;
+
+use crate::rustree::profiling::{Phase, timed};
+use crate::rustree::registry::{PyTreeKind, PyTreeTypeRegistry};
+use crate::rustree::treespec::flatten::{
+    DictOrder, MAX_RECURSION_DEPTH, NodeChildren, get_children, weak_leaf,
+};
+
+// Visit the leaves in flatten order without building the leaves list or the treespec
+pub(crate) struct LeafWalker<'a, 'py> {
+    leaf_predicate: Option<Bound<'py, PyAny>>,
+    none_is_leaf: bool,
+    namespace: &'a str,
+    path: Option<Vec<Bound<'py, PyAny>>>,
+    dict_order: DictOrder<'py>,
+}
+
+impl<'a, 'py> LeafWalker<'a, 'py> {
+    pub(crate) fn new(
+        py: Python<'py>,
+        leaf_predicate: Option<&Bound<'py, PyAny>>,
+        none_is_leaf: bool,
+        namespace: &'a str,
+        with_path: bool,
+    ) -> Self {
+        LeafWalker {
+            leaf_predicate: PyTreeTypeRegistry::leaf_predicate(py, leaf_predicate, namespace),
+            none_is_leaf,
+            namespace,
+            path: with_path.then(Vec::new),
+            dict_order: DictOrder::for_namespace(namespace),
+        }
+    }
+
+    // The visitor receives the depth of the leaf, its path entries (if requested), and the leaf
+    pub(crate) fn walk<F>(
+        &mut self,
+        obj: &Bound<'py, PyAny>,
+        depth: usize,
+        visit: &mut F,
+    ) -> PyResult<()>
+    where
+        F: FnMut(usize, Option<&[Bound<'py, PyAny>]>, &Bound<'py, PyAny>) -> PyResult<()>,
+    {
+        self.walk_while(obj, depth, &mut |depth, path, leaf| {
+            visit(depth, path, leaf).map(|()| true)
+        })?;
+        Ok(())
+    }
+
+    // Stop the traversal once the visitor returns `false`, and return whether it was completed
+    pub(crate) fn walk_while<F>(
+        &mut self,
+        obj: &Bound<'py, PyAny>,
+        depth: usize,
+        visit: &mut F,
+    ) -> PyResult<bool>
+    where
+        F: FnMut(usize, Option<&[Bound<'py, PyAny>]>, &Bound<'py, PyAny>) -> PyResult<bool>,
+    {
+        if depth > MAX_RECURSION_DEPTH {
+            return Err(PyRecursionError::new_err(
+                "Maximum recursion depth exceeded during flattening the tree.",
+            ));
+        }
+
+        if let Some(leaf_predicate) = &self.leaf_predicate
+            && timed(Phase::Callback, || leaf_predicate.call1((obj,)))?.is_truthy()?
+        {
+            return visit(depth, self.path.as_deref(), obj);
+        }
+        let (kind, custom) = PyTreeTypeRegistry::get_kind(obj, self.none_is_leaf, self.namespace)?;
+        if kind == PyTreeKind::Leaf {
+            return visit(depth, self.path.as_deref(), obj);
+        }
+
+        let NodeChildren {
+            children, entries, ..
+        } = get_children(
+            obj,
+            kind,
+            custom.as_ref(),
+            self.path.is_some(),
+            &self.dict_order,
+        )?;
+        match entries {
+            Some(entries) => {
+                for (child, entry) in children.iter().zip(entries) {
+                    self.path.as_mut().unwrap().push(entry);
+                    let result = self.walk_while(child, depth + 1, visit);
+                    self.path.as_mut().unwrap().pop();
+                    if !result? {
+                        return Ok(false);
+                    }
+                }
+            }
+            None => {
+                for child in children.iter() {
+                    if !self.walk_while(child, depth + 1, visit)? {
+                        return Ok(false);
+                    }
+                }
+            }
+        }
+        Ok(true)
+    }
+}
+
+// The types to match the leaves with, given as a type or a tuple of types
+fn leaf_types<'py>(of_type: &Bound<'py, PyAny>) -> PyResult<Vec<Bound<'py, PyType>>> {
+    let types = match of_type.downcast::<PyTuple>() {
+        Ok(types) => types.iter().collect::<Vec<_>>(),
+        Err(_) => vec![of_type.clone()],
+    };
+    let mut leaf_types = Vec::with_capacity(types.len());
+    for cls in types {
+        match cls.downcast_into::<PyType>() {
+            Ok(cls) => leaf_types.push(cls),
+            Err(_) => {
+                return Err(PyTypeError::new_err(std::format!(
+                    "Expected a type or a tuple of types for `of_type`, got {}.",
+                    of_type.repr()?,
+                )));
+            }
+        }
+    }
+    Ok(leaf_types)
+}
+
+#[pyfunction]
+#[pyo3(
+    signature = (
+        tree,
+        /,
+        leaf_predicate=None,
+        none_is_leaf=false,
+        namespace="",
+        *,
+        of_type=None,
+        exact_type=false,
+        weak_leaves=false,
+    )
+)]
+#[allow(clippy::too_many_arguments)]
+pub fn leaves<'py>(
+    tree: &Bound<'py, PyAny>,
+    leaf_predicate: Option<&Bound<'py, PyAny>>,
+    none_is_leaf: Option<bool>,
+    namespace: Option<&str>,
+    of_type: Option<&Bound<'py, PyAny>>,
+    exact_type: bool,
+    weak_leaves: bool,
+) -> PyResult<Bound<'py, PyList>> {
+    let py = tree.py();
+    let types = of_type.map(leaf_types).transpose()?;
+    let leaves = PyList::empty(py);
+    let mut walker = LeafWalker::new(
+        tree.py(),
+        leaf_predicate,
+        none_is_leaf.unwrap_or(false),
+        namespace.unwrap_or(""),
+        false,
+    );
+    walker.walk(tree, 0, &mut |_, _, leaf| {
+        let matched = match &types {
+            None => true,
+            // Compare the type pointers without the subclass checks
+            Some(types) if exact_type => {
+                let cls = leaf.get_type();
+                types.iter().any(|t| t.is(&cls))
+            }
+            Some(types) => {
+                let mut matched = false;
+                for t in types {
+                    if leaf.is_instance(t)? {
+                        matched = true;
+                        break;
+                    }
+                }
+                matched
+            }
+        };
+        if matched {
+            match weak_leaves {
+                true => leaves.append(weak_leaf(leaf)?)?,
+                false => leaves.append(leaf)?,
+            }
+        }
+        Ok(())
+    })?;
+    Ok(leaves)
+}
+
+#[pyfunction]
+#[pyo3(signature = (func, tree, /, *initial, leaf_predicate=None, none_is_leaf=false, namespace=""))]
+pub fn reduce_with_path<'py>(
+    func: &Bound<'py, PyAny>,
+    tree: &Bound<'py, PyAny>,
+    initial: &Bound<'py, PyTuple>,
+    leaf_predicate: Option<&Bound<'py, PyAny>>,
+    none_is_leaf: Option<bool>,
+    namespace: Option<&str>,
+) -> PyResult<Bound<'py, PyAny>> {
+    let py = tree.py();
+    if initial.len() > 1 {
+        return Err(PyTypeError::new_err(std::format!(
+            "reduce_with_path() expected at most 3 positional arguments, got {}.",
+            initial.len() + 2,
+        )));
+    }
+    let mut accumulator = initial.iter().next();
+    let mut walker = LeafWalker::new(
+        tree.py(),
+        leaf_predicate,
+        none_is_leaf.unwrap_or(false),
+        namespace.unwrap_or(""),
+        true,
+    );
+    walker.walk(tree, 0, &mut |_, path, leaf| {
+        let path = PyTuple::new(py, path.unwrap())?;
+        accumulator = Some(match accumulator.take() {
+            Some(accumulator) => timed(Phase::Callback, || func.call1((accumulator, path, leaf)))?,
+            // Without an initial value, the first leaf is the initial accumulator
+            None => leaf.clone(),
+        });
+        Ok(())
+    })?;
+    accumulator.ok_or_else(|| {
+        PyTypeError::new_err("reduce_with_path() of empty tree with no initial value.")
+    })
+}
+
+#[pyfunction]
+#[pyo3(signature = (predicate, tree, /, leaf_predicate=None, none_is_leaf=false, namespace=""))]
+pub fn count<'py>(
+    predicate: &Bound<'py, PyAny>,
+    tree: &Bound<'py, PyAny>,
+    leaf_predicate: Option<&Bound<'py, PyAny>>,
+    none_is_leaf: Option<bool>,
+    namespace: Option<&str>,
+) -> PyResult<usize> {
+    let mut count: usize = 0;
+    let mut walker = LeafWalker::new(
+        tree.py(),
+        leaf_predicate,
+        none_is_leaf.unwrap_or(false),
+        namespace.unwrap_or(""),
+        false,
+    );
+    walker.walk(tree, 0, &mut |_, _, leaf| {
+        if timed(Phase::Callback, || predicate.call1((leaf,)))?.is_truthy()? {
+            count += 1;
+        }
+        Ok(())
+    })?;
+    Ok(count)
+}
+
+#[pyfunction]
+#[pyo3(
+    name = "sum",
+    signature = (tree, /, leaf_predicate=None, none_is_leaf=false, namespace="", *, start=None)
+)]
+pub fn sum_leaves<'py>(
+    tree: &Bound<'py, PyAny>,
+    leaf_predicate: Option<&Bound<'py, PyAny>>,
+    none_is_leaf: Option<bool>,
+    namespace: Option<&str>,
+    start: Option<&Bound<'py, PyAny>>,
+) -> PyResult<Bound<'py, PyAny>> {
+    let py = tree.py();
+    // Add the leaves one by one to the start value as the built-in `sum` does
+    let mut total = match start {
+        Some(start) => start.clone(),
+        None => 0_i32.into_pyobject(py)?.into_any(),
+    };
+    let mut walker = LeafWalker::new(
+        py,
+        leaf_predicate,
+        none_is_leaf.unwrap_or(false),
+        namespace.unwrap_or(""),
+        false,
+    );
+    walker.walk(tree, 0, &mut |_, _, leaf| {
+        total = total.add(leaf)?;
+        Ok(())
+    })?;
+    Ok(total)
+}
+
+#[pyfunction]
+#[pyo3(name = "mean", signature = (tree, /, leaf_predicate=None, none_is_leaf=false, namespace=""))]
+pub fn mean_leaves<'py>(
+    tree: &Bound<'py, PyAny>,
+    leaf_predicate: Option<&Bound<'py, PyAny>>,
+    none_is_leaf: Option<bool>,
+    namespace: Option<&str>,
+) -> PyResult<Bound<'py, PyAny>> {
+    // Start from the first leaf rather than zero, so the leaves only need to support `+` and `/`
+    let mut total: Option<Bound<'py, PyAny>> = None;
+    let mut count: usize = 0;
+    let mut walker = LeafWalker::new(
+        tree.py(),
+        leaf_predicate,
+        none_is_leaf.unwrap_or(false),
+        namespace.unwrap_or(""),
+        false,
+    );
+    walker.walk(tree, 0, &mut |_, _, leaf| {
+        total = Some(match total.take() {
+            Some(total) => total.add(leaf)?,
+            None => leaf.clone(),
+        });
+        count += 1;
+        Ok(())
+    })?;
+    match total {
+        Some(total) => total.div(count),
+        None => Err(PyValueError::new_err("mean() arg is an empty tree.")),
+    }
+}
+
+#[pyfunction]
+#[pyo3(name = "all", signature = (tree, /, leaf_predicate=None, none_is_leaf=false, namespace=""))]
+pub fn all_leaves<'py>(
+    tree: &Bound<'py, PyAny>,
+    leaf_predicate: Option<&Bound<'py, PyAny>>,
+    none_is_leaf: Option<bool>,
+    namespace: Option<&str>,
+) -> PyResult<bool> {
+    let mut walker = LeafWalker::new(
+        tree.py(),
+        leaf_predicate,
+        none_is_leaf.unwrap_or(false),
+        namespace.unwrap_or(""),
+        false,
+    );
+    // Stop at the first falsy leaf
+    walker.walk_while(tree, 0, &mut |_, _, leaf| leaf.is_truthy())
+}
+
+#[pyfunction]
+#[pyo3(name = "any", signature = (tree, /, leaf_predicate=None, none_is_leaf=false, namespace=""))]
+pub fn any_leaf<'py>(
+    tree: &Bound<'py, PyAny>,
+    leaf_predicate: Option<&Bound<'py, PyAny>>,
+    none_is_leaf: Option<bool>,
+    namespace: Option<&str>,
+) -> PyResult<bool> {
+    let mut walker = LeafWalker::new(
+        tree.py(),
+        leaf_predicate,
+        none_is_leaf.unwrap_or(false),
+        namespace.unwrap_or(""),
+        false,
+    );
+    // Stop at the first truthy leaf
+    let completed = walker.walk_while(tree, 0, &mut |_, _, leaf| Ok(!leaf.is_truthy()?))?;
+    Ok(!completed)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn extremum<'py>(
+    name: &str,
+    compare_op: CompareOp,
+    tree: &Bound<'py, PyAny>,
+    default: &Bound<'py, PyTuple>,
+    key: Option<&Bound<'py, PyAny>>,
+    leaf_predicate: Option<&Bound<'py, PyAny>>,
+    none_is_leaf: bool,
+    namespace: &str,
+) -> PyResult<Bound<'py, PyAny>> {
+    if default.len() > 1 {
+        return Err(PyTypeError::new_err(std::format!(
+            "{}() expected at most 1 default value, got {}.",
+            name,
+            default.len(),
+        )));
+    }
+    // Keep the first extremal leaf as the built-in `max` and `min` do
+    let mut best: Option<(Bound<'py, PyAny>, Bound<'py, PyAny>)> = None;
+    let mut walker = LeafWalker::new(tree.py(), leaf_predicate, none_is_leaf, namespace, false);
+    walker.walk(tree, 0, &mut |_, _, leaf| {
+        let leaf_key = match key {
+            Some(key) => timed(Phase::Callback, || key.call1((leaf,)))?,
+            None => leaf.clone(),
+        };
+        let replace = match &best {
+            Some((_, best_key)) => leaf_key.rich_compare(best_key, compare_op)?.is_truthy()?,
+            None => true,
+        };
+        if replace {
+            best = Some((leaf.clone(), leaf_key));
+        }
+        Ok(())
+    })?;
+    match best {
+        Some((leaf, _)) => Ok(leaf),
+        // The default value is returned only for an empty tree as the built-in `max` and `min` do
+        None if !default.is_empty() => default.get_item(0),
+        None => Err(PyValueError::new_err(std::format!(
+            "{}() arg is an empty tree.",
+            name,
+        ))),
+    }
+}
+
+#[pyfunction]
+#[pyo3(
+    name = "max",
+    signature = (tree, /, leaf_predicate=None, none_is_leaf=false, namespace="", *default, key=None)
+)]
+pub fn max_leaf<'py>(
+    tree: &Bound<'py, PyAny>,
+    leaf_predicate: Option<&Bound<'py, PyAny>>,
+    none_is_leaf: Option<bool>,
+    namespace: Option<&str>,
+    default: &Bound<'py, PyTuple>,
+    key: Option<&Bound<'py, PyAny>>,
+) -> PyResult<Bound<'py, PyAny>> {
+    extremum(
+        "max",
+        CompareOp::Gt,
+        tree,
+        default,
+        key,
+        leaf_predicate,
+        none_is_leaf.unwrap_or(false),
+        namespace.unwrap_or(""),
+    )
+}
+
+#[pyfunction]
+#[pyo3(
+    name = "min",
+    signature = (tree, /, leaf_predicate=None, none_is_leaf=false, namespace="", *default, key=None)
+)]
+pub fn min_leaf<'py>(
+    tree: &Bound<'py, PyAny>,
+    leaf_predicate: Option<&Bound<'py, PyAny>>,
+    none_is_leaf: Option<bool>,
+    namespace: Option<&str>,
+    default: &Bound<'py, PyTuple>,
+    key: Option<&Bound<'py, PyAny>>,
+) -> PyResult<Bound<'py, PyAny>> {
+    extremum(
+        "min",
+        CompareOp::Lt,
+        tree,
+        default,
+        key,
+        leaf_predicate,
+        none_is_leaf.unwrap_or(false),
+        namespace.unwrap_or(""),
+    )
+}
+
+#[pyfunction]
+#[pyo3(signature = (tree, /, leaf_predicate=None, none_is_leaf=false, namespace=""))]
+pub fn leaf_depths<'py>(
+    tree: &Bound<'py, PyAny>,
+    leaf_predicate: Option<&Bound<'py, PyAny>>,
+    none_is_leaf: Option<bool>,
+    namespace: Option<&str>,
+) -> PyResult<Vec<usize>> {
+    let mut depths = Vec::new();
+    let mut walker = LeafWalker::new(
+        tree.py(),
+        leaf_predicate,
+        none_is_leaf.unwrap_or(false),
+        namespace.unwrap_or(""),
+        false,
+    );
+    walker.walk(tree, 0, &mut |depth, _, _| {
+        depths.push(depth);
+        Ok(())
+    })?;
+    Ok(depths)
+}
+
+#[pyfunction]
+#[pyo3(
+    signature = (tree, /, leaf_predicate=None, none_is_leaf=false, namespace="", *, ignore_types=None)
+)]
+pub fn shared_leaves<'py>(
+    tree: &Bound<'py, PyAny>,
+    leaf_predicate: Option<&Bound<'py, PyAny>>,
+    none_is_leaf: Option<bool>,
+    namespace: Option<&str>,
+    ignore_types: Option<&Bound<'py, PyAny>>,
+) -> PyResult<Vec<Vec<Bound<'py, PyTuple>>>> {
+    let py = tree.py();
+    // Group the paths by the identities of the leaves, in the order of the first occurrences
+    let mut groups: Vec<Vec<Bound<'py, PyTuple>>> = Vec::new();
+    let mut group_indices: HashMap<usize, usize> = HashMap::new();
+    let mut walker = LeafWalker::new(
+        tree.py(),
+        leaf_predicate,
+        none_is_leaf.unwrap_or(false),
+        namespace.unwrap_or(""),
+        true,
+    );
+    walker.walk(tree, 0, &mut |_, path, leaf| {
+        // The cached and interned immutable objects are shared without being aliased on purpose
+        if let Some(ignore_types) = ignore_types
+            && leaf.is_instance(ignore_types)?
+        {
+            return Ok(());
+        }
+        let path = PyTuple::new(py, path.unwrap())?;
+        let index = *group_indices
+            .entry(leaf.as_ptr() as usize)
+            .or_insert_with(|| {
+                groups.push(Vec::new());
+                groups.len() - 1
+            });
+        groups[index].push(path);
+        Ok(())
+    })?;
+    Ok(groups.into_iter().filter(|paths| paths.len() > 1).collect())
+}
+
+#[pyfunction]
+#[pyo3(
+    signature = (tree, /, leaf_predicate=None, none_is_leaf=false, namespace="", *, with_sizes=false)
+)]
+pub fn type_counts<'py>(
+    tree: &Bound<'py, PyAny>,
+    leaf_predicate: Option<&Bound<'py, PyAny>>,
+    none_is_leaf: Option<bool>,
+    namespace: Option<&str>,
+    with_sizes: bool,
+) -> PyResult<Bound<'py, PyDict>> {
+    let py = tree.py();
+    // Count the leaves by their exact types, in the order of the first occurrences
+    let mut counts: Vec<(Bound<'py, PyType>, usize, usize)> = Vec::new();
+    let mut type_indices: HashMap<usize, usize> = HashMap::new();
+    let mut walker = LeafWalker::new(
+        tree.py(),
+        leaf_predicate,
+        none_is_leaf.unwrap_or(false),
+        namespace.unwrap_or(""),
+        false,
+    );
+    walker.walk(tree, 0, &mut |_, _, leaf| {
+        let cls = leaf.get_type();
+        let index = *type_indices
+            .entry(cls.as_ptr() as usize)
+            .or_insert_with(|| {
+                counts.push((cls, 0, 0));
+                counts.len() - 1
+            });
+        counts[index].1 += 1;
+        if with_sizes {
+            counts[index].2 += leaf.call_method0("__sizeof__")?.extract::<usize>()?;
+        }
+        Ok(())
+    })?;
+    let dict = PyDict::new(py);
+    for (cls, count, size) in counts {
+        match with_sizes {
+            true => dict.set_item(cls, (count, size))?,
+            false => dict.set_item(cls, count)?,
+        }
+    }
+    Ok(dict)
+}
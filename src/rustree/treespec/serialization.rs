@@ -0,0 +1,697 @@
+// Copyright 2024-2025 Xuehai Pan. All Rights Reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+// =============================================================================
+
+use pyo3::exceptions::{PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::*;
+use std::sync::Mutex;
+
+use crate::rustree::pytypes::{namedtuple_fields, structseq_fields};
+use crate::rustree::registry::{KindHint, PyTreeKind, PyTreeTypeRegistry};
+use crate::rustree::treespec::flatten::MAX_RECURSION_DEPTH;
+use crate::rustree::treespec::{Node, PyTreeSpec, TreeSpecBuilder};
+
+// The default `repr` renders at most this many nodes, the remaining children are elided as `...`
+const REPR_MAX_NODES: usize = 1000;
+// The maximum number of children rendered for the one-level node shape in error messages
+const REPR_SHAPE_MAX_CHILDREN: usize = 32;
+
+// The limits on the treespecs loaded from the untrusted data, e.g., by `pickle`
+#[derive(Clone, Copy)]
+struct LoadLimits {
+    max_nodes: Option<usize>,
+    max_arity: Option<usize>,
+    max_depth: Option<usize>,
+    // Whether the pickled treespecs may resolve the arbitrary classes and callables
+    trusted: bool,
+}
+
+static LOAD_LIMITS: Mutex<LoadLimits> = Mutex::new(LoadLimits {
+    max_nodes: None,
+    max_arity: None,
+    max_depth: Some(MAX_RECURSION_DEPTH),
+    trusted: false,
+});
+
+// The binary format of `to_bytes()`: the magic and the version, the flags and the namespace of the
+// treespec, then the nodes in post-order with the metadata and the entries pickled. The classes of
+// the custom, namedtuple, and PyStructSequence nodes are written by their qualified names
+const BYTES_MAGIC: &[u8; 4] = b"RTSP";
+const BYTES_VERSION: u8 = 2;
+const PICKLE_PROTOCOL: u8 = 4;
+const HAS_REFERENCE: u8 = 1 << 0;
+const HAS_METADATA: u8 = 1 << 1;
+const HAS_NODE_TYPE: u8 = 1 << 2;
+const HAS_ENTRIES: u8 = 1 << 3;
+// The kinds in the order of their values
+const KINDS: [PyTreeKind; 11] = [
+    PyTreeKind::Custom,
+    PyTreeKind::Leaf,
+    PyTreeKind::None,
+    PyTreeKind::Tuple,
+    PyTreeKind::List,
+    PyTreeKind::Dict,
+    PyTreeKind::NamedTuple,
+    PyTreeKind::OrderedDict,
+    PyTreeKind::DefaultDict,
+    PyTreeKind::Deque,
+    PyTreeKind::StructSequence,
+];
+
+fn write_u32(buffer: &mut Vec<u8>, value: usize) -> PyResult<()> {
+    let value = u32::try_from(value).map_err(|_| {
+        PyValueError::new_err(std::format!(
+            "Cannot serialize the PyTreeSpec, {} exceeds the maximum size.",
+            value,
+        ))
+    })?;
+    buffer.extend_from_slice(&value.to_le_bytes());
+    Ok(())
+}
+
+fn write_bytes(buffer: &mut Vec<u8>, bytes: &[u8]) -> PyResult<()> {
+    write_u32(buffer, bytes.len())?;
+    buffer.extend_from_slice(bytes);
+    Ok(())
+}
+
+// The qualified name of a class, e.g., `package.module:Outer.Inner`
+fn qualified_name(cls: &Bound<'_, PyType>) -> PyResult<String> {
+    Ok(std::format!("{}:{}", cls.module()?, cls.qualname()?))
+}
+
+// Resolve a qualified name without importing, only from the modules that are already loaded
+fn resolve_loaded<'py>(py: Python<'py>, name: &str) -> PyResult<Option<Bound<'py, PyType>>> {
+    let Some((module, qualname)) = name.split_once(':') else {
+        return Ok(None);
+    };
+    let Some(mut obj) = py
+        .import("sys")?
+        .getattr("modules")?
+        .downcast::<PyDict>()?
+        .get_item(module)?
+    else {
+        return Ok(None);
+    };
+    for attr in qualname.split('.') {
+        match obj.getattr(attr) {
+            Ok(value) => obj = value,
+            Err(_) => return Ok(None),
+        }
+    }
+    Ok(obj.downcast_into::<PyType>().ok())
+}
+
+struct BytesReader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> BytesReader<'a> {
+    fn take(&mut self, size: usize) -> PyResult<&'a [u8]> {
+        match self.data.get(self.offset..self.offset.saturating_add(size)) {
+            Some(bytes) => {
+                self.offset += size;
+                Ok(bytes)
+            }
+            None => Err(PyValueError::new_err(
+                "Malformed PyTreeSpec bytes; unexpected end of data.",
+            )),
+        }
+    }
+
+    fn read_u8(&mut self) -> PyResult<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn read_u32(&mut self) -> PyResult<usize> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()) as usize)
+    }
+
+    fn read_bytes(&mut self) -> PyResult<&'a [u8]> {
+        let size = self.read_u32()?;
+        self.take(size)
+    }
+
+    fn read_str(&mut self) -> PyResult<&'a str> {
+        std::str::from_utf8(self.read_bytes()?)
+            .map_err(|_| PyValueError::new_err("Malformed PyTreeSpec bytes; invalid UTF-8 string."))
+    }
+}
+
+fn join(children: &[String], truncated: bool) -> String {
+    match (children.is_empty(), truncated) {
+        (_, false) => children.join(", "),
+        (true, true) => String::from("..."),
+        (false, true) => std::format!("{}, ...", children.join(", ")),
+    }
+}
+
+fn repr_mapping<'py>(
+    keys: &Bound<'py, PyAny>,
+    children: &[String],
+    truncated: bool,
+) -> PyResult<String> {
+    let items = keys
+        .try_iter()?
+        .zip(children)
+        .map(|(key, child)| Ok(std::format!("{}: {}", key?.repr()?, child)))
+        .collect::<PyResult<Vec<_>>>()?;
+    Ok(std::format!("{{{}}}", join(&items, truncated)))
+}
+
+fn repr_fields<'py>(
+    fields: &Bound<'py, PyTuple>,
+    children: &[String],
+    truncated: bool,
+) -> PyResult<String> {
+    let items = fields
+        .iter()
+        .zip(children)
+        .map(|(field, child)| Ok(std::format!("{}={}", field.extract::<String>()?, child)))
+        .collect::<PyResult<Vec<_>>>()?;
+    Ok(join(&items, truncated))
+}
+
+// Render the node by one level with placeholders for the children, e.g., `{'a': *, 'b': *}`
+pub(crate) fn repr_node_shape(py: Python<'_>, node: &Node) -> PyResult<String> {
+    let num_children = node.arity.min(REPR_SHAPE_MAX_CHILDREN);
+    repr_node(
+        py,
+        node,
+        &vec![String::from("*"); num_children],
+        num_children < node.arity,
+    )
+}
+
+// Render the node with the first `children.len()` children, followed by `...` if truncated
+fn repr_node(
+    py: Python<'_>,
+    node: &Node,
+    children: &[String],
+    truncated: bool,
+) -> PyResult<String> {
+    let node_data = node.node_data.as_ref().map(|data| data.bind(py));
+
+    Ok(match node.kind {
+        PyTreeKind::Leaf => String::from("*"),
+        PyTreeKind::None => String::from("None"),
+        PyTreeKind::Tuple => match (children.len(), truncated) {
+            (1, false) => std::format!("({},)", children[0]),
+            _ => std::format!("({})", join(children, truncated)),
+        },
+        PyTreeKind::List => std::format!("[{}]", join(children, truncated)),
+        PyTreeKind::Dict => repr_mapping(node_data.unwrap(), children, truncated)?,
+        PyTreeKind::OrderedDict => {
+            std::format!(
+                "OrderedDict({})",
+                repr_mapping(node_data.unwrap(), children, truncated)?
+            )
+        }
+        PyTreeKind::DefaultDict => {
+            let (default_factory, keys) = node_data
+                .unwrap()
+                .extract::<(Bound<PyAny>, Bound<PyAny>)>()?;
+            std::format!(
+                "defaultdict({}, {})",
+                default_factory.repr()?,
+                repr_mapping(&keys, children, truncated)?,
+            )
+        }
+        PyTreeKind::NamedTuple => {
+            let cls = node_data.unwrap();
+            std::format!(
+                "{}({})",
+                cls.getattr("__name__")?,
+                repr_fields(&namedtuple_fields(cls)?, children, truncated)?,
+            )
+        }
+        PyTreeKind::StructSequence => {
+            let cls = node_data.unwrap().downcast::<PyType>()?;
+            let module = cls.module()?;
+            let qualname = cls.qualname()?;
+            let fields = repr_fields(&structseq_fields(cls)?, children, truncated)?;
+            match module.to_cow()?.as_ref() {
+                "builtins" => std::format!("{}({})", qualname, fields),
+                module => std::format!("{}.{}({})", module, qualname, fields),
+            }
+        }
+        PyTreeKind::Deque => {
+            let maxlen = node_data.unwrap();
+            match maxlen.is_none() {
+                true => std::format!("deque([{}])", join(children, truncated)),
+                false => std::format!("deque([{}], maxlen={})", join(children, truncated), maxlen),
+            }
+        }
+        PyTreeKind::Custom => {
+            let registration = node.custom.as_ref().unwrap();
+            let name = registration.node_type.bind(py).name()?;
+            let node_data = node_data.unwrap();
+            let Some(kind_hint) = registration.kind_hint else {
+                return Ok(std::format!(
+                    "CustomTreeNode({}[{}], [{}])",
+                    name,
+                    node_data.repr()?,
+                    join(children, truncated),
+                ));
+            };
+            let head = match node_data.is_none() {
+                true => name.to_string(),
+                false => std::format!("{}[{}]", name, node_data.repr()?),
+            };
+            let entries = node.entries.as_ref().map(|entries| entries.bind(py));
+            match (kind_hint, entries) {
+                (KindHint::Mapping, Some(entries)) => {
+                    std::format!("{}({})", head, repr_mapping(entries, children, truncated)?)
+                }
+                (KindHint::Object, Some(entries)) => {
+                    // The entries are rendered as keyword arguments if they are attribute names
+                    let items = entries
+                        .iter()
+                        .zip(children)
+                        .map(|(entry, child)| match entry.downcast::<PyString>() {
+                            Ok(entry) => std::format!("{}={}", entry, child),
+                            Err(_) => child.clone(),
+                        })
+                        .collect::<Vec<_>>();
+                    std::format!("{}({})", head, join(&items, truncated))
+                }
+                _ => std::format!("{}([{}])", head, join(children, truncated)),
+            }
+        }
+    })
+}
+
+struct ReprLimits {
+    depth: Option<usize>,
+    max_children: Option<usize>,
+    // The number of nodes that can still be rendered
+    budget: Option<usize>,
+}
+
+impl PyTreeSpec {
+    fn repr_subtree(
+        &self,
+        py: Python<'_>,
+        num_nodes: &[usize],
+        index: usize,
+        depth: usize,
+        limits: &mut ReprLimits,
+    ) -> PyResult<String> {
+        let node = &self.traversal[index];
+        if let Some(budget) = limits.budget.as_mut() {
+            *budget = budget.saturating_sub(1);
+        }
+        if let Some(target) = node.reference {
+            return Ok(std::format!(
+                "<shared {}>",
+                self.node_path(py, num_nodes, target)?.repr()?,
+            ));
+        }
+        let mut num_children = node.arity;
+        if limits.depth.is_some_and(|max_depth| depth >= max_depth) {
+            num_children = 0;
+        }
+        if let Some(max_children) = limits.max_children {
+            num_children = num_children.min(max_children);
+        }
+
+        let mut children = Vec::with_capacity(num_children);
+        for child_index in self
+            .child_indices(num_nodes, index)
+            .into_iter()
+            .take(num_children)
+        {
+            if limits.budget == Some(0) {
+                break;
+            }
+            children.push(self.repr_subtree(py, num_nodes, child_index, depth + 1, limits)?);
+        }
+        let truncated = children.len() < node.arity;
+        repr_node(py, node, &children, truncated)
+    }
+
+    pub(crate) fn repr_impl(&self, py: Python<'_>) -> PyResult<String> {
+        self.repr_limited(py, None, None, Some(REPR_MAX_NODES))
+    }
+
+    pub(crate) fn repr_limited(
+        &self,
+        py: Python<'_>,
+        depth: Option<usize>,
+        max_children: Option<usize>,
+        max_nodes: Option<usize>,
+    ) -> PyResult<String> {
+        let mut limits = ReprLimits {
+            depth,
+            max_children,
+            budget: max_nodes,
+        };
+        let num_nodes = self.subtree_num_nodes();
+        let root = self.repr_subtree(py, &num_nodes, self.traversal.len() - 1, 0, &mut limits)?;
+
+        let mut repr = std::format!("PyTreeSpec({}", root);
+        if self.none_is_leaf {
+            repr.push_str(", NoneIsLeaf");
+        }
+        if !self.namespace.is_empty() {
+            repr.push_str(&std::format!(
+                ", namespace={}",
+                PyString::new(py, &self.namespace).repr()?,
+            ));
+        }
+        repr.push(')');
+        Ok(repr)
+    }
+}
+
+impl PyTreeSpec {
+    // The post-order traversal as plain Python objects, the custom node types are stored by
+    // reference and resolved against the registry on load
+    pub(crate) fn state<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyTuple>> {
+        let nodes = self
+            .traversal
+            .iter()
+            .map(|node| {
+                (
+                    node.kind,
+                    node.arity,
+                    node.node_data.as_ref().map(|data| data.bind(py)),
+                    node.custom
+                        .as_ref()
+                        .map(|registration| registration.node_type.bind(py)),
+                    node.entries.as_ref().map(|entries| entries.bind(py)),
+                    node.reference,
+                )
+                    .into_pyobject(py)
+            })
+            .collect::<PyResult<Vec<_>>>()?;
+        (self.none_is_leaf, &self.namespace, PyTuple::new(py, nodes)?).into_pyobject(py)
+    }
+
+    pub(crate) fn from_state(state: &Bound<'_, PyAny>) -> PyResult<PyTreeSpec> {
+        let py = state.py();
+        let (none_is_leaf, namespace, nodes) = state
+            .extract::<(bool, String, Bound<PyTuple>)>()
+            .map_err(|_| {
+                PyTypeError::new_err(
+                    "Expected a tuple `(none_is_leaf, namespace, nodes)` as the state of a \
+                    PyTreeSpec.",
+                )
+            })?;
+        let limits = *LOAD_LIMITS.lock().unwrap();
+        if let Some(max_nodes) = limits.max_nodes
+            && nodes.len() > max_nodes
+        {
+            return Err(PyValueError::new_err(std::format!(
+                "Maximum number of nodes ({}) exceeded during loading the PyTreeSpec.",
+                max_nodes,
+            )));
+        }
+
+        let mut builder = TreeSpecBuilder::new(none_is_leaf, &namespace);
+        // The height of the subtrees that are not yet attached to a parent node
+        let mut heights: Vec<usize> = Vec::new();
+        for node in nodes.iter() {
+            let (kind, arity, metadata, node_type, entries, reference) = node
+                .extract::<(
+                    PyTreeKind,
+                    usize,
+                    Option<Bound<PyAny>>,
+                    Option<Bound<PyType>>,
+                    Option<Bound<PyAny>>,
+                    Option<usize>,
+                )>()
+                .map_err(|_| {
+                    PyTypeError::new_err(
+                        "Expected a tuple `(kind, arity, metadata, node_type, entries, \
+                        reference)` for each node in the state of a PyTreeSpec.",
+                    )
+                })?;
+            if let Some(max_arity) = limits.max_arity
+                && arity > max_arity
+            {
+                return Err(PyValueError::new_err(std::format!(
+                    "Maximum arity ({}) exceeded during loading the PyTreeSpec.",
+                    max_arity,
+                )));
+            }
+            let is_empty =
+                arity == 0 && metadata.is_none() && node_type.is_none() && entries.is_none();
+            match kind {
+                PyTreeKind::Leaf if is_empty && reference.is_none() => builder.push_leaf(),
+                // A pruned `None` is recorded as a node even if `None` is a leaf
+                PyTreeKind::None if is_empty && (none_is_leaf || reference.is_some()) => {
+                    builder.push_empty(reference)?
+                }
+                _ if reference.is_some() || kind == PyTreeKind::Leaf => {
+                    return Err(PyValueError::new_err(std::format!(
+                        "Expected no arity, metadata, or references for a node of kind {}.",
+                        kind.name(),
+                    )));
+                }
+                _ => builder.push_node(
+                    py,
+                    kind,
+                    arity,
+                    metadata.as_ref(),
+                    node_type.as_ref(),
+                    entries.as_ref(),
+                )?,
+            }
+            let height = heights
+                .drain(heights.len() - arity..)
+                .max()
+                .map_or(0, |height| height + 1);
+            if let Some(max_depth) = limits.max_depth
+                && height > max_depth
+            {
+                return Err(PyValueError::new_err(std::format!(
+                    "Maximum depth ({}) exceeded during loading the PyTreeSpec.",
+                    max_depth,
+                )));
+            }
+            heights.push(height);
+        }
+        let mut treespec = builder.build()?;
+        // The namespace is kept even without custom nodes, e.g., for the default leaf predicate
+        treespec.namespace = namespace;
+        Ok(treespec)
+    }
+}
+
+impl PyTreeSpec {
+    pub(crate) fn to_bytes_impl<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyBytes>> {
+        let dumps = py.import("pickle")?.getattr("dumps")?;
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(BYTES_MAGIC);
+        buffer.push(BYTES_VERSION);
+        buffer.push(self.none_is_leaf as u8);
+        write_bytes(&mut buffer, self.namespace.as_bytes())?;
+        write_u32(&mut buffer, self.traversal.len())?;
+        for node in self.traversal.iter() {
+            // The namedtuple and PyStructSequence classes are the metadata of their nodes
+            let class_node = matches!(
+                node.kind,
+                PyTreeKind::NamedTuple | PyTreeKind::StructSequence
+            );
+            let node_type = match (&node.custom, &node.node_data) {
+                (Some(registration), _) => Some(qualified_name(registration.node_type.bind(py))?),
+                (None, Some(node_data)) if class_node => {
+                    Some(qualified_name(node_data.bind(py).downcast::<PyType>()?)?)
+                }
+                _ => None,
+            };
+            let metadata = node.node_data.as_ref().filter(|_| !class_node);
+            let flags = [
+                (node.reference.is_some(), HAS_REFERENCE),
+                (metadata.is_some(), HAS_METADATA),
+                (node_type.is_some(), HAS_NODE_TYPE),
+                (node.entries.is_some(), HAS_ENTRIES),
+            ]
+            .into_iter()
+            .filter(|(present, _)| *present)
+            .fold(0, |flags, (_, flag)| flags | flag);
+            buffer.push(node.kind as u8);
+            write_u32(&mut buffer, node.arity)?;
+            buffer.push(flags);
+            if let Some(target) = node.reference {
+                write_u32(&mut buffer, target)?;
+            }
+            if let Some(metadata) = metadata {
+                let pickled = dumps.call1((metadata.bind(py), PICKLE_PROTOCOL))?;
+                write_bytes(&mut buffer, pickled.downcast::<PyBytes>()?.as_bytes())?;
+            }
+            if let Some(node_type) = &node_type {
+                write_bytes(&mut buffer, node_type.as_bytes())?;
+            }
+            if let Some(entries) = &node.entries {
+                let pickled = dumps.call1((entries.bind(py), PICKLE_PROTOCOL))?;
+                write_bytes(&mut buffer, pickled.downcast::<PyBytes>()?.as_bytes())?;
+            }
+        }
+        Ok(PyBytes::new(py, &buffer))
+    }
+
+    // Decode the bytes into the state of the treespec, so the loading limits apply as for pickle
+    pub(crate) fn from_bytes_impl(
+        py: Python<'_>,
+        data: &[u8],
+        trusted: bool,
+    ) -> PyResult<PyTreeSpec> {
+        let mut reader = BytesReader { data, offset: 0 };
+        if reader.take(BYTES_MAGIC.len()).ok() != Some(BYTES_MAGIC.as_slice()) {
+            return Err(PyValueError::new_err(
+                "Malformed PyTreeSpec bytes; missing the magic header.",
+            ));
+        }
+        let version = reader.read_u8()?;
+        if version > BYTES_VERSION {
+            return Err(PyValueError::new_err(std::format!(
+                "Unsupported PyTreeSpec bytes version {}, expected at most {}.",
+                version,
+                BYTES_VERSION,
+            )));
+        }
+        let none_is_leaf = reader.read_u8()? != 0;
+        let namespace = reader.read_str()?;
+        let num_nodes = reader.read_u32()?;
+        if let Some(max_nodes) = LOAD_LIMITS.lock().unwrap().max_nodes
+            && num_nodes > max_nodes
+        {
+            return Err(PyValueError::new_err(std::format!(
+                "Maximum number of nodes ({}) exceeded during loading the PyTreeSpec.",
+                max_nodes,
+            )));
+        }
+
+        // The untrusted data can only refer to the custom types that are registered in the
+        // namespace, and the pickled metadata and entries cannot resolve any other classes
+        let custom_types = PyTreeTypeRegistry::custom_types(py, none_is_leaf, namespace);
+        let loads = match trusted {
+            true => py.import("pickle")?.getattr("loads")?,
+            false => py.import("rustree.utils")?.getattr("restricted_loads")?,
+        };
+        let resolve_name = py.import("pkgutil")?.getattr("resolve_name")?;
+
+        let mut nodes = Vec::with_capacity(num_nodes.min(data.len()));
+        for _ in 0..num_nodes {
+            let kind = *KINDS.get(reader.read_u8()? as usize).ok_or_else(|| {
+                PyValueError::new_err("Malformed PyTreeSpec bytes; unknown node kind.")
+            })?;
+            let arity = reader.read_u32()?;
+            let flags = reader.read_u8()?;
+            let reference = match flags & HAS_REFERENCE {
+                0 => None,
+                _ => Some(reader.read_u32()?),
+            };
+            let mut metadata = match flags & HAS_METADATA {
+                0 => None,
+                _ => Some(loads.call1((PyBytes::new(py, reader.read_bytes()?),))?),
+            };
+            let node_type = match flags & HAS_NODE_TYPE {
+                0 => None,
+                _ if matches!(kind, PyTreeKind::NamedTuple | PyTreeKind::StructSequence) => {
+                    let name = reader.read_str()?;
+                    let cls = match trusted {
+                        true => Some(resolve_name.call1((name,))?),
+                        false => resolve_loaded(py, name)?.map(Bound::into_any),
+                    };
+                    let Some(cls) = cls else {
+                        return Err(PyValueError::new_err(std::format!(
+                            "Cannot resolve the {} class {} from the loaded modules, \
+                            pass `trusted=True` to import it.",
+                            match kind {
+                                PyTreeKind::NamedTuple => "namedtuple",
+                                _ => "PyStructSequence",
+                            },
+                            PyString::new(py, name).repr()?,
+                        )));
+                    };
+                    // The class is checked to be a namedtuple or PyStructSequence class on build
+                    metadata = Some(cls);
+                    None
+                }
+                _ => {
+                    let name = reader.read_str()?;
+                    let mut found = None;
+                    for cls in custom_types.iter() {
+                        if qualified_name(cls)? == name {
+                            found = Some(cls.clone().into_any());
+                            break;
+                        }
+                    }
+                    match (found, trusted) {
+                        (Some(cls), _) => Some(cls),
+                        (None, true) => Some(resolve_name.call1((name,))?),
+                        (None, false) => {
+                            return Err(PyValueError::new_err(std::format!(
+                                "Cannot resolve the custom node type {} from the types registered \
+                                in namespace {}, pass `trusted=True` to import it.",
+                                PyString::new(py, name).repr()?,
+                                PyString::new(py, namespace).repr()?,
+                            )));
+                        }
+                    }
+                }
+            };
+            let entries = match flags & HAS_ENTRIES {
+                0 => None,
+                _ => Some(loads.call1((PyBytes::new(py, reader.read_bytes()?),))?),
+            };
+            nodes.push((kind, arity, metadata, node_type, entries, reference).into_pyobject(py)?);
+        }
+        if reader.offset != data.len() {
+            return Err(PyValueError::new_err(
+                "Malformed PyTreeSpec bytes; unexpected trailing data.",
+            ));
+        }
+        let state = (none_is_leaf, namespace, PyTuple::new(py, nodes)?).into_pyobject(py)?;
+        PyTreeSpec::from_state(state.as_any())
+    }
+
+    // The pickled treespecs are loaded as `from_bytes()`, trusted only inside the context manager
+    pub(crate) fn from_pickle_impl(py: Python<'_>, data: &[u8]) -> PyResult<PyTreeSpec> {
+        let trusted = LOAD_LIMITS.lock().unwrap().trusted;
+        PyTreeSpec::from_bytes_impl(py, data, trusted)
+    }
+}
+
+#[pyfunction]
+pub fn get_load_limits() -> (Option<usize>, Option<usize>, Option<usize>, bool) {
+    let limits = *LOAD_LIMITS.lock().unwrap();
+    (
+        limits.max_nodes,
+        limits.max_arity,
+        limits.max_depth,
+        limits.trusted,
+    )
+}
+
+#[pyfunction]
+#[pyo3(signature = (max_nodes, max_arity, max_depth, trusted, /))]
+pub fn set_load_limits(
+    max_nodes: Option<usize>,
+    max_arity: Option<usize>,
+    max_depth: Option<usize>,
+    trusted: bool,
+) {
+    *LOAD_LIMITS.lock().unwrap() = LoadLimits {
+        max_nodes,
+        max_arity,
+        max_depth,
+        trusted,
+    };
+}
@@ -0,0 +1,70 @@
+// Copyright 2024-2025 Xuehai Pan. All Rights Reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+// =============================================================================
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::*;
+
+use crate::rustree::treespec::PyTreeSpec;
+
+pub(crate) fn check_transposable(
+    py: Python<'_>,
+    outer: &PyTreeSpec,
+    inner: &PyTreeSpec,
+) -> PyResult<()> {
+    if outer.none_is_leaf != inner.none_is_leaf {
+        return Err(PyValueError::new_err(
+            "Tree structures must have the same none_is_leaf value.",
+        ));
+    }
+    if inner.num_leaves() == 0 {
+        return Err(PyValueError::new_err(std::format!(
+            "Tree structures must have at least one leaf, got {}.",
+            inner.repr_impl(py)?,
+        )));
+    }
+    Ok(())
+}
+
+// Swap the outer and the inner structures of the leaves of a composed treespec
+pub(crate) fn transpose_leaves<'py>(
+    py: Python<'py>,
+    outer: &PyTreeSpec,
+    inner: &PyTreeSpec,
+    leaves: &[Bound<'py, PyAny>],
+) -> PyResult<Bound<'py, PyAny>> {
+    let inner_size = inner.num_leaves();
+    let mut subtrees = Vec::with_capacity(inner_size);
+    for offset in 0..inner_size {
+        let column = PyList::new(py, leaves.iter().skip(offset).step_by(inner_size))?;
+        subtrees.push(outer.unflatten_impl(column.as_any(), None)?);
+    }
+    inner.unflatten_impl(PyList::new(py, subtrees)?.as_any(), None)
+}
+
+#[pyfunction]
+#[pyo3(name = "transpose", signature = (outer_treespec, inner_treespec, tree, /, leaf_predicate=None))]
+pub fn transpose_tree<'py>(
+    outer_treespec: &PyTreeSpec,
+    inner_treespec: &PyTreeSpec,
+    tree: &Bound<'py, PyAny>,
+    leaf_predicate: Option<&Bound<'py, PyAny>>,
+) -> PyResult<Bound<'py, PyAny>> {
+    let py = tree.py();
+    check_transposable(py, outer_treespec, inner_treespec)?;
+    let treespec = outer_treespec.compose_impl(py, inner_treespec)?;
+    let leaves = treespec.flatten_impl(tree, leaf_predicate)?;
+    transpose_leaves(py, outer_treespec, inner_treespec, &leaves)
+}
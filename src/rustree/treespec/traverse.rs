@@ -0,0 +1,91 @@
+// Copyright 2024-2025 Xuehai Pan. All Rights Reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+// =============================================================================
+
+use pyo3::prelude::*;
+use pyo3::types::*;
+
+use crate::rustree::registry::PyTreeKind;
+use crate::rustree::treespec::query::node_entries;
+use crate::rustree::treespec::{Node, PyTreeSpec};
+
+// A read-only view of a node of the treespec
+#[pyclass(frozen, get_all, module = "rustree")]
+pub struct PyTreeNode {
+    kind: PyTreeKind,
+    arity: usize,
+    node_data: Py<PyAny>,
+    node_type: Option<Py<PyType>>,
+    num_leaves: usize,
+    entries: Py<PyTuple>,
+    reference: Option<usize>,
+}
+
+impl PyTreeNode {
+    fn new(py: Python<'_>, node: &Node) -> PyResult<Self> {
+        let node_data = match node.node_data.as_ref().map(|data| data.bind(py)) {
+            // Copy the keys of the dictionaries to keep the treespec immutable
+            Some(data) => match node.kind {
+                PyTreeKind::Dict | PyTreeKind::OrderedDict => {
+                    PyTuple::new(py, data.try_iter()?.collect::<PyResult<Vec<_>>>()?)?.into_any()
+                }
+                PyTreeKind::DefaultDict => {
+                    let (default_factory, keys) = data.extract::<(Bound<PyAny>, Bound<PyAny>)>()?;
+                    let keys = PyTuple::new(py, keys.try_iter()?.collect::<PyResult<Vec<_>>>()?)?;
+                    (default_factory, keys).into_pyobject(py)?.into_any()
+                }
+                _ => data.clone(),
+            },
+            None => py.None().into_bound(py),
+        };
+        Ok(PyTreeNode {
+            kind: node.kind,
+            arity: node.arity,
+            node_data: node_data.unbind(),
+            node_type: node.node_type(py)?.map(Bound::unbind),
+            num_leaves: node.num_leaves,
+            entries: PyTuple::new(py, node_entries(py, node)?)?.unbind(),
+            reference: node.reference,
+        })
+    }
+}
+
+#[pymethods]
+impl PyTreeNode {
+    fn __repr__(&self, py: Python<'_>) -> PyResult<String> {
+        let mut fields = vec![
+            std::format!("kind=PyTreeKind.{}", self.kind.name()),
+            std::format!("arity={}", self.arity),
+            std::format!("node_data={}", self.node_data.bind(py).repr()?),
+            std::format!("num_leaves={}", self.num_leaves),
+            std::format!("entries={}", self.entries.bind(py).repr()?),
+        ];
+        if let Some(target) = self.reference {
+            fields.push(std::format!("reference={}", target));
+        }
+        Ok(std::format!("PyTreeNode({})", fields.join(", ")))
+    }
+}
+
+impl PyTreeSpec {
+    // The descriptors of the nodes, in post-order
+    pub(crate) fn traverse_impl<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyIterator>> {
+        let nodes = self
+            .traversal
+            .iter()
+            .map(|node| Bound::new(py, PyTreeNode::new(py, node)?))
+            .collect::<PyResult<Vec<_>>>()?;
+        PyTuple::new(py, nodes)?.try_iter()
+    }
+}
@@ -0,0 +1,377 @@
+// Copyright 2024-2025 Xuehai Pan. All Rights Reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+// =============================================================================
+
+use pyo3::exceptions::{PyIndexError, PyRuntimeError, PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::*;
+use std::collections::{HashMap, HashSet};
+
+use crate::rustree::profiling::{Phase, record_allocations, timed};
+use crate::rustree::pytypes::{defaultdict_type, deque_type, ordereddict_type};
+use crate::rustree::registry::PyTreeKind;
+use crate::rustree::treespec::{Node, PyTreeSpec};
+
+pub(crate) fn make_node<'py>(
+    py: Python<'py>,
+    node: &Node,
+    children: Vec<Bound<'py, PyAny>>,
+) -> PyResult<Bound<'py, PyAny>> {
+    timed(Phase::Construction, || make_node_impl(py, node, children))
+}
+
+fn make_node_impl<'py>(
+    py: Python<'py>,
+    node: &Node,
+    children: Vec<Bound<'py, PyAny>>,
+) -> PyResult<Bound<'py, PyAny>> {
+    if children.len() != node.arity {
+        return Err(PyRuntimeError::new_err(std::format!(
+            "Node arity mismatch; expected {}, got {}.",
+            node.arity,
+            children.len(),
+        )));
+    }
+    let node_data = node.node_data.as_ref().map(|data| data.bind(py));
+    // The container itself, plus the intermediate argument tuple or list for the constructors
+    record_allocations(match node.kind {
+        PyTreeKind::None => 0,
+        PyTreeKind::Tuple | PyTreeKind::List | PyTreeKind::Dict => 1,
+        _ => 2,
+    });
+
+    Ok(match node.kind {
+        PyTreeKind::Leaf => {
+            return Err(PyRuntimeError::new_err(
+                "make_node not implemented for leaves.",
+            ));
+        }
+        PyTreeKind::None => py.None().into_bound(py),
+        PyTreeKind::Tuple => PyTuple::new(py, children)?.into_any(),
+        PyTreeKind::List => PyList::new(py, children)?.into_any(),
+        PyTreeKind::Dict | PyTreeKind::OrderedDict | PyTreeKind::DefaultDict => {
+            let (dict, keys) = match node.kind {
+                PyTreeKind::Dict => (PyDict::new(py).into_any(), node_data.unwrap().clone()),
+                PyTreeKind::OrderedDict => {
+                    (ordereddict_type(py)?.call0()?, node_data.unwrap().clone())
+                }
+                _ => {
+                    let (default_factory, keys) = node_data
+                        .unwrap()
+                        .extract::<(Bound<PyAny>, Bound<PyAny>)>()?;
+                    (defaultdict_type(py)?.call1((default_factory,))?, keys)
+                }
+            };
+            for (key, child) in keys.try_iter()?.zip(children) {
+                dict.set_item(key?, child)?;
+            }
+            dict
+        }
+        PyTreeKind::NamedTuple => node_data.unwrap().call1(PyTuple::new(py, children)?)?,
+        PyTreeKind::StructSequence => node_data.unwrap().call1((PyTuple::new(py, children)?,))?,
+        PyTreeKind::Deque => {
+            let kwargs = PyDict::new(py);
+            kwargs.set_item("maxlen", node_data.unwrap())?;
+            deque_type(py)?.call((PyList::new(py, children)?,), Some(&kwargs))?
+        }
+        PyTreeKind::Custom if node.custom.as_ref().unwrap().fields.is_some() => {
+            let registration = node.custom.as_ref().unwrap();
+            let fields = registration.fields.as_ref().unwrap();
+            let kwargs = PyDict::new(py);
+            for (name, child) in fields.init.bind(py).iter().zip(children) {
+                kwargs.set_item(name, child)?;
+            }
+            let meta = fields.meta.bind(py);
+            if !meta.is_empty() {
+                for (name, value) in meta.iter().zip(node_data.unwrap().try_iter()?) {
+                    kwargs.set_item(name, value?)?;
+                }
+            }
+            match &fields.init_func {
+                Some(init_func) => init_func.bind(py).call((), Some(&kwargs))?,
+                None => registration.node_type.bind(py).call((), Some(&kwargs))?,
+            }
+        }
+        PyTreeKind::Custom => {
+            let children = PyTuple::new(py, children)?;
+            timed(Phase::Callback, || {
+                node.custom
+                    .as_ref()
+                    .unwrap()
+                    .unflatten_func
+                    .as_ref()
+                    .unwrap()
+                    .bind(py)
+                    .call1((node_data.unwrap(), children))
+            })?
+        }
+    })
+}
+
+// The agenda of a well-formed traversal always holds the children of the next node
+fn take_children<'py>(
+    agenda: &mut Vec<Bound<'py, PyAny>>,
+    arity: usize,
+) -> PyResult<Vec<Bound<'py, PyAny>>> {
+    if agenda.len() < arity {
+        return Err(PyRuntimeError::new_err(std::format!(
+            "Malformed PyTreeSpec; expected {} children for the node, got {}.",
+            arity,
+            agenda.len(),
+        )));
+    }
+    Ok(agenda.split_off(agenda.len() - arity))
+}
+
+fn shared_subtree<'py>(
+    shared: &HashMap<usize, Bound<'py, PyAny>>,
+    target: usize,
+) -> PyResult<Bound<'py, PyAny>> {
+    shared.get(&target).cloned().ok_or_else(|| {
+        PyRuntimeError::new_err(std::format!(
+            "Malformed PyTreeSpec; the reference to node {} does not precede it.",
+            target,
+        ))
+    })
+}
+
+fn take_root<'py>(mut agenda: Vec<Bound<'py, PyAny>>) -> PyResult<Bound<'py, PyAny>> {
+    if agenda.len() != 1 {
+        return Err(PyRuntimeError::new_err(std::format!(
+            "Malformed PyTreeSpec; expected exactly one root, got {}.",
+            agenda.len(),
+        )));
+    }
+    Ok(agenda.pop().unwrap())
+}
+
+impl PyTreeSpec {
+    pub(crate) fn unflatten_impl<'py>(
+        &self,
+        leaves: &Bound<'py, PyAny>,
+        validate: Option<&Bound<'py, PyAny>>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let py = leaves.py();
+        let paths = match validate {
+            Some(_) => self.leaf_paths(py)?,
+            None => Vec::new(),
+        };
+        let mut agenda: Vec<Bound<'py, PyAny>> = Vec::new();
+        let mut leaves = leaves.try_iter()?;
+        let mut leaf_count: usize = 0;
+        // The shared subtrees are built once and reused at the repeated occurrences
+        let referenced: HashSet<usize> = self
+            .traversal
+            .iter()
+            .filter_map(|node| node.reference)
+            .collect();
+        let mut shared: HashMap<usize, Bound<'py, PyAny>> = HashMap::new();
+
+        for (index, node) in self.traversal.iter().enumerate() {
+            if node.kind == PyTreeKind::Leaf {
+                match leaves.next() {
+                    Some(leaf) => {
+                        let mut leaf = leaf?;
+                        // The validator may raise or substitute the leaf before the insertion
+                        if let Some(validate) = validate {
+                            let args = (leaf_count, &paths[leaf_count], leaf);
+                            leaf = timed(Phase::Callback, || validate.call1(args))?;
+                        }
+                        agenda.push(leaf);
+                        leaf_count += 1;
+                    }
+                    None => {
+                        return Err(PyValueError::new_err(std::format!(
+                            "Too few leaves for PyTreeSpec; expected {}, got {}.",
+                            self.num_leaves(),
+                            leaf_count,
+                        )));
+                    }
+                }
+            } else if let Some(target) = node.reference {
+                agenda.push(shared_subtree(&shared, target)?);
+            } else {
+                let children = take_children(&mut agenda, node.arity)?;
+                let subtree = make_node(py, node, children)?;
+                if referenced.contains(&index) {
+                    shared.insert(index, subtree.clone());
+                }
+                agenda.push(subtree);
+            }
+        }
+
+        let mut leaf_count_excess: usize = 0;
+        for leaf in leaves {
+            leaf?;
+            leaf_count_excess += 1;
+        }
+        if leaf_count_excess > 0 {
+            return Err(PyValueError::new_err(std::format!(
+                "Too many leaves for PyTreeSpec; expected {}, got {}.",
+                self.num_leaves(),
+                leaf_count + leaf_count_excess,
+            )));
+        }
+
+        take_root(agenda)
+    }
+
+    // Fold over the traversal bottom-up, the containers are rebuilt for the nodes without `f_node`
+    pub(crate) fn walk_impl<'py>(
+        &self,
+        leaves: &Bound<'py, PyAny>,
+        f_node: Option<&Bound<'py, PyAny>>,
+        f_leaf: Option<&Bound<'py, PyAny>>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let py = leaves.py();
+        let mut agenda: Vec<Bound<'py, PyAny>> = Vec::new();
+        let mut leaves = leaves.try_iter()?;
+        let mut leaf_count: usize = 0;
+        // The results of the shared subtrees are computed once and reused at the repeated
+        // occurrences
+        let referenced: HashSet<usize> = self
+            .traversal
+            .iter()
+            .filter_map(|node| node.reference)
+            .collect();
+        let mut shared: HashMap<usize, Bound<'py, PyAny>> = HashMap::new();
+
+        for (index, node) in self.traversal.iter().enumerate() {
+            if node.kind == PyTreeKind::Leaf {
+                let Some(leaf) = leaves.next() else {
+                    return Err(PyValueError::new_err(std::format!(
+                        "Too few leaves for PyTreeSpec; expected {}, got {}.",
+                        self.num_leaves(),
+                        leaf_count,
+                    )));
+                };
+                let leaf = leaf?;
+                agenda.push(match f_leaf {
+                    Some(f_leaf) => timed(Phase::Callback, || f_leaf.call1((leaf,)))?,
+                    None => leaf,
+                });
+                leaf_count += 1;
+            } else if let Some(target) = node.reference {
+                agenda.push(shared_subtree(&shared, target)?);
+            } else {
+                let children = take_children(&mut agenda, node.arity)?;
+                let result = match f_node {
+                    Some(f_node) => {
+                        let args = (
+                            node.node_type(py)?,
+                            node.node_data.as_ref().map(|data| data.bind(py)),
+                            PyTuple::new(py, children)?,
+                        );
+                        timed(Phase::Callback, || f_node.call1(args))?
+                    }
+                    None => make_node(py, node, children)?,
+                };
+                if referenced.contains(&index) {
+                    shared.insert(index, result.clone());
+                }
+                agenda.push(result);
+            }
+        }
+
+        let mut leaf_count_excess: usize = 0;
+        for leaf in leaves {
+            leaf?;
+            leaf_count_excess += 1;
+        }
+        if leaf_count_excess > 0 {
+            return Err(PyValueError::new_err(std::format!(
+                "Too many leaves for PyTreeSpec; expected {}, got {}.",
+                self.num_leaves(),
+                leaf_count + leaf_count_excess,
+            )));
+        }
+
+        take_root(agenda)
+    }
+
+    // Resolve a leaf index or a path to a leaf to the index of the leaf
+    fn leaf_index(&self, py: Python<'_>, key: &Bound<'_, PyAny>) -> PyResult<usize> {
+        if !key.is_instance_of::<PyBool>()
+            && let Ok(index) = key.extract::<isize>()
+        {
+            let num_leaves = self.num_leaves() as isize;
+            let position = if index < 0 { index + num_leaves } else { index };
+            if !(0..num_leaves).contains(&position) {
+                return Err(PyIndexError::new_err(std::format!(
+                    "Leaf index {} out of range for PyTreeSpec with {} leaves.",
+                    index,
+                    num_leaves,
+                )));
+            }
+            return Ok(position as usize);
+        }
+        if !(key.is_instance_of::<PyTuple>() || key.is_instance_of::<PyList>()) {
+            return Err(PyTypeError::new_err(std::format!(
+                "Expected a leaf index or a path as a tuple of path entries, got {}.",
+                key.repr()?,
+            )));
+        }
+        self.leaf_index_at(py, key)
+    }
+
+    pub(crate) fn unflatten_partial_impl<'py>(
+        &self,
+        py: Python<'py>,
+        leaves: &Bound<'py, PyAny>,
+        default: &Bound<'py, PyAny>,
+        default_factory: Option<&Bound<'py, PyAny>>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let mut slots: Vec<Option<Bound<'py, PyAny>>> = vec![None; self.num_leaves()];
+        let items = if let Ok(leaves) = leaves.downcast::<PyDict>() {
+            leaves.items().into_any()
+        } else if let Ok(leaves) = leaves.downcast::<PyMapping>() {
+            leaves.items()?.into_any()
+        } else {
+            return Err(PyTypeError::new_err(std::format!(
+                "Expected a mapping from leaf index or path to leaf, got {}.",
+                leaves.repr()?,
+            )));
+        };
+        for item in items.try_iter()? {
+            let (key, leaf) = item?.extract::<(Bound<PyAny>, Bound<PyAny>)>()?;
+            let index = self.leaf_index(py, &key)?;
+            if slots[index].is_some() {
+                return Err(PyValueError::new_err(std::format!(
+                    "Leaf {} is specified more than once, got {}.",
+                    index,
+                    key.repr()?,
+                )));
+            }
+            slots[index] = Some(leaf);
+        }
+
+        // The factory is always called as `default_factory(index, path)` for each missing leaf
+        let paths = match default_factory.is_some() && slots.iter().any(Option::is_none) {
+            true => self.leaf_paths(py)?,
+            false => Vec::new(),
+        };
+        let leaves = slots
+            .into_iter()
+            .enumerate()
+            .map(|(index, leaf)| match (leaf, default_factory) {
+                (Some(leaf), _) => Ok(leaf),
+                (None, Some(default_factory)) => timed(Phase::Callback, || {
+                    default_factory.call1((index, &paths[index]))
+                }),
+                (None, None) => Ok(default.clone()),
+            })
+            .collect::<PyResult<Vec<_>>>()?;
+        self.unflatten_impl(PyList::new(py, leaves)?.as_any(), None)
+    }
+}